@@ -10,7 +10,7 @@ use rand::Rng;
 use tempfile::TempDir;
 use tokio::sync::Mutex;
 
-use musq::{Error, JournalMode, Pool, Row, Synchronous};
+use musq::{writer::Writer, Error, JournalMode, Pool, Row, Synchronous};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -46,6 +46,10 @@ struct Args {
     /// Maximum number of connections in the pool
     #[arg(long, default_value_t = 10)]
     max_connections: u32,
+
+    /// Funnel all writes through the pool's single writer task
+    #[arg(long)]
+    writer: bool,
 }
 
 #[derive(Debug)]
@@ -182,6 +186,25 @@ async fn insert_record(pool: &Pool, a_data: &[u8], b_data: &[u8]) -> Result<(),
     Ok(())
 }
 
+async fn insert_record_with_writer(
+    writer: &Writer,
+    a_data: &[u8],
+    b_data: &[u8],
+) -> Result<(), Error> {
+    // The writer runs one write at a time, so nothing can insert between the two statements
+    writer
+        .execute(
+            musq::query(
+                "INSERT INTO a (data) VALUES (?);
+                 INSERT INTO b (a_id, data) VALUES (last_insert_rowid(), ?)",
+            )
+            .bind(a_data)
+            .bind(b_data),
+        )
+        .await?;
+    Ok(())
+}
+
 async fn read_random_record(pool: &Pool, max_id: u64) -> Result<(Row, Row), Error> {
     let random_id = rand::thread_rng().gen_range(1..=max_id) as i64;
 
@@ -226,7 +249,9 @@ async fn perform_operations(
     num_records: u64,
     concurrency: usize,
     blob_size: usize,
+    use_writer: bool,
 ) -> Result<TimingData, Error> {
+    let writer = use_writer.then(|| pool.writer());
     let start = Instant::now();
     let timing_data = Arc::new(Mutex::new(TimingData::new(num_records as usize)));
     let max_id = Arc::new(Mutex::new(0u64));
@@ -236,12 +261,17 @@ async fn perform_operations(
             let timing_data = Arc::clone(&timing_data);
             let max_id = Arc::clone(&max_id);
             let pool = pool.clone();
+            let writer = writer.clone();
             async move {
                 let operation_start = Instant::now();
 
                 let (a_data, b_data) = generate_random_data(blob_size);
 
-                if (insert_record(&pool, &a_data, &b_data).await).is_ok() {
+                let inserted = match &writer {
+                    Some(writer) => insert_record_with_writer(writer, &a_data, &b_data).await,
+                    None => insert_record(&pool, &a_data, &b_data).await,
+                };
+                if inserted.is_ok() {
                     let mut id = max_id.lock().await;
                     *id += 1;
                     drop(id);
@@ -290,8 +320,14 @@ async fn main() -> Result<(), Error> {
     let pool = setup_database(&args, &database_path).await?;
     create_schema(&pool).await?;
 
-    let timing_data =
-        perform_operations(&pool, args.records, args.concurrency, args.blob_size).await?;
+    let timing_data = perform_operations(
+        &pool,
+        args.records,
+        args.concurrency,
+        args.blob_size,
+        args.writer,
+    )
+    .await?;
 
     // Sanity check
     let (a_count, b_count) = count_records(&pool).await?;
@@ -75,6 +75,11 @@ pub struct RowContainer {
 
     #[darling(default)]
     pub rename_all: RenameAll,
+    /// The table this type maps to. If set, `musq::schema::Table` is implemented as well as `FromRow`.
+    pub table: Option<String>,
+    /// Decode fields by column position rather than by name, as tuple structs always are.
+    #[darling(default)]
+    pub positional: bool,
 }
 
 #[derive(Debug, FromField)]
@@ -93,6 +98,9 @@ pub struct RowField {
     pub try_from: Option<Type>,
     #[darling(default)]
     pub skip: bool,
+    /// The field holds the row's rowid, and is set by `Insert` after the row is inserted.
+    #[darling(default)]
+    pub rowid: bool,
 }
 
 #[derive(Debug, FromDeriveInput)]
@@ -124,11 +132,25 @@ pub struct TypeField {
     pub rename: Option<String>,
 }
 
-pub(crate) fn check_repr_enum_attrs(attrs: &TypeContainer) -> syn::Result<()> {
-    if attrs.repr.is_none() {
-        span_err!(&attrs.ident, "repr attribute is required")?;
+/// The integer type named by a `#[repr(...)]` attribute, if there is one. Enums with an integer representation are
+/// stored as integers even without a `#[musq(repr = "...")]` attribute.
+fn int_repr(input: &DeriveInput) -> Option<Type> {
+    const INTS: &[&str] = &[
+        "i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize",
+    ];
+    let mut repr = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        // Other representation hints, like `C`, are ignored
+        let _ = attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident() {
+                if INTS.contains(&ident.to_string().as_str()) {
+                    repr = Some(syn::parse_quote!(#ident));
+                }
+            }
+            Ok(())
+        });
     }
-    Ok(())
+    repr
 }
 
 pub(crate) fn expand_type_derive(
@@ -153,10 +175,15 @@ pub(crate) fn expand_type_derive(
             }
             expand_struct(&attrs, fields.iter().next().unwrap())?
         }
-        ast::Data::Enum(v) => match &attrs.repr {
+        ast::Data::Enum(v) => match attrs.repr.clone().or_else(|| int_repr(input)) {
             Some(t) => {
-                check_repr_enum_attrs(&attrs)?;
-                expand_repr_enum(&attrs, v, t)?
+                if let Some(variant) = v.iter().find(|v| !v.fields.is_empty()) {
+                    return span_err!(
+                        &variant.ident,
+                        "enums stored as integers must only have unit variants"
+                    );
+                }
+                expand_repr_enum(&attrs, v, &t)?
             }
             None => expand_enum(&attrs, v)?,
         },
@@ -168,6 +195,17 @@ pub(crate) fn expand_type_derive(
 mod tests {
     use super::*;
 
+    #[test]
+    fn it_reads_int_reprs() {
+        let repr = |txt: &str| {
+            int_repr(&syn::parse_str(txt).unwrap()).map(|t| quote::quote!(#t).to_string())
+        };
+        assert_eq!(repr("#[repr(i32)] enum Foo {One}").as_deref(), Some("i32"));
+        assert_eq!(repr("#[repr(C, u8)] enum Foo {One}").as_deref(), Some("u8"));
+        assert_eq!(repr("#[repr(C)] enum Foo {One}"), None);
+        assert_eq!(repr("enum Foo {One}"), None);
+    }
+
     #[test]
     fn it_parses_type_attrs() {
         let good_input = r#"
@@ -0,0 +1,190 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    Expr, Ident, LitStr, Token,
+};
+
+/// The input of `fragment!`: a template string, followed by optional `name = expr` arguments.
+pub struct FragmentInput {
+    template: LitStr,
+    args: Vec<(Ident, Expr)>,
+}
+
+impl Parse for FragmentInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let template = input.parse()?;
+        let mut args = Vec::new();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let expr: Expr = input.parse()?;
+            if args.iter().any(|(n, _)| *n == name) {
+                return Err(syn::Error::new(name.span(), "duplicate argument"));
+            }
+            args.push((name, expr));
+        }
+        Ok(Self { template, args })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    /// `{name}`: bind the value as a parameter.
+    Bind(String),
+    /// `{name:frag}`: splice in a fragment.
+    Splice(String),
+}
+
+fn parse_template(template: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err("unclosed `{` in fragment".into()),
+                    }
+                }
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                let (name, spec) = match placeholder.split_once(':') {
+                    Some((name, spec)) => (name.trim(), Some(spec.trim())),
+                    None => (placeholder.trim(), None),
+                };
+                if syn::parse_str::<Ident>(name).is_err() {
+                    return Err(format!("invalid placeholder `{{{placeholder}}}`"));
+                }
+                pieces.push(match spec {
+                    None => Piece::Bind(name.into()),
+                    Some("frag") => Piece::Splice(name.into()),
+                    Some(spec) => return Err(format!("unknown placeholder format `{spec}`")),
+                });
+            }
+            '}' => return Err("unmatched `}` in fragment".into()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// Expand `fragment!` into a block that builds a `musq::Fragment`. Placeholders name either an argument of the macro
+/// or a variable in scope, which is moved into the fragment for `{name}` and borrowed for `{name:frag}`. A value bound
+/// more than once is cloned for each use but the last.
+pub fn expand_fragment(input: &FragmentInput) -> syn::Result<TokenStream> {
+    let pieces = parse_template(&input.template.value())
+        .map_err(|e| syn::Error::new(input.template.span(), e))?;
+
+    // Arguments are evaluated once, in order, before the fragment is built
+    let arg_ident = |name: &Ident| format_ident!("__musq_arg_{}", name, span = Span::mixed_site());
+    let lets = input.args.iter().map(|(name, expr)| {
+        let ident = arg_ident(name);
+        quote!(let #ident = #expr;)
+    });
+    for (name, _) in &input.args {
+        let used = pieces
+            .iter()
+            .any(|p| matches!(p, Piece::Bind(n) | Piece::Splice(n) if name == n));
+        if !used {
+            return Err(syn::Error::new(name.span(), "argument never used"));
+        }
+    }
+
+    let fragment = Ident::new("fragment", Span::mixed_site());
+    let value = |name: &str| match input.args.iter().find(|(n, _)| n == name) {
+        Some((n, _)) => arg_ident(n),
+        None => Ident::new(name, input.template.span()),
+    };
+    let last_bind = |name: &str| {
+        pieces
+            .iter()
+            .rposition(|p| matches!(p, Piece::Bind(n) if n == name))
+    };
+    let pushes = pieces.iter().enumerate().map(|(i, piece)| match piece {
+        Piece::Text(text) => quote!(#fragment.push(#text);),
+        Piece::Bind(name) if last_bind(name) == Some(i) => {
+            let value = value(name);
+            quote!(#fragment.push_bind(#value);)
+        }
+        Piece::Bind(name) => {
+            let value = value(name);
+            quote!(#fragment.push_bind(::std::clone::Clone::clone(&#value));)
+        }
+        Piece::Splice(name) => {
+            let value = value(name);
+            quote!(#fragment.push_fragment(&#value);)
+        }
+    });
+    Ok(quote! {
+        {
+            #(#lets)*
+            let mut #fragment = musq::Fragment::default();
+            #(#pushes)*
+            #fragment
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::assert_errors_with;
+
+    #[test]
+    fn it_parses_templates() {
+        assert_eq!(
+            parse_template("price > {min} AND {{x}} {f:frag}").unwrap(),
+            vec![
+                Piece::Text("price > ".into()),
+                Piece::Bind("min".into()),
+                Piece::Text(" AND {x} ".into()),
+                Piece::Splice("f".into()),
+            ]
+        );
+        assert!(parse_template("{a").is_err());
+        assert!(parse_template("a}").is_err());
+        assert!(parse_template("{a b}").is_err());
+        assert!(parse_template("{a:sql}").is_err());
+    }
+
+    #[test]
+    fn it_expands_fragments() {
+        let input: FragmentInput =
+            syn::parse_str(r#""a = {a} AND {b:frag}", b = other()"#).unwrap();
+        let out = expand_fragment(&input).unwrap().to_string();
+        assert!(out.contains("push_bind (a)"));
+        assert!(out.contains("push_fragment (& __musq_arg_b)"));
+
+        let input: FragmentInput = syn::parse_str(r#""{a} {b} {a} {b}", b = other()"#).unwrap();
+        let out = expand_fragment(&input).unwrap().to_string();
+        assert_eq!(out.matches("push_bind (a)").count(), 1);
+        assert_eq!(out.matches("clone (& a)").count(), 1);
+        assert_eq!(out.matches("push_bind (__musq_arg_b)").count(), 1);
+        assert_eq!(out.matches("clone (& __musq_arg_b)").count(), 1);
+
+        let input: FragmentInput = syn::parse_str(r#""a = {a}", b = 1"#).unwrap();
+        assert_errors_with!(expand_fragment(&input), "argument never used");
+    }
+}
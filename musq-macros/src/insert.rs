@@ -0,0 +1,118 @@
+use darling::{ast, FromDeriveInput};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, DeriveInput};
+
+use super::core;
+
+/// Implement `musq::schema::Insert` for a named struct with a `table` attribute. The columns are those of the
+/// `musq::schema::Table` implementation derived by `FromRow`: skipped, flattened and prefixed fields are left out.
+pub fn expand_derive_insert(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let container = core::RowContainer::from_derive_input(input)?;
+    let ast::Data::Struct(fields) = &container.data else {
+        return Err(syn::Error::new_spanned(input, "type not supported"));
+    };
+    if container.table.is_none() {
+        return Err(syn::Error::new_spanned(
+            input,
+            "Insert requires a #[musq(table = \"...\")] attribute",
+        ));
+    }
+    if fields.iter().any(|f| f.ident.is_none()) {
+        return Err(syn::Error::new_spanned(input, "type not supported"));
+    }
+
+    let mut rowid = None;
+    let mut generics = container.generics.clone();
+    let predicates = &mut generics.make_where_clause().predicates;
+    let mut pushes = Vec::new();
+    for field in fields.iter() {
+        let id = field.ident.as_ref().unwrap();
+        if field.skip || field.flatten || !field.prefix.is_empty() {
+            continue;
+        }
+        let name = container.rename_all.rename(
+            &field
+                .rename
+                .clone()
+                .unwrap_or_else(|| id.to_string().trim_start_matches("r#").to_owned()),
+        );
+        let ty = &field.ty;
+        if field.rowid {
+            if rowid.is_some() {
+                return Err(syn::Error::new_spanned(
+                    id,
+                    "only one field may be marked #[musq(rowid)]",
+                ));
+            }
+            rowid = Some(id);
+            pushes.push(quote!(
+                if let ::std::option::Option::Some(rowid) = self.#id {
+                    values.push((#name, musq::encode::Encode::encode(rowid)));
+                }
+            ));
+        } else {
+            predicates.push(parse_quote!(#ty: ::std::clone::Clone + musq::encode::Encode));
+            pushes.push(quote!(
+                values.push((#name, musq::encode::Encode::encode(::std::clone::Clone::clone(&self.#id))));
+            ));
+        }
+    }
+
+    let set_rowid = rowid.map(|id| {
+        quote!(
+            self.#id = ::std::option::Option::Some(rowid);
+        )
+    });
+    let ident = &container.ident;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote!(
+        #[automatically_derived]
+        impl #impl_generics musq::schema::Insert for #ident #ty_generics #where_clause {
+            fn values(&self) -> ::std::vec::Vec<(&'static str, musq::ArgumentValue)> {
+                let mut values = ::std::vec::Vec::new();
+                #(#pushes)*
+                values
+            }
+
+            #[allow(unused_variables)]
+            fn set_rowid(&mut self, rowid: i64) {
+                #set_rowid
+            }
+        }
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::core::assert_errors_with;
+    use super::*;
+
+    #[test]
+    fn it_derives_insert() {
+        let txt = r#"
+            #[musq(table = "foos")]
+            struct Foo {
+                #[musq(rowid)]
+                id: Option<i64>,
+                name: String,
+                #[musq(skip)]
+                cache: u32,
+            }
+        "#;
+        let out = expand_derive_insert(&syn::parse_str(txt).unwrap())
+            .unwrap()
+            .to_string();
+        assert!(out.contains("musq :: schema :: Insert for Foo"));
+        assert!(out.contains("self . id = :: std :: option :: Option :: Some (rowid)"));
+        assert!(!out.contains("cache"));
+
+        let txt = r#"
+            struct Foo {
+                name: String,
+            }
+        "#;
+        let e = expand_derive_insert(&syn::parse_str(txt).unwrap());
+        assert_errors_with!(e, "requires a #[musq(table");
+    }
+}
@@ -1,7 +1,10 @@
 mod core;
 mod decode;
 mod encode;
+mod fragment;
+mod insert;
 mod json;
+mod migrations;
 mod row;
 
 #[proc_macro_derive(Json, attributes(musq))]
@@ -55,3 +58,39 @@ pub fn derive_from_row(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
         Err(e) => e.to_compile_error().into(),
     }
 }
+
+#[proc_macro_derive(Insert, attributes(musq))]
+pub fn derive_insert(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match insert::expand_derive_insert(&input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Build a `musq::Fragment` from a template. `{name}` binds the value of `name` as a parameter, and `{name:frag}`
+/// splices in the fragment `name`. Names refer to the `name = expr` arguments that follow the template, or else to
+/// variables in scope. A name bound more than once is cloned for each use but the last. Use `{{` and `}}` for
+/// literal braces.
+#[proc_macro]
+pub fn fragment(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as fragment::FragmentInput);
+    match fragment::expand_fragment(&input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Embed the SQL files in a directory as a `musq::migrate::Migrator`. The path is relative to the crate's manifest,
+/// and files are named `<version>_<description>.sql`. See the `musq::migrate` module.
+///
+/// Adding a file to the directory doesn't by itself trigger a rebuild; emit `cargo:rerun-if-changed=<dir>` from the
+/// crate's build script so that new migrations are picked up.
+#[proc_macro]
+pub fn migrations(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::LitStr);
+    match migrations::expand_migrations(&input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,122 @@
+use std::{env, fs, path::PathBuf};
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+#[derive(Debug, PartialEq)]
+struct MigrationFile {
+    name: String,
+    version: i64,
+    description: String,
+}
+
+/// Parse a file name of the form `<version>_<description>.sql`. Returns `None` for files that aren't SQL.
+fn parse_file_name(name: &str) -> Option<Result<MigrationFile, String>> {
+    let stem = name.strip_suffix(".sql")?;
+    let parsed = stem
+        .split_once('_')
+        .and_then(|(version, description)| {
+            let version = version
+                .parse::<i64>()
+                .ok()
+                .filter(|v| *v > 0 && version.bytes().all(|b| b.is_ascii_digit()))?;
+            Some(MigrationFile {
+                name: name.to_string(),
+                version,
+                description: description.replace('_', " "),
+            })
+        })
+        .ok_or_else(|| {
+            format!("migration file `{name}` should be named `<version>_<description>.sql`")
+        });
+    Some(parsed)
+}
+
+/// Check that files sorted by name are sorted by version, with no duplicates.
+fn check_order(files: &[MigrationFile]) -> Result<(), String> {
+    for pair in files.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if a.version == b.version {
+            return Err(format!(
+                "migrations `{}` and `{}` have the same version",
+                a.name, b.name
+            ));
+        }
+        if a.version > b.version {
+            return Err(format!(
+                "migration `{}` sorts before `{}` but has a later version; pad versions with zeros",
+                a.name, b.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Expand `migrations!` into a `musq::migrate::Migrator` that embeds every SQL file in the directory, which is
+/// relative to the manifest of the crate being compiled. Only the files found are tracked by Cargo, through
+/// `include_str!`; the directory itself can't be from a stable proc macro.
+pub fn expand_migrations(dir: &LitStr) -> syn::Result<TokenStream> {
+    let err = |msg: String| syn::Error::new(dir.span(), msg);
+    let root = env::var("CARGO_MANIFEST_DIR").map_err(|e| err(e.to_string()))?;
+    let path = PathBuf::from(root).join(dir.value());
+    let entries =
+        fs::read_dir(&path).map_err(|e| err(format!("failed to read {}: {e}", path.display())))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| err(e.to_string()))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(file) = parse_file_name(&name) {
+            files.push(file.map_err(err)?);
+        }
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    check_order(&files).map_err(err)?;
+
+    let migrations = files.iter().map(|file| {
+        let version = file.version;
+        let description = &file.description;
+        let sql_path = path.join(&file.name).to_string_lossy().into_owned();
+        quote!(musq::migrate::Migration::new(#version, #description, include_str!(#sql_path)))
+    });
+    Ok(quote! {
+        musq::migrate::Migrator::new(&[#(#migrations),*])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Vec<MigrationFile> {
+        names
+            .iter()
+            .map(|n| parse_file_name(n).unwrap().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn it_parses_file_names() {
+        assert_eq!(
+            parse_file_name("0002_add_users.sql").unwrap().unwrap(),
+            MigrationFile {
+                name: "0002_add_users.sql".into(),
+                version: 2,
+                description: "add users".into(),
+            }
+        );
+        assert!(parse_file_name("README.md").is_none());
+        assert!(parse_file_name("add_users.sql").unwrap().is_err());
+        assert!(parse_file_name("0_init.sql").unwrap().is_err());
+        assert!(parse_file_name("+1_init.sql").unwrap().is_err());
+    }
+
+    #[test]
+    fn it_checks_order() {
+        assert!(check_order(&files(&["01_a.sql", "02_b.sql", "10_c.sql"])).is_ok());
+        assert!(check_order(&files(&["1_a.sql", "1_b.sql"])).is_err());
+        // "10" sorts before "9"
+        assert!(check_order(&files(&["10_b.sql", "9_a.sql"])).is_err());
+    }
+}
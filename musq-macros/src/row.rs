@@ -1,7 +1,7 @@
 use darling::{ast, FromDeriveInput};
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use syn::{parse_quote, DeriveInput, Expr, Lifetime, Stmt};
+use syn::{parse_quote, DeriveInput, Expr, GenericArgument, Lifetime, PathArguments, Stmt, Type};
 
 use super::core;
 
@@ -12,8 +12,8 @@ pub fn expand_derive_from_row(input: &DeriveInput) -> syn::Result<TokenStream> {
             // We know it's either a named struct or a tuple struct from darling restrictions.
             let unnamed = fields.iter().filter(|f| f.ident.is_none()).count();
             let named = fields.iter().filter(|f| f.ident.is_some()).count();
-            if unnamed > 0 {
-                expand_tuple_struct(&container, fields)?
+            if unnamed > 0 || (named > 0 && container.positional) {
+                expand_positional(&container, fields)?
             } else if named > 0 {
                 expand_struct(&container, fields)?
             } else {
@@ -58,20 +58,23 @@ fn expand_struct(
                 .unwrap();
 
             let ty = &field.ty;
+            let field_name = id.to_string().trim_start_matches("r#").to_owned();
 
             if field.skip {
+                predicates.push(parse_quote!(#ty: ::std::default::Default));
                 return Some(parse_quote!(
                     let #id: #ty = Default::default();
                 ));
             }
 
-            let expr: Expr = if field.flatten {
+            let expr: Expr = if field.flatten || !field.prefix.is_empty() {
+                // Prefixes of nested structs add up
                 predicates.push(parse_quote!(#ty: musq::FromRow<#lifetime>));
-                parse_quote!(<#ty as musq::FromRow<#lifetime>>::from_row("", row))
-            } else if !field.prefix.is_empty() {
-                predicates.push(parse_quote!(#ty: musq::FromRow<#lifetime>));
-                let prefix = &field.prefix;
-                parse_quote!(<#ty as musq::FromRow<#lifetime>>::from_row(#prefix, row))
+                let field_prefix = &field.prefix;
+                parse_quote!(<#ty as musq::FromRow<#lifetime>>::from_row(
+                    &format!("{}{}", prefix, #field_prefix),
+                    row
+                ))
             } else if let Some(try_from) = &field.try_from {
                 predicates.push(parse_quote!(#try_from: musq::decode::Decode<#lifetime>));
                 parse_quote!(
@@ -83,7 +86,10 @@ fn expand_struct(
                 )
             } else {
                 predicates.push(parse_quote!(#ty: musq::decode::Decode<#lifetime>));
-                parse_quote!(row.get_value(&format!("{}{}", prefix, #column_name)))
+                parse_quote!(
+                    row.get_value(&format!("{}{}", prefix, #column_name))
+                        .map_err(|e| e.in_field(#field_name))
+                )
             };
 
             if field.default {
@@ -105,6 +111,7 @@ fn expand_struct(
 
     let (impl_generics, _, where_clause) = generics.split_for_impl();
     let names = fields.iter().map(|field| &field.ident);
+    let table = expand_table(container, fields);
 
     Ok(quote!(
         #[automatically_derived]
@@ -117,10 +124,123 @@ fn expand_struct(
                 })
             }
         }
+
+        #table
     ))
 }
 
-fn expand_tuple_struct(
+/// Implement `musq::schema::Table` for structs with a `table` attribute. Skipped, flattened and prefixed fields don't
+/// map to columns of the table, and are left out.
+fn expand_table(
+    container: &core::RowContainer,
+    fields: &ast::Fields<core::RowField>,
+) -> Option<TokenStream> {
+    let table = container.table.as_ref()?;
+    let ident = &container.ident;
+    let (impl_generics, ty_generics, where_clause) = container.generics.split_for_impl();
+
+    let columns = fields.iter().filter_map(|field| {
+        let id = field.ident.as_ref()?;
+        if field.skip || field.flatten || !field.prefix.is_empty() {
+            return None;
+        }
+        let name = container.rename_all.rename(
+            &field
+                .rename
+                .clone()
+                .unwrap_or_else(|| id.to_string().trim_start_matches("r#").to_owned()),
+        );
+        let (ty, nullable) = match option_inner(&field.ty) {
+            Some(inner) => (inner, true),
+            None => (&field.ty, false),
+        };
+        let affinity = match field
+            .try_from
+            .as_ref()
+            .map_or_else(|| affinity(ty), affinity)
+        {
+            Some(a) => {
+                let a = syn::Ident::new(a, Span::call_site());
+                quote!(::std::option::Option::Some(musq::schema::Affinity::#a))
+            }
+            None => quote!(::std::option::Option::None),
+        };
+        let optional = field.default;
+        Some(quote!(
+            musq::schema::ColumnSpec {
+                name: #name,
+                affinity: #affinity,
+                nullable: #nullable,
+                optional: #optional,
+            }
+        ))
+    });
+
+    Some(quote!(
+        #[automatically_derived]
+        impl #impl_generics musq::schema::Table for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #table;
+
+            fn columns() -> ::std::vec::Vec<musq::schema::ColumnSpec> {
+                ::std::vec![#(#columns),*]
+            }
+        }
+    ))
+}
+
+/// If `ty` is `Option<T>`, return `T`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let last = path.path.segments.last()?;
+    if last.ident != "Option" {
+        return None;
+    }
+    match &last.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The column affinity expected for well-known Rust types, by name. Other types, including those with custom codecs,
+/// aren't checked.
+fn affinity(ty: &Type) -> Option<&'static str> {
+    match ty {
+        Type::Reference(r) => affinity(&r.elem),
+        Type::Slice(s) => match &*s.elem {
+            Type::Path(p) if p.path.is_ident("u8") => Some("Blob"),
+            _ => None,
+        },
+        Type::Path(path) => {
+            let last = path.path.segments.last()?;
+            match last.ident.to_string().as_str() {
+                "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize"
+                | "bool" => Some("Integer"),
+                "f32" | "f64" => Some("Real"),
+                "String" | "str" => Some("Text"),
+                "Vec" => match &last.arguments {
+                    PathArguments::AngleBracketed(args) => match args.args.first()? {
+                        GenericArgument::Type(Type::Path(p)) if p.path.is_ident("u8") => {
+                            Some("Blob")
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Tuple structs, and named structs with the `positional` attribute, are decoded by position: each field that isn't
+/// skipped reads the next column of the row.
+fn expand_positional(
     container: &core::RowContainer,
     fields: &ast::Fields<core::RowField>,
 ) -> syn::Result<TokenStream> {
@@ -136,36 +256,67 @@ fn expand_tuple_struct(
     let (_, ty_generics, _) = generics.split_for_impl();
 
     let mut generics = generics.clone();
-    generics.params.insert(0, parse_quote!(R: musq::Row));
-
     if provided {
         generics.params.insert(0, parse_quote!(#lifetime));
     }
 
     let predicates = &mut generics.make_where_clause().predicates;
 
-    for field in fields.iter() {
+    let mut index = 0usize;
+    let mut values: Vec<Expr> = Vec::new();
+    for (position, field) in fields.iter().enumerate() {
         let ty = &field.ty;
+        if field.rename.is_some() || field.flatten || !field.prefix.is_empty() || field.default {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "fields decoded by position only support `skip` and `try_from`",
+            ));
+        }
+        let field_name = match &field.ident {
+            Some(id) => id.to_string().trim_start_matches("r#").to_owned(),
+            None => position.to_string(),
+        };
+
+        if field.skip {
+            predicates.push(parse_quote!(#ty: ::std::default::Default));
+            values.push(parse_quote!(::std::default::Default::default()));
+            continue;
+        }
 
-        predicates.push(parse_quote!(#ty: musq::decode::Decode<#lifetime>));
+        values.push(if let Some(try_from) = &field.try_from {
+            predicates.push(parse_quote!(#try_from: musq::decode::Decode<#lifetime>));
+            parse_quote!(
+                row.get_value_idx::<#try_from>(#index).and_then(
+                    |v| <#ty as ::std::convert::TryFrom::<#try_from>>::try_from(v).map_err(
+                        |e| musq::Error::ColumnNotFound("FromRow: try_from failed".to_string())
+                    )
+                )?
+            )
+        } else {
+            predicates.push(parse_quote!(#ty: musq::decode::Decode<#lifetime>));
+            parse_quote!(row.get_value_idx(#index).map_err(|e| e.in_field(#field_name))?)
+        });
+        index += 1;
     }
 
     let (impl_generics, _, where_clause) = generics.split_for_impl();
-
-    let gets = fields
-        .iter()
-        .enumerate()
-        .map(|(idx, _)| quote!(row.get_value_idx(#idx)?));
+    let construct = if fields.style == ast::Style::Tuple {
+        quote!(#ident ( #(#values),* ))
+    } else {
+        let names = fields.iter().map(|field| &field.ident);
+        quote!(#ident { #(#names: #values),* })
+    };
+    let table = expand_table(container, fields);
 
     Ok(quote!(
         #[automatically_derived]
         impl #impl_generics musq::FromRow<#lifetime> for #ident #ty_generics #where_clause {
             fn from_row(prefix: &str, row: &#lifetime musq::Row) -> musq::Result<Self> {
-                ::std::result::Result::Ok(#ident (
-                    #(#gets),*
-                ))
+                ::std::result::Result::Ok(#construct)
             }
         }
+
+        #table
     ))
 }
 
@@ -203,5 +354,42 @@ mod tests {
             struct Foo(i32, String);
         "#;
         expand_derive_from_row(&syn::parse_str(txt).unwrap()).unwrap();
+
+        let txt = r#"
+            struct Foo(i32, #[musq(rename = "b")] String);
+        "#;
+        let e = expand_derive_from_row(&syn::parse_str(txt).unwrap());
+        assert_errors_with!(e, "decoded by position");
+
+        let txt = r#"
+            #[musq(positional)]
+            struct Foo {
+                a: i32,
+                #[musq(flatten)]
+                b: Bar,
+            }
+        "#;
+        let e = expand_derive_from_row(&syn::parse_str(txt).unwrap());
+        assert_errors_with!(e, "decoded by position");
+    }
+
+    #[test]
+    fn it_derives_table() {
+        let txt = r#"
+            #[musq(table = "foos")]
+            struct Foo {
+                a: i32,
+                b: Option<String>,
+                c: Vec<u8>,
+                d: Custom,
+            }
+        "#;
+        let out = expand_derive_from_row(&syn::parse_str(txt).unwrap())
+            .unwrap()
+            .to_string();
+        assert!(out.contains("musq :: schema :: Table for Foo"));
+        assert!(out.contains("Affinity :: Integer"));
+        assert!(out.contains("Affinity :: Blob"));
+        assert!(out.contains("nullable : true"));
     }
 }
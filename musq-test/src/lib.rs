@@ -1,7 +1,24 @@
-use musq::{Connection, Musq};
+use std::{
+    env,
+    fmt::{Debug, Write},
+    fs,
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+use musq::{explain::QueryPlanNode, Connection, FromRow, Musq, Row};
+use rand::{distributions::Alphanumeric, Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
 
 const TEST_SCHEMA: &str = include_str!("setup.sql");
 
+/// Set this environment variable to write query plan snapshots instead of checking them. See
+/// [`assert_plan_snapshot`].
+pub const UPDATE_SNAPSHOTS_VAR: &str = "MUSQ_UPDATE_SNAPSHOTS";
+
+/// The seed used by [`TestDb`] unless another is given, so that generated data is the same on every run.
+pub const DEFAULT_SEED: u64 = 0x6d75_7371;
+
 // Make a new connection
 pub async fn connection() -> anyhow::Result<Connection> {
     Ok(Connection::connect_with(&Musq::new()).await?)
@@ -9,9 +26,205 @@ pub async fn connection() -> anyhow::Result<Connection> {
 
 /// Return a connection to a database pre-configured with our test schema.
 pub async fn tdb() -> anyhow::Result<Connection> {
-    let mut conn = connection().await?;
-    musq::query::query(TEST_SCHEMA).execute(&mut conn).await?;
-    Ok(conn)
+    Ok(TestDb::new().test_schema().connect().await?.into_inner())
+}
+
+/// A builder for test databases: an in-memory database with a schema applied, and a random number generator with a
+/// fixed seed for generating data, so that tests are reproducible.
+///
+/// ```rust,ignore
+/// let mut db = TestDb::new().schema("CREATE TABLE t (v TEXT)").connect().await?;
+/// let text = db.random_text(8);
+/// query("INSERT INTO t VALUES (?)").bind(&text).execute(&mut *db).await?;
+/// assert_rows_eq!(&mut *db, "SELECT v FROM t", [(text.as_str(),)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestDb {
+    seed: u64,
+    schema: Vec<String>,
+}
+
+impl Default for TestDb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestDb {
+    pub fn new() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            schema: Vec::new(),
+        }
+    }
+
+    /// Set the seed of the random number generator. Defaults to [`DEFAULT_SEED`].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Add SQL to run when the database is created. Schemas are applied in the order they were added.
+    pub fn schema(mut self, sql: impl Into<String>) -> Self {
+        self.schema.push(sql.into());
+        self
+    }
+
+    /// Add our test schema, with its `tweet`, `tweet_reply` and `products` tables.
+    pub fn test_schema(self) -> Self {
+        self.schema(TEST_SCHEMA)
+    }
+
+    /// Create the database and apply the schema.
+    pub async fn connect(self) -> anyhow::Result<TestConnection> {
+        let mut conn = connection().await?;
+        for sql in &self.schema {
+            musq::query::query(sql).execute(&mut conn).await?;
+        }
+        Ok(TestConnection {
+            conn,
+            seed: self.seed,
+            rng: Xoshiro256PlusPlus::seed_from_u64(self.seed),
+        })
+    }
+}
+
+/// A connection to a test database, created by [`TestDb`]. Dereferences to the [`Connection`].
+#[derive(Debug)]
+pub struct TestConnection {
+    conn: Connection,
+    seed: u64,
+    rng: Xoshiro256PlusPlus,
+}
+
+impl TestConnection {
+    /// The seed the random number generator started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The random number generator, for generating test data.
+    pub fn rng(&mut self) -> &mut Xoshiro256PlusPlus {
+        &mut self.rng
+    }
+
+    /// Generate a random alphanumeric string of `len` characters.
+    pub fn random_text(&mut self, len: usize) -> String {
+        (&mut self.rng)
+            .sample_iter(Alphanumeric)
+            .take(len)
+            .map(char::from)
+            .collect()
+    }
+
+    pub fn into_inner(self) -> Connection {
+        self.conn
+    }
+}
+
+impl Deref for TestConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl DerefMut for TestConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        &mut self.conn
+    }
+}
+
+/// Decode `rows` as the type of the `expected` rows, and assert that they are equal. Used by [`assert_rows_eq`].
+#[doc(hidden)]
+pub fn check_rows<'r, T>(sql: &str, rows: &'r [Row], expected: &[T])
+where
+    T: FromRow<'r> + PartialEq + Debug,
+{
+    let actual = rows
+        .iter()
+        .map(|row| T::from_row("", row))
+        .collect::<musq::Result<Vec<T>>>()
+        .unwrap_or_else(|e| panic!("failed to decode the rows of `{sql}`: {e}"));
+    assert_eq!(actual, expected, "rows of `{sql}`");
+}
+
+/// Run a query, and assert that it returns the expected rows, in order. Rows are written as tuples, and each column is
+/// decoded as the type of the corresponding tuple element. Must be used in a function that returns a `Result` that
+/// can hold a `musq::Error`.
+///
+/// ```rust,ignore
+/// assert_rows_eq!(&mut *conn, "SELECT id, text FROM tweet", [(1, "two")]);
+/// ```
+#[macro_export]
+macro_rules! assert_rows_eq {
+    ($executor:expr, $sql:expr, [$($row:expr),* $(,)?] $(,)?) => {{
+        let sql: &str = $sql;
+        let rows = musq::query(sql).fetch_all($executor).await?;
+        $crate::check_rows(sql, &rows, &[$($row),*]);
+    }};
+}
+
+/// Render a query plan as text, one step per line, with nested steps indented. Step ids are left out, since they
+/// change whenever SQLite's plan numbering does.
+pub fn render_plan(plan: &[QueryPlanNode]) -> String {
+    fn render(out: &mut String, node: &QueryPlanNode, depth: usize) {
+        writeln!(out, "{:indent$}{}", "", node.detail, indent = depth * 2).unwrap();
+        for child in &node.children {
+            render(out, child, depth + 1);
+        }
+    }
+    let mut out = String::new();
+    for node in plan {
+        render(&mut out, node, 0);
+    }
+    out
+}
+
+/// Compare a query plan with the snapshot `<dir>/<name>.plan`, and panic if it differs. The snapshot is written
+/// instead if it doesn't exist yet, or if [`UPDATE_SNAPSHOTS_VAR`] is set. Used by [`assert_plan_snapshot`].
+pub fn check_plan_snapshot(dir: impl AsRef<Path>, name: &str, plan: &[QueryPlanNode]) {
+    let path = dir.as_ref().join(format!("{name}.plan"));
+    let actual = render_plan(plan);
+    let expected = fs::read_to_string(&path).ok();
+    if expected.as_deref() == Some(actual.as_str()) {
+        return;
+    }
+    if expected.is_none() || env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+        fs::create_dir_all(dir.as_ref()).unwrap();
+        fs::write(&path, &actual)
+            .unwrap_or_else(|e| panic!("failed to write {}: {e}", path.display()));
+        return;
+    }
+    panic!(
+        "query plan `{name}` differs from {}\n\
+         expected:\n{}\n\
+         actual:\n{actual}\n\
+         set {UPDATE_SNAPSHOTS_VAR}=1 to update the snapshot",
+        path.display(),
+        expected.unwrap_or_default(),
+    );
+}
+
+/// Explain a query on a connection, and compare its plan with a named snapshot in the calling crate's
+/// `tests/snapshots` directory. This guards against changes to a schema or query that stop it using an index. Run
+/// the tests with `MUSQ_UPDATE_SNAPSHOTS=1` to accept new plans. Must be used in a function that returns a `Result`
+/// that can hold a `musq::Error`.
+///
+/// ```rust,ignore
+/// assert_plan_snapshot!(conn, "users_by_email", query("SELECT * FROM users WHERE email = ?").bind(email));
+/// ```
+#[macro_export]
+macro_rules! assert_plan_snapshot {
+    ($conn:expr, $name:expr, $query:expr $(,)?) => {{
+        let plan = $conn.explain($query).await?;
+        $crate::check_plan_snapshot(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots"),
+            $name,
+            &plan,
+        );
+    }};
 }
 
 // Test type encoding and decoding
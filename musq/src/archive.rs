@@ -0,0 +1,308 @@
+//! Archiving old rows out of time-series tables.
+//!
+//! An [`Archiver`] moves rows older than a cutoff out of a table, grouping them by period (day, month or year) into
+//! either per-period database files or per-period tables alongside the original. Rows are moved in batched
+//! transactions, so the archiver can run against a live database without holding long write locks, and a failure
+//! part-way leaves every row in exactly one place.
+//!
+//! For [`Destination::Files`], that relies on SQLite committing a transaction across the main and archive databases
+//! atomically, which it only does when both use a rollback journal (`DELETE`, `TRUNCATE` or `PERSIST`). In WAL mode
+//! each database commits on its own, and a crash between the two commits can lose the rows being moved, so archiving
+//! to files fails with [`Error::Configuration`] unless both databases use a rollback journal. Archiving to
+//! [`Destination::Tables`] works in any journal mode.
+//!
+//! ```rust,ignore
+//! let moved = Archiver::new(pool, "samples", "ts")
+//!     .period(Period::Month)
+//!     .destination(Destination::Files("archive/".into()))
+//!     .on_progress(|p| println!("{}: {} of {}", p.period, p.archived, p.total))
+//!     .run("2024-01-01")
+//!     .await?;
+//! ```
+//!
+//! Archive tables are created with `CREATE TABLE ... AS SELECT`, so they keep the source table's columns and declared
+//! types but not its constraints or indexes. Rows are selected by `rowid`, so `WITHOUT ROWID` tables aren't supported.
+//! Rows whose time can't be parsed into a period are left in place.
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+    debugfn::DebugFn, encode::Encode, query, query_scalar, schema::quote_identifier, Connection,
+    Error, Pool, Result,
+};
+
+/// The period by which archived rows are grouped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Month,
+    Year,
+}
+
+impl Period {
+    fn format(&self) -> &'static str {
+        match self {
+            Period::Day => "%Y-%m-%d",
+            Period::Month => "%Y-%m",
+            Period::Year => "%Y",
+        }
+    }
+}
+
+/// How the time column is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Text in one of the formats understood by SQLite's date functions, e.g. `2024-01-31 12:00:00`.
+    Text,
+    /// Seconds since the Unix epoch.
+    UnixSeconds,
+    /// Milliseconds since the Unix epoch.
+    UnixMillis,
+}
+
+/// Where archived rows go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    /// A database file per period, named `{table}-{period}.db`, in the given directory. The directory is created if
+    /// needed.
+    Files(PathBuf),
+    /// A table per period in the same database, named `{table}_{period}` with dashes replaced by underscores.
+    Tables,
+}
+
+/// Progress of an archiving run, reported after each batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// The period being archived, e.g. `2024-01` for monthly periods.
+    pub period: String,
+    /// Rows moved so far for this period.
+    pub archived: u64,
+    /// Rows moved so far in this run.
+    pub total: u64,
+}
+
+type ProgressCallback = dyn Fn(&Progress) + Send + Sync + 'static;
+
+/// Moves old rows out of a table. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Archiver {
+    pool: Pool,
+    table: String,
+    column: String,
+    period: Period,
+    time_format: TimeFormat,
+    destination: Destination,
+    batch_size: u32,
+    on_progress: Option<Arc<DebugFn<ProgressCallback>>>,
+}
+
+impl Archiver {
+    /// Archive rows of `table` based on the time stored in `column`.
+    pub fn new(pool: Pool, table: &str, column: &str) -> Self {
+        Self {
+            pool,
+            table: table.into(),
+            column: column.into(),
+            period: Period::Month,
+            time_format: TimeFormat::Text,
+            destination: Destination::Tables,
+            batch_size: 1000,
+            on_progress: None,
+        }
+    }
+
+    /// The period by which rows are grouped. The default is [`Period::Month`].
+    pub fn period(mut self, period: Period) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// How the time column is stored. The default is [`TimeFormat::Text`].
+    pub fn time_format(mut self, format: TimeFormat) -> Self {
+        self.time_format = format;
+        self
+    }
+
+    /// Where archived rows go. The default is [`Destination::Tables`].
+    pub fn destination(mut self, destination: Destination) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    /// The maximum number of rows moved per transaction. The default is 1000.
+    pub fn batch_size(mut self, n: u32) -> Self {
+        self.batch_size = n.max(1);
+        self
+    }
+
+    /// Call `callback` after each batch is committed.
+    pub fn on_progress(mut self, callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(DebugFn(callback)));
+        self
+    }
+
+    /// Move every row whose time column is less than `cutoff`, which must be in the column's own format. Returns the
+    /// number of rows moved.
+    pub async fn run<T>(&self, cutoff: T) -> Result<u64>
+    where
+        T: Encode + Clone + Send + Sync + 'static,
+    {
+        let table = quote_identifier(&self.table);
+        let column = quote_identifier(&self.column);
+        let key = match self.time_format {
+            TimeFormat::Text => format!("strftime('{}', {column})", self.period.format()),
+            TimeFormat::UnixSeconds => {
+                format!(
+                    "strftime('{}', {column}, 'unixepoch')",
+                    self.period.format()
+                )
+            }
+            TimeFormat::UnixMillis => format!(
+                "strftime('{}', {column} / 1000, 'unixepoch')",
+                self.period.format()
+            ),
+        };
+        let periods: Vec<String> = query_scalar(&format!(
+            "SELECT DISTINCT {key} FROM main.{table} WHERE {column} < ? AND {key} IS NOT NULL ORDER BY 1"
+        ))
+        .bind(cutoff.clone())
+        .fetch_all(&self.pool)
+        .await?;
+
+        if let Destination::Files(dir) = &self.destination {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        let mut total = 0;
+        for period in periods {
+            let dest = match &self.destination {
+                Destination::Files(_) => format!("\"archive\".{table}"),
+                Destination::Tables => format!(
+                    "main.{}",
+                    quote_identifier(&format!("{}_{}", self.table, period.replace('-', "_")))
+                ),
+            };
+            let batch = Batch {
+                create: format!(
+                    "CREATE TABLE IF NOT EXISTS {dest} AS SELECT * FROM main.{table} WHERE 0"
+                ),
+                copy: format!(
+                    "INSERT INTO {dest} SELECT * FROM main.{table} WHERE rowid IN (
+                        SELECT rowid FROM main.{table} WHERE {column} < ? AND {key} = ? ORDER BY rowid LIMIT ?
+                    )"
+                ),
+                delete: format!(
+                    "DELETE FROM main.{table} WHERE rowid IN (
+                        SELECT rowid FROM main.{table} WHERE {column} < ? AND {key} = ? ORDER BY rowid LIMIT ?
+                    )"
+                ),
+                period: period.clone(),
+                limit: self.batch_size,
+            };
+
+            match &self.destination {
+                Destination::Files(dir) => {
+                    let path = dir.join(format!("{}-{}.db", self.table, period));
+                    // SQLite treats an empty file as an empty database, so this lets us attach databases that don't
+                    // exist yet without requiring the pool to have been opened with `create_if_missing`.
+                    tokio::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&path)
+                        .await?;
+                    conn.attach(&path, "archive").await?;
+                    let moved = match check_atomic(&mut conn).await {
+                        Ok(()) => self.drain(&mut conn, &batch, &cutoff, &mut total).await,
+                        Err(e) => Err(e),
+                    };
+                    let detached = Connection::detach(&mut conn, "archive").await;
+                    moved?;
+                    detached?;
+                }
+                Destination::Tables => self.drain(&mut conn, &batch, &cutoff, &mut total).await?,
+            }
+        }
+        Ok(total)
+    }
+
+    /// Move the rows of one period, a batch per transaction, adding the number moved to `total`.
+    async fn drain<T: Encode + Clone + Send>(
+        &self,
+        conn: &mut Connection,
+        batch: &Batch,
+        cutoff: &T,
+        total: &mut u64,
+    ) -> Result<()> {
+        let mut archived = 0;
+        loop {
+            let mut tx = conn.begin().await?;
+            let moved = batch.run(&mut tx, cutoff.clone()).await?;
+            tx.commit().await?;
+
+            archived += moved;
+            *total += moved;
+            if moved > 0 {
+                if let Some(callback) = &self.on_progress {
+                    callback(&Progress {
+                        period: batch.period.clone(),
+                        archived,
+                        total: *total,
+                    });
+                }
+            }
+            if moved < u64::from(self.batch_size) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Fail unless SQLite commits transactions spanning the main and archive databases atomically.
+async fn check_atomic(conn: &mut Connection) -> Result<()> {
+    for schema in ["main", "archive"] {
+        let mode: String = query_scalar(&format!("PRAGMA {schema}.journal_mode"))
+            .fetch_one(&mut *conn)
+            .await?;
+        if !matches!(
+            mode.to_ascii_lowercase().as_str(),
+            "delete" | "truncate" | "persist"
+        ) {
+            return Err(Error::Configuration(format!(
+                "archiving to files needs a rollback journal, but the {schema} database uses {mode}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The statements that move one batch of rows for a period.
+struct Batch {
+    create: String,
+    copy: String,
+    delete: String,
+    period: String,
+    limit: u32,
+}
+
+impl Batch {
+    async fn run<T: Encode + Clone + Send>(
+        &self,
+        conn: &mut Connection,
+        cutoff: T,
+    ) -> Result<u64, Error> {
+        query(&self.create).execute(&mut *conn).await?;
+        query(&self.copy)
+            .bind(cutoff.clone())
+            .bind(self.period.clone())
+            .bind(self.limit)
+            .execute(&mut *conn)
+            .await?;
+        let deleted = query(&self.delete)
+            .bind(cutoff)
+            .bind(self.period.clone())
+            .bind(self.limit)
+            .execute(&mut *conn)
+            .await?;
+        Ok(deleted.rows_affected())
+    }
+}
@@ -0,0 +1,241 @@
+//! Online backups with the [SQLite backup API](https://www.sqlite.org/backup.html).
+//!
+//! [`Connection::backup_to`] copies the connection's main database into a file, and [`Connection::backup_from`]
+//! replaces it with the contents of one. Pages are copied in chunks on the connection's worker thread, so the async
+//! runtime isn't blocked, and other connections can keep reading and writing between chunks. If the source database
+//! is written to by another connection part-way through, the backup restarts from the beginning, so the result is
+//! always a consistent snapshot. This makes it the right way to copy a live WAL database, whose committed state may be
+//! partly in the `-wal` file.
+//!
+//! ```rust,ignore
+//! conn.backup_to("snapshot.db")
+//!     .pages_per_step(1000)
+//!     .on_progress(|p| println!("{} of {} pages left", p.remaining, p.total))
+//!     .run()
+//!     .await?;
+//! ```
+use std::{
+    ffi::{CStr, CString},
+    path::{Path, PathBuf},
+    ptr,
+    sync::Arc,
+    time::Duration,
+};
+
+use libsqlite3_sys::{
+    sqlite3, sqlite3_backup, sqlite3_backup_finish, sqlite3_backup_init, sqlite3_backup_pagecount,
+    sqlite3_backup_remaining, sqlite3_backup_step, sqlite3_close, sqlite3_errstr,
+    sqlite3_file_control, sqlite3_open_v2, sqlite3_vfs, SQLITE_BUSY, SQLITE_DONE,
+    SQLITE_FCNTL_VFS_POINTER, SQLITE_LOCKED, SQLITE_OK, SQLITE_OPEN_CREATE, SQLITE_OPEN_READONLY,
+    SQLITE_OPEN_READWRITE,
+};
+
+use crate::{debugfn::DebugFn, Connection, Error, Result, SqliteError};
+
+/// How far a backup has got, reported after each step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Pages still to be copied.
+    pub remaining: u32,
+    /// Pages in the source database.
+    pub total: u32,
+}
+
+type ProgressCallback = dyn Fn(Progress) + Send + Sync + 'static;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    To,
+    From,
+}
+
+/// A pending backup, returned by [`Connection::backup_to`] and [`Connection::backup_from`]. Nothing happens until
+/// [`run`](Self::run) is awaited.
+#[derive(Debug)]
+#[must_use = "backups do nothing unless run"]
+pub struct Backup<'c> {
+    conn: &'c mut Connection,
+    path: PathBuf,
+    direction: Direction,
+    pages_per_step: u32,
+    busy_wait: Duration,
+    on_progress: Option<Arc<DebugFn<ProgressCallback>>>,
+}
+
+impl<'c> Backup<'c> {
+    pub(crate) fn to(conn: &'c mut Connection, path: &Path) -> Self {
+        Self::new(conn, path, Direction::To)
+    }
+
+    pub(crate) fn from(conn: &'c mut Connection, path: &Path) -> Self {
+        Self::new(conn, path, Direction::From)
+    }
+
+    fn new(conn: &'c mut Connection, path: &Path, direction: Direction) -> Self {
+        Self {
+            conn,
+            path: path.to_path_buf(),
+            direction,
+            pages_per_step: 100,
+            busy_wait: Duration::from_millis(10),
+            on_progress: None,
+        }
+    }
+
+    /// The number of pages copied per step. Smaller steps let other connections in more often, larger ones finish
+    /// sooner. The default is 100.
+    pub fn pages_per_step(mut self, n: u32) -> Self {
+        self.pages_per_step = n.max(1);
+        self
+    }
+
+    /// How long to wait before retrying a step that found the database locked. The default is 10ms.
+    pub fn busy_wait(mut self, wait: Duration) -> Self {
+        self.busy_wait = wait;
+        self
+    }
+
+    /// Call `callback` after each step.
+    pub fn on_progress(mut self, callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(DebugFn(callback)));
+        self
+    }
+
+    /// Run the backup to completion.
+    pub async fn run(self) -> Result<()> {
+        let path = CString::new(self.path.to_string_lossy().into_owned())
+            .map_err(|_| Error::Protocol("backup path contains nul bytes".into()))?;
+        let direction = self.direction;
+        let mut backup = self
+            .conn
+            .worker
+            .run(move |conn| RawBackup::init(conn.handle.as_ptr(), &path, direction))
+            .await??;
+
+        let pages = self.pages_per_step as i32;
+        loop {
+            let (rc, progress, returned) = self
+                .conn
+                .worker
+                .run(move |_| {
+                    let rc = unsafe { sqlite3_backup_step(backup.backup, pages) };
+                    let progress = backup.progress();
+                    (rc, progress, backup)
+                })
+                .await?;
+            backup = returned;
+
+            match rc {
+                SQLITE_OK | SQLITE_DONE => {
+                    if let Some(callback) = &self.on_progress {
+                        callback(progress);
+                    }
+                    if rc == SQLITE_DONE {
+                        break;
+                    }
+                    tokio::task::yield_now().await;
+                }
+                SQLITE_BUSY | SQLITE_LOCKED => tokio::time::sleep(self.busy_wait).await,
+                rc => return Err(error(rc).into()),
+            }
+        }
+
+        let rc = self.conn.worker.run(move |_| backup.finish()).await?;
+        if rc != SQLITE_OK {
+            return Err(error(rc).into());
+        }
+        Ok(())
+    }
+}
+
+fn error(rc: i32) -> SqliteError {
+    let message = unsafe { CStr::from_ptr(sqlite3_errstr(rc)) };
+    SqliteError::from_code(rc, message.to_string_lossy())
+}
+
+/// A backup in progress, along with the handle for the file on the other side of it.
+struct RawBackup {
+    /// The connection to the file, which we own.
+    file: *mut sqlite3,
+    backup: *mut sqlite3_backup,
+}
+
+// The handles are only used on the worker thread of the connection they belong to, or once that connection is idle.
+unsafe impl Send for RawBackup {}
+
+impl RawBackup {
+    fn init(conn: *mut sqlite3, path: &CStr, direction: Direction) -> Result<Self> {
+        let flags = match direction {
+            Direction::To => SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE,
+            Direction::From => SQLITE_OPEN_READONLY,
+        };
+        // Use the connection's VFS, so that, for instance, backups of encrypted databases are encrypted too
+        let mut vfs: *mut sqlite3_vfs = ptr::null_mut();
+        let rc = unsafe {
+            sqlite3_file_control(
+                conn,
+                c"main".as_ptr(),
+                SQLITE_FCNTL_VFS_POINTER,
+                (&mut vfs as *mut *mut sqlite3_vfs).cast(),
+            )
+        };
+        let vfs_name = if rc == SQLITE_OK && !vfs.is_null() {
+            unsafe { (*vfs).zName }
+        } else {
+            ptr::null()
+        };
+
+        let mut file = ptr::null_mut();
+        let rc = unsafe { sqlite3_open_v2(path.as_ptr(), &mut file, flags, vfs_name) };
+        if rc != SQLITE_OK {
+            let err = if file.is_null() {
+                error(rc)
+            } else {
+                SqliteError::new(file)
+            };
+            unsafe { sqlite3_close(file) };
+            return Err(err.into());
+        }
+
+        let (dest, source) = match direction {
+            Direction::To => (file, conn),
+            Direction::From => (conn, file),
+        };
+        let backup =
+            unsafe { sqlite3_backup_init(dest, c"main".as_ptr(), source, c"main".as_ptr()) };
+        if backup.is_null() {
+            // Errors are recorded on the destination
+            let err = SqliteError::new(dest);
+            unsafe { sqlite3_close(file) };
+            return Err(err.into());
+        }
+        Ok(Self { file, backup })
+    }
+
+    fn progress(&self) -> Progress {
+        unsafe {
+            Progress {
+                remaining: sqlite3_backup_remaining(self.backup) as u32,
+                total: sqlite3_backup_pagecount(self.backup) as u32,
+            }
+        }
+    }
+
+    /// Release the backup and close the file, returning the backup's result.
+    fn finish(mut self) -> i32 {
+        let rc = unsafe { sqlite3_backup_finish(self.backup) };
+        self.backup = ptr::null_mut();
+        rc
+    }
+}
+
+impl Drop for RawBackup {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.backup.is_null() {
+                sqlite3_backup_finish(self.backup);
+            }
+            sqlite3_close(self.file);
+        }
+    }
+}
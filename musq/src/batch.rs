@@ -0,0 +1,176 @@
+//! Coalesce many small writes into periodic transactions.
+//!
+//! SQLite commits are expensive relative to small writes, so services that ingest a high frequency of small
+//! INSERT/UPDATE statements from many tasks (telemetry, event logs) can gain a great deal of throughput by grouping
+//! them into shared transactions. [`WriteBatcher`] does this transparently: each caller submits a single query and
+//! awaits its own outcome.
+use std::{sync::Arc, time::Duration};
+
+use futures_channel::oneshot;
+use tokio::time::Instant;
+
+use crate::{query::Query, Arguments, Error, Pool, QueryResult, Result};
+
+struct Write {
+    query: Query<Arguments>,
+    tx: oneshot::Sender<Result<QueryResult>>,
+}
+
+/// Options for a [`WriteBatcher`].
+#[derive(Debug, Clone)]
+pub struct WriteBatcherOptions {
+    max_batch: usize,
+    max_delay: Duration,
+    queue_size: usize,
+}
+
+impl Default for WriteBatcherOptions {
+    fn default() -> Self {
+        Self {
+            max_batch: 100,
+            max_delay: Duration::from_millis(10),
+            queue_size: 1024,
+        }
+    }
+}
+
+impl WriteBatcherOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The maximum number of writes to group into a single transaction. The default is 100.
+    pub fn max_batch(mut self, n: usize) -> Self {
+        self.max_batch = n.max(1);
+        self
+    }
+
+    /// The maximum time to wait for further writes after the first write of a batch arrives. The default is 10ms.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// The number of writes that may be queued before [`WriteBatcher::execute`] applies backpressure. The default is
+    /// 1024.
+    pub fn queue_size(mut self, n: usize) -> Self {
+        self.queue_size = n.max(1);
+        self
+    }
+}
+
+/// Accepts individual writes from many tasks and commits them together in periodic transactions.
+///
+/// A batch is committed once it holds [`max_batch`](WriteBatcherOptions::max_batch) writes, or
+/// [`max_delay`](WriteBatcherOptions::max_delay) after its first write arrived, whichever comes first. Writes are
+/// executed in the order they were submitted.
+///
+/// Each write is executed as an individual statement inside the batch transaction. If a statement fails, only that
+/// caller receives the error - SQLite rolls back the failed statement and the rest of the batch proceeds. If the batch
+/// as a whole fails (the transaction could not be started or committed), every caller in the batch receives
+/// [`Error::BatchFailed`]. So does every caller when a failing statement ends the transaction itself, for instance
+/// through `RAISE(ROLLBACK)` or an `ON CONFLICT ROLLBACK` constraint: the writes before it have been undone, and the
+/// writes after it are not executed.
+///
+/// The batcher runs on a background task, which exits once every handle has been dropped and the queue is drained.
+///
+/// ```rust,ignore
+/// let batcher = WriteBatcher::new(pool.clone(), WriteBatcherOptions::new());
+/// batcher
+///     .execute(query("INSERT INTO events (kind) VALUES (?)").bind("click"))
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct WriteBatcher {
+    tx: flume::Sender<Write>,
+}
+
+impl WriteBatcher {
+    /// Start a batcher writing to `pool`. Must be called within a Tokio runtime, unless the pool has a
+    /// [`spawner`](crate::Musq::spawner).
+    pub fn new(pool: Pool, options: WriteBatcherOptions) -> Self {
+        let (tx, rx) = flume::bounded(options.queue_size);
+        let inner = pool.0.clone();
+        inner.options.spawn(run(pool, rx, options));
+        Self { tx }
+    }
+
+    /// Submit a write, and wait for the batch containing it to be committed.
+    pub async fn execute(&self, query: Query<Arguments>) -> Result<QueryResult> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send_async(Write { query, tx })
+            .await
+            .map_err(|_| Error::WorkerCrashed)?;
+        rx.await.map_err(|_| Error::WorkerCrashed)?
+    }
+}
+
+async fn run(pool: Pool, rx: flume::Receiver<Write>, options: WriteBatcherOptions) {
+    while let Ok(first) = rx.recv_async().await {
+        let deadline = Instant::now() + options.max_delay;
+        let mut batch = vec![first];
+        while batch.len() < options.max_batch {
+            match tokio::time::timeout_at(deadline, rx.recv_async()).await {
+                Ok(Ok(write)) => batch.push(write),
+                _ => break,
+            }
+        }
+        commit(&pool, batch).await;
+    }
+}
+
+async fn commit(pool: &Pool, batch: Vec<Write>) {
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return fail(batch, e),
+    };
+
+    let mut done = Vec::with_capacity(batch.len());
+    let mut writes = batch.into_iter();
+    while let Some(write) = writes.next() {
+        let e = match write.query.execute(&mut *tx).await {
+            Ok(result) => {
+                done.push((write.tx, result));
+                continue;
+            }
+            Err(e) => e,
+        };
+        // Without a transaction, the writes so far are lost and the rest would each commit on their own
+        if tx.is_autocommit().await.unwrap_or(true) {
+            tx.rollback().await.ok();
+            let e = Arc::new(e);
+            let callers = done
+                .into_iter()
+                .map(|(tx, _)| tx)
+                .chain([write.tx])
+                .chain(writes.map(|w| w.tx));
+            for tx in callers {
+                tx.send(Err(Error::BatchFailed(e.clone()))).ok();
+            }
+            return;
+        }
+        write.tx.send(Err(e)).ok();
+    }
+
+    match tx.commit().await {
+        Ok(()) => {
+            for (tx, result) in done {
+                tx.send(Ok(result)).ok();
+            }
+        }
+        Err(e) => {
+            let e = Arc::new(e);
+            for (tx, _) in done {
+                tx.send(Err(Error::BatchFailed(e.clone()))).ok();
+            }
+        }
+    }
+}
+
+fn fail(batch: Vec<Write>, e: Error) {
+    let e = Arc::new(e);
+    for write in batch {
+        write.tx.send(Err(Error::BatchFailed(e.clone()))).ok();
+    }
+}
@@ -0,0 +1,152 @@
+//! Streaming large blobs with [incremental blob I/O](https://www.sqlite.org/c3ref/blob_open.html).
+//!
+//! Reading a blob column with a query loads the whole value into memory. [`Connection::open_blob`] instead opens a
+//! single blob, identified by its table, column and rowid, and reads it a chunk at a time on the connection's worker
+//! thread. Use this to forward large blobs, for instance to an HTTP response, without holding them in memory.
+//!
+//! ```rust,ignore
+//! let mut chunks = conn.open_blob("files", "data", id).await?.into_stream(64 * 1024);
+//! while let Some(chunk) = chunks.try_next().await? {
+//!     body.send(chunk).await?;
+//! }
+//! ```
+//!
+//! If the row is changed or deleted while the blob is open, further reads fail with an `SQLITE_ABORT` error.
+use std::{ffi::CString, os::raw::c_void};
+
+use futures_core::stream::BoxStream;
+use libsqlite3_sys::{
+    sqlite3_blob, sqlite3_blob_bytes, sqlite3_blob_close, sqlite3_blob_open, sqlite3_blob_read,
+    SQLITE_OK,
+};
+
+use crate::{Connection, Error, Result, SqliteError};
+
+/// An open blob in the main database, returned by [`Connection::open_blob`]. The blob is closed when the reader is
+/// dropped.
+#[derive(Debug)]
+pub struct BlobReader<'c> {
+    conn: &'c mut Connection,
+    blob: Option<RawBlob>,
+    len: u64,
+    offset: u64,
+}
+
+impl<'c> BlobReader<'c> {
+    pub(crate) async fn open(
+        conn: &'c mut Connection,
+        table: &str,
+        column: &str,
+        rowid: i64,
+    ) -> Result<Self> {
+        let table = CString::new(table)
+            .map_err(|_| Error::Protocol("table name contains nul bytes".into()))?;
+        let column = CString::new(column)
+            .map_err(|_| Error::Protocol("column name contains nul bytes".into()))?;
+        let (blob, len) = conn
+            .worker
+            .run(move |conn| {
+                let db = conn.handle.as_ptr();
+                let mut blob = std::ptr::null_mut();
+                let rc = unsafe {
+                    sqlite3_blob_open(
+                        db,
+                        c"main".as_ptr(),
+                        table.as_ptr(),
+                        column.as_ptr(),
+                        rowid,
+                        0,
+                        &mut blob,
+                    )
+                };
+                if rc != SQLITE_OK {
+                    return Err(Error::from(SqliteError::new(db)));
+                }
+                let len = unsafe { sqlite3_blob_bytes(blob) };
+                Ok((RawBlob(blob), len as u64))
+            })
+            .await??;
+        Ok(Self {
+            conn,
+            blob: Some(blob),
+            len,
+            offset: 0,
+        })
+    }
+
+    /// The size of the blob in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Read the next chunk of up to `max` bytes, or `None` once the whole blob has been read.
+    pub async fn read_chunk(&mut self, max: usize) -> Result<Option<Vec<u8>>> {
+        let n = (self.len - self.offset).min(max.max(1) as u64) as usize;
+        if n == 0 {
+            return Ok(None);
+        }
+        // The blob is only missing if an earlier read was cancelled, in which case it was closed on the worker
+        let Some(blob) = self.blob.take() else {
+            return Err(Error::Protocol(
+                "blob reader used after a cancelled read".into(),
+            ));
+        };
+        let offset = self.offset as i32;
+        let (res, blob) = self
+            .conn
+            .worker
+            .run(move |conn| {
+                let mut buf = vec![0u8; n];
+                let rc = unsafe {
+                    sqlite3_blob_read(blob.0, buf.as_mut_ptr() as *mut c_void, n as i32, offset)
+                };
+                let res = if rc == SQLITE_OK {
+                    Ok(buf)
+                } else {
+                    Err(Error::from(SqliteError::new(conn.handle.as_ptr())))
+                };
+                (res, blob)
+            })
+            .await?;
+        self.blob = Some(blob);
+        let buf = res?;
+        self.offset += n as u64;
+        Ok(Some(buf))
+    }
+
+    /// Read the rest of the blob as a stream of chunks of up to `chunk_size` bytes.
+    pub fn into_stream(mut self, chunk_size: usize) -> BoxStream<'c, Result<Vec<u8>>> {
+        Box::pin(try_stream! {
+            while let Some(chunk) = self.read_chunk(chunk_size).await? {
+                r#yield!(chunk);
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Drop for BlobReader<'_> {
+    fn drop(&mut self) {
+        if let Some(blob) = self.blob.take() {
+            // Close the blob on the worker, which can only fail if the worker is gone
+            let _ = self.conn.worker.handle().start_run(move |_| drop(blob));
+        }
+    }
+}
+
+/// An open blob handle, closed when dropped. It must be dropped on the worker thread of its connection.
+#[derive(Debug)]
+struct RawBlob(*mut sqlite3_blob);
+
+// The handle is only used on the worker thread of the connection it belongs to.
+unsafe impl Send for RawBlob {}
+
+impl Drop for RawBlob {
+    fn drop(&mut self) {
+        unsafe { sqlite3_blob_close(self.0) };
+    }
+}
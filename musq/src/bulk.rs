@@ -0,0 +1,186 @@
+//! Bulk inserts and lookups.
+//!
+//! Inserting rows one statement at a time is slow, and a single multi-row `INSERT` can only bind as many values as
+//! SQLite's variable limit allows. [`Query::bind_all`](crate::query::Query::bind_all) expands rows into multi-row
+//! `INSERT ... VALUES (...), (...)` statements, each as large as the limit allows, and runs them in one transaction.
+//!
+//! ```rust,ignore
+//! let rows = (0..50_000).map(|i| (i, format!("name {i}")));
+//! let inserted = query("INSERT INTO t (id, name) VALUES")
+//!     .bind_all(rows)
+//!     .execute(&mut conn)
+//!     .await?;
+//! ```
+//!
+//! Looking rows up by a list of composite keys hits the same limit. [`fetch_in`] splits the keys into chunks, runs a
+//! query for each chunk with the chunk's keys as a [`Fragment::values`] list, and collects the results:
+//!
+//! ```rust,ignore
+//! let keys = [(1, "a"), (2, "b")];
+//! let rows: Vec<(i64, String, i64)> = bulk::fetch_in(&mut conn, &keys, |values| {
+//!     fragment!("SELECT a, b, v FROM t WHERE (a, b) IN ({values:frag})")
+//! })
+//! .await?;
+//! ```
+use libsqlite3_sys::{sqlite3_limit, SQLITE_LIMIT_VARIABLE_NUMBER};
+
+use crate::{
+    encode::Encode, query_with, Arguments, Connection, Executor, Fragment, FromRow, QueryBuilder,
+    Result,
+};
+
+/// A row of values for a bulk insert or lookup. Implemented for tuples of up to 16 [`Encode`] values, and for
+/// references to them.
+pub trait Values: Send {
+    /// The number of values in the row.
+    const LEN: usize;
+
+    /// Add the values to `arguments`, in order.
+    fn add_to(self, arguments: &mut Arguments);
+}
+
+macro_rules! impl_values_for_tuple {
+    ($len:expr; $($T:ident $idx:tt),+) => {
+        impl<$($T: Encode + Send),+> Values for ($($T,)+) {
+            const LEN: usize = $len;
+
+            fn add_to(self, arguments: &mut Arguments) {
+                $(arguments.add(self.$idx);)+
+            }
+        }
+    };
+}
+
+impl<T> Values for &T
+where
+    T: Values + Clone + Sync,
+{
+    const LEN: usize = T::LEN;
+
+    fn add_to(self, arguments: &mut Arguments) {
+        self.clone().add_to(arguments);
+    }
+}
+
+impl_values_for_tuple!(1; T1 0);
+impl_values_for_tuple!(2; T1 0, T2 1);
+impl_values_for_tuple!(3; T1 0, T2 1, T3 2);
+impl_values_for_tuple!(4; T1 0, T2 1, T3 2, T4 3);
+impl_values_for_tuple!(5; T1 0, T2 1, T3 2, T4 3, T5 4);
+impl_values_for_tuple!(6; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5);
+impl_values_for_tuple!(7; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6);
+impl_values_for_tuple!(8; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7);
+impl_values_for_tuple!(9; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8);
+impl_values_for_tuple!(10; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9);
+impl_values_for_tuple!(11; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10);
+impl_values_for_tuple!(12; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11);
+impl_values_for_tuple!(13; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12);
+impl_values_for_tuple!(14; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12,
+    T14 13);
+impl_values_for_tuple!(15; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12,
+    T14 13, T15 14);
+impl_values_for_tuple!(16; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11, T13 12,
+    T14 13, T15 14, T16 15);
+
+/// A bulk insert, returned by [`Query::bind_all`](crate::query::Query::bind_all).
+#[must_use = "query must be executed to affect database"]
+#[derive(Debug)]
+pub struct BulkInsert<I> {
+    sql: String,
+    rows: I,
+}
+
+impl<I> BulkInsert<I>
+where
+    I: IntoIterator,
+    I::Item: Values,
+{
+    pub(crate) fn new(sql: &str, rows: I) -> Self {
+        Self {
+            sql: sql.trim_end().to_string(),
+            rows,
+        }
+    }
+
+    /// Insert all the rows, returning the number of rows inserted.
+    ///
+    /// The rows are split into statements that each bind as many values as the connection's
+    /// [variable limit](https://www.sqlite.org/limits.html#max_variable_number) allows. All statements run in a single
+    /// transaction, or a savepoint if a transaction is already open, so either every row is inserted or none is.
+    pub async fn execute(self, conn: &mut Connection) -> Result<u64> {
+        let max_variables = max_variables(conn).await?;
+        let mut rows = self.rows.into_iter().peekable();
+        if rows.peek().is_none() {
+            return Ok(0);
+        }
+        let chunk_rows = (max_variables / I::Item::LEN).max(1);
+        let placeholders = format!("({})", vec!["?"; I::Item::LEN].join(", "));
+
+        let mut tx = conn.begin().await?;
+        let mut inserted = 0;
+        while rows.peek().is_some() {
+            let mut arguments = Arguments::default();
+            let mut n = 0;
+            for row in rows.by_ref().take(chunk_rows) {
+                row.add_to(&mut arguments);
+                n += 1;
+            }
+            let sql = format!("{} {}", self.sql, vec![placeholders.as_str(); n].join(", "));
+            inserted += (&mut *tx)
+                .execute(query_with(&sql, arguments))
+                .await?
+                .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+}
+
+/// Fetch the rows matching a list of keys, in as few queries as the connection's
+/// [variable limit](https://www.sqlite.org/limits.html#max_variable_number) allows.
+///
+/// `build` returns the query for a chunk of keys, given a [`Fragment::values`] list of the chunk's keys. It may bind
+/// other values too; it is called once with an empty list to count them. The results of the queries are concatenated
+/// in chunk order, so an `ORDER BY` only orders rows within a chunk. Keys that appear in more than one chunk can
+/// return the same row more than once.
+pub async fn fetch_in<I, O, F>(conn: &mut Connection, keys: I, mut build: F) -> Result<Vec<O>>
+where
+    I: IntoIterator,
+    I::Item: Values,
+    O: for<'r> FromRow<'r> + Send + Unpin,
+    F: FnMut(Fragment) -> Fragment,
+{
+    let mut keys = keys.into_iter().peekable();
+    if keys.peek().is_none() {
+        return Ok(Vec::new());
+    }
+    let others = build(Fragment::default()).bound();
+    let available = max_variables(conn).await?.saturating_sub(others);
+    let chunk_keys = (available / I::Item::LEN).max(1);
+
+    let mut rows = Vec::new();
+    while keys.peek().is_some() {
+        let values = Fragment::values(keys.by_ref().take(chunk_keys));
+        let chunk: Vec<O> = QueryBuilder::from(build(values))
+            .build_query_as()
+            .fetch_all(&mut *conn)
+            .await?;
+        rows.extend(chunk);
+    }
+    Ok(rows)
+}
+
+/// The most values a statement can bind on this connection.
+async fn max_variables(conn: &mut Connection) -> Result<usize> {
+    let max_variables = {
+        let mut handle = conn.lock_handle().await?;
+        unsafe {
+            sqlite3_limit(
+                handle.as_raw_handle().as_ptr(),
+                SQLITE_LIMIT_VARIABLE_NUMBER,
+                -1,
+            )
+        }
+    };
+    Ok(usize::try_from(max_variables).unwrap_or(0).max(1))
+}
@@ -0,0 +1,297 @@
+//! A read-through cache for query results.
+//!
+//! [`QueryCache`] memoizes the results of read queries, keyed by SQL and bound arguments. Each entry remembers the
+//! tables its query read, and is discarded as soon as a transaction that modifies one of those tables commits through
+//! the pool. The pool must be opened with [`Musq::track_changes`](crate::Musq::track_changes).
+//!
+//! ```rust,ignore
+//! let pool = Musq::new().track_changes(true).open("app.db").await?;
+//! let cache = QueryCache::new(&pool, 1000)?;
+//!
+//! let users: Vec<User> = cache.query_as("SELECT * FROM users WHERE org = ?").bind(org).fetch_all().await?;
+//! ```
+//!
+//! Writes made outside the pool - by another process, or a connection opened separately - are not seen, and entries
+//! read before such writes will be served stale.
+use std::{
+    any::{Any, TypeId},
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+use hashlink::lru_cache::LruCache;
+
+use crate::{
+    encode::Encode, query_as, query_as_with, sqlite::ChangeTracker, ArgumentValue, Arguments,
+    Error, FromRow, Pool, Result,
+};
+
+/// The tables a query reads from.
+#[derive(Debug)]
+enum ReadSet {
+    Tables(Vec<String>),
+    /// The tables could not be determined, so any change invalidates the entry.
+    Unknown,
+}
+
+#[derive(Hash, PartialEq, Eq)]
+struct Key {
+    output: TypeId,
+    sql: String,
+    arguments: Vec<u8>,
+}
+
+struct Entry {
+    version: u64,
+    reads: Arc<ReadSet>,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+struct Inner {
+    entries: LruCache<Key, Entry>,
+    /// The tables read by each query, bounded like the entries, since the queries can be built at runtime.
+    reads: LruCache<String, Arc<ReadSet>>,
+}
+
+/// A cache of query results, invalidated when the tables they read are modified.
+///
+/// Cloning a `QueryCache` is cheap, and clones share their entries.
+#[derive(Clone)]
+pub struct QueryCache {
+    pool: Pool,
+    tracker: Arc<ChangeTracker>,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl fmt::Debug for QueryCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryCache")
+            .field("pool", &self.pool)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl QueryCache {
+    /// Create a cache holding up to `capacity` results. Returns [`Error::Configuration`] if the pool does not have
+    /// change tracking enabled.
+    pub fn new(pool: &Pool, capacity: usize) -> Result<Self> {
+        let tracker = pool.change_tracker().ok_or_else(|| {
+            Error::Configuration(
+                "QueryCache requires a pool opened with track_changes(true)".into(),
+            )
+        })?;
+        Ok(Self {
+            pool: pool.clone(),
+            tracker,
+            inner: Arc::new(Mutex::new(Inner {
+                entries: LruCache::new(capacity.max(1)),
+                reads: LruCache::new(capacity.max(1)),
+            })),
+        })
+    }
+
+    /// Make a cached query, mapping rows to `T`.
+    pub fn query_as<T>(&self, sql: &str) -> CachedQuery<'_, T> {
+        CachedQuery {
+            cache: self,
+            sql: sql.into(),
+            arguments: Arguments::default(),
+            output: PhantomData,
+        }
+    }
+
+    /// The number of cached results, including any that are stale but have not yet been evicted.
+    pub fn len(&self) -> usize {
+        self.inner
+            .lock()
+            .map(|i| i.entries.len())
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Discard all cached results.
+    pub fn clear(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.entries.clear();
+            inner.reads.clear();
+        }
+    }
+
+    fn is_fresh(&self, entry: &Entry) -> bool {
+        match &*entry.reads {
+            ReadSet::Tables(tables) => tables
+                .iter()
+                .all(|t| self.tracker.table_version(t) <= entry.version),
+            ReadSet::Unknown => self.tracker.version() <= entry.version,
+        }
+    }
+
+    fn get<V: Clone + 'static>(&self, key: &Key) -> Option<V> {
+        let mut inner = self.inner.lock().ok()?;
+        let entry = inner.entries.get(key)?;
+        if self.is_fresh(entry) {
+            return entry.value.downcast_ref::<V>().cloned();
+        }
+        inner.entries.remove(key);
+        None
+    }
+
+    /// Find the tables read by `sql`, from the `OpenRead` instructions of its query plan.
+    async fn reads(&self, sql: &str) -> Result<Arc<ReadSet>> {
+        if let Some(reads) = self
+            .inner
+            .lock()
+            .ok()
+            .and_then(|mut i| i.reads.get(sql).cloned())
+        {
+            return Ok(reads);
+        }
+
+        let plan: Vec<(i64, String, i64, i64, i64)> = query_as(&format!("EXPLAIN {sql}"))
+            .fetch_all(&self.pool)
+            .await?;
+        let mut pages = vec![];
+        let mut unknown = false;
+        for (_, opcode, _, p2, p3) in plan {
+            match opcode.as_str() {
+                // p3 is the database; tables outside "main" aren't resolved
+                "OpenRead" if p3 == 0 => pages.push(p2),
+                "OpenRead" | "VOpen" => unknown = true,
+                _ => {}
+            }
+        }
+
+        let reads = if unknown {
+            ReadSet::Unknown
+        } else {
+            let schema: Vec<(i64, String)> =
+                query_as("SELECT rootpage, tbl_name FROM sqlite_schema WHERE rootpage > 0")
+                    .fetch_all(&self.pool)
+                    .await?;
+            let mut tables: Vec<String> = schema
+                .into_iter()
+                .filter(|(page, _)| pages.contains(page))
+                .map(|(_, table)| table)
+                .collect();
+            tables.sort();
+            tables.dedup();
+            ReadSet::Tables(tables)
+        };
+
+        let reads = Arc::new(reads);
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.reads.insert(sql.into(), reads.clone());
+        }
+        Ok(reads)
+    }
+}
+
+/// A query whose result is served from a [`QueryCache`] when possible. Returned by [`QueryCache::query_as`].
+#[must_use = "query must be executed to return results"]
+pub struct CachedQuery<'c, T> {
+    cache: &'c QueryCache,
+    sql: String,
+    arguments: Arguments,
+    output: PhantomData<fn() -> T>,
+}
+
+impl<T> CachedQuery<'_, T>
+where
+    T: for<'r> FromRow<'r> + Clone + Send + Sync + Unpin + 'static,
+{
+    /// Bind a value for use with this SQL query. Bound values are part of the cache key.
+    pub fn bind<V: Encode>(mut self, value: V) -> Self {
+        self.arguments.add(value);
+        self
+    }
+
+    /// Fetch all rows, from the cache if possible.
+    pub async fn fetch_all(self) -> Result<Vec<T>> {
+        self.run(|q, pool| async move { q.fetch_all(&pool).await })
+            .await
+    }
+
+    /// Fetch at most one row, from the cache if possible.
+    pub async fn fetch_optional(self) -> Result<Option<T>> {
+        self.run(|q, pool| async move { q.fetch_optional(&pool).await })
+            .await
+    }
+
+    /// Fetch exactly one row, from the cache if possible. Returns [`Error::RowNotFound`] if there are no rows; this
+    /// outcome is not cached.
+    pub async fn fetch_one(self) -> Result<T> {
+        self.fetch_optional().await?.ok_or(Error::RowNotFound)
+    }
+
+    async fn run<V, F, Fut>(self, fetch: F) -> Result<V>
+    where
+        V: Clone + Send + Sync + 'static,
+        F: FnOnce(crate::query_as::QueryAs<T, Arguments>, Pool) -> Fut,
+        Fut: std::future::Future<Output = Result<V>>,
+    {
+        let cache = self.cache;
+        let key = Key {
+            output: TypeId::of::<V>(),
+            arguments: encode_arguments(&self.arguments),
+            sql: self.sql,
+        };
+        if let Some(value) = cache.get::<V>(&key) {
+            return Ok(value);
+        }
+
+        let reads = cache.reads(&key.sql).await?;
+        // Taken before the query runs, so a write that lands while it runs makes the entry stale rather than lost
+        let version = cache.tracker.version();
+        let value = fetch(query_as_with(&key.sql, self.arguments), cache.pool.clone()).await?;
+
+        if let Ok(mut inner) = cache.inner.lock() {
+            inner.entries.insert(
+                key,
+                Entry {
+                    version,
+                    reads,
+                    value: Arc::new(value.clone()),
+                },
+            );
+        }
+        Ok(value)
+    }
+}
+
+/// Encode bound arguments into a byte string for use in a cache key.
+fn encode_arguments(arguments: &Arguments) -> Vec<u8> {
+    let mut out = vec![];
+    for value in &arguments.values {
+        match value {
+            ArgumentValue::Null => out.push(0),
+            ArgumentValue::Int(v) => {
+                out.push(1);
+                out.extend_from_slice(&i64::from(*v).to_le_bytes());
+            }
+            ArgumentValue::Int64(v) => {
+                out.push(1);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            ArgumentValue::Double(v) => {
+                out.push(2);
+                out.extend_from_slice(&v.to_bits().to_le_bytes());
+            }
+            ArgumentValue::Text(v) => {
+                out.push(3);
+                out.extend_from_slice(&(v.len() as u64).to_le_bytes());
+                out.extend_from_slice(v.as_bytes());
+            }
+            ArgumentValue::Blob(v) => {
+                out.push(4);
+                out.extend_from_slice(&(v.len() as u64).to_le_bytes());
+                out.extend_from_slice(v);
+            }
+        }
+    }
+    out
+}
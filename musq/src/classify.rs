@@ -0,0 +1,152 @@
+//! Lexical classification of SQL into reads, writes and schema changes.
+
+/// The kind of a query, as returned by [`Execute::classify`](crate::Execute::classify).
+///
+/// Kinds are ordered by severity, so for SQL containing several statements the kind is the maximum over all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QueryKind {
+    /// Reads without modifying the database, e.g. `SELECT`.
+    Read,
+    /// Modifies data or connection state, e.g. `INSERT`, `BEGIN` or `PRAGMA x = y`.
+    Write,
+    /// Modifies the schema, e.g. `CREATE TABLE`.
+    Ddl,
+}
+
+/// Classify SQL by the leading keyword of each statement. Comments, string literals and quoted identifiers are skipped.
+pub(crate) fn classify(sql: &str) -> QueryKind {
+    let mut kind = None;
+    let mut statement: Vec<String> = Vec::new();
+    let mut depth = 0usize;
+    for token in tokens(sql) {
+        match token {
+            Token::Semicolon => {
+                kind = kind.max(classify_statement(&statement));
+                statement.clear();
+                depth = 0;
+            }
+            Token::Open => depth += 1,
+            Token::Close => depth = depth.saturating_sub(1),
+            Token::Equals if depth == 0 => statement.push("=".into()),
+            Token::Word(w) if depth == 0 => statement.push(w.to_ascii_uppercase()),
+            _ => {}
+        }
+    }
+    kind.max(classify_statement(&statement))
+        .unwrap_or(QueryKind::Read)
+}
+
+/// Classify one statement from its top-level words, or `None` if it's empty.
+fn classify_statement(words: &[String]) -> Option<QueryKind> {
+    let first = words.first()?;
+    Some(match first.as_str() {
+        "CREATE" | "DROP" | "ALTER" => QueryKind::Ddl,
+        "SELECT" | "VALUES" | "EXPLAIN" => QueryKind::Read,
+        "PRAGMA" if words.iter().any(|w| w == "=") => QueryKind::Write,
+        "PRAGMA" => QueryKind::Read,
+        // Common table expressions may precede a data-modifying statement
+        "WITH" => match words.iter().find(|w| {
+            matches!(
+                w.as_str(),
+                "SELECT" | "VALUES" | "INSERT" | "UPDATE" | "DELETE" | "REPLACE"
+            )
+        }) {
+            Some(w) if w != "SELECT" && w != "VALUES" => QueryKind::Write,
+            _ => QueryKind::Read,
+        },
+        _ => QueryKind::Write,
+    })
+}
+
+enum Token<'a> {
+    Word(&'a str),
+    Semicolon,
+    Open,
+    Close,
+    Equals,
+    Other,
+}
+
+fn tokens(sql: &str) -> impl Iterator<Item = Token<'_>> {
+    let bytes = sql.as_bytes();
+    let mut i = 0;
+    std::iter::from_fn(move || loop {
+        let c = *bytes.get(i)?;
+        let start = i;
+        i += 1;
+        match c {
+            b'-' if bytes.get(i) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i) == Some(&b'*') => {
+                i += 1;
+                while i + 1 < bytes.len() && !(bytes[i] == b'*' && bytes[i + 1] == b'/') {
+                    i += 1;
+                }
+                i += 2;
+            }
+            b'\'' | b'"' | b'`' => {
+                // A doubled quote is an escaped quote, which this loop consumes as two adjacent literals
+                while i < bytes.len() && bytes[i] != c {
+                    i += 1;
+                }
+                i += 1;
+                return Some(Token::Other);
+            }
+            b'[' => {
+                while i < bytes.len() && bytes[i] != b']' {
+                    i += 1;
+                }
+                i += 1;
+                return Some(Token::Other);
+            }
+            b';' => return Some(Token::Semicolon),
+            b'(' => return Some(Token::Open),
+            b')' => return Some(Token::Close),
+            b'=' => return Some(Token::Equals),
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                return Some(Token::Word(&sql[start..i]));
+            }
+            c if c.is_ascii_whitespace() => {}
+            _ => return Some(Token::Other),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_classifies() {
+        use QueryKind::*;
+        for (sql, kind) in [
+            ("SELECT * FROM t", Read),
+            ("  -- comment\n select 1", Read),
+            ("/* insert */ VALUES (1)", Read),
+            ("EXPLAIN QUERY PLAN SELECT 1", Read),
+            ("PRAGMA table_info(t)", Read),
+            ("PRAGMA journal_mode = WAL", Write),
+            ("INSERT INTO t VALUES ('CREATE')", Write),
+            ("with x as (select 1) select * from x", Read),
+            ("WITH x AS (SELECT 1) DELETE FROM t WHERE id IN x", Write),
+            ("WITH x AS (SELECT 1) SELECT * FROM \"update\"", Read),
+            (
+                "WITH x AS (SELECT 1) SELECT replace(a, 'b', 'c') FROM x",
+                Read,
+            ),
+            ("BEGIN", Write),
+            ("create table t (a)", Ddl),
+            ("SELECT 1; DROP TABLE t;", Ddl),
+            ("UPDATE t SET a = 1; SELECT 1", Write),
+            ("", Read),
+        ] {
+            assert_eq!(classify(sql), kind, "{sql}");
+        }
+    }
+}
@@ -2,6 +2,9 @@
 use crate::{error::DecodeError, Result, Value};
 
 /// A type that can be decoded from the database.
+///
+/// The built-in implementations for non-optional types fail with [`DecodeError::UnexpectedNull`] when given a `NULL`;
+/// decode into an `Option` to accept `NULL`s.
 pub trait Decode<'r>: Sized {
     /// Decode a new value of this type using a raw value from the database.
     fn decode(value: &'r Value) -> Result<Self, DecodeError>;
@@ -0,0 +1,154 @@
+//! Describing the results of a query without running it.
+//!
+//! [`Connection::describe`](crate::Connection::describe) prepares a query and reports the shape of its results: the
+//! name and type of each column, and, for columns read straight from a table, where they come from and whether they
+//! can be `NULL`. Code generators can use this to derive Rust types from SQL.
+//!
+//! ```rust,ignore
+//! let described = conn.describe("SELECT id, email FROM users WHERE id = ?").await?;
+//! assert_eq!(described.parameters, 1);
+//! assert_eq!(described.columns[1].nullable, Some(true));
+//! ```
+use std::{ffi::CString, os::raw::c_int, ptr};
+
+use libsqlite3_sys::{sqlite3_table_column_metadata, SQLITE_OK};
+
+use crate::{
+    sqlite::statement::{CompoundStatement, StatementHandle},
+    Connection, Error, Result, SqliteDataType, SqliteError,
+};
+
+/// The shape of a query's results, returned by [`Connection::describe`](crate::Connection::describe).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Describe {
+    /// The columns of the first statement in the query that returns any.
+    pub columns: Vec<ColumnDescription>,
+    /// The number of parameters the query takes, over all its statements.
+    pub parameters: usize,
+}
+
+/// A column of a query's results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDescription {
+    pub name: String,
+    /// The type the column was declared with, as written in the table definition, or `None` if the column is an
+    /// expression.
+    pub declared_type: Option<String>,
+    /// The type musq decodes the column as: the declared type if it is known, and otherwise `Null`, since an
+    /// expression's type is only known once a row is read.
+    pub type_info: SqliteDataType,
+    /// The table column this column is read from, or `None` if it is an expression.
+    pub origin: Option<ColumnOrigin>,
+    /// Whether the column can be `NULL`, or `None` if that isn't known. Only columns read from a table are known, and
+    /// a column on the nullable side of an outer join can be `NULL` even if its table column can't.
+    pub nullable: Option<bool>,
+}
+
+/// The table column a result column is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnOrigin {
+    /// The schema the table belongs to, such as `main` or `temp`.
+    pub database: String,
+    pub table: String,
+    pub column: String,
+    /// Whether the column is part of the table's primary key.
+    pub primary_key: bool,
+}
+
+pub(crate) async fn describe(conn: &mut Connection, sql: &str) -> Result<Describe> {
+    let sql = sql.to_string();
+    conn.worker
+        .run(move |conn| {
+            let mut statement = CompoundStatement::new(&sql)?;
+            let mut columns = None;
+            let mut parameters = 0;
+            while let Some(prepared) =
+                statement.prepare_next(&mut conn.handle, conn.hooks.changes())?
+            {
+                parameters += prepared.handle.bind_parameter_count();
+                if columns.is_none() && !prepared.columns.is_empty() {
+                    let db = conn.handle.as_ptr();
+                    columns = Some(
+                        (0..prepared.columns.len())
+                            .map(|i| describe_column(db, &*prepared.handle, i))
+                            .collect::<Result<Vec<_>>>()?,
+                    );
+                }
+            }
+            Ok(Describe {
+                columns: columns.unwrap_or_default(),
+                parameters,
+            })
+        })
+        .await?
+}
+
+fn describe_column(
+    db: *mut libsqlite3_sys::sqlite3,
+    handle: &StatementHandle,
+    index: usize,
+) -> Result<ColumnDescription> {
+    let declared_type = handle.column_decltype_str(index).map(str::to_string);
+    let type_info = handle
+        .column_decltype(index)
+        .unwrap_or_else(|| handle.column_type_info(index));
+    let mut nullable = None;
+    let origin = match handle.column_origin(index) {
+        Some((database, table, column)) => {
+            let (not_null, primary_key) = column_metadata(db, database, table, column)?;
+            // An INTEGER PRIMARY KEY is the rowid, which is never NULL
+            let rowid = primary_key
+                && declared_type
+                    .as_deref()
+                    .is_some_and(|t| t.eq_ignore_ascii_case("INTEGER"));
+            nullable = Some(!(not_null || rowid));
+            Some(ColumnOrigin {
+                database: database.to_string(),
+                table: table.to_string(),
+                column: column.to_string(),
+                primary_key,
+            })
+        }
+        None => None,
+    };
+    Ok(ColumnDescription {
+        name: handle.column_name(index).to_string(),
+        declared_type,
+        type_info,
+        origin,
+        nullable,
+    })
+}
+
+/// Whether a table column is declared `NOT NULL`, and whether it is part of the primary key. See
+/// [`sqlite3_table_column_metadata`](https://www.sqlite.org/c3ref/table_column_metadata.html).
+fn column_metadata(
+    db: *mut libsqlite3_sys::sqlite3,
+    database: &str,
+    table: &str,
+    column: &str,
+) -> Result<(bool, bool)> {
+    let cstr = |s: &str| {
+        CString::new(s).map_err(|_| Error::Protocol("identifier contains nul bytes".into()))
+    };
+    let (database, table, column) = (cstr(database)?, cstr(table)?, cstr(column)?);
+    let mut not_null: c_int = 0;
+    let mut primary_key: c_int = 0;
+    let rc = unsafe {
+        sqlite3_table_column_metadata(
+            db,
+            database.as_ptr(),
+            table.as_ptr(),
+            column.as_ptr(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut not_null,
+            &mut primary_key,
+            ptr::null_mut(),
+        )
+    };
+    if rc != SQLITE_OK {
+        return Err(Error::from(SqliteError::new(db)));
+    }
+    Ok((not_null != 0, primary_key != 0))
+}
@@ -0,0 +1,200 @@
+//! A document store on top of SQLite's JSON functions.
+//!
+//! A [`Collection`] stores serializable values as JSON documents in a single table, keyed by an integer id. Documents
+//! can be looked up by id, or found by the value at a [JSON path](https://www.sqlite.org/json1.html#path_arguments)
+//! inside them. Paths that are queried often can be indexed with [`Collection::create_index`].
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct User {
+//!     name: String,
+//!     address: Address,
+//! }
+//!
+//! let users = Collection::<User>::open(&pool, "users").await?;
+//! users.create_index("$.address.city").await?;
+//!
+//! let id = users.insert(&user).await?;
+//! let locals = users.find("$.address.city", "Dunedin").await?;
+//! ```
+use std::{fmt, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    encode::Encode, query, query_as, query_scalar, schema::quote_identifier, DecodeError, Error,
+    Pool, Result,
+};
+
+/// A document and its id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document<T> {
+    pub id: i64,
+    pub doc: T,
+}
+
+/// A collection of JSON documents of type `T`, stored in a table of the same name.
+pub struct Collection<T> {
+    pool: Pool,
+    name: String,
+    table: String,
+    doc: PhantomData<fn() -> T>,
+}
+
+impl<T> fmt::Debug for Collection<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Collection")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl<T> Clone for Collection<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            name: self.name.clone(),
+            table: self.table.clone(),
+            doc: PhantomData,
+        }
+    }
+}
+
+impl<T> Collection<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Open the collection called `name`, creating its table if it doesn't already exist.
+    pub async fn open(pool: &Pool, name: &str) -> Result<Self> {
+        let table = quote_identifier(name);
+        query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (id INTEGER PRIMARY KEY, doc TEXT NOT NULL CHECK (json_valid(doc)))"
+        ))
+        .execute(pool)
+        .await?;
+        Ok(Self {
+            pool: pool.clone(),
+            name: name.to_string(),
+            table,
+            doc: PhantomData,
+        })
+    }
+
+    /// The name of the collection's table.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Index the value at `path`, so that [`find`](Self::find) can use it. Does nothing if the index already exists.
+    pub async fn create_index(&self, path: &str) -> Result<()> {
+        let index = quote_identifier(&format!("{}_{}", self.name, index_suffix(path)));
+        query(&format!(
+            "CREATE INDEX IF NOT EXISTS {index} ON {} ({})",
+            self.table,
+            extract(path)?
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Add a document, returning its id.
+    pub async fn insert(&self, doc: &T) -> Result<i64> {
+        query_scalar(&format!(
+            "INSERT INTO {} (doc) VALUES (json(?)) RETURNING id",
+            self.table
+        ))
+        .bind(to_json(doc)?)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// The document with the given id, if there is one.
+    pub async fn get(&self, id: i64) -> Result<Option<T>> {
+        let doc: Option<String> =
+            query_scalar(&format!("SELECT doc FROM {} WHERE id = ?", self.table))
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+        doc.as_deref().map(from_json).transpose()
+    }
+
+    /// All the documents whose value at `path` equals `value`, in id order. JSON booleans compare equal to 1 and 0.
+    pub async fn find<V>(&self, path: &str, value: V) -> Result<Vec<Document<T>>>
+    where
+        V: Encode + Send,
+    {
+        let rows: Vec<(i64, String)> = query_as(&format!(
+            "SELECT id, doc FROM {} WHERE {} = ? ORDER BY id",
+            self.table,
+            extract(path)?
+        ))
+        .bind(value)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|(id, doc)| {
+                Ok(Document {
+                    id,
+                    doc: from_json(&doc)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Replace the document with the given id. Returns `false` if there is no such document.
+    pub async fn update(&self, id: i64, doc: &T) -> Result<bool> {
+        let result = query(&format!(
+            "UPDATE {} SET doc = json(?) WHERE id = ?",
+            self.table
+        ))
+        .bind(to_json(doc)?)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remove the document with the given id. Returns `false` if there is no such document.
+    pub async fn delete(&self, id: i64) -> Result<bool> {
+        let result = query(&format!("DELETE FROM {} WHERE id = ?", self.table))
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// The expression extracting `path` from a document. The path is inlined rather than bound, because SQLite only uses
+/// an index on an expression if the query contains the same expression.
+fn extract(path: &str) -> Result<String> {
+    if !path.starts_with('$') {
+        return Err(Error::Protocol(format!(
+            "JSON path must start with '$': {path}"
+        )));
+    }
+    Ok(format!("json_extract(doc, '{}')", path.replace('\'', "''")))
+}
+
+/// A readable, unique index name suffix for `path`. Characters other than ASCII letters and digits are hex-escaped,
+/// so that distinct paths never share an index name.
+fn index_suffix(path: &str) -> String {
+    let mut suffix = String::new();
+    for c in path.trim_start_matches('$').chars() {
+        if c.is_ascii_alphanumeric() {
+            suffix.push(c);
+        } else {
+            suffix.push_str(&format!("_{:x}_", c as u32));
+        }
+    }
+    suffix
+}
+
+fn to_json<T: Serialize>(doc: &T) -> Result<String> {
+    serde_json::to_string(doc)
+        .map_err(|e| Error::Protocol(format!("failed to encode document as JSON: {e}")))
+}
+
+fn from_json<T: DeserializeOwned>(doc: &str) -> Result<T> {
+    serde_json::from_str(doc).map_err(|e| Error::Decode(DecodeError::Conversion(e.to_string())))
+}
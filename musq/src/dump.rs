@@ -0,0 +1,251 @@
+//! Textual SQL dumps, like the `sqlite3` shell's `.dump` command.
+//!
+//! [`Connection::dump`] writes the main database as SQL: the statements that create its tables, an `INSERT` for every
+//! row, and then its indexes, triggers and views. [`Connection::restore_from_sql`] executes a dump, either one written
+//! by musq or by the `sqlite3` shell, in a single transaction, so a failed restore leaves the database unchanged.
+//!
+//! ```rust,ignore
+//! let mut out = tokio::fs::File::create("backup.sql").await?;
+//! conn.dump(&mut out).await?;
+//!
+//! let input = tokio::io::BufReader::new(tokio::fs::File::open("backup.sql").await?);
+//! other
+//!     .restore_from_sql(input)
+//!     .on_progress(|p| println!("{} statements, {} bytes", p.statements, p.bytes))
+//!     .run()
+//!     .await?;
+//! ```
+//!
+//! Dumps are taken inside a transaction, so they are a consistent snapshot. Only the schema of virtual tables is
+//! dumped, not their contents. Rowids are not preserved, except where a table's `INTEGER PRIMARY KEY` aliases them.
+use std::{ffi::CString, sync::Arc};
+
+use futures_util::TryStreamExt;
+use libsqlite3_sys::sqlite3_complete;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    debugfn::DebugFn,
+    query, query_as, query_scalar,
+    schema::{quote_identifier, table_columns},
+    Connection, Error, Executor, Result,
+};
+
+/// How far a restore has got, reported after each statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Statements executed so far.
+    pub statements: u64,
+    /// Bytes of input read so far.
+    pub bytes: u64,
+}
+
+type ProgressCallback = dyn Fn(Progress) + Send + Sync + 'static;
+
+pub(crate) async fn dump<W>(conn: &mut Connection, mut writer: W) -> Result<()>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    let mut tx = conn.begin().await?;
+    writer
+        .write_all(b"PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n")
+        .await?;
+
+    let tables: Vec<(String, String, String)> = query_as(
+        "SELECT s.name, s.sql, l.type FROM main.sqlite_schema s
+        JOIN pragma_table_list l ON l.schema = 'main' AND l.name = s.name
+        WHERE s.type = 'table' AND s.name NOT LIKE 'sqlite_%' AND l.type != 'shadow'
+        ORDER BY s.rowid",
+    )
+    .fetch_all(&mut tx)
+    .await?;
+    for (name, sql, kind) in &tables {
+        writer.write_all(format!("{sql};\n").as_bytes()).await?;
+        if kind == "virtual" {
+            continue;
+        }
+        let columns: Vec<String> = table_columns(&mut tx, name)
+            .await?
+            .into_iter()
+            .filter(|c| c.is_insertable())
+            .map(|c| quote_identifier(&c.name))
+            .collect();
+        if columns.is_empty() {
+            continue;
+        }
+        let columns = columns.join(",");
+        let insert = format!("INSERT INTO {} ({columns}) VALUES(", quote_identifier(name));
+        let select = format!("SELECT {columns} FROM {}", quote_identifier(name));
+        let mut rows = (&mut tx).fetch(query(&select));
+        while let Some(row) = rows.try_next().await? {
+            let mut line = insert.clone();
+            for (i, (_, value)) in row.iter().enumerate() {
+                if i > 0 {
+                    line.push(',');
+                }
+                line.push_str(&value.sql_literal());
+            }
+            line.push_str(");\n");
+            writer.write_all(line.as_bytes()).await?;
+        }
+    }
+
+    let sequences: Vec<(String, i64)> = if table_exists(&mut tx, "sqlite_sequence").await? {
+        query_as("SELECT name, seq FROM main.sqlite_sequence")
+            .fetch_all(&mut tx)
+            .await?
+    } else {
+        Vec::new()
+    };
+    if !sequences.is_empty() {
+        writer.write_all(b"DELETE FROM sqlite_sequence;\n").await?;
+        for (name, seq) in sequences {
+            let name = name.replace('\'', "''");
+            writer
+                .write_all(
+                    format!("INSERT INTO sqlite_sequence VALUES('{name}',{seq});\n").as_bytes(),
+                )
+                .await?;
+        }
+    }
+
+    let objects: Vec<String> = query_as::<(String,)>(
+        "SELECT sql FROM main.sqlite_schema
+        WHERE type IN ('index', 'trigger', 'view') AND sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+        ORDER BY type != 'index', rowid",
+    )
+    .fetch_all(&mut tx)
+    .await?
+    .into_iter()
+    .map(|(sql,)| sql)
+    .collect();
+    for sql in objects {
+        writer.write_all(format!("{sql};\n").as_bytes()).await?;
+    }
+
+    writer.write_all(b"COMMIT;\n").await?;
+    writer.flush().await?;
+    tx.rollback().await
+}
+
+async fn table_exists(conn: &mut Connection, name: &str) -> Result<bool> {
+    let found: Option<(i64,)> =
+        query_as("SELECT 1 FROM main.sqlite_schema WHERE type = 'table' AND name = ?")
+            .bind(name)
+            .fetch_optional(conn)
+            .await?;
+    Ok(found.is_some())
+}
+
+/// A pending restore, returned by [`Connection::restore_from_sql`]. Nothing happens until [`run`](Self::run) is
+/// awaited.
+#[derive(Debug)]
+#[must_use = "restores do nothing unless run"]
+pub struct Restore<'c, R> {
+    conn: &'c mut Connection,
+    reader: R,
+    on_progress: Option<Arc<DebugFn<ProgressCallback>>>,
+}
+
+impl<'c, R> Restore<'c, R>
+where
+    R: AsyncBufRead + Unpin + Send,
+{
+    pub(crate) fn new(conn: &'c mut Connection, reader: R) -> Self {
+        Self {
+            conn,
+            reader,
+            on_progress: None,
+        }
+    }
+
+    /// Call `callback` after each statement.
+    pub fn on_progress(mut self, callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(DebugFn(callback)));
+        self
+    }
+
+    /// Execute every statement in the input, and commit them. Returns the number of statements executed.
+    ///
+    /// The dump's own `BEGIN` and `COMMIT` statements are skipped. Foreign keys are turned off while the statements
+    /// run, so tables and rows may appear in any order, and checked before committing: the restore fails if any
+    /// violations remain.
+    pub async fn run(mut self) -> Result<u64> {
+        let enforced: bool = query_scalar("PRAGMA foreign_keys")
+            .fetch_one(&mut *self.conn)
+            .await?;
+        if enforced {
+            self.conn
+                .execute_uncached("PRAGMA foreign_keys = OFF")
+                .await?;
+        }
+        let restored = self.restore().await;
+        if enforced {
+            self.conn
+                .execute_uncached("PRAGMA foreign_keys = ON")
+                .await?;
+        }
+        restored
+    }
+
+    async fn restore(&mut self) -> Result<u64> {
+        let mut tx = self.conn.begin().await?;
+        let mut progress = Progress {
+            statements: 0,
+            bytes: 0,
+        };
+        let mut statement = String::new();
+        loop {
+            let read = self.reader.read_line(&mut statement).await?;
+            progress.bytes += read as u64;
+            let end = read == 0;
+            if !end && !is_complete(&statement) {
+                continue;
+            }
+            if !statement.trim().is_empty() && !is_transaction_control(&statement) {
+                tx.execute_uncached(&statement).await?;
+                progress.statements += 1;
+                if let Some(callback) = &self.on_progress {
+                    callback(progress);
+                }
+            }
+            statement.clear();
+            if end {
+                break;
+            }
+        }
+        let violations = tx.foreign_key_violations().await?;
+        if !violations.is_empty() {
+            return Err(Error::Protocol(format!(
+                "restore left {} foreign key violations",
+                violations.len()
+            )));
+        }
+        tx.commit().await?;
+        Ok(progress.statements)
+    }
+}
+
+/// Whether `sql` ends with a complete statement, as decided by
+/// [`sqlite3_complete`](https://www.sqlite.org/c3ref/complete.html).
+fn is_complete(sql: &str) -> bool {
+    if !sql.trim_end().ends_with(';') {
+        return false;
+    }
+    match CString::new(sql) {
+        Ok(sql) => unsafe { sqlite3_complete(sql.as_ptr()) != 0 },
+        Err(_) => true,
+    }
+}
+
+/// Whether `sql` begins or ends a transaction, which the restore manages itself.
+fn is_transaction_control(sql: &str) -> bool {
+    let word = sql
+        .trim_start()
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default();
+    ["BEGIN", "COMMIT", "END", "ROLLBACK"]
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(word))
+}
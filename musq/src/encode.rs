@@ -23,3 +23,9 @@ where
         }
     }
 }
+
+impl Encode for ArgumentValue {
+    fn encode(self) -> ArgumentValue {
+        self
+    }
+}
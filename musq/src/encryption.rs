@@ -0,0 +1,647 @@
+//! Encryption at rest through a VFS shim.
+//!
+//! [`Musq::encrypted_vfs`](crate::Musq::encrypted_vfs) registers a VFS that wraps the platform default, encrypting
+//! the pages SQLite writes to disk - in the database, its rollback journal and its WAL file - and decrypting them
+//! again on read. Existing code works unchanged.
+//!
+//! Every page is sealed with AES-256-GCM under a fresh random nonce. The nonce and the authentication tag are stored in
+//! the last 28 bytes of the page, which connections opened through the VFS reserve with
+//! [`SQLITE_FCNTL_RESERVE_BYTES`](https://www.sqlite.org/c3ref/c_fcntl_begin_atomic_write.html#sqlitefcntlreservebytes),
+//! and the tag also covers the page's position in its file. A page of the database that has been modified or moved
+//! fails to read with `SQLITE_IOERR_DATA`, as does every page when the key is wrong. Pages of the journal and WAL that
+//! fail to authenticate read as zeroes, so that SQLite's own checksums discard them, as they do torn writes.
+//!
+//! Limitations to weigh against SQLCipher or filesystem-level encryption:
+//!
+//! - Only databases created through the VFS can be opened with it, since other databases have no room for the nonce
+//!   and tag. Copy existing data in with [`Connection::dump`](crate::Connection::dump) and
+//!   [`Connection::restore_from_sql`](crate::Connection::restore_from_sql). Databases attached with an `ATTACH`
+//!   statement rather than [`Connection::attach`](crate::Connection::attach) fail on their first write for the same
+//!   reason.
+//! - SQLite's temporary files are refused. Connections are opened with `PRAGMA temp_store = MEMORY`, so that SQLite
+//!   keeps temporary tables, indexes and statement journals in memory instead.
+//! - A page can be replaced with an older copy of itself, so an attacker who keeps old versions of the file can roll
+//!   parts of it back.
+//! - File sizes, the headers of journals and WAL frames, which include SQLite's checksums of the pages, the
+//!   shared-memory index used in WAL mode, and super-journals, which only hold file names, are not hidden.
+//! - Nonces are random, so keys should be rotated well before 2^32 page writes.
+//! - Memory-mapped I/O is disabled for encrypted files.
+use std::{
+    cell::Cell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    io, mem, ptr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use aes_gcm::{
+    aead::{AeadCore, AeadInPlace, KeyInit, OsRng},
+    Aes256Gcm, Nonce, Tag,
+};
+use libsqlite3_sys::{
+    sqlite3_file, sqlite3_int64, sqlite3_io_methods, sqlite3_vfs, sqlite3_vfs_find,
+    sqlite3_vfs_register, SQLITE_CANTOPEN, SQLITE_IOERR, SQLITE_IOERR_DATA,
+    SQLITE_IOERR_SHORT_READ, SQLITE_IOERR_WRITE, SQLITE_NOMEM, SQLITE_OK, SQLITE_OPEN_MAIN_DB,
+    SQLITE_OPEN_MAIN_JOURNAL, SQLITE_OPEN_SUPER_JOURNAL, SQLITE_OPEN_WAL,
+};
+
+/// Supplies the 256-bit encryption key. The provider is called each time a file is opened, so it can fetch the key
+/// from a secrets manager or keychain rather than holding it in memory.
+pub trait KeyProvider: Send + Sync + 'static {
+    fn key(&self) -> io::Result<[u8; 32]>;
+}
+
+impl<F> KeyProvider for F
+where
+    F: Fn() -> io::Result<[u8; 32]> + Send + Sync + 'static,
+{
+    fn key(&self) -> io::Result<[u8; 32]> {
+        self()
+    }
+}
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// The bytes reserved at the end of every page for its nonce and tag.
+pub(crate) const RESERVED_BYTES: usize = NONCE_LEN + TAG_LEN;
+
+const VFS_PREFIX: &str = "musq-encrypted-";
+
+static VFS_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The WAL file header, and the header in front of each page in it.
+const WAL_HEADER: u64 = 32;
+const WAL_FRAME_HEADER: u64 = 24;
+
+const JOURNAL_MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+
+/// Whether `name` is the name of a VFS registered by [`register`].
+pub(crate) fn is_encrypted_vfs(name: &CStr) -> bool {
+    name.to_bytes().starts_with(VFS_PREFIX.as_bytes())
+}
+
+/// Our VFS. The base is a copy of the wrapped VFS with its own `pAppData`, so that every method but `xOpen` can be
+/// inherited as-is.
+#[repr(C)]
+struct EncryptedVfs {
+    base: sqlite3_vfs,
+    inner: *mut sqlite3_vfs,
+    provider: Arc<dyn KeyProvider>,
+    name: CString,
+}
+
+/// An open file. SQLite allocates `szOsFile` bytes for it; the wrapped VFS's file follows immediately after.
+#[repr(C)]
+struct EncryptedFile {
+    base: sqlite3_file,
+    pages: *mut Pages,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Database = 1,
+    Wal = 2,
+    Journal = 3,
+    /// Super-journals, which hold only the names of other journals.
+    Plain = 4,
+}
+
+/// The pages of one file, and the cipher that seals them.
+struct Pages {
+    cipher: Aes256Gcm,
+    kind: Kind,
+    /// The page size, once known. Journals don't track it, since their pages are told apart by the size of the reads
+    /// and writes that carry them.
+    page_size: Cell<u64>,
+}
+
+/// Part of a read or write, lying either within one page or entirely outside of pages.
+struct Segment {
+    start: u64,
+    end: u64,
+    /// The offset of the page the segment lies in.
+    page: Option<u64>,
+}
+
+impl Pages {
+    fn aad(&self, offset: u64) -> [u8; 9] {
+        let mut aad = [self.kind as u8; 9];
+        aad[1..].copy_from_slice(&offset.to_be_bytes());
+        aad
+    }
+
+    /// Encrypt `page`, which lives at `offset`, storing its nonce and tag in its reserved bytes.
+    fn seal(&self, offset: u64, page: &mut [u8]) -> bool {
+        let (data, reserved) = page.split_at_mut(page.len() - RESERVED_BYTES);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        match self
+            .cipher
+            .encrypt_in_place_detached(&nonce, &self.aad(offset), data)
+        {
+            Ok(tag) => {
+                reserved[..NONCE_LEN].copy_from_slice(&nonce);
+                reserved[NONCE_LEN..].copy_from_slice(&tag);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Authenticate and decrypt `page`, which lives at `offset`, and zero its reserved bytes. Returns `false` if the
+    /// page fails to authenticate.
+    fn open(&self, offset: u64, page: &mut [u8]) -> bool {
+        let (data, reserved) = page.split_at_mut(page.len() - RESERVED_BYTES);
+        let nonce = *Nonce::from_slice(&reserved[..NONCE_LEN]);
+        let tag = *Tag::from_slice(&reserved[NONCE_LEN..]);
+        reserved.fill(0);
+        self.cipher
+            .decrypt_in_place_detached(&nonce, &self.aad(offset), data, &tag)
+            .is_ok()
+    }
+
+    /// Split `offset..end` at page boundaries. Pages in the database follow each other directly, while in the WAL
+    /// each follows a frame header.
+    fn segments(&self, offset: u64, end: u64) -> Vec<Segment> {
+        let size = self.page_size.get();
+        let (first, stride) = match self.kind {
+            Kind::Wal => (WAL_HEADER + WAL_FRAME_HEADER, WAL_FRAME_HEADER + size),
+            _ => (0, size),
+        };
+        let mut segments = Vec::new();
+        let mut pos = offset;
+        while pos < end {
+            let (stop, page) = if pos < first {
+                (first, None)
+            } else {
+                let page = first + (pos - first) / stride * stride;
+                if pos < page + size {
+                    (page + size, Some(page))
+                } else {
+                    (page + stride, None)
+                }
+            };
+            let stop = stop.min(end);
+            segments.push(Segment {
+                start: pos,
+                end: stop,
+                page,
+            });
+            pos = stop;
+        }
+        segments
+    }
+}
+
+fn is_page_size(n: u64) -> bool {
+    n.is_power_of_two() && (512..=65536).contains(&n)
+}
+
+fn u32_at(buf: &[u8], i: usize) -> u32 {
+    u32::from_be_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]])
+}
+
+/// Whether `buf` is a rollback journal header, which SQLite writes in page-sized chunks when its sector size is at
+/// least the page size. Headers are left in the clear, since SQLite reads them back field by field.
+fn is_journal_header(buf: &[u8]) -> bool {
+    let sector_size = u32_at(buf, 20);
+    (buf[..8] == JOURNAL_MAGIC || buf[..8] == [0; 8])
+        && matches!(u32_at(buf, 8), 0 | u32::MAX)
+        && sector_size.is_power_of_two()
+        && (32..=65536).contains(&sector_size)
+        && is_page_size(u32_at(buf, 24) as u64)
+        && buf[28..].iter().all(|&b| b == 0)
+}
+
+/// Register an encrypting VFS wrapping the default VFS, and return its name. The VFS lives for the remainder of the
+/// process.
+///
+/// # Panics
+///
+/// Panics if SQLite fails to initialize.
+pub(crate) fn register(provider: Arc<dyn KeyProvider>) -> String {
+    let name = format!("{VFS_PREFIX}{}", VFS_ID.fetch_add(1, Ordering::Relaxed));
+    unsafe {
+        let inner = sqlite3_vfs_find(ptr::null());
+        assert!(!inner.is_null(), "SQLite has no default VFS");
+
+        let mut base = *inner;
+        base.szOsFile = (mem::size_of::<EncryptedFile>() as c_int) + (*inner).szOsFile;
+        base.pNext = ptr::null_mut();
+        base.xOpen = Some(open);
+        let vfs = Box::into_raw(Box::new(EncryptedVfs {
+            base,
+            inner,
+            provider,
+            name: CString::new(name.clone()).expect("VFS name contains a NUL"),
+        }));
+        (*vfs).base.zName = (*vfs).name.as_ptr();
+
+        let rc = sqlite3_vfs_register(vfs.cast(), 0);
+        assert_eq!(rc, SQLITE_OK, "failed to register VFS");
+    }
+    name
+}
+
+static IO_METHODS: sqlite3_io_methods = sqlite3_io_methods {
+    // Version 3 adds xFetch, which would hand SQLite memory-mapped ciphertext
+    iVersion: 2,
+    xClose: Some(close),
+    xRead: Some(read),
+    xWrite: Some(write),
+    xTruncate: Some(truncate),
+    xSync: Some(sync),
+    xFileSize: Some(file_size),
+    xLock: Some(lock),
+    xUnlock: Some(unlock),
+    xCheckReservedLock: Some(check_reserved_lock),
+    xFileControl: Some(file_control),
+    xSectorSize: Some(sector_size),
+    xDeviceCharacteristics: Some(device_characteristics),
+    xShmMap: Some(shm_map),
+    xShmLock: Some(shm_lock),
+    xShmBarrier: Some(shm_barrier),
+    xShmUnmap: Some(shm_unmap),
+    xFetch: None,
+    xUnfetch: None,
+};
+
+unsafe fn inner_file(file: *mut sqlite3_file) -> *mut sqlite3_file {
+    file.cast::<u8>()
+        .add(mem::size_of::<EncryptedFile>())
+        .cast()
+}
+
+unsafe fn inner_methods<'a>(file: *mut sqlite3_file) -> &'a sqlite3_io_methods {
+    &*(*inner_file(file)).pMethods
+}
+
+unsafe fn pages<'a>(file: *mut sqlite3_file) -> &'a Pages {
+    &*(*file.cast::<EncryptedFile>()).pages
+}
+
+unsafe extern "C" fn open(
+    vfs: *mut sqlite3_vfs,
+    name: *const c_char,
+    file: *mut sqlite3_file,
+    flags: c_int,
+    out_flags: *mut c_int,
+) -> c_int {
+    let vfs = &*(vfs as *const EncryptedVfs);
+    let encrypted = file.cast::<EncryptedFile>();
+    (*encrypted).base.pMethods = ptr::null();
+    (*encrypted).pages = ptr::null_mut();
+
+    let kind = if flags & SQLITE_OPEN_MAIN_DB != 0 {
+        Kind::Database
+    } else if flags & SQLITE_OPEN_WAL != 0 {
+        Kind::Wal
+    } else if flags & SQLITE_OPEN_MAIN_JOURNAL != 0 {
+        Kind::Journal
+    } else if flags & SQLITE_OPEN_SUPER_JOURNAL != 0 {
+        Kind::Plain
+    } else {
+        // Temporary files, which have no room for nonces, and which `PRAGMA temp_store = MEMORY` keeps in memory
+        return SQLITE_CANTOPEN;
+    };
+
+    let key = match vfs.provider.key() {
+        Ok(key) => key,
+        Err(_) => return SQLITE_CANTOPEN,
+    };
+
+    let inner = inner_file(file);
+    let rc = match (*vfs.inner).xOpen {
+        Some(f) => f(vfs.inner, name, inner, flags, out_flags),
+        None => SQLITE_CANTOPEN,
+    };
+    // If the wrapped VFS set its methods, SQLite will call xClose even on failure
+    if !(*inner).pMethods.is_null() {
+        (*encrypted).pages = Box::into_raw(Box::new(Pages {
+            cipher: Aes256Gcm::new(&key.into()),
+            kind,
+            page_size: Cell::new(0),
+        }));
+        (*encrypted).base.pMethods = &IO_METHODS;
+    }
+    rc
+}
+
+unsafe extern "C" fn close(file: *mut sqlite3_file) -> c_int {
+    let encrypted = file.cast::<EncryptedFile>();
+    if !(*encrypted).pages.is_null() {
+        drop(Box::from_raw((*encrypted).pages));
+        (*encrypted).pages = ptr::null_mut();
+    }
+    match inner_methods(file).xClose {
+        Some(f) => f(inner_file(file)),
+        None => SQLITE_OK,
+    }
+}
+
+/// Read `buf` from `offset` in the wrapped file, as is.
+unsafe fn read_raw(file: *mut sqlite3_file, buf: &mut [u8], offset: u64) -> c_int {
+    match inner_methods(file).xRead {
+        Some(f) => f(
+            inner_file(file),
+            buf.as_mut_ptr().cast(),
+            buf.len() as c_int,
+            offset as sqlite3_int64,
+        ),
+        None => SQLITE_IOERR,
+    }
+}
+
+/// Write `buf` at `offset` in the wrapped file, as is.
+unsafe fn write_raw(file: *mut sqlite3_file, buf: &[u8], offset: u64) -> c_int {
+    match inner_methods(file).xWrite {
+        Some(f) => f(
+            inner_file(file),
+            buf.as_ptr().cast(),
+            buf.len() as c_int,
+            offset as sqlite3_int64,
+        ),
+        None => SQLITE_IOERR,
+    }
+}
+
+/// Read the page at `offset` into `page`, and decrypt it. Pages past the end of the file read as zeroes.
+unsafe fn read_page(file: *mut sqlite3_file, page: &mut [u8], offset: u64) -> c_int {
+    let pages = pages(file);
+    match read_raw(file, page, offset) {
+        SQLITE_OK => {
+            if !pages.open(offset, page) {
+                if pages.kind == Kind::Database {
+                    return SQLITE_IOERR_DATA;
+                }
+                page.fill(0);
+            }
+            SQLITE_OK
+        }
+        SQLITE_IOERR_SHORT_READ => {
+            page.fill(0);
+            SQLITE_IOERR_SHORT_READ
+        }
+        rc => rc,
+    }
+}
+
+/// Find the page size of the database by trying each one on the first page, which is the only one SQLite reads
+/// before it knows the page size. Leaves the page size unknown if the file is empty.
+unsafe fn find_page_size(file: *mut sqlite3_file) -> c_int {
+    let mut size = 0;
+    let rc = file_size(file, &mut size);
+    if rc != SQLITE_OK {
+        return rc;
+    }
+    if size == 0 {
+        return SQLITE_OK;
+    }
+    let mut first = vec![0; size.min(65536) as usize];
+    let rc = read_raw(file, &mut first, 0);
+    if rc != SQLITE_OK {
+        return rc;
+    }
+    let pages = pages(file);
+    let mut page_size = 512;
+    while page_size <= first.len() {
+        let mut page = first[..page_size].to_vec();
+        if pages.open(0, &mut page) {
+            pages.page_size.set(page_size as u64);
+            return SQLITE_OK;
+        }
+        page_size *= 2;
+    }
+    SQLITE_IOERR_DATA
+}
+
+/// Find the page size of a WAL file from its header, leaving it unknown if the header hasn't been written yet.
+unsafe fn read_wal_page_size(file: *mut sqlite3_file) -> c_int {
+    let mut header = [0; WAL_HEADER as usize];
+    match read_raw(file, &mut header, 0) {
+        SQLITE_OK => {
+            let size = u32_at(&header, 8) as u64;
+            if is_page_size(size) {
+                pages(file).page_size.set(size);
+            }
+            SQLITE_OK
+        }
+        SQLITE_IOERR_SHORT_READ => SQLITE_OK,
+        rc => rc,
+    }
+}
+
+/// Read `buf` from `offset` in a database or WAL file, decrypting the pages it overlaps.
+unsafe fn read_pages(file: *mut sqlite3_file, buf: &mut [u8], offset: u64) -> c_int {
+    let pages = pages(file);
+    let size = pages.page_size.get();
+    let mut rc = SQLITE_OK;
+    for segment in pages.segments(offset, offset + buf.len() as u64) {
+        let out = &mut buf[(segment.start - offset) as usize..(segment.end - offset) as usize];
+        let status = match segment.page {
+            None => read_raw(file, out, segment.start),
+            Some(page) if segment.start == page && segment.end == page + size => {
+                read_page(file, out, page)
+            }
+            // Part of a page, such as the database header
+            Some(page) => {
+                let mut whole = vec![0; size as usize];
+                let status = read_page(file, &mut whole, page);
+                let from = (segment.start - page) as usize;
+                out.copy_from_slice(&whole[from..from + out.len()]);
+                status
+            }
+        };
+        match status {
+            SQLITE_OK => {}
+            SQLITE_IOERR_SHORT_READ => rc = SQLITE_IOERR_SHORT_READ,
+            status => return status,
+        }
+    }
+    rc
+}
+
+unsafe extern "C" fn read(
+    file: *mut sqlite3_file,
+    buf: *mut c_void,
+    amount: c_int,
+    offset: sqlite3_int64,
+) -> c_int {
+    let buf = std::slice::from_raw_parts_mut(buf.cast::<u8>(), amount as usize);
+    let offset = offset as u64;
+    let pages = pages(file);
+    match pages.kind {
+        Kind::Plain => read_raw(file, buf, offset),
+        Kind::Journal if is_page_size(buf.len() as u64) => {
+            let rc = read_raw(file, buf, offset);
+            match rc {
+                SQLITE_OK if is_journal_header(buf) => {}
+                SQLITE_OK if !pages.open(offset, buf) => buf.fill(0),
+                SQLITE_IOERR_SHORT_READ => buf.fill(0),
+                _ => {}
+            }
+            rc
+        }
+        Kind::Journal => read_raw(file, buf, offset),
+        Kind::Database | Kind::Wal => {
+            if pages.page_size.get() == 0 {
+                let rc = match pages.kind {
+                    Kind::Database => find_page_size(file),
+                    _ => read_wal_page_size(file),
+                };
+                if rc != SQLITE_OK {
+                    return rc;
+                }
+                if pages.page_size.get() == 0 {
+                    // Nothing has been written yet
+                    return read_raw(file, buf, offset);
+                }
+            }
+            let rc = read_pages(file, buf, offset);
+            // Another connection may have changed the page size with VACUUM, which SQLite notices by reading the
+            // header on the first page
+            let size = pages.page_size.get();
+            if rc == SQLITE_IOERR_DATA
+                && pages.kind == Kind::Database
+                && offset < size
+                && find_page_size(file) == SQLITE_OK
+                && pages.page_size.get() != size
+            {
+                return read_pages(file, buf, offset);
+            }
+            rc
+        }
+    }
+}
+
+/// Copy `buf`, failing with `SQLITE_NOMEM` if there isn't room.
+fn copy(buf: &[u8]) -> Result<Vec<u8>, c_int> {
+    let mut data = Vec::new();
+    if data.try_reserve_exact(buf.len()).is_err() {
+        return Err(SQLITE_NOMEM);
+    }
+    data.extend_from_slice(buf);
+    Ok(data)
+}
+
+/// Seal `page` and write it at `offset`. The first page of a database must reserve room for the nonce and tag.
+unsafe fn write_page(file: *mut sqlite3_file, mut page: Vec<u8>, offset: u64) -> c_int {
+    if page.starts_with(b"SQLite format 3\0") && (page[20] as usize) < RESERVED_BYTES {
+        return SQLITE_IOERR_WRITE;
+    }
+    if !pages(file).seal(offset, &mut page) {
+        return SQLITE_IOERR_WRITE;
+    }
+    write_raw(file, &page, offset)
+}
+
+/// Write `buf` at `offset` in a WAL file, sealing the pages it overlaps. SQLite writes frame headers and pages
+/// separately, but can split a page in two around the point it syncs the file, so parts of pages are merged into the
+/// page already on disk.
+unsafe fn write_wal(file: *mut sqlite3_file, buf: &[u8], offset: u64) -> c_int {
+    let size = pages(file).page_size.get();
+    for segment in pages(file).segments(offset, offset + buf.len() as u64) {
+        let data = &buf[(segment.start - offset) as usize..(segment.end - offset) as usize];
+        let rc = match segment.page {
+            None => write_raw(file, data, segment.start),
+            Some(page) if segment.start == page && segment.end == page + size => match copy(data) {
+                Ok(data) => write_page(file, data, page),
+                Err(rc) => return rc,
+            },
+            Some(page) => {
+                let mut whole = vec![0; size as usize];
+                match read_page(file, &mut whole, page) {
+                    SQLITE_OK | SQLITE_IOERR_SHORT_READ => {}
+                    rc => return rc,
+                }
+                let from = (segment.start - page) as usize;
+                whole[from..from + data.len()].copy_from_slice(data);
+                write_page(file, whole, page)
+            }
+        };
+        if rc != SQLITE_OK {
+            return rc;
+        }
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn write(
+    file: *mut sqlite3_file,
+    buf: *const c_void,
+    amount: c_int,
+    offset: sqlite3_int64,
+) -> c_int {
+    let buf = std::slice::from_raw_parts(buf.cast::<u8>(), amount as usize);
+    let offset = offset as u64;
+    let pages = pages(file);
+    match pages.kind {
+        Kind::Plain => write_raw(file, buf, offset),
+        Kind::Journal if is_page_size(buf.len() as u64) && !is_journal_header(buf) => {
+            match copy(buf) {
+                Ok(page) => write_page(file, page, offset),
+                Err(rc) => rc,
+            }
+        }
+        Kind::Journal => write_raw(file, buf, offset),
+        // SQLite writes whole pages to the database
+        Kind::Database => {
+            let size = buf.len() as u64;
+            if !is_page_size(size) || !offset.is_multiple_of(size) {
+                return SQLITE_IOERR_WRITE;
+            }
+            pages.page_size.set(size);
+            match copy(buf) {
+                Ok(page) => write_page(file, page, offset),
+                Err(rc) => rc,
+            }
+        }
+        Kind::Wal => {
+            if offset == 0 && buf.len() >= 12 && is_page_size(u32_at(buf, 8) as u64) {
+                pages.page_size.set(u32_at(buf, 8) as u64);
+            }
+            if pages.page_size.get() == 0 {
+                let rc = read_wal_page_size(file);
+                if rc != SQLITE_OK {
+                    return rc;
+                }
+                if pages.page_size.get() == 0 {
+                    return SQLITE_IOERR_WRITE;
+                }
+            }
+            write_wal(file, buf, offset)
+        }
+    }
+}
+
+macro_rules! delegate {
+    ($name:ident, $method:ident, ($($arg:ident: $ty:ty),*), $missing:expr) => {
+        unsafe extern "C" fn $name(file: *mut sqlite3_file, $($arg: $ty),*) -> c_int {
+            match inner_methods(file).$method {
+                Some(f) => f(inner_file(file), $($arg),*),
+                None => $missing,
+            }
+        }
+    };
+}
+
+delegate!(truncate, xTruncate, (size: sqlite3_int64), SQLITE_IOERR);
+delegate!(sync, xSync, (flags: c_int), SQLITE_IOERR);
+delegate!(file_size, xFileSize, (size: *mut sqlite3_int64), SQLITE_IOERR);
+delegate!(lock, xLock, (level: c_int), SQLITE_IOERR);
+delegate!(unlock, xUnlock, (level: c_int), SQLITE_IOERR);
+delegate!(check_reserved_lock, xCheckReservedLock, (out: *mut c_int), SQLITE_IOERR);
+delegate!(file_control, xFileControl, (op: c_int, arg: *mut c_void), libsqlite3_sys::SQLITE_NOTFOUND);
+delegate!(sector_size, xSectorSize, (), 4096);
+delegate!(device_characteristics, xDeviceCharacteristics, (), 0);
+delegate!(shm_map, xShmMap, (page: c_int, page_size: c_int, extend: c_int, out: *mut *mut c_void), SQLITE_IOERR);
+delegate!(shm_lock, xShmLock, (offset: c_int, n: c_int, flags: c_int), SQLITE_IOERR);
+delegate!(shm_unmap, xShmUnmap, (delete: c_int), SQLITE_OK);
+
+unsafe extern "C" fn shm_barrier(file: *mut sqlite3_file) {
+    if let Some(f) = inner_methods(file).xShmBarrier {
+        f(inner_file(file))
+    }
+}
@@ -2,8 +2,12 @@
 
 use std::io;
 use std::num::TryFromIntError;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::{sqlite, sqlite::error::SqliteError, SqliteDataType};
+use crate::{
+    pool::PoolStats, query::ResultLimit, sqlite, sqlite::error::SqliteError, SqliteDataType,
+};
 
 /// A specialized `Result` type for SQLx.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -14,6 +18,25 @@ pub enum DecodeError {
     DataType(SqliteDataType),
     #[error("decoding conversion error: {0}")]
     Conversion(String),
+    /// A `NULL` was decoded into a type that can't hold one. Decode into an `Option` to accept `NULL`s.
+    #[error("unexpected NULL in column {column:?}{}", field.as_ref().map(|f| format!(" for field `{f}`")).unwrap_or_default())]
+    UnexpectedNull {
+        /// The name of the column.
+        column: String,
+        /// The field of the struct being decoded, when decoding with `#[derive(FromRow)]`.
+        field: Option<String>,
+    },
+}
+
+impl DecodeError {
+    /// An [`UnexpectedNull`](Self::UnexpectedNull) error, with the column to be filled in by the caller that knows it.
+    #[doc(hidden)]
+    pub fn unexpected_null() -> Self {
+        DecodeError::UnexpectedNull {
+            column: String::new(),
+            field: None,
+        }
+    }
 }
 
 impl From<TryFromIntError> for DecodeError {
@@ -47,6 +70,11 @@ pub enum Error {
     #[error("encountered unexpected or invalid data: {0}")]
     Protocol(String),
 
+    /// The pool or connection is not configured for the requested operation, for instance a
+    /// [`QueryCache`](crate::cache::QueryCache) on a pool without change tracking.
+    #[error("invalid configuration: {0}")]
+    Configuration(String),
+
     /// No rows returned by a query that expected to return at least one row.
     #[error("no rows returned by a query that expected to return at least one row")]
     RowNotFound,
@@ -76,26 +104,94 @@ pub enum Error {
     #[error("error occurred while decoding: {0}")]
     Decode(#[source] DecodeError),
 
-    /// A [`Pool::acquire`] timed out due to connections not becoming available or
-    /// because another task encountered too many errors while trying to open a new connection.
+    /// A [`Pool::acquire`] timed out because no connection became available, or a new connection could not be opened,
+    /// within the [acquire timeout](crate::Musq::acquire_timeout).
     ///
     /// [`Pool::acquire`]: crate::pool::Pool::acquire
-    #[error("pool timed out while waiting for an open connection")]
-    PoolTimedOut,
+    #[error(
+        "pool timed out after {waited:?} waiting for a connection ({} of {} connections open, {} idle)",
+        stats.size,
+        stats.max_connections,
+        stats.idle
+    )]
+    PoolAcquireTimedOut {
+        /// How long the acquire waited.
+        waited: Duration,
+        /// The pool's connection counts when the acquire gave up.
+        stats: PoolStats,
+    },
 
-    /// [`Pool::close`] was called while we were waiting in [`Pool::acquire`].
+    /// The pool was closed, either before or while we were waiting in [`Pool::acquire`]. Only returned for closed
+    /// pools; timeouts are reported as [`Error::PoolAcquireTimedOut`].
     ///
     /// [`Pool::acquire`]: crate::pool::Pool::acquire
     /// [`Pool::close`]: crate::pool::Pool::close
     #[error("attempted to acquire a connection on a closed pool")]
     PoolClosed,
 
+    /// [Warming up](crate::Pool::warm_up) a pool failed to open some of its connections. The connections are opened
+    /// in parallel, so there is an error for each one that failed. The connections that were opened are kept.
+    #[error(
+        "failed to open {} connections while warming up the pool ({opened} opened): {}",
+        errors.len(),
+        errors[0]
+    )]
+    WarmUpFailed { opened: u32, errors: Vec<Error> },
+
+    /// A user callback invoked by SQLite panicked. The panic was caught and the callback poisoned, so every later
+    /// operation that would invoke it fails with this error until the callback is replaced.
+    #[error("callback {callback} panicked: {message}")]
+    CallbackPanicked { callback: String, message: String },
+
+    /// A [`WriteBatcher`](crate::batch::WriteBatcher) batch failed as a whole, for instance because its transaction
+    /// could not be committed. Every write in the batch receives the same underlying error.
+    #[error("write batch failed: {0}")]
+    BatchFailed(#[source] Arc<Error>),
+
+    /// The query was cancelled through [`Connection::interrupt`](crate::Connection::interrupt) or an
+    /// [`InterruptHandle`](crate::InterruptHandle).
+    #[error("query interrupted")]
+    Interrupted,
+
+    /// A query ran for longer than the timeout set with [`Query::timeout`](crate::query::Query::timeout), and was
+    /// aborted.
+    #[error("query timed out after {timeout:?}")]
+    QueryTimedOut { timeout: Duration },
+
+    /// A query was aborted because the cancellation token set with
+    /// [`Query::with_cancellation`](crate::query::Query::with_cancellation) was cancelled.
+    #[error("query cancelled")]
+    Cancelled,
+
+    /// A query returned more rows or more data than its [`ResultLimits`](crate::ResultLimits) allow.
+    #[error("query result exceeded the limit of {limit}")]
+    ResultLimitExceeded { limit: ResultLimit },
+
     /// A background worker has crashed.
     #[error("attempted to communicate with a crashed background worker")]
     WorkerCrashed,
 }
 
 impl Error {
+    /// Attach the name of the struct field being decoded to an [`UnexpectedNull`](DecodeError::UnexpectedNull)
+    /// error. Used by `#[derive(FromRow)]`.
+    #[doc(hidden)]
+    pub fn in_field(self, name: &str) -> Self {
+        match self {
+            Error::ColumnDecode {
+                index,
+                source: DecodeError::UnexpectedNull { column, .. },
+            } => Error::ColumnDecode {
+                index,
+                source: DecodeError::UnexpectedNull {
+                    column,
+                    field: Some(name.to_string()),
+                },
+            },
+            e => e,
+        }
+    }
+
     pub fn into_sqlite_error(self) -> Option<sqlite::error::SqliteError> {
         match self {
             Error::Sqlite(err) => Some(err),
@@ -1,10 +1,15 @@
-use crate::{error::Error, sqlite, Arguments, QueryResult, Row, Statement};
+use crate::{
+    classify, decode::Decode, error::Error, sqlite, Arguments, QueryKind, QueryResult,
+    ResultLimits, Row, Statement,
+};
 
 use either::Either;
 use futures_core::future::BoxFuture;
 use futures_core::stream::BoxStream;
 use futures_util::{future, FutureExt, StreamExt, TryFutureExt, TryStreamExt};
 use std::fmt::Debug;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 /// A type that contains or can provide a database connection to use for executing queries against
 /// the database.
@@ -20,7 +25,11 @@ use std::fmt::Debug;
 ///  * [`&Pool`](super::pool::Pool)
 ///  * [`&mut PoolConnection`](super::pool::PoolConnection)
 ///  * [`&mut Connection`](super::connection::Connection)
+///  * [`&mut Transaction`](crate::Transaction) and [`&mut Savepoint`](crate::Savepoint)
+///  * [`&mut ReadScope`](crate::ReadScope)
 ///
+/// Functions that take `impl Executor<'c>` accept any of these, so the same code can run against a pool, a single
+/// connection, or inside a transaction.
 pub trait Executor<'c>: Send + Debug + Sized {
     /// Execute the query and return the total number of rows affected.
     fn execute<'e, 'q: 'e, E>(self, query: E) -> BoxFuture<'e, Result<QueryResult, Error>>
@@ -73,6 +82,37 @@ pub trait Executor<'c>: Send + Debug + Sized {
         'c: 'e,
         E: Execute + 'q;
 
+    /// Execute the query and return the generated rows in pages of up to `page_size` rows. Only the last page may be
+    /// shorter. A `page_size` of zero is treated as one.
+    ///
+    /// For large results, this is cheaper than [`fetch`](Self::fetch): a connection sends each page from its worker
+    /// thread as a single message, rather than one message per row.
+    fn fetch_paged<'e, 'q: 'e, E>(
+        self,
+        query: E,
+        page_size: usize,
+    ) -> BoxStream<'e, Result<Vec<Row>, Error>>
+    where
+        'c: 'e,
+        E: Execute + 'q,
+    {
+        let size = page_size.max(1);
+        let mut rows = self.fetch(query);
+        Box::pin(try_stream! {
+            let mut page = Vec::with_capacity(size);
+            while let Some(row) = rows.try_next().await? {
+                page.push(row);
+                if page.len() == size {
+                    r#yield!(std::mem::replace(&mut page, Vec::with_capacity(size)));
+                }
+            }
+            if !page.is_empty() {
+                r#yield!(page);
+            }
+            Ok(())
+        })
+    }
+
     /// Execute the query and return all the generated results, collected into a [`Vec`].
     fn fetch_all<'e, 'q: 'e, E>(self, query: E) -> BoxFuture<'e, Result<Vec<Row>, Error>>
     where
@@ -96,6 +136,25 @@ pub trait Executor<'c>: Send + Debug + Sized {
             .boxed()
     }
 
+    /// Execute the query and return the first column of the first row, decoded as `T`.
+    ///
+    /// This is a shortcut for quick single-value reads like pragmas and counts:
+    ///
+    /// ```rust,ignore
+    /// let count = conn.fetch_scalar::<i64>("SELECT count(*) FROM users").await?;
+    /// ```
+    ///
+    /// Returns [`Error::RowNotFound`] if the query produced no rows.
+    fn fetch_scalar<'e, 'q: 'e, T>(self, sql: &'q str) -> BoxFuture<'e, Result<T, Error>>
+    where
+        'c: 'e,
+        T: for<'r> Decode<'r> + Send + 'e,
+    {
+        self.fetch_one(sql)
+            .and_then(|row| future::ready(row.get_value_idx(0)))
+            .boxed()
+    }
+
     /// Execute the query and returns at most one row.
     fn fetch_optional<'e, 'q: 'e, E>(self, query: E) -> BoxFuture<'e, Result<Option<Row>, Error>>
     where
@@ -152,6 +211,39 @@ pub trait Execute: Send + Sized {
     /// prepare the query. Returning `Some(Default::default())` is an empty arguments object that
     /// will be prepared (and cached) before execution.
     fn take_arguments(&mut self) -> Option<Arguments>;
+
+    /// Bounds on the size of the query's results. By default, results are unbounded.
+    fn limits(&self) -> ResultLimits {
+        ResultLimits::default()
+    }
+
+    /// How long the query may run before it is aborted. By default, queries run until they finish.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// A token that aborts the query when it is cancelled. By default, queries can't be cancelled this way.
+    fn cancellation(&self) -> Option<CancellationToken> {
+        None
+    }
+
+    /// The number of rows the connection may produce ahead of the caller. By default, the connection's
+    /// [row buffer size](crate::Musq::row_buffer_size).
+    fn row_buffer_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Classify the query as a read, a write or a schema change.
+    ///
+    /// Classification is based on the leading keyword of each statement in the SQL. If the query holds a prepared
+    /// statement that SQLite reports as modifying the database, it is classified as at least a write.
+    fn classify(&self) -> QueryKind {
+        let kind = classify::classify(self.sql());
+        match self.statement() {
+            Some(statement) if !statement.is_readonly() => kind.max(QueryKind::Write),
+            _ => kind,
+        }
+    }
 }
 
 impl Execute for &str {
@@ -167,3 +259,57 @@ impl Execute for &str {
         None
     }
 }
+
+/// Implement [`Executor`] for a mutable reference to a type that derefs to a
+/// [`Connection`](crate::Connection), by running every query on that connection.
+macro_rules! impl_executor_for_deref {
+    ($ty:ty $(, $lt:lifetime)?) => {
+        impl<'c, $($lt)?> Executor<'c> for &'c mut $ty {
+            fn fetch_many<'e, 'q: 'e, E>(
+                self,
+                query: E,
+            ) -> BoxStream<'e, Result<Either<QueryResult, Row>, Error>>
+            where
+                'c: 'e,
+                E: Execute + 'q,
+            {
+                (&mut **self).fetch_many(query)
+            }
+
+            fn fetch_paged<'e, 'q: 'e, E>(
+                self,
+                query: E,
+                page_size: usize,
+            ) -> BoxStream<'e, Result<Vec<Row>, Error>>
+            where
+                'c: 'e,
+                E: Execute + 'q,
+            {
+                (&mut **self).fetch_paged(query, page_size)
+            }
+
+            fn fetch_optional<'e, 'q: 'e, E>(self, query: E) -> BoxFuture<'e, Result<Option<Row>, Error>>
+            where
+                'c: 'e,
+                E: Execute + 'q,
+            {
+                (&mut **self).fetch_optional(query)
+            }
+
+            fn prepare_with<'e, 'q: 'e>(
+                self,
+                sql: &'q str,
+                parameters: &'e [sqlite::SqliteDataType],
+            ) -> BoxFuture<'e, Result<Statement, Error>>
+            where
+                'c: 'e,
+            {
+                (&mut **self).prepare_with(sql, parameters)
+            }
+        }
+    };
+}
+
+impl_executor_for_deref!(crate::pool::PoolConnection);
+impl_executor_for_deref!(crate::Transaction<'t>, 't);
+impl_executor_for_deref!(crate::Savepoint<'t>, 't);
@@ -0,0 +1,81 @@
+//! Typed query plans.
+//!
+//! [`Connection::explain`](crate::Connection::explain) runs
+//! [`EXPLAIN QUERY PLAN`](https://www.sqlite.org/eqp.html) for a query and returns the plan as a tree of
+//! [`QueryPlanNode`]s, with full table scans flagged. This makes it possible to test that queries keep using the
+//! indexes they were written for:
+//!
+//! ```rust,ignore
+//! let plan = conn.explain(query("SELECT * FROM users WHERE email = ?").bind(email)).await?;
+//! assert!(!plan.iter().flat_map(QueryPlanNode::nodes).any(|n| n.full_scan));
+//! ```
+//!
+//! The wording of the plan details is not a stable part of SQLite, and may change between versions.
+use crate::{query_as_with, Connection, Execute, Result};
+
+/// A step of a query plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryPlanNode {
+    /// The id of the step within the plan.
+    pub id: i64,
+    /// The id of the enclosing step, or 0 for a top-level step.
+    pub parent: i64,
+    /// SQLite's description of the step, such as `SEARCH users USING INDEX users_email (email=?)`.
+    pub detail: String,
+    /// Whether the step reads every row of a table without using an index.
+    pub full_scan: bool,
+    /// The steps nested in this one, in plan order.
+    pub children: Vec<QueryPlanNode>,
+}
+
+impl QueryPlanNode {
+    /// This step and all the steps nested in it, depth first.
+    pub fn nodes(&self) -> Vec<&QueryPlanNode> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.extend(child.nodes());
+        }
+        nodes
+    }
+}
+
+/// Whether a plan detail describes a scan of a whole table, rather than of an index, a subquery or a constant row.
+fn is_full_scan(detail: &str) -> bool {
+    // SQLite before 3.36 writes "SCAN TABLE t"
+    let Some(target) = detail.strip_prefix("SCAN ") else {
+        return false;
+    };
+    !target.starts_with('(')
+        && !target.starts_with("CONSTANT ROW")
+        && !target.starts_with("SUBQUERY")
+        && !target.contains(" USING ")
+        && !target.contains("VIRTUAL TABLE")
+}
+
+fn build(rows: &[(i64, i64, String)], parent: i64) -> Vec<QueryPlanNode> {
+    rows.iter()
+        .filter(|(_, p, _)| *p == parent)
+        .map(|(id, parent, detail)| QueryPlanNode {
+            id: *id,
+            parent: *parent,
+            full_scan: is_full_scan(detail),
+            detail: detail.clone(),
+            children: build(rows, *id),
+        })
+        .collect()
+}
+
+pub(crate) async fn explain(
+    conn: &mut Connection,
+    mut query: impl Execute,
+) -> Result<Vec<QueryPlanNode>> {
+    let sql = format!("EXPLAIN QUERY PLAN {}", query.sql());
+    let arguments = query.take_arguments().unwrap_or_default();
+    let rows: Vec<(i64, i64, i64, String)> =
+        query_as_with(&sql, arguments).fetch_all(&mut *conn).await?;
+    let rows: Vec<_> = rows
+        .into_iter()
+        .map(|(id, parent, _, detail)| (id, parent, detail))
+        .collect();
+    Ok(build(&rows, 0))
+}
@@ -0,0 +1,117 @@
+//! Finding and repairing foreign key violations.
+//!
+//! SQLite only enforces foreign keys while `PRAGMA foreign_keys` is on, so bulk imports that turn it off for speed can
+//! leave orphaned rows behind. [`Connection::foreign_key_violations`](crate::Connection::foreign_key_violations) lists
+//! them, and [`Connection::repair_foreign_keys`](crate::Connection::repair_foreign_keys) deletes or detaches them in
+//! batches.
+//!
+//! Bulk loads that keep enforcement on can instead call
+//! [`Transaction::defer_foreign_keys`](crate::Transaction::defer_foreign_keys), so that rows may be inserted in any
+//! order, and check the tables they touched with
+//! [`Connection::foreign_key_check`](crate::Connection::foreign_key_check) before committing.
+use std::collections::HashMap;
+
+use crate::{query, query_as, query_scalar, schema::quote_identifier, Connection, Result};
+
+/// A row that violates a foreign key constraint, as reported by
+/// [`PRAGMA foreign_key_check`](https://www.sqlite.org/pragma.html#pragma_foreign_key_check).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FkViolation {
+    /// The table containing the orphaned row.
+    pub table: String,
+    /// The rowid of the orphaned row, or `None` for `WITHOUT ROWID` tables.
+    pub rowid: Option<i64>,
+    /// The table the foreign key refers to.
+    pub parent: String,
+    /// The index of the violated foreign key in the output of `PRAGMA foreign_key_list(table)`.
+    pub fkid: i64,
+}
+
+/// How [`Connection::repair_foreign_keys`](crate::Connection::repair_foreign_keys) resolves a violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repair {
+    /// Delete the orphaned row.
+    Delete,
+    /// Set the row's foreign key columns to `NULL`. This fails for columns declared `NOT NULL`.
+    SetNull,
+}
+
+/// The violations in `table`, or in every table if it is `None`.
+pub(crate) async fn violations(
+    conn: &mut Connection,
+    table: Option<&str>,
+) -> Result<Vec<FkViolation>> {
+    let rows: Vec<(String, Option<i64>, String, i64)> = match table {
+        Some(table) => {
+            query_as("SELECT \"table\", rowid, parent, fkid FROM pragma_foreign_key_check(?)")
+                .bind(table)
+                .fetch_all(&mut *conn)
+                .await?
+        }
+        None => {
+            query_as("SELECT \"table\", rowid, parent, fkid FROM pragma_foreign_key_check")
+                .fetch_all(&mut *conn)
+                .await?
+        }
+    };
+    Ok(rows
+        .into_iter()
+        .map(|(table, rowid, parent, fkid)| FkViolation {
+            table,
+            rowid,
+            parent,
+            fkid,
+        })
+        .collect())
+}
+
+pub(crate) async fn repair(conn: &mut Connection, repair: Repair, batch_size: u32) -> Result<u64> {
+    // The columns of each foreign key, by table and fkid
+    let mut columns: HashMap<(String, i64), Vec<String>> = HashMap::new();
+    let mut total = 0;
+    loop {
+        let batch: Vec<(String, i64, i64)> = query_as(
+            "SELECT \"table\", rowid, fkid FROM pragma_foreign_key_check WHERE rowid IS NOT NULL LIMIT ?",
+        )
+        .bind(batch_size.max(1))
+        .fetch_all(&mut *conn)
+        .await?;
+        if batch.is_empty() {
+            return Ok(total);
+        }
+
+        total += batch.len() as u64;
+        let mut tx = conn.begin().await?;
+        for (table, rowid, fkid) in batch {
+            let sql = match repair {
+                Repair::Delete => {
+                    format!("DELETE FROM {} WHERE rowid = ?", quote_identifier(&table))
+                }
+                Repair::SetNull => {
+                    let key = (table, fkid);
+                    if !columns.contains_key(&key) {
+                        let from: Vec<String> = query_scalar(
+                            "SELECT \"from\" FROM pragma_foreign_key_list(?) WHERE id = ?",
+                        )
+                        .bind(key.0.clone())
+                        .bind(key.1)
+                        .fetch_all(&mut *tx)
+                        .await?;
+                        columns.insert(key.clone(), from);
+                    }
+                    let set = columns[&key]
+                        .iter()
+                        .map(|c| format!("{} = NULL", quote_identifier(c)))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!(
+                        "UPDATE {} SET {set} WHERE rowid = ?",
+                        quote_identifier(&key.0)
+                    )
+                }
+            };
+            query(&sql).bind(rowid).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+    }
+}
@@ -1,4 +1,9 @@
-use crate::error::Error;
+use std::hash::Hash;
+
+use indexmap::IndexMap;
+
+use crate::decode::Decode;
+use crate::error::{DecodeError, Error};
 use crate::Row;
 
 /// A record that can be built from a row returned by the database.
@@ -18,6 +23,42 @@ use crate::Row;
 /// }
 /// ```
 ///
+/// Tuple structs are decoded by position instead, with each field reading the next column. Fields marked `skip` take
+/// their default value and don't consume a column, and `try_from` is supported; other field attributes are not.
+///
+/// ```rust,ignore
+/// #[derive(FromRow)]
+/// struct Pair(i64, String, #[musq(skip)] Vec<Tag>);
+/// ```
+///
+/// Named structs are decoded by position too with the `positional` attribute, for queries whose column names
+/// collide, such as `SELECT *` over a join:
+///
+/// ```rust,ignore
+/// #[derive(FromRow)]
+/// #[musq(positional)]
+/// struct UserPost {
+///     user_id: i64,
+///     user_name: String,
+///     post_id: i64,
+///     title: String,
+/// }
+///
+/// let rows: Vec<UserPost> = query_as("SELECT users.id, users.name, posts.id, posts.title FROM users JOIN posts ...")
+/// ```
+///
+/// Generic structs are supported, with each type parameter bounded by [`Decode`](crate::decode::Decode) or `FromRow`
+/// as its fields require:
+///
+/// ```rust,ignore
+/// #[derive(FromRow)]
+/// struct Entity<T> {
+///     id: i64,
+///     #[musq(flatten)]
+///     data: T,
+/// }
+/// ```
+///
 /// ### Field attributes
 ///
 /// Several attributes can be specified to customize how each column in a row is read:
@@ -64,6 +105,20 @@ use crate::Row;
 /// `lowercase`, `UPPERCASE`, `camelCase`, `PascalCase`, `SCREAMING_SNAKE_CASE` and `kebab-case`. The styling of each
 /// option is intended to be an example of its behavior.
 ///
+/// #### `table`
+/// Placed at the struct level, this names the table the struct maps to, and additionally implements
+/// [`schema::Table`](crate::schema::Table) so the struct can be checked against the live schema with
+/// [`validate_schema`](crate::validate_schema):
+///
+/// ```rust,ignore
+/// #[derive(FromRow)]
+/// #[musq(table = "users")]
+/// struct User {
+///     id: i64,
+///     name: String,
+/// }
+/// ```
+///
 /// #### `default`
 ///
 /// When your struct contains a field that is not present in your query, if the field type has an implementation for
@@ -114,7 +169,22 @@ use crate::Row;
 /// SELECT id, name, country, city, road FROM users;
 /// ```
 ///
-/// This field is compatible with the `default` attribute.
+/// This field is compatible with the `default` attribute. Combined with `prefix`, the flattened struct reads its columns
+/// with the prefix prepended, as in `#[musq(flatten, prefix = "addr_")]`. Prefixes of nested flattened structs add up.
+///
+/// A flattened field may be an `Option`, which is `None` when a column of a non-nullable field of the inner struct is
+/// `NULL`, as it is for the columns of a `LEFT JOIN` that matched nothing:
+///
+/// ```rust,ignore
+/// #[derive(FromRow)]
+/// struct User {
+///     id: i32,
+///     #[musq(flatten, prefix = "addr_")]
+///     address: Option<Address>,
+/// }
+/// ```
+///
+/// See [`group_rows_by`] for loading one-to-many relations from joined rows.
 ///
 /// #### `skip`
 ///
@@ -195,6 +265,68 @@ pub trait FromRow<'r>: Sized {
     fn from_row(prefix: &str, row: &'r Row) -> Result<Self, Error>;
 }
 
+/// `None` if a non-nullable field of `T` is `NULL` in the row, for the columns of an outer join that matched nothing.
+impl<'r, T: FromRow<'r>> FromRow<'r> for Option<T> {
+    fn from_row(prefix: &str, row: &'r Row) -> Result<Self, Error> {
+        match T::from_row(prefix, row) {
+            Ok(value) => Ok(Some(value)),
+            Err(Error::ColumnDecode {
+                source: DecodeError::UnexpectedNull { .. },
+                ..
+            }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Fold the rows of a one-to-many join into parents with their children, for queries such as:
+///
+/// ```sql
+/// SELECT users.*, posts.id AS post_id, posts.title AS post_title
+/// FROM users LEFT JOIN posts ON posts.user_id = users.id
+/// ```
+///
+/// Rows are grouped by the value of the `key` column. The parent `P` is decoded from the first row of each group, and
+/// a child `C` from every row of the group, reading its columns with `child_prefix` prepended, and pushed onto the
+/// vector that `children` returns for the parent. Rows in which a non-nullable field of the child is `NULL`, as for a
+/// parent without children in a `LEFT JOIN`, add no child. Parents are returned in the order their keys first appear.
+///
+/// ```rust,ignore
+/// #[derive(FromRow)]
+/// struct User {
+///     id: i64,
+///     name: String,
+///     #[musq(skip)]
+///     posts: Vec<Post>,
+/// }
+///
+/// let rows = query(sql).fetch_all(&pool).await?;
+/// let users: Vec<User> = group_rows_by::<i64, _, _>(&rows, "id", "post_", |u: &mut User| &mut u.posts)?;
+/// ```
+pub fn group_rows_by<'r, K, P, C>(
+    rows: &'r [Row],
+    key: &str,
+    child_prefix: &str,
+    mut children: impl FnMut(&mut P) -> &mut Vec<C>,
+) -> Result<Vec<P>, Error>
+where
+    K: Decode<'r> + Hash + Eq,
+    P: FromRow<'r>,
+    C: FromRow<'r>,
+{
+    let mut parents: IndexMap<K, P> = IndexMap::new();
+    for row in rows {
+        let parent = match parents.entry(row.get_value::<K>(key)?) {
+            indexmap::map::Entry::Occupied(entry) => entry.into_mut(),
+            indexmap::map::Entry::Vacant(entry) => entry.insert(P::from_row("", row)?),
+        };
+        if let Some(child) = Option::<C>::from_row(child_prefix, row)? {
+            children(parent).push(child);
+        }
+    }
+    Ok(parents.into_values().collect())
+}
+
 // implement FromRow for tuples of types that implement Decode
 // up to tuples of 9 values
 
@@ -0,0 +1,443 @@
+//! User-defined SQL functions and collations.
+//!
+//! Functions and collations are registered on the [`Musq`](crate::Musq) builder, and are installed on every
+//! connection it opens. They run on the connection's worker thread, inside the query that calls them.
+//!
+//! ```rust,ignore
+//! let pool = Musq::new()
+//!     .create_scalar_function("double", 1, FunctionFlags::DETERMINISTIC, |args| {
+//!         Ok((args[0].int64() * 2).encode())
+//!     })
+//!     .open_in_memory()
+//!     .await?;
+//! ```
+//!
+//! Aggregate functions keep state across the rows of a group:
+//!
+//! ```rust,ignore
+//! #[derive(Default)]
+//! struct Median(Vec<f64>);
+//!
+//! impl AggregateFunction for Median {
+//!     fn step(&mut self, args: &[Value]) {
+//!         if !args[0].is_null() {
+//!             self.0.push(args[0].double());
+//!         }
+//!     }
+//!
+//!     fn finalize(mut self) -> ArgumentValue {
+//!         self.0.sort_by(f64::total_cmp);
+//!         self.0.get(self.0.len() / 2).copied().encode()
+//!     }
+//! }
+//!
+//! let pool = Musq::new()
+//!     .create_aggregate_function("median", 1, Median::default)
+//!     .open_in_memory()
+//!     .await?;
+//! ```
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    ffi::CString,
+    mem,
+    ops::BitOr,
+    os::raw::{c_int, c_void},
+    ptr,
+    sync::Arc,
+};
+
+use libsqlite3_sys::{
+    sqlite3, sqlite3_aggregate_context, sqlite3_context, sqlite3_create_collation_v2,
+    sqlite3_create_function_v2, sqlite3_result_blob64, sqlite3_result_double, sqlite3_result_error,
+    sqlite3_result_int, sqlite3_result_int64, sqlite3_result_null, sqlite3_result_text64,
+    sqlite3_user_data, sqlite3_value, sqlite3_value_type, SQLITE_DETERMINISTIC, SQLITE_DIRECTONLY,
+    SQLITE_INNOCUOUS, SQLITE_OK, SQLITE_TRANSIENT, SQLITE_UTF8,
+};
+
+use crate::{
+    debugfn::DebugFn,
+    sqlite::{Callback, CallbackPanics},
+    ArgumentValue, Error, Result, SqliteDataType, SqliteError, Value,
+};
+
+/// Flags for a user-defined function, combined with `|`. See
+/// [Function Flags](https://www.sqlite.org/c3ref/c_deterministic.html).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FunctionFlags(i32);
+
+impl FunctionFlags {
+    /// No flags.
+    pub const NONE: Self = Self(0);
+    /// The function always gives the same result for the same arguments, so SQLite may factor calls out of loops,
+    /// and the function may be used in indexes, `CHECK` constraints and generated columns.
+    pub const DETERMINISTIC: Self = Self(SQLITE_DETERMINISTIC);
+    /// The function may only be called from top-level SQL, and not from triggers, views or schema structures.
+    pub const DIRECT_ONLY: Self = Self(SQLITE_DIRECTONLY);
+    /// The function has no side effects and doesn't leak information, so it is safe to call from the schema even
+    /// when the database comes from an untrusted source.
+    pub const INNOCUOUS: Self = Self(SQLITE_INNOCUOUS);
+
+    /// Whether all the flags in `other` are set.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for FunctionFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A user-defined aggregate function, registered with
+/// [`Musq::create_aggregate_function`](crate::Musq::create_aggregate_function).
+///
+/// A fresh value is created for each group the aggregate is computed over. [`step`](Self::step) is called once for
+/// every row in the group, and [`finalize`](Self::finalize) then produces the result. For an empty group,
+/// `finalize` is called on a fresh value without any steps.
+///
+/// If either method panics, the query fails with [`Error::CallbackPanicked`], and the function is poisoned: every
+/// later query that calls it on the same connection fails the same way.
+pub trait AggregateFunction: Send + 'static {
+    /// Add a row's arguments to the aggregate.
+    fn step(&mut self, args: &[Value]);
+
+    /// Produce the result of the aggregate.
+    fn finalize(self) -> ArgumentValue;
+}
+
+type Register = dyn Fn(*mut sqlite3, &Arc<CallbackPanics>) -> Result<()> + Send + Sync + 'static;
+
+/// A function or collation registration, applied to each new connection.
+#[derive(Debug, Clone)]
+pub(crate) struct Function {
+    register: Arc<DebugFn<Register>>,
+}
+
+impl Function {
+    /// A registration that runs `register` on each new connection.
+    pub(crate) fn new<R>(register: R) -> Self
+    where
+        R: Fn(*mut sqlite3, &Arc<CallbackPanics>) -> Result<()> + Send + Sync + 'static,
+    {
+        Self {
+            register: Arc::new(DebugFn(register)),
+        }
+    }
+
+    pub(crate) fn scalar<F>(name: &str, n_args: i32, flags: FunctionFlags, f: F) -> Self
+    where
+        F: Fn(&[Value]) -> std::result::Result<ArgumentValue, String> + Send + Sync + 'static,
+    {
+        let name = name.to_string();
+        let f = Arc::new(f);
+        let register = move |db: *mut sqlite3, panics: &Arc<CallbackPanics>| {
+            let callback = Callback::new(format!("function {name}"), f.clone(), panics.clone());
+            let c_name = CString::new(name.as_str())
+                .map_err(|_| Error::Protocol("function name contains nul bytes".into()))?;
+            let data = Box::into_raw(Box::new(callback));
+            // As for aggregates, SQLite owns `data` from here on
+            let rc = unsafe {
+                sqlite3_create_function_v2(
+                    db,
+                    c_name.as_ptr(),
+                    n_args,
+                    SQLITE_UTF8 | flags.0,
+                    data.cast(),
+                    Some(scalar_call::<F>),
+                    None,
+                    None,
+                    Some(destroy::<Callback<Arc<F>>>),
+                )
+            };
+            if rc != SQLITE_OK {
+                return Err(SqliteError::new(db).into());
+            }
+            Ok(())
+        };
+        Self {
+            register: Arc::new(DebugFn(register)),
+        }
+    }
+
+    pub(crate) fn aggregate<A, F>(name: &str, n_args: i32, factory: F) -> Self
+    where
+        A: AggregateFunction,
+        F: Fn() -> A + Send + Sync + 'static,
+    {
+        let name = name.to_string();
+        let factory = Arc::new(factory);
+        let register = move |db: *mut sqlite3, panics: &Arc<CallbackPanics>| {
+            let callback = Callback::new(
+                format!("aggregate function {name}"),
+                factory.clone(),
+                panics.clone(),
+            );
+            let c_name = CString::new(name.as_str())
+                .map_err(|_| Error::Protocol("function name contains nul bytes".into()))?;
+            let data = Box::into_raw(Box::new(callback));
+            // SQLite takes ownership of `data`, and frees it with `destroy` when the function is replaced or the
+            // connection closes, even if registration fails
+            let rc = unsafe {
+                sqlite3_create_function_v2(
+                    db,
+                    c_name.as_ptr(),
+                    n_args,
+                    SQLITE_UTF8,
+                    data.cast(),
+                    None,
+                    Some(aggregate_step::<A, F>),
+                    Some(aggregate_final::<A, F>),
+                    Some(destroy::<Callback<Arc<F>>>),
+                )
+            };
+            if rc != SQLITE_OK {
+                return Err(SqliteError::new(db).into());
+            }
+            Ok(())
+        };
+        Self {
+            register: Arc::new(DebugFn(register)),
+        }
+    }
+
+    pub(crate) fn collation<F>(name: &str, cmp: F) -> Self
+    where
+        F: Fn(&str, &str) -> Ordering + Send + Sync + 'static,
+    {
+        let name = name.to_string();
+        let cmp = Arc::new(cmp);
+        let register = move |db: *mut sqlite3, panics: &Arc<CallbackPanics>| {
+            let callback = Callback::new(format!("collation {name}"), cmp.clone(), panics.clone());
+            let c_name = CString::new(name.as_str())
+                .map_err(|_| Error::Protocol("collation name contains nul bytes".into()))?;
+            let data = Box::into_raw(Box::new(callback));
+            // As for functions, SQLite owns `data` from here on
+            let rc = unsafe {
+                sqlite3_create_collation_v2(
+                    db,
+                    c_name.as_ptr(),
+                    SQLITE_UTF8,
+                    data.cast(),
+                    Some(compare::<F>),
+                    Some(destroy::<Callback<Arc<F>>>),
+                )
+            };
+            if rc != SQLITE_OK {
+                return Err(SqliteError::new(db).into());
+            }
+            Ok(())
+        };
+        Self {
+            register: Arc::new(DebugFn(register)),
+        }
+    }
+
+    pub(crate) fn register(&self, db: *mut sqlite3, panics: &Arc<CallbackPanics>) -> Result<()> {
+        (self.register)(db, panics)
+    }
+}
+
+/// The aggregate state of the current group, or `None` if it hasn't been allocated. SQLite zeroes the memory it
+/// allocates for the aggregate context, so a null pointer means a fresh group.
+unsafe fn aggregate_state<A>(ctx: *mut sqlite3_context, allocate: bool) -> Option<*mut *mut A> {
+    let size = if allocate {
+        mem::size_of::<*mut A>()
+    } else {
+        0
+    };
+    let slot = sqlite3_aggregate_context(ctx, size as c_int) as *mut *mut A;
+    (!slot.is_null()).then_some(slot)
+}
+
+unsafe extern "C" fn scalar_call<F>(
+    ctx: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) where
+    F: Fn(&[Value]) -> std::result::Result<ArgumentValue, String>,
+{
+    let callback = &mut *(sqlite3_user_data(ctx) as *mut Callback<Arc<F>>);
+    let args = values(argc, argv);
+    match callback.call(|f| f(&args)) {
+        Some(Ok(value)) => set_result(ctx, &value),
+        Some(Err(msg)) => {
+            sqlite3_result_error(ctx, msg.as_ptr().cast(), msg.len() as c_int);
+        }
+        None => sqlite3_result_error(ctx, c"function panicked".as_ptr(), -1),
+    }
+}
+
+unsafe extern "C" fn aggregate_step<A, F>(
+    ctx: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) where
+    A: AggregateFunction,
+    F: Fn() -> A,
+{
+    let callback = &mut *(sqlite3_user_data(ctx) as *mut Callback<Arc<F>>);
+    let Some(slot) = aggregate_state::<A>(ctx, true) else {
+        return sqlite3_result_error(ctx, c"out of memory".as_ptr(), -1);
+    };
+    let args = values(argc, argv);
+    let stepped = callback.call(|factory| {
+        if (*slot).is_null() {
+            *slot = Box::into_raw(Box::new(factory()));
+        }
+        (**slot).step(&args);
+    });
+    if stepped.is_none() {
+        sqlite3_result_error(ctx, c"aggregate function panicked".as_ptr(), -1);
+    }
+}
+
+unsafe extern "C" fn aggregate_final<A, F>(ctx: *mut sqlite3_context)
+where
+    A: AggregateFunction,
+    F: Fn() -> A,
+{
+    let callback = &mut *(sqlite3_user_data(ctx) as *mut Callback<Arc<F>>);
+    let state = match aggregate_state::<A>(ctx, false) {
+        Some(slot) if !(*slot).is_null() => {
+            Some(Box::from_raw(mem::replace(&mut *slot, ptr::null_mut())))
+        }
+        _ => None,
+    };
+    let result = callback.call(|factory| state.unwrap_or_else(|| Box::new(factory())).finalize());
+    match result {
+        Some(value) => set_result(ctx, &value),
+        None => sqlite3_result_error(ctx, c"aggregate function panicked".as_ptr(), -1),
+    }
+}
+
+unsafe extern "C" fn compare<F>(
+    data: *mut c_void,
+    len_a: c_int,
+    a: *const c_void,
+    len_b: c_int,
+    b: *const c_void,
+) -> c_int
+where
+    F: Fn(&str, &str) -> Ordering,
+{
+    let callback = &mut *(data as *mut Callback<Arc<F>>);
+    let a = text(a, len_a);
+    let b = text(b, len_b);
+    // Collations can't fail, so a panicking comparator treats everything as equal
+    match callback.call(|cmp| cmp(&a, &b)) {
+        Some(Ordering::Less) => -1,
+        Some(Ordering::Greater) => 1,
+        Some(Ordering::Equal) | None => 0,
+    }
+}
+
+/// Text passed to a collation, which is not guaranteed to be valid UTF-8.
+unsafe fn text<'a>(ptr: *const c_void, len: c_int) -> Cow<'a, str> {
+    if ptr.is_null() || len <= 0 {
+        return Cow::Borrowed("");
+    }
+    String::from_utf8_lossy(std::slice::from_raw_parts(ptr.cast(), len as usize))
+}
+
+unsafe extern "C" fn destroy<T>(data: *mut c_void) {
+    drop(Box::from_raw(data as *mut T));
+}
+
+pub(crate) unsafe fn values(argc: c_int, argv: *mut *mut sqlite3_value) -> Vec<Value> {
+    (0..argc as usize)
+        .map(|i| {
+            let value = *argv.add(i);
+            Value::new(value, SqliteDataType::from_code(sqlite3_value_type(value)))
+        })
+        .collect()
+}
+
+pub(crate) unsafe fn set_result(ctx: *mut sqlite3_context, value: &ArgumentValue) {
+    match value {
+        ArgumentValue::Null => sqlite3_result_null(ctx),
+        ArgumentValue::Text(v) => sqlite3_result_text64(
+            ctx,
+            v.as_ptr().cast(),
+            v.len() as u64,
+            SQLITE_TRANSIENT(),
+            SQLITE_UTF8 as u8,
+        ),
+        ArgumentValue::Blob(v) => {
+            sqlite3_result_blob64(ctx, v.as_ptr().cast(), v.len() as u64, SQLITE_TRANSIENT())
+        }
+        ArgumentValue::Double(v) => sqlite3_result_double(ctx, *v),
+        ArgumentValue::Int(v) => sqlite3_result_int(ctx, *v),
+        ArgumentValue::Int64(v) => sqlite3_result_int64(ctx, *v),
+    }
+}
+
+/// The functions registered by [`Musq::with_helper_functions`](crate::Musq::with_helper_functions).
+#[cfg(feature = "helper-functions")]
+pub(crate) fn helpers() -> Vec<Function> {
+    use std::{fmt::Write, sync::Mutex};
+
+    use regex::Regex;
+    use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+    use crate::encode::Encode;
+
+    let flags = FunctionFlags::DETERMINISTIC | FunctionFlags::INNOCUOUS;
+    // Queries usually call `regexp_replace` with the same pattern for every row, so keep the last one compiled
+    let last = Mutex::new(None::<(String, Regex)>);
+    vec![
+        Function::scalar("regexp_replace", 3, flags, move |args| {
+            if args.iter().any(Value::is_null) {
+                return Ok(ArgumentValue::Null);
+            }
+            let text = args[0].text().map_err(|e| e.to_string())?;
+            let pattern = args[1].text().map_err(|e| e.to_string())?;
+            let replacement = args[2].text().map_err(|e| e.to_string())?;
+            let mut last = last.lock().map_err(|e| e.to_string())?;
+            let re = match last.take() {
+                Some((p, re)) if p == pattern => re,
+                _ => Regex::new(pattern).map_err(|e| e.to_string())?,
+            };
+            let replaced = re.replace_all(text, replacement).into_owned();
+            *last = Some((pattern.to_string(), re));
+            Ok(replaced.encode())
+        }),
+        Function::scalar("uuid_blob_to_text", 1, flags, |args| {
+            if args[0].is_null() {
+                return Ok(ArgumentValue::Null);
+            }
+            let blob = args[0].blob();
+            if blob.len() != 16 {
+                return Err(format!(
+                    "uuid_blob_to_text: expected a 16-byte blob, got {} bytes",
+                    blob.len()
+                ));
+            }
+            let mut text = String::with_capacity(36);
+            for (i, b) in blob.iter().enumerate() {
+                if matches!(i, 4 | 6 | 8 | 10) {
+                    text.push('-');
+                }
+                let _ = write!(text, "{b:02x}");
+            }
+            Ok(text.encode())
+        }),
+        Function::scalar("unix_to_iso8601", 1, flags, |args| {
+            let time = match args[0].type_info() {
+                SqliteDataType::Null => return Ok(ArgumentValue::Null),
+                SqliteDataType::Float => {
+                    OffsetDateTime::from_unix_timestamp_nanos((args[0].double() * 1e9) as i128)
+                }
+                _ => OffsetDateTime::from_unix_timestamp(args[0].int64()),
+            };
+            let text = time
+                .map_err(|e| e.to_string())?
+                .format(&Rfc3339)
+                .map_err(|e| e.to_string())?;
+            Ok(text.encode())
+        }),
+    ]
+}
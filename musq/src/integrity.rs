@@ -0,0 +1,56 @@
+//! Checking a database for corruption.
+//!
+//! [`Connection::integrity_check`](crate::Connection::integrity_check) runs
+//! [`PRAGMA integrity_check`](https://www.sqlite.org/pragma.html#pragma_integrity_check), which verifies the whole
+//! database file, and [`Connection::quick_check`](crate::Connection::quick_check) runs the much faster
+//! [`PRAGMA quick_check`](https://www.sqlite.org/pragma.html#pragma_quick_check), which skips checking that indexes
+//! match their tables. Both return the problems found, and an empty list for a healthy database.
+//!
+//! A pool can run the quick check on idle connections before handing them out, with
+//! [`Musq::quick_check_before_acquire`](crate::Musq::quick_check_before_acquire).
+use crate::{query_scalar, Connection, Result};
+
+/// A problem reported by an integrity check.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IntegrityError {
+    /// The message reported by SQLite, such as `row 3 missing from index idx_users_email`.
+    pub message: String,
+    /// The index the problem was found in, if the message names one.
+    pub index: Option<String>,
+    /// The rowid of the row the problem concerns, if the message names one.
+    pub rowid: Option<i64>,
+}
+
+impl IntegrityError {
+    fn parse(message: String) -> Self {
+        let index = ["from index ", "in index "].iter().find_map(|marker| {
+            message
+                .rfind(marker)
+                .map(|i| message[i + marker.len()..].trim().to_string())
+        });
+        let rowid = message
+            .strip_prefix("row ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|n| n.parse().ok());
+        Self {
+            message,
+            index,
+            rowid,
+        }
+    }
+}
+
+/// Run `pragma` with its error limit, and parse its output.
+pub(crate) async fn check(
+    conn: &mut Connection,
+    pragma: &str,
+    max_errors: u32,
+) -> Result<Vec<IntegrityError>> {
+    // A bound argument would be taken as a table name, so the limit is inlined
+    let sql = format!("PRAGMA {pragma}({})", max_errors.max(1));
+    let rows: Vec<String> = query_scalar(&sql).fetch_all(&mut *conn).await?;
+    if rows.len() == 1 && rows[0] == "ok" {
+        return Ok(Vec::new());
+    }
+    Ok(rows.into_iter().map(IntegrityError::parse).collect())
+}
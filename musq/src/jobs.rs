@@ -0,0 +1,288 @@
+//! A persistent job queue.
+//!
+//! Jobs are [`enqueue`]d onto a named queue with an optional priority and delay, and processed by [`JobQueue`]
+//! workers. A claimed job is hidden from other workers for a visibility timeout; if the worker dies, the job becomes
+//! visible again and is retried. Jobs that fail too many times are moved to a dead-letter table for inspection.
+//!
+//! ```rust,ignore
+//! jobs::create_tables(&pool).await?;
+//! jobs::enqueue_with(&pool, "email", r#"{"to": "a@example.com"}"#, &Enqueue::new().priority(10)).await?;
+//!
+//! JobQueue::new(pool, "email").run(|job| async move { send(job.payload).await }).await?;
+//! ```
+use std::{fmt::Display, future::Future, time::Duration};
+
+use crate::{
+    outbox::{millis, now_millis},
+    query,
+    query::Query,
+    query_as, query_scalar, Arguments, Connection, Executor, Pool, Result,
+};
+
+/// The schema of the job tables, created by [`create_tables`]. Job ids are never reused, so a job keeps its id when
+/// it is moved to the dead-letter table.
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS musq_jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    queue TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    priority INTEGER NOT NULL DEFAULT 0,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    visible_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    last_error TEXT
+);
+CREATE INDEX IF NOT EXISTS musq_jobs_ready ON musq_jobs (queue, priority DESC, id);
+CREATE TABLE IF NOT EXISTS musq_jobs_dead (
+    id INTEGER PRIMARY KEY,
+    queue TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    priority INTEGER NOT NULL,
+    attempts INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    failed_at INTEGER NOT NULL,
+    last_error TEXT
+);
+";
+
+/// A job claimed from a queue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub id: i64,
+    pub queue: String,
+    pub payload: String,
+    pub priority: i64,
+    /// The number of attempts made so far, including the current one.
+    pub attempts: i64,
+}
+
+/// Options for [`enqueue_with`].
+#[derive(Debug, Clone, Default)]
+pub struct Enqueue {
+    priority: i64,
+    delay: Duration,
+}
+
+impl Enqueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Jobs with a higher priority are claimed first. Jobs of equal priority are claimed in the order they were
+    /// enqueued. The default is 0.
+    pub fn priority(mut self, priority: i64) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Don't make the job available to workers until `delay` has passed.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// Create the job tables if they don't already exist.
+pub async fn create_tables<'c, E>(executor: E) -> Result<()>
+where
+    E: Executor<'c>,
+{
+    executor.execute(query(SCHEMA)).await?;
+    Ok(())
+}
+
+/// Add a job to `queue` with default options. Returns the id of the new job.
+pub async fn enqueue<'c, E>(executor: E, queue: &str, payload: &str) -> Result<i64>
+where
+    E: Executor<'c>,
+{
+    enqueue_with(executor, queue, payload, &Enqueue::default()).await
+}
+
+/// Add a job to `queue`. Returns the id of the new job.
+///
+/// Pass a transaction as the executor to make the job conditional on the transaction committing.
+pub async fn enqueue_with<'c, E>(
+    executor: E,
+    queue: &str,
+    payload: &str,
+    options: &Enqueue,
+) -> Result<i64>
+where
+    E: Executor<'c>,
+{
+    let now = now_millis();
+    query_scalar(
+        "INSERT INTO musq_jobs (queue, payload, priority, visible_at, created_at)
+        VALUES (?, ?, ?, ?, ?) RETURNING id",
+    )
+    .bind(queue.to_string())
+    .bind(payload.to_string())
+    .bind(options.priority)
+    .bind(now.saturating_add(millis(options.delay)))
+    .bind(now)
+    .fetch_one(executor)
+    .await
+}
+
+/// A worker for a single named queue.
+#[derive(Debug, Clone)]
+pub struct JobQueue {
+    pool: Pool,
+    queue: String,
+    visibility_timeout: Duration,
+    retry_delay: Duration,
+    max_attempts: u32,
+    poll_interval: Duration,
+}
+
+impl JobQueue {
+    pub fn new(pool: Pool, queue: &str) -> Self {
+        Self {
+            pool,
+            queue: queue.into(),
+            visibility_timeout: Duration::from_secs(60),
+            retry_delay: Duration::from_secs(5),
+            max_attempts: 5,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// How long a claimed job is hidden from other workers. If the job is neither completed nor failed in this time,
+    /// it becomes visible again and is retried. The default is 60 seconds.
+    pub fn visibility_timeout(mut self, timeout: Duration) -> Self {
+        self.visibility_timeout = timeout;
+        self
+    }
+
+    /// How long to wait before retrying a failed job. The default is 5 seconds.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// The number of attempts after which a job is moved to the dead-letter table. The default is 5.
+    pub fn max_attempts(mut self, n: u32) -> Self {
+        self.max_attempts = n.max(1);
+        self
+    }
+
+    /// How long [`run`](Self::run) sleeps when the queue is empty. The default is 1 second.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Claim the next available job, if any. The job must subsequently be passed to [`complete`](Self::complete) or
+    /// [`fail`](Self::fail).
+    pub async fn claim(&self) -> Result<Option<Job>> {
+        let now = now_millis();
+        let mut tx = self.pool.begin().await?;
+
+        // Jobs whose worker died on their final attempt can't be retried
+        bury(
+            &mut tx,
+            "queue = ? AND visible_at <= ? AND attempts >= ?",
+            |q| q.bind(self.queue.clone()).bind(now).bind(self.max_attempts),
+        )
+        .await?;
+
+        let job: Option<(i64, String, String, i64, i64)> = query_as(
+            "UPDATE musq_jobs SET visible_at = ?, attempts = attempts + 1
+            WHERE id = (
+                SELECT id FROM musq_jobs
+                WHERE queue = ? AND visible_at <= ?
+                ORDER BY priority DESC, id LIMIT 1
+            )
+            RETURNING id, queue, payload, priority, attempts",
+        )
+        .bind(now.saturating_add(millis(self.visibility_timeout)))
+        .bind(self.queue.clone())
+        .bind(now)
+        .fetch_optional(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(job.map(|(id, queue, payload, priority, attempts)| Job {
+            id,
+            queue,
+            payload,
+            priority,
+            attempts,
+        }))
+    }
+
+    /// Mark a job as done, removing it from the queue.
+    pub async fn complete(&self, job: &Job) -> Result<()> {
+        query("DELETE FROM musq_jobs WHERE id = ?")
+            .bind(job.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Record a failed attempt at a job. The job is retried after the [retry delay](Self::retry_delay), or moved to
+    /// the dead-letter table if it has used up its attempts.
+    pub async fn fail(&self, job: &Job, error: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        query("UPDATE musq_jobs SET visible_at = ?, last_error = ? WHERE id = ?")
+            .bind(now_millis().saturating_add(millis(self.retry_delay)))
+            .bind(error.to_string())
+            .bind(job.id)
+            .execute(&mut *tx)
+            .await?;
+        if job.attempts >= i64::from(self.max_attempts) {
+            bury(&mut tx, "id = ?", |q| q.bind(job.id)).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Process jobs until the pool is closed, sleeping for the [poll interval](Self::poll_interval) whenever the
+    /// queue is empty.
+    pub async fn run<F, Fut, E>(&self, handler: F) -> Result<()>
+    where
+        F: Fn(Job) -> Fut,
+        Fut: Future<Output = std::result::Result<(), E>>,
+        E: Display,
+    {
+        while !self.pool.is_closed() {
+            match self.claim().await? {
+                Some(job) => match handler(job.clone()).await {
+                    Ok(()) => self.complete(&job).await?,
+                    Err(e) => self.fail(&job, &e.to_string()).await?,
+                },
+                None => {
+                    let _ = self
+                        .pool
+                        .close_event()
+                        .do_until(tokio::time::sleep(self.poll_interval))
+                        .await;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Move jobs matching `filter` to the dead-letter table. `bind` binds the parameters of the filter.
+async fn bury<F>(conn: &mut Connection, filter: &str, bind: F) -> Result<()>
+where
+    F: Fn(Query<Arguments>) -> Query<Arguments>,
+{
+    bind(
+        query(&format!(
+            "INSERT INTO musq_jobs_dead
+                (id, queue, payload, priority, attempts, created_at, failed_at, last_error)
+            SELECT id, queue, payload, priority, attempts, created_at, ?, last_error
+            FROM musq_jobs WHERE {filter}"
+        ))
+        .bind(now_millis()),
+    )
+    .execute(&mut *conn)
+    .await?;
+    bind(query(&format!("DELETE FROM musq_jobs WHERE {filter}")))
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
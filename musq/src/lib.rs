@@ -6,44 +6,74 @@ pub use musq_macros::*;
 #[macro_use]
 pub mod async_stream;
 
+pub mod archive;
+pub mod backup;
+pub mod batch;
+pub mod blob;
+pub mod bulk;
+pub mod cache;
+mod classify;
 mod column;
 mod debugfn;
 pub mod decode;
+pub mod describe;
+pub mod docs;
+pub mod dump;
 pub mod encode;
+#[cfg(feature = "encryption")]
+pub mod encryption;
 mod error;
 mod executor;
+pub mod explain;
+pub mod foreign_keys;
 mod from_row;
+pub mod functions;
+pub mod integrity;
+pub mod jobs;
 mod logger;
+pub mod migrate;
 mod musq;
+pub mod outbox;
+pub mod patch;
 pub mod pool;
 pub mod query;
 mod query_as;
+pub mod query_builder;
 mod query_result;
 mod query_scalar;
-mod row;
+pub mod row;
+pub mod schema;
 mod statement_cache;
 mod transaction;
 pub mod types;
+pub mod vtab;
+pub mod writer;
 
 pub use either::Either;
 pub use indexmap::IndexMap;
 
 pub use crate::{
+    classify::QueryKind,
     column::Column,
     error::{DecodeError, Error, Result},
     executor::{Execute, Executor},
-    from_row::FromRow,
-    musq::{AutoVacuum, JournalMode, LockingMode, Musq, Synchronous},
-    pool::Pool,
-    query::{query, query_with},
-    query_as::{query_as, query_as_with},
+    from_row::{group_rows_by, FromRow},
+    functions::{AggregateFunction, FunctionFlags},
+    logger::{QueryEvent, QueryLogSink},
+    musq::{AutoVacuum, JournalMode, LockingMode, Musq, ResetOnReturn, RetryPolicy, Synchronous},
+    pool::{Pool, PoolMetrics, PoolStats},
+    query::{query, query_with, ResultLimit, ResultLimits},
+    query_as::{query_as, query_as_serde, query_as_with},
+    query_builder::{Fragment, QueryBuilder, Separated, SortDirection, SortSpec},
     query_result::QueryResult,
     query_scalar::{query_scalar, query_scalar_with},
     row::Row,
+    schema::validate_schema,
     sqlite::{
         error::{ExtendedErrCode, PrimaryErrCode},
-        ArgumentValue, Arguments, Connection, IntoArguments, SqliteDataType, SqliteError,
-        Statement, Value,
+        ActiveQuery, ArgumentValue, Arguments, Connection, ConnectionState, InterruptHandle,
+        IntoArguments, QueryState, QueueMetrics, SlowQuery, SqliteDataType, SqliteError, Statement,
+        TempTable, UpdateOp, Value, WatchdogAction,
     },
-    transaction::Transaction,
+    transaction::{ReadScope, Savepoint, Transaction, TransactionBehavior},
 };
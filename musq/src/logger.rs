@@ -1,14 +1,57 @@
 use log::LevelFilter;
 use std::fmt::Debug;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
 
+use crate::debugfn::DebugFn;
+
+/// A completed query, as passed to a [`QueryLogSink`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct QueryEvent<'a> {
+    /// The full SQL of the query.
+    pub sql: &'a str,
+    /// The level the query is logged at, which depends on whether it was slow.
+    pub level: log::Level,
+    /// Whether the query took at least the duration set with
+    /// [`log_slow_statements`](crate::Musq::log_slow_statements).
+    pub slow: bool,
+    /// The number of rows returned by the query.
+    pub rows_returned: u64,
+    /// The number of rows changed by the query.
+    pub rows_affected: u64,
+    /// How long the query took to run, including the time spent waiting for rows to be consumed.
+    pub elapsed: Duration,
+}
+
+/// Receives query events in place of the `log` and `tracing` facades. Install one with
+/// [`Musq::log_sink`](crate::Musq::log_sink).
+///
+/// The sink is called synchronously on the connection's worker thread once each query completes, so it should be
+/// quick, and must not wait on queries against the same connection. To write logs back into SQLite, hand events to a
+/// task that writes through a separate connection.
+pub trait QueryLogSink: Send + Sync + 'static {
+    fn log(&self, event: &QueryEvent<'_>);
+}
+
+impl<F> QueryLogSink for F
+where
+    F: Fn(&QueryEvent<'_>) + Send + Sync + 'static,
+{
+    fn log(&self, event: &QueryEvent<'_>) {
+        self(event)
+    }
+}
+
 #[derive(Clone, Debug)]
 #[non_exhaustive]
 pub struct LogSettings {
     pub statements_level: LevelFilter,
     pub slow_statements_level: LevelFilter,
     pub slow_statements_duration: Duration,
+    pub(crate) sink: Option<Arc<DebugFn<dyn QueryLogSink>>>,
 }
 
 impl Default for LogSettings {
@@ -17,6 +60,7 @@ impl Default for LogSettings {
             statements_level: LevelFilter::Debug,
             slow_statements_level: LevelFilter::Warn,
             slow_statements_duration: Duration::from_secs(1),
+            sink: None,
         }
     }
 }
@@ -29,6 +73,9 @@ impl LogSettings {
         self.slow_statements_level = level;
         self.slow_statements_duration = duration;
     }
+    pub(crate) fn log_sink(&mut self, sink: impl QueryLogSink) {
+        self.sink = Some(Arc::new(DebugFn(sink)));
+    }
 }
 
 // Yes these look silly. `tracing` doesn't currently support dynamic levels
@@ -116,12 +163,31 @@ impl<'q> QueryLogger<'q> {
     pub fn finish(&self) {
         let elapsed = self.start.elapsed();
 
-        let lvl = if elapsed >= self.settings.slow_statements_duration {
+        let slow = elapsed >= self.settings.slow_statements_duration;
+        let lvl = if slow {
             self.settings.slow_statements_level
         } else {
             self.settings.statements_level
         };
 
+        if let Some(sink) = &self.settings.sink {
+            if let Some(level) = lvl.to_level() {
+                let event = QueryEvent {
+                    sql: self.sql,
+                    level,
+                    slow,
+                    rows_returned: self.rows_returned,
+                    rows_affected: self.rows_affected,
+                    elapsed,
+                };
+                // A panicking sink must not take the worker thread down with it
+                if catch_unwind(AssertUnwindSafe(|| sink.log(&event))).is_err() {
+                    tracing::error!("query log sink panicked");
+                }
+            }
+            return;
+        }
+
         if let Some((tracing_level, log_level)) = private_level_filter_to_levels(lvl) {
             // The enabled level could be set from either tracing world or log world, so check both
             // to see if logging should be enabled for our level
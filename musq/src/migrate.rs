@@ -0,0 +1,157 @@
+//! Schema migrations.
+//!
+//! A [`Migrator`] applies a list of [`Migration`]s in version order, recording each one in the `musq_migrations`
+//! table so that it is only applied once. Each migration runs in its own `BEGIN IMMEDIATE` transaction, together with
+//! the insert that records it, and is skipped if the table shows it was applied in the meantime. Several processes
+//! can therefore run the same migrator against a database at once, and each migration is applied exactly once.
+//!
+//! The [`migrations!`](crate::migrations) macro embeds a directory of SQL files in the binary at compile time, so
+//! that it can migrate a database without shipping the directory:
+//!
+//! ```rust,ignore
+//! static MIGRATOR: Migrator = musq::migrations!("./migrations");
+//!
+//! let applied = MIGRATOR.run(&mut conn).await?;
+//! ```
+//!
+//! Files are named `<version>_<description>.sql`, such as `0001_create_users.sql`, where the version is a positive
+//! integer. The macro checks that the versions are unique, and that sorting the files by name sorts them by version.
+//!
+//! Cargo rebuilds the crate when an embedded file changes, but can't know that a file was added to the directory. To
+//! pick up new migrations without a `cargo clean`, add a build script that tells Cargo to watch the directory:
+//!
+//! ```rust,ignore
+//! // build.rs
+//! fn main() {
+//!     println!("cargo:rerun-if-changed=migrations");
+//! }
+//! ```
+//!
+//! Each migration carries a checksum of its SQL. Running a migrator against a database where an applied migration
+//! has a different checksum, or where a migration has been applied that the migrator doesn't know about, fails
+//! without applying anything: migrations must not be edited or removed once they have been applied.
+use std::collections::HashMap;
+
+use crate::{query, query_as, query_scalar, Connection, Error, Result, TransactionBehavior};
+
+/// The schema of the table that records applied migrations, created by [`Migrator::run`].
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS musq_migrations (
+    version INTEGER PRIMARY KEY,
+    description TEXT NOT NULL,
+    checksum INTEGER NOT NULL,
+    applied_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+);
+";
+
+/// A single migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+    /// A 64-bit FNV-1a hash of the SQL.
+    pub checksum: u64,
+}
+
+impl Migration {
+    pub const fn new(version: i64, description: &'static str, sql: &'static str) -> Self {
+        Self {
+            version,
+            description,
+            sql,
+            checksum: checksum(sql.as_bytes()),
+        }
+    }
+}
+
+/// Applies a list of migrations. See the [module documentation](self).
+#[derive(Debug, Clone, Copy)]
+pub struct Migrator {
+    migrations: &'static [Migration],
+}
+
+impl Migrator {
+    /// Create a migrator. The migrations must be sorted by version, with no duplicates.
+    pub const fn new(migrations: &'static [Migration]) -> Self {
+        Self { migrations }
+    }
+
+    pub fn migrations(&self) -> &'static [Migration] {
+        self.migrations
+    }
+
+    /// Apply every migration that hasn't been applied yet, in order, and return the versions applied.
+    pub async fn run(&self, conn: &mut Connection) -> Result<Vec<i64>> {
+        query(SCHEMA).execute(&mut *conn).await?;
+        let applied: HashMap<i64, u64> =
+            query_as::<(i64, i64)>("SELECT version, checksum FROM musq_migrations")
+                .fetch_all(&mut *conn)
+                .await?
+                .into_iter()
+                .map(|(version, checksum)| (version, checksum as u64))
+                .collect();
+
+        for (version, checksum) in &applied {
+            match self.migrations.iter().find(|m| m.version == *version) {
+                None => {
+                    return Err(Error::Protocol(format!(
+                        "migration {version} was applied, but is missing"
+                    )))
+                }
+                Some(m) if m.checksum != *checksum => {
+                    return Err(Error::Protocol(format!(
+                        "migration {version} was changed after it was applied"
+                    )))
+                }
+                Some(_) => {}
+            }
+        }
+
+        let mut done = Vec::new();
+        for migration in self.migrations {
+            if applied.contains_key(&migration.version) {
+                continue;
+            }
+            let mut tx = conn.begin_with(TransactionBehavior::Immediate).await?;
+            // Another migrator may have applied the migration since we looked
+            let checksum: Option<i64> =
+                query_scalar("SELECT checksum FROM musq_migrations WHERE version = ?")
+                    .bind(migration.version)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            match checksum {
+                Some(checksum) if checksum as u64 != migration.checksum => {
+                    return Err(Error::Protocol(format!(
+                        "migration {} was changed after it was applied",
+                        migration.version
+                    )))
+                }
+                Some(_) => continue,
+                None => {}
+            }
+            tx.execute_uncached(migration.sql).await?;
+            query("INSERT INTO musq_migrations (version, description, checksum) VALUES (?, ?, ?)")
+                .bind(migration.version)
+                .bind(migration.description)
+                .bind(migration.checksum as i64)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            done.push(migration.version);
+        }
+        Ok(done)
+    }
+}
+
+/// 64-bit FNV-1a, usable in constants.
+const fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+        i += 1;
+    }
+    hash
+}
@@ -1,5 +1,9 @@
 use std::{
+    cmp,
+    collections::hash_map::RandomState,
     fmt::Write,
+    future::Future,
+    hash::{BuildHasher, Hasher},
     path::{Path, PathBuf},
     sync::atomic::{AtomicUsize, Ordering},
     sync::Arc,
@@ -7,9 +11,17 @@ use std::{
 };
 
 use crate::{
-    debugfn::DebugFn, executor::Executor, logger::LogSettings, pool, sqlite::Connection, Result,
+    debugfn::DebugFn,
+    executor::Executor,
+    functions::{AggregateFunction, Function, FunctionFlags},
+    logger::{LogSettings, QueryLogSink},
+    pool,
+    sqlite::{ChangeTracker, Connection, Watchdog, WatchdogAction},
+    vtab::{self, VirtualTable},
+    ArgumentValue, Result, Value,
 };
 
+use futures_core::future::BoxFuture;
 use log::LevelFilter;
 
 use indexmap::IndexMap;
@@ -115,19 +127,153 @@ pub struct Musq {
     pub(crate) log_settings: LogSettings,
     pub(crate) immutable: bool,
     pub(crate) vfs: Option<String>,
+    pub(crate) attachments: Vec<(PathBuf, String)>,
+    pub(crate) functions: Vec<Function>,
+    pub(crate) extensions: Vec<(PathBuf, Option<String>)>,
 
     pub(crate) pragmas: IndexMap<String, Option<String>>,
 
     pub(crate) command_channel_size: usize,
     pub(crate) row_channel_size: usize,
+    pub(crate) on_command_buffer_saturated: Option<CommandSaturation>,
 
     pub(crate) serialized: bool,
     pub(crate) thread_name: Arc<DebugFn<dyn Fn(u64) -> String + Send + Sync + 'static>>,
 
     pub(crate) pool_max_connections: u32,
+    pub(crate) pool_min_connections: u32,
     pub(crate) pool_acquire_timeout: Duration,
+    pub(crate) pool_max_lifetime: Option<Duration>,
+    pub(crate) pool_idle_timeout: Option<Duration>,
+    pub(crate) pool_on_acquire: Option<Arc<DebugFn<ConnectionCallback>>>,
+    pub(crate) pool_on_release: Option<Arc<DebugFn<ConnectionCallback>>>,
+    pub(crate) after_connect: Option<Arc<DebugFn<ConnectHook>>>,
+    pub(crate) pool_before_acquire: Option<Arc<DebugFn<CheckHook>>>,
+    pub(crate) pool_quick_check: bool,
+    pub(crate) pool_after_release: Option<Arc<DebugFn<CheckHook>>>,
+    pub(crate) spawner: Option<Arc<DebugFn<Spawner>>>,
 
     pub(crate) optimize_on_close: OptimizeOnClose,
+    pub(crate) reset_on_return: ResetOnReturn,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) watchdog: Option<Watchdog>,
+
+    pub(crate) capture_query_sql: bool,
+
+    pub(crate) track_changes: bool,
+    /// Set by the pool when `track_changes` is enabled, and shared by all its connections.
+    pub(crate) change_tracker: Option<Arc<ChangeTracker>>,
+}
+
+/// A pool instrumentation callback, receiving a connection id and a duration.
+pub(crate) type ConnectionCallback = dyn Fn(u64, Duration) + Send + Sync + 'static;
+
+/// A hook that sets up a new connection, set with [`Musq::after_connect`].
+pub(crate) type ConnectHook =
+    dyn for<'c> Fn(&'c mut Connection) -> BoxFuture<'c, Result<()>> + Send + Sync + 'static;
+
+/// A hook that decides whether to keep a pooled connection, set with [`Musq::before_acquire`] and
+/// [`Musq::after_release`].
+pub(crate) type CheckHook =
+    dyn for<'c> Fn(&'c mut Connection) -> BoxFuture<'c, Result<bool>> + Send + Sync + 'static;
+
+pub(crate) type Spawner = dyn Fn(BoxFuture<'static, ()>) + Send + Sync + 'static;
+
+/// The callback set with [`Musq::on_command_buffer_saturated`].
+#[derive(Debug, Clone)]
+pub(crate) struct CommandSaturation {
+    pub(crate) threshold: Duration,
+    pub(crate) callback: Arc<DebugFn<ConnectionCallback>>,
+}
+
+/// Connection state to reset when a connection is returned to the pool, so that changes made by one user of a
+/// connection can't leak into the next. Set with [`Musq::reset_on_return`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResetOnReturn {
+    /// Roll back any transaction left open, for instance by a `BEGIN` executed as a plain query.
+    pub rollback: bool,
+    /// Drop every table and view in the `temp` schema. Tables created with
+    /// [`Connection::create_temp_table`](Connection::create_temp_table) are dropped regardless.
+    pub temp_tables: bool,
+    /// Re-apply the pragmas configured on the [`Musq`] builder, undoing changes made to them. Pragmas that weren't
+    /// configured aren't restored.
+    pub pragmas: bool,
+    /// Remove any authorizer installed on the connection with `sqlite3_set_authorizer`, restoring the one installed by
+    /// [change tracking](Musq::track_changes) if it is enabled.
+    pub authorizer: bool,
+}
+
+impl ResetOnReturn {
+    /// Reset everything.
+    pub fn all() -> Self {
+        Self {
+            rollback: true,
+            temp_tables: true,
+            pragmas: true,
+            authorizer: true,
+        }
+    }
+
+    /// Reset nothing. This is the default.
+    pub fn none() -> Self {
+        Self::default()
+    }
+}
+
+/// How to retry statements that fail with `SQLITE_BUSY` because another connection holds a lock. Set with
+/// [`Musq::retry_policy`].
+///
+/// The [busy timeout](Musq::busy_timeout) already makes SQLite wait for locks inside each attempt. A retry policy adds
+/// attempts on top of that, sleeping with exponential backoff between them: the `n`th retry waits `base_delay * 2^(n -
+/// 1)`, capped at `max_delay`, and shortened by a random fraction of up to `jitter` so that competing writers spread
+/// out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first. 1 disables retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub base_delay: Duration,
+    /// The longest delay between attempts.
+    pub max_delay: Duration,
+    /// The fraction of each delay, from 0 to 1, that may be randomly removed.
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// Don't retry. This is the default.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            jitter: 0.0,
+        }
+    }
+
+    /// Make up to `max_attempts` attempts, starting with a 10ms delay, doubling up to 1s, with half of each delay
+    /// jittered.
+    pub fn exponential(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+            jitter: 0.5,
+        }
+    }
+
+    /// The delay before retry number `retry`, counting from 1.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        delay.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * random)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -196,14 +342,34 @@ impl Musq {
             log_settings: Default::default(),
             immutable: false,
             vfs: None,
+            attachments: Vec::new(),
+            functions: Vec::new(),
+            extensions: Vec::new(),
             pragmas,
             serialized: false,
             thread_name: Arc::new(DebugFn(|id| format!("sqlx-sqlite-worker-{}", id))),
             command_channel_size: 50,
             row_channel_size: 50,
+            on_command_buffer_saturated: None,
             optimize_on_close: OptimizeOnClose::Disabled,
+            reset_on_return: ResetOnReturn::none(),
+            retry_policy: RetryPolicy::none(),
+            watchdog: None,
             pool_acquire_timeout: Duration::from_secs(30),
+            pool_max_lifetime: None,
+            pool_idle_timeout: None,
             pool_max_connections: 10,
+            pool_min_connections: 0,
+            pool_on_acquire: None,
+            pool_on_release: None,
+            after_connect: None,
+            pool_before_acquire: None,
+            pool_quick_check: false,
+            pool_after_release: None,
+            spawner: None,
+            capture_query_sql: false,
+            track_changes: false,
+            change_tracker: None,
         }
     }
 
@@ -392,12 +558,31 @@ impl Musq {
     /// Set the maximum number of rows to buffer back to the calling task when a query is executed.
     ///
     /// If the calling task cannot keep up, backpressure will be applied to the worker thread
-    /// in order to limit CPU and memory usage.
+    /// in order to limit CPU and memory usage. Individual queries can override the size with
+    /// [`Query::row_buffer_size`](crate::query::Query::row_buffer_size).
     pub fn row_buffer_size(mut self, size: usize) -> Self {
         self.row_channel_size = size;
         self
     }
 
+    /// Set a callback that is invoked when a command has to wait longer than `threshold` for space in a
+    /// connection's command buffer. The callback receives the [id](Connection::id) of the connection and the time the
+    /// command waited.
+    ///
+    /// A buffer that stays full suggests that [`command_buffer_size`](Self::command_buffer_size) is too small for the
+    /// load. [`Connection::queue_metrics`] shows how full the buffers are at a given moment.
+    pub fn on_command_buffer_saturated(
+        mut self,
+        threshold: Duration,
+        callback: impl Fn(u64, Duration) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_command_buffer_saturated = Some(CommandSaturation {
+            threshold,
+            callback: Arc::new(DebugFn(callback)),
+        });
+        self
+    }
+
     /// Sets the [`vfs`](https://www.sqlite.org/vfs.html) parameter of the database connection.
     ///
     /// The default value is empty, and sqlite will use the default VFS object depending on the
@@ -407,6 +592,126 @@ impl Musq {
         self
     }
 
+    /// Encrypt the database at rest, using a VFS that wraps the platform default. Keys are fetched from
+    /// `key_provider` whenever a file is opened. This also sets `PRAGMA temp_store = MEMORY`, since the VFS refuses
+    /// to create temporary files.
+    ///
+    /// Each call registers a new VFS for the lifetime of the process, so call this once and clone the resulting
+    /// `Musq`. This replaces any VFS set with [`vfs`](Self::vfs). See the [`encryption`](crate::encryption) module
+    /// for the scheme and its limitations.
+    #[cfg(feature = "encryption")]
+    pub fn encrypted_vfs(mut self, key_provider: impl crate::encryption::KeyProvider) -> Self {
+        self.vfs = Some(crate::encryption::register(Arc::new(key_provider)));
+        self.pragma("temp_store", "MEMORY")
+    }
+
+    /// Attach the database at `path` under the schema name `schema` on every connection opened with these options.
+    /// Tables in the attached database are addressed as `schema.table`. See
+    /// [`Connection::attach`](Connection::attach).
+    ///
+    /// May be called more than once to attach several databases.
+    pub fn attach(mut self, path: impl AsRef<Path>, schema: &str) -> Self {
+        self.attachments
+            .push((path.as_ref().to_path_buf(), schema.to_string()));
+        self
+    }
+
+    /// Load the SQLite extension in the shared library at `path` on every connection opened with these options, using
+    /// the entry point SQLite derives from the file name. See
+    /// [`sqlite3_load_extension`](https://www.sqlite.org/c3ref/load_extension.html) for how `path` is resolved.
+    ///
+    /// Extensions are loaded before functions are registered and pragmas are run, and opening a connection fails if
+    /// one can't be loaded. Loading is enabled only while extensions load, so SQL can't load further extensions with
+    /// the `load_extension()` function.
+    ///
+    /// May be called more than once to load several extensions, in order.
+    pub fn extension(mut self, path: impl AsRef<Path>) -> Self {
+        self.extensions.push((path.as_ref().to_path_buf(), None));
+        self
+    }
+
+    /// Load the SQLite extension in the shared library at `path` on every connection, calling the entry point
+    /// `entry_point`. See [`extension`](Self::extension).
+    pub fn extension_with_entrypoint(mut self, path: impl AsRef<Path>, entry_point: &str) -> Self {
+        self.extensions
+            .push((path.as_ref().to_path_buf(), Some(entry_point.to_string())));
+        self
+    }
+
+    /// Register a user-defined scalar function on every connection. `f` is called with the arguments of each call,
+    /// and returns the result, or an error message that fails the query.
+    ///
+    /// `n_args` is the number of arguments the function takes, or -1 for any number. `flags` describe the function
+    /// to SQLite; a function must be [`FunctionFlags::DETERMINISTIC`] to be used in indexes and generated columns. If
+    /// `f` panics, it is poisoned, and every later query that calls it on the same connection fails.
+    pub fn create_scalar_function<F>(
+        mut self,
+        name: &str,
+        n_args: i32,
+        flags: FunctionFlags,
+        f: F,
+    ) -> Self
+    where
+        F: Fn(&[Value]) -> std::result::Result<ArgumentValue, String> + Send + Sync + 'static,
+    {
+        self.functions
+            .push(Function::scalar(name, n_args, flags, f));
+        self
+    }
+
+    /// Register a pack of helper functions on every connection:
+    ///
+    /// - `regexp_replace(text, pattern, replacement)` replaces every match of a regular expression, with `$1`-style
+    ///   references to capture groups in `replacement`.
+    /// - `uuid_blob_to_text(blob)` formats a 16-byte UUID as hyphenated lowercase hex.
+    /// - `unix_to_iso8601(seconds)` formats a Unix timestamp as an RFC 3339 UTC date and time.
+    ///
+    /// All of them return `NULL` when given a `NULL`, and are deterministic and innocuous. Requires the
+    /// `helper-functions` feature.
+    #[cfg(feature = "helper-functions")]
+    pub fn with_helper_functions(mut self) -> Self {
+        self.functions.extend(crate::functions::helpers());
+        self
+    }
+
+    /// Register the eponymous virtual table `table` under `name` on every connection, so that queries can read it
+    /// as `name`, or call it as a table-valued function `name(...)` if it has hidden columns. See the
+    /// [`vtab`](crate::vtab) module.
+    pub fn register_vtab<T: VirtualTable>(mut self, name: &str, table: T) -> Self {
+        self.functions.push(vtab::module(name, table));
+        self
+    }
+
+    /// Register a user-defined aggregate function on every connection. `factory` creates the aggregate state for
+    /// each group the function is computed over.
+    ///
+    /// `n_args` is the number of arguments the function takes, or -1 for any number. A function may be registered
+    /// more than once under the same name with different argument counts. See the [`functions`](crate::functions)
+    /// module for an example.
+    pub fn create_aggregate_function<A, F>(mut self, name: &str, n_args: i32, factory: F) -> Self
+    where
+        A: AggregateFunction,
+        F: Fn() -> A + Send + Sync + 'static,
+    {
+        self.functions
+            .push(Function::aggregate(name, n_args, factory));
+        self
+    }
+
+    /// Register a collation on every connection, for use in `COLLATE` clauses and column definitions. `cmp` compares
+    /// two strings, for instance case-insensitively or by the rules of a locale. See
+    /// [`sqlite3_create_collation_v2`](https://www.sqlite.org/c3ref/create_collation.html).
+    ///
+    /// Text that isn't valid UTF-8 is converted lossily before it is compared. If `cmp` panics, it is poisoned, and
+    /// treats every pair of strings as equal from then on.
+    pub fn create_collation<F>(mut self, name: &str, cmp: F) -> Self
+    where
+        F: Fn(&str, &str) -> cmp::Ordering + Send + Sync + 'static,
+    {
+        self.functions.push(Function::collation(name, cmp));
+        self
+    }
+
     /// Execute `PRAGMA optimize;` on the SQLite connection before closing.
     ///
     /// The SQLite manual recommends using this for long-lived databases.
@@ -466,6 +771,14 @@ impl Musq {
         self
     }
 
+    /// Send query events to `sink` instead of the `log` and `tracing` facades. The levels set with
+    /// [`log_statements`](Self::log_statements) and [`log_slow_statements`](Self::log_slow_statements) still apply:
+    /// queries whose level is `Off` are not passed to the sink.
+    pub fn log_sink(mut self, sink: impl QueryLogSink) -> Self {
+        self.log_settings.log_sink(sink);
+        self
+    }
+
     /// Collect all `PRAMGA` commands into a single string
     pub(crate) fn pragma_string(&self) -> String {
         let mut string = String::new();
@@ -481,6 +794,12 @@ impl Musq {
         let mut conn = Connection::establish(self).await?;
         // Execute PRAGMAs
         conn.execute(crate::query(&self.pragma_string())).await?;
+        for (path, schema) in &self.attachments {
+            conn.attach(path, schema).await?;
+        }
+        if let Some(hook) = &self.after_connect {
+            hook(&mut conn).await?;
+        }
         Ok(conn)
     }
 
@@ -494,16 +813,27 @@ impl Musq {
         self
     }
 
+    /// Set the number of connections that the pool keeps open, capped at
+    /// [`max_connections`](Self::max_connections). Defaults to 0.
+    ///
+    /// The connections are opened in parallel when the pool is, so that opening fails with
+    /// [`Error::WarmUpFailed`](crate::Error::WarmUpFailed) if any can't be. After that a background
+    /// task opens replacements whenever connections close, for instance when they are
+    /// [detached](crate::pool::PoolConnection::detach) or fail to reset on return, retrying periodically if opening
+    /// a connection fails. The task stops when the pool is closed.
+    pub fn min_connections(mut self, min: u32) -> Self {
+        self.pool_min_connections = min;
+        self
+    }
+
     /// Set the maximum amount of time to spend waiting for a connection in [`Pool::acquire()`].
     ///
     /// Caps the total amount of time `Pool::acquire()` can spend waiting across multiple phases:
     ///
     /// * First, it may need to wait for a permit from the semaphore, which grants it the privilege
     ///   of opening a connection or popping one from the idle queue.
-    /// * If an existing idle connection is acquired, by default it will be checked for liveness
-    ///   and integrity before being returned, which may require executing a command on the
-    ///   connection. This can be disabled with [`test_before_acquire(false)`][Self::test_before_acquire].
-    ///     * If [`before_acquire`][Self::before_acquire] is set, that will also be executed.
+    /// * If an existing idle connection is acquired and [`before_acquire`][Self::before_acquire] is
+    ///   set, the hook is executed on it.
     /// * If a new connection needs to be opened, that will obviously require I/O, handshaking,
     ///   and initialization commands.
     ///     * If [`after_connect`][Self::after_connect] is set, that will also be executed.
@@ -512,6 +842,183 @@ impl Musq {
         self
     }
 
+    /// Close connections once they have been open for `lifetime`, replacing them with fresh ones. A connection in use
+    /// is closed when it is returned to the pool. Not set by default.
+    ///
+    /// Closing every connection to an in-memory database destroys it, so don't set this for pools opened with
+    /// [`open_in_memory`](Self::open_in_memory).
+    pub fn max_lifetime(mut self, lifetime: Duration) -> Self {
+        self.pool_max_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Close connections that have been idle for longer than `timeout`, without taking the pool below
+    /// [`min_connections`](Self::min_connections). Not set by default.
+    ///
+    /// As with [`max_lifetime`](Self::max_lifetime), this can destroy an in-memory database.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a callback that is invoked each time a connection is acquired from the pool. The callback receives the
+    /// [id](Connection::id) of the connection and the time spent waiting for it.
+    ///
+    /// The callback runs synchronously on the acquiring task, so it must be cheap. It is intended for feeding metrics
+    /// systems.
+    pub fn on_acquire(mut self, callback: impl Fn(u64, Duration) + Send + Sync + 'static) -> Self {
+        self.pool_on_acquire = Some(Arc::new(DebugFn(callback)));
+        self
+    }
+
+    /// Set a callback that is invoked each time a connection is returned to the pool. The callback receives the
+    /// [id](Connection::id) of the connection and the time it was checked out for.
+    ///
+    /// The callback runs synchronously when the connection is dropped, so it must be cheap. It is intended for feeding
+    /// metrics systems.
+    pub fn on_release(mut self, callback: impl Fn(u64, Duration) + Send + Sync + 'static) -> Self {
+        self.pool_on_release = Some(Arc::new(DebugFn(callback)));
+        self
+    }
+
+    /// Set a hook that runs on every new connection, after its pragmas are set and its databases attached. Use this
+    /// to set up per-connection state, such as `PRAGMA temp_store` or temporary tables. If the hook fails, opening the
+    /// connection fails with its error.
+    ///
+    /// ```rust,ignore
+    /// Musq::new().after_connect(|conn| {
+    ///     Box::pin(async move {
+    ///         conn.execute("PRAGMA temp_store = MEMORY").await?;
+    ///         Ok(())
+    ///     })
+    /// })
+    /// ```
+    pub fn after_connect<F>(mut self, hook: F) -> Self
+    where
+        F: for<'c> Fn(&'c mut Connection) -> BoxFuture<'c, Result<()>> + Send + Sync + 'static,
+    {
+        self.after_connect = Some(Arc::new(DebugFn(hook)));
+        self
+    }
+
+    /// Set a hook that runs on an idle connection before it is handed out by [`Pool::acquire`](pool::Pool::acquire).
+    /// If the hook returns `false` or fails, the connection is closed, and the pool tries another one. Use this to
+    /// veto unhealthy connections.
+    ///
+    /// The hook counts towards the [`acquire_timeout`](Self::acquire_timeout). It doesn't run on newly opened
+    /// connections, nor for [`Pool::try_acquire`](pool::Pool::try_acquire), which doesn't wait.
+    pub fn before_acquire<F>(mut self, hook: F) -> Self
+    where
+        F: for<'c> Fn(&'c mut Connection) -> BoxFuture<'c, Result<bool>> + Send + Sync + 'static,
+    {
+        self.pool_before_acquire = Some(Arc::new(DebugFn(hook)));
+        self
+    }
+
+    /// Run [`Connection::quick_check`] on an idle connection before it is handed out by
+    /// [`Pool::acquire`](pool::Pool::acquire), closing the connection if the check fails or finds problems. Off by
+    /// default.
+    ///
+    /// The check reads the whole database, so this is only practical for small databases. It runs before the
+    /// [`before_acquire`](Self::before_acquire) hook, under the same conditions.
+    pub fn quick_check_before_acquire(mut self, on: bool) -> Self {
+        self.pool_quick_check = on;
+        self
+    }
+
+    /// Set a hook that runs on a connection when it is returned to the pool, before it is
+    /// [reset](Self::reset_on_return). If the hook returns `false` or fails, the connection is closed instead of
+    /// being returned.
+    pub fn after_release<F>(mut self, hook: F) -> Self
+    where
+        F: for<'c> Fn(&'c mut Connection) -> BoxFuture<'c, Result<bool>> + Send + Sync + 'static,
+    {
+        self.pool_after_release = Some(Arc::new(DebugFn(hook)));
+        self
+    }
+
+    /// Set the function used to spawn the pool's background tasks: returning dropped connections to the pool,
+    /// maintaining [`min_connections`](Self::min_connections), and running a [`WriteBatcher`](crate::batch::WriteBatcher).
+    /// By default these are spawned with `tokio::spawn`.
+    ///
+    /// Use this to run musq inside runtimes with restricted spawn policies, or to attach instrumentation to its tasks.
+    /// The spawner must drive each future to completion.
+    pub fn spawner(
+        mut self,
+        spawn: impl Fn(BoxFuture<'static, ()>) + Send + Sync + 'static,
+    ) -> Self {
+        self.spawner = Some(Arc::new(DebugFn(spawn)));
+        self
+    }
+
+    /// Spawn a background task, with the configured [`spawner`](Self::spawner) if there is one.
+    pub(crate) fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        match &self.spawner {
+            Some(spawner) => spawner(Box::pin(task)),
+            None => {
+                tokio::spawn(task);
+            }
+        }
+    }
+
+    /// Watch for statements that run for longer than `threshold`, and log them, interrupt them, or report them to a
+    /// callback, depending on `action`. Use this as a safety net against accidental full table scans in production.
+    ///
+    /// The watchdog runs from SQLite's progress handler, so it only notices a slow statement while SQLite is working
+    /// on it, and fires at most once per statement. A statement's runtime is measured from its first step, and
+    /// includes time spent waiting for the caller to consume its rows. A progress handler set through
+    /// [`Connection::lock_handle`] keeps working alongside the watchdog.
+    pub fn watchdog(mut self, threshold: Duration, action: WatchdogAction) -> Self {
+        self.watchdog = Some(Watchdog { threshold, action });
+        self
+    }
+
+    /// Retry statements that fail because the database is busy, with backoff. See [`RetryPolicy`].
+    ///
+    /// Statements are only retried outside explicit transactions, where retrying is safe: inside a transaction,
+    /// `SQLITE_BUSY` usually means another writer is waiting for this transaction to finish, and retrying would only
+    /// delay the failure. `COMMIT` and the statements that begin transactions are always retried.
+    ///
+    /// Not enabled by default.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Reset connection state when a connection is returned to the pool. See [`ResetOnReturn`].
+    ///
+    /// Each reset adds work to every release, so only enable what your application needs. If a reset fails, the
+    /// connection is closed instead of being returned.
+    ///
+    /// Not enabled by default.
+    pub fn reset_on_return(mut self, policy: ResetOnReturn) -> Self {
+        self.reset_on_return = policy;
+        self
+    }
+
+    /// Include the SQL of each query in the snapshots returned by [`Pool::active_queries`](pool::Pool::active_queries).
+    ///
+    /// This copies the SQL of every query as it starts. Queries are reported without their SQL when this is off.
+    ///
+    /// Not enabled by default.
+    pub fn capture_query_sql(mut self, enabled: bool) -> Self {
+        self.capture_query_sql = enabled;
+        self
+    }
+
+    /// Track which tables are modified by transactions committed through the pool.
+    ///
+    /// This installs hooks and an authorizer on every connection, adding a small cost to each write and to preparing
+    /// each statement. It is required by [`QueryCache`](crate::cache::QueryCache). Changes made outside the pool, for
+    /// instance by another process, are not seen, and neither are those made while an authorizer installed with
+    /// `sqlite3_set_authorizer` replaces the one change tracking relies on.
+    ///
+    /// Not enabled by default.
+    pub fn track_changes(mut self, enabled: bool) -> Self {
+        self.track_changes = enabled;
+        self
+    }
+
     pub(crate) fn configure_in_memory(self) -> Self {
         let seqno = IN_MEMORY_DB_SEQ.fetch_add(1, Ordering::Relaxed);
         self.in_memory(true)
@@ -0,0 +1,226 @@
+//! A transactional outbox.
+//!
+//! The outbox pattern makes sending a message to an external system atomic with a database change: the message is
+//! [`enqueue`]d in the same transaction as the change, and an [`Outbox`] poller later claims pending messages and
+//! hands them to an async handler, recording attempts and errors so failed deliveries are retried.
+//!
+//! ```rust,ignore
+//! outbox::create_table(&pool).await?;
+//!
+//! let mut tx = pool.begin().await?;
+//! query("INSERT INTO orders (id) VALUES (?)").bind(1).execute(&mut *tx).await?;
+//! outbox::enqueue(&mut *tx, "order.created", r#"{"id": 1}"#).await?;
+//! tx.commit().await?;
+//!
+//! Outbox::new(pool).run(|msg| async move { publish(msg).await }).await?;
+//! ```
+//!
+//! Each message is claimed by one poller at a time, and is deleted once its handler succeeds. If the process dies
+//! between a handler succeeding and the message being deleted, the message is delivered again once its claim expires,
+//! so handlers should be idempotent.
+use std::{
+    fmt::Display,
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{query, query_as, query_scalar, Executor, Pool, Result};
+
+/// The schema of the outbox table, created by [`create_table`].
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS musq_outbox (
+    id INTEGER PRIMARY KEY,
+    topic TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    claimed_until INTEGER,
+    last_error TEXT
+);
+CREATE INDEX IF NOT EXISTS musq_outbox_claimed ON musq_outbox (claimed_until);
+";
+
+/// A message claimed from the outbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub id: i64,
+    pub topic: String,
+    pub payload: String,
+    /// The number of delivery attempts made so far, including the current one.
+    pub attempts: i64,
+}
+
+/// Create the outbox table if it doesn't already exist.
+pub async fn create_table<'c, E>(executor: E) -> Result<()>
+where
+    E: Executor<'c>,
+{
+    executor.execute(query(SCHEMA)).await?;
+    Ok(())
+}
+
+/// Add a message to the outbox. Call this with the transaction that makes the change the message describes, so that
+/// the message is only sent if the transaction commits. Returns the id of the new message.
+pub async fn enqueue<'c, E>(executor: E, topic: &str, payload: &str) -> Result<i64>
+where
+    E: Executor<'c>,
+{
+    query_scalar(
+        "INSERT INTO musq_outbox (topic, payload, created_at) VALUES (?, ?, ?) RETURNING id",
+    )
+    .bind(topic.to_string())
+    .bind(payload.to_string())
+    .bind(now_millis())
+    .fetch_one(executor)
+    .await
+}
+
+/// Polls the outbox and dispatches pending messages to a handler.
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    pool: Pool,
+    batch_size: u32,
+    lease: Duration,
+    retry_delay: Duration,
+    max_attempts: u32,
+    poll_interval: Duration,
+}
+
+impl Outbox {
+    pub fn new(pool: Pool) -> Self {
+        Self {
+            pool,
+            batch_size: 100,
+            lease: Duration::from_secs(60),
+            retry_delay: Duration::from_secs(5),
+            max_attempts: 10,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// The maximum number of messages to claim at once, at least one. The default is 100.
+    pub fn batch_size(mut self, n: u32) -> Self {
+        self.batch_size = n.max(1);
+        self
+    }
+
+    /// How long a claim on a message lasts. If a poller dies while holding a claim, other pollers can pick the message
+    /// up once the claim expires. This should comfortably exceed the time taken to handle a batch. The default is 60
+    /// seconds.
+    pub fn lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// How long to wait before retrying a message whose handler failed. The default is 5 seconds.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// The number of delivery attempts after which a message is abandoned. Abandoned messages stay in the table, with
+    /// the last error recorded, for inspection. The default is 10.
+    pub fn max_attempts(mut self, n: u32) -> Self {
+        self.max_attempts = n;
+        self
+    }
+
+    /// How long [`run`](Self::run) sleeps when the outbox is empty. The default is 1 second.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Claim a batch of pending messages and hand them to `handler` in order. Messages are deleted if the handler
+    /// succeeds, and scheduled for a retry with the error recorded if it fails.
+    ///
+    /// Returns the number of messages claimed.
+    pub async fn dispatch<F, Fut, E>(&self, handler: F) -> Result<usize>
+    where
+        F: Fn(Message) -> Fut,
+        Fut: Future<Output = std::result::Result<(), E>>,
+        E: Display,
+    {
+        let now = now_millis();
+        let rows: Vec<(i64, String, String, i64)> = query_as(
+            "UPDATE musq_outbox SET claimed_until = ?, attempts = attempts + 1
+            WHERE id IN (
+                SELECT id FROM musq_outbox
+                WHERE attempts < ? AND (claimed_until IS NULL OR claimed_until <= ?)
+                ORDER BY id LIMIT ?
+            )
+            RETURNING id, topic, payload, attempts",
+        )
+        .bind(now.saturating_add(millis(self.lease)))
+        .bind(self.max_attempts)
+        .bind(now)
+        .bind(self.batch_size)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut messages: Vec<Message> = rows
+            .into_iter()
+            .map(|(id, topic, payload, attempts)| Message {
+                id,
+                topic,
+                payload,
+                attempts,
+            })
+            .collect();
+        messages.sort_by_key(|m| m.id);
+
+        let claimed = messages.len();
+        for msg in messages {
+            let id = msg.id;
+            match handler(msg).await {
+                Ok(()) => {
+                    query("DELETE FROM musq_outbox WHERE id = ?")
+                        .bind(id)
+                        .execute(&self.pool)
+                        .await?;
+                }
+                Err(e) => {
+                    query("UPDATE musq_outbox SET claimed_until = ?, last_error = ? WHERE id = ?")
+                        .bind(now_millis().saturating_add(millis(self.retry_delay)))
+                        .bind(e.to_string())
+                        .bind(id)
+                        .execute(&self.pool)
+                        .await?;
+                }
+            }
+        }
+        Ok(claimed)
+    }
+
+    /// Dispatch messages until the pool is closed, sleeping for the [poll interval](Self::poll_interval) whenever
+    /// the outbox is empty.
+    pub async fn run<F, Fut, E>(&self, handler: F) -> Result<()>
+    where
+        F: Fn(Message) -> Fut,
+        Fut: Future<Output = std::result::Result<(), E>>,
+        E: Display,
+    {
+        while !self.pool.is_closed() {
+            if self.dispatch(&handler).await? == 0 {
+                let _ = self
+                    .pool
+                    .close_event()
+                    .do_until(tokio::time::sleep(self.poll_interval))
+                    .await;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn millis(d: Duration) -> i64 {
+    i64::try_from(d.as_millis()).unwrap_or(i64::MAX)
+}
+
+/// Milliseconds since the Unix epoch.
+pub(crate) fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(millis)
+        .unwrap_or_default()
+}
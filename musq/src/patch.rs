@@ -0,0 +1,125 @@
+//! Partial updates of table rows.
+//!
+//! A [`Patch`] collects new values for some of the columns of a [`Table`] type, and writes only those columns. Unlike
+//! updating every column from a full struct, this doesn't overwrite concurrent changes to the columns it leaves alone.
+//!
+//! ```rust,ignore
+//! #[derive(FromRow)]
+//! #[musq(table = "users")]
+//! struct User {
+//!     id: i64,
+//!     name: String,
+//!     email: Option<String>,
+//! }
+//!
+//! Patch::<User>::new().set("email", "a@example.com").apply_update(&pool, 1).await?;
+//! ```
+use std::{fmt, marker::PhantomData};
+
+use crate::{
+    encode::Encode,
+    query_scalar, query_with,
+    schema::{quote_identifier, Table},
+    ArgumentValue, Arguments, Error, Executor, Result,
+};
+
+/// New values for some of the columns of a row of table `T`, written by [`apply_update`](Self::apply_update).
+pub struct Patch<T> {
+    values: Vec<(String, ArgumentValue)>,
+    table: PhantomData<fn() -> T>,
+}
+
+impl<T> fmt::Debug for Patch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Patch")
+            .field("values", &self.values)
+            .finish()
+    }
+}
+
+impl<T> Default for Patch<T> {
+    fn default() -> Self {
+        Self {
+            values: Vec::new(),
+            table: PhantomData,
+        }
+    }
+}
+
+impl<T: Table> Patch<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `column` to `value`. Setting the same column again replaces the earlier value.
+    pub fn set(mut self, column: &str, value: impl Encode) -> Self {
+        let value = value.encode();
+        match self.values.iter_mut().find(|(c, _)| c == column) {
+            Some((_, v)) => *v = value,
+            None => self.values.push((column.to_string(), value)),
+        }
+        self
+    }
+
+    /// Set `column` to `value` if it is `Some`, and leave it alone otherwise. Use this to build a patch from optional
+    /// inputs, such as the fields of a request.
+    pub fn set_opt(self, column: &str, value: Option<impl Encode>) -> Self {
+        match value {
+            Some(v) => self.set(column, v),
+            None => self,
+        }
+    }
+
+    /// The columns set so far, in the order they were first set.
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.values.iter().map(|(c, _)| c.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Update the row with the given rowid, writing only the columns that were set. For a table with an `INTEGER
+    /// PRIMARY KEY`, the rowid is the primary key. Returns `false` if there is no such row.
+    ///
+    /// Fails with [`Error::ColumnNotFound`] if a column isn't one of the columns of `T`. An empty patch does nothing,
+    /// and returns whether the row exists.
+    pub async fn apply_update<'c, E>(self, executor: E, id: i64) -> Result<bool>
+    where
+        E: Executor<'c>,
+    {
+        let known = T::columns();
+        if let Some((column, _)) = self
+            .values
+            .iter()
+            .find(|(c, _)| !known.iter().any(|k| k.name == c))
+        {
+            return Err(Error::ColumnNotFound(column.clone()));
+        }
+
+        let table = quote_identifier(T::NAME);
+        if self.values.is_empty() {
+            let exists: Option<i64> =
+                query_scalar(&format!("SELECT 1 FROM {table} WHERE rowid = ?"))
+                    .bind(id)
+                    .fetch_optional(executor)
+                    .await?;
+            return Ok(exists.is_some());
+        }
+
+        let set = self
+            .values
+            .iter()
+            .map(|(c, _)| format!("{} = ?", quote_identifier(c)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!("UPDATE {table} SET {set} WHERE rowid = ?");
+        let mut arguments = Arguments {
+            values: self.values.into_iter().map(|(_, v)| v).collect(),
+            ..Default::default()
+        };
+        arguments.add(id);
+        let result = executor.execute(query_with(&sql, arguments)).await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt::{self, Debug, Formatter};
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
+use std::time::Instant;
 
 use crate::{error::Error, Connection};
 
@@ -13,14 +14,17 @@ use std::future::Future;
 pub struct PoolConnection {
     live: Option<Live>,
     pool: Arc<PoolInner>,
+    acquired_at: Instant,
 }
 
 pub(super) struct Live {
-    raw: Connection,
+    pub(super) raw: Connection,
+    pub(super) created_at: Instant,
 }
 
 pub(super) struct Idle {
     pub(super) live: Live,
+    pub(super) idle_since: Instant,
 }
 
 /// RAII wrapper for connections being handled by functions that may drop them
@@ -87,8 +91,8 @@ impl PoolConnection {
     /// If you want the pool to treat this connection as permanently checked-out,
     /// use [`.leak()`][Self::leak] instead.
     ///
-    /// [`max_connections`]: crate::pool::PoolOptions::max_connections
-    /// [`min_connections`]: crate::pool::PoolOptions::min_connections
+    /// [`max_connections`]: crate::Musq::max_connections
+    /// [`min_connections`]: crate::Musq::min_connections
     pub fn detach(mut self) -> Connection {
         self.take_live().float(self.pool.clone()).detach()
     }
@@ -102,6 +106,16 @@ impl PoolConnection {
         self.take_live().raw
     }
 
+    /// Mark the connection as handed out to a caller that started waiting for it at `started`.
+    pub(super) fn acquired(mut self, started: Instant) -> Self {
+        self.acquired_at = Instant::now();
+        self.pool.metrics.acquired(self.acquired_at - started);
+        if let Some(callback) = &self.pool.options.pool_on_acquire {
+            callback(self.id(), self.acquired_at - started);
+        }
+        self
+    }
+
     fn take_live(&mut self) -> Live {
         self.live.take().expect(EXPECT_MSG)
     }
@@ -111,6 +125,10 @@ impl PoolConnection {
     /// This effectively runs the drop handler eagerly instead of spawning a task to do it.
     #[doc(hidden)]
     pub fn return_to_pool(&mut self) -> impl Future<Output = ()> + Send + 'static {
+        if let (Some(live), Some(callback)) = (&self.live, &self.pool.options.pool_on_release) {
+            callback(live.raw.id(), self.acquired_at.elapsed());
+        }
+
         // float the connection in the pool before we move into the task
         // in case the returned `Future` isn't executed, like if it's spawned into a dying runtime
         // https://github.com/launchbadge/sqlx/issues/1396
@@ -133,7 +151,8 @@ impl Drop for PoolConnection {
     fn drop(&mut self) {
         // We still need to spawn a task to maintain `min_connections`.
         if self.live.is_some() {
-            tokio::task::spawn(self.return_to_pool());
+            let pool = self.pool.clone();
+            pool.options.spawn(self.return_to_pool());
         }
     }
 }
@@ -148,7 +167,10 @@ impl Live {
     }
 
     pub fn into_idle(self) -> Idle {
-        Idle { live: self }
+        Idle {
+            live: self,
+            idle_since: Instant::now(),
+        }
     }
 }
 
@@ -169,8 +191,11 @@ impl DerefMut for Idle {
 impl Floating<Live> {
     pub fn new_live(conn: Connection, guard: DecrementSizeGuard) -> Self {
         Self {
-            inner: Live { raw: conn },
-            guard,
+            inner: Live {
+                raw: conn,
+                created_at: Instant::now(),
+            },
+            guard: guard.connected(),
         }
     }
 
@@ -183,6 +208,7 @@ impl Floating<Live> {
         PoolConnection {
             live: Some(inner),
             pool,
+            acquired_at: Instant::now(),
         }
     }
 
@@ -193,12 +219,34 @@ impl Floating<Live> {
     /// Return the connection to the pool.
     ///
     /// Returns `true` if the connection was successfully returned, `false` if it was closed.
-    async fn return_to_pool(self) -> bool {
+    async fn return_to_pool(mut self) -> bool {
         // Immediately close the connection.
         if self.guard.pool.is_closed() {
             self.close().await;
             return false;
         }
+        // Connections past their max lifetime, or whose database was replaced, are replaced rather than reused
+        if self.guard.pool.is_expired(&self.inner) || self.inner.raw.deserialized {
+            self.close().await;
+            return false;
+        }
+        if let Some(hook) = self.guard.pool.options.pool_after_release.clone() {
+            let keep = hook(&mut self.inner.raw).await.unwrap_or_else(|error| {
+                tracing::warn!(%error, "after_release hook failed; closing the connection");
+                false
+            });
+            if !keep {
+                self.close().await;
+                return false;
+            }
+        }
+        // A connection that can't be reset can't be handed to anyone else
+        let options = &self.guard.pool.options;
+        let (policy, pragmas) = (options.reset_on_return, options.pragma_string());
+        if self.inner.raw.reset(policy, &pragmas).await.is_err() {
+            self.close().await;
+            return false;
+        }
         self.release();
         true
     }
@@ -31,6 +31,28 @@ where
         })
     }
 
+    fn fetch_paged<'e, 'q: 'e, E>(
+        self,
+        query: E,
+        page_size: usize,
+    ) -> BoxStream<'e, Result<Vec<Row>>>
+    where
+        E: Execute + 'q,
+    {
+        let pool = self.clone();
+
+        Box::pin(try_stream! {
+            let mut conn = pool.acquire().await?;
+            let mut s = conn.fetch_paged(query, page_size);
+
+            while let Some(v) = s.try_next().await? {
+                r#yield!(v);
+            }
+
+            Ok(())
+        })
+    }
+
     fn fetch_optional<'e, 'q: 'e, E>(self, query: E) -> BoxFuture<'e, Result<Option<Row>>>
     where
         E: Execute + 'q,
@@ -1,26 +1,30 @@
 use std::{
     sync::{
         atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
-        Arc,
+        Arc, Mutex, Weak,
     },
     time::{Duration, Instant},
 };
 
 use crossbeam_queue::ArrayQueue;
-use futures_util::FutureExt;
-
-use crate::{pool::CloseEvent, Error, Result};
+use futures_util::{future, FutureExt};
+use tokio::sync::Notify;
+
+use crate::{
+    pool::{metrics::MetricsRecorder, CloseEvent, PoolMetrics, PoolStats},
+    sqlite::WorkerSharedState,
+    writer::Write,
+    ActiveQuery, Error, QueueMetrics, Result,
+};
 
 use super::connection::{Floating, Idle, Live};
 
-/// get the time between the deadline and now and use that as our timeout
-///
-/// returns `Error::PoolTimedOut` if the deadline is in the past
-fn deadline_as_timeout(deadline: Instant) -> Result<Duration> {
-    deadline
-        .checked_duration_since(Instant::now())
-        .ok_or(Error::PoolTimedOut)
-}
+/// How long the maintenance task waits before trying again when it fails to open a connection.
+const REPLENISH_RETRY: Duration = Duration::from_secs(1);
+
+/// Bounds on how often the reaper checks for expired connections.
+const MIN_REAP_INTERVAL: Duration = Duration::from_millis(10);
+const MAX_REAP_INTERVAL: Duration = Duration::from_secs(30);
 
 pub(crate) struct PoolInner {
     idle_conns: ArrayQueue<Idle>,
@@ -29,11 +33,22 @@ pub(crate) struct PoolInner {
     num_idle: AtomicUsize,
     is_closed: AtomicBool,
     on_closed: event_listener::Event,
-    pub(super) options: crate::Musq,
+    /// The shared state of every connection the pool has opened. Entries for closed connections are pruned lazily.
+    workers: Mutex<Vec<Weak<WorkerSharedState>>>,
+    /// Notified when a connection closes or the pool is closed, to wake the task that maintains
+    /// [`min_connections`](crate::Musq::min_connections).
+    replenish: Arc<Notify>,
+    pub(super) metrics: MetricsRecorder,
+    /// The pool's [`Writer`](crate::writer::Writer), while any handle to it is alive.
+    pub(crate) writer: Mutex<Option<flume::WeakSender<Write>>>,
+    pub(crate) options: crate::Musq,
 }
 
 impl PoolInner {
-    pub(super) fn new_arc(options: crate::Musq) -> Arc<Self> {
+    pub(super) fn new_arc(mut options: crate::Musq) -> Arc<Self> {
+        if options.track_changes {
+            options.change_tracker = Some(Default::default());
+        }
         Arc::new(Self {
             idle_conns: ArrayQueue::new(options.pool_max_connections as usize),
             semaphore: tokio::sync::Semaphore::new(options.pool_max_connections as usize),
@@ -41,6 +56,10 @@ impl PoolInner {
             num_idle: AtomicUsize::new(0),
             is_closed: AtomicBool::new(false),
             on_closed: event_listener::Event::new(),
+            workers: Mutex::default(),
+            replenish: Arc::default(),
+            metrics: MetricsRecorder::default(),
+            writer: Mutex::default(),
             options,
         })
     }
@@ -49,6 +68,26 @@ impl PoolInner {
         self.size.load(Ordering::Acquire)
     }
 
+    pub(super) fn stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.size(),
+            idle: self.num_idle(),
+            max_connections: self.options.pool_max_connections,
+        }
+    }
+
+    pub(super) fn metrics(&self) -> PoolMetrics {
+        self.metrics.snapshot(self.size(), self.num_idle())
+    }
+
+    /// The error for an acquire that started at `started` and ran out of time.
+    fn timed_out(&self, started: Instant) -> Error {
+        Error::PoolAcquireTimedOut {
+            waited: started.elapsed(),
+            stats: self.stats(),
+        }
+    }
+
     pub(super) fn num_idle(&self) -> usize {
         // We don't use `self.idle_conns.len()` as it waits for the internal
         // head and tail pointers to stop changing for a moment before calculating the length,
@@ -58,6 +97,31 @@ impl PoolInner {
         self.num_idle.load(Ordering::Acquire)
     }
 
+    /// The shared state of the pool's open connections.
+    fn workers(&self) -> Vec<Arc<WorkerSharedState>> {
+        let Ok(mut workers) = self.workers.lock() else {
+            return Vec::new();
+        };
+        workers.retain(|w| w.strong_count() > 0);
+        workers.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    pub(super) fn active_queries(&self) -> Vec<ActiveQuery> {
+        let mut queries: Vec<_> = self
+            .workers()
+            .iter()
+            .filter_map(|w| w.activity.snapshot())
+            .collect();
+        queries.sort_by_key(|q| q.started);
+        queries
+    }
+
+    pub(super) fn queue_metrics(&self) -> Vec<QueueMetrics> {
+        let mut metrics: Vec<_> = self.workers().iter().map(|w| w.queues.metrics()).collect();
+        metrics.sort_by_key(|m| m.connection);
+        metrics
+    }
+
     pub(super) fn is_closed(&self) -> bool {
         self.is_closed.load(Ordering::Acquire)
     }
@@ -65,6 +129,104 @@ impl PoolInner {
     fn mark_closed(&self) {
         self.is_closed.store(true, Ordering::Release);
         self.on_closed.notify(usize::MAX);
+        self.replenish.notify_one();
+    }
+
+    /// Spawn the task that keeps [`min_connections`](crate::Musq::min_connections) connections open. The task holds
+    /// only a weak reference to the pool, and stops when the pool is closed or dropped.
+    pub(super) fn spawn_maintenance(self: &Arc<Self>) {
+        let min = self.options.pool_min_connections;
+        if min == 0 {
+            return;
+        }
+        let pool = Arc::downgrade(self);
+        let replenish = self.replenish.clone();
+        self.options.spawn(async move {
+            loop {
+                replenish.notified().await;
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                match pool.warm_up(min).await {
+                    Ok(_) => {}
+                    Err(_) if pool.is_closed() => break,
+                    Err(error) => {
+                        tracing::warn!(%error, "failed to open a connection to maintain min_connections");
+                        drop(pool);
+                        tokio::time::sleep(REPLENISH_RETRY).await;
+                        replenish.notify_one();
+                    }
+                }
+            }
+        });
+    }
+
+    /// Spawn the task that closes connections that have outlived [`max_lifetime`](crate::Musq::max_lifetime) or
+    /// been idle for longer than [`idle_timeout`](crate::Musq::idle_timeout). Like the maintenance task, it holds only
+    /// a weak reference to the pool.
+    pub(super) fn spawn_reaper(self: &Arc<Self>) {
+        let Some(period) = [
+            self.options.pool_max_lifetime,
+            self.options.pool_idle_timeout,
+        ]
+        .into_iter()
+        .flatten()
+        .min() else {
+            return;
+        };
+        let period = (period / 2).clamp(MIN_REAP_INTERVAL, MAX_REAP_INTERVAL);
+        let pool = Arc::downgrade(self);
+        self.options.spawn(async move {
+            loop {
+                tokio::time::sleep(period).await;
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                if pool.is_closed() {
+                    break;
+                }
+                pool.reap().await;
+            }
+        });
+    }
+
+    /// Close the idle connections that have expired. Idle timeouts never take the pool below
+    /// [`min_connections`](crate::Musq::min_connections), but lifetimes do, and the maintenance task then opens
+    /// replacements.
+    async fn reap(self: &Arc<Self>) {
+        let min = self.options.pool_min_connections as usize;
+        let mut expired = Vec::new();
+        for _ in 0..self.num_idle() {
+            let Ok(permit) = self.semaphore.try_acquire_many(1) else {
+                break;
+            };
+            let Ok(idle) = self.pop_idle(permit) else {
+                break;
+            };
+            let removable = (self.size() as usize).saturating_sub(expired.len()) > min;
+            if self.is_expired(&idle.live) || (removable && self.is_stale(&idle)) {
+                expired.push(idle.into_live());
+            } else {
+                self.push_idle(idle);
+            }
+        }
+        for conn in expired {
+            conn.close().await;
+        }
+    }
+
+    /// Whether a connection has outlived [`max_lifetime`](crate::Musq::max_lifetime).
+    pub(super) fn is_expired(&self, live: &Live) -> bool {
+        self.options
+            .pool_max_lifetime
+            .is_some_and(|max| live.created_at.elapsed() >= max)
+    }
+
+    /// Whether a connection has been idle for longer than [`idle_timeout`](crate::Musq::idle_timeout).
+    fn is_stale(&self, idle: &Idle) -> bool {
+        self.options
+            .pool_idle_timeout
+            .is_some_and(|timeout| idle.idle_since.elapsed() >= timeout)
     }
 
     pub(super) async fn close<'a>(self: &'a Arc<Self>) {
@@ -123,7 +285,12 @@ impl PoolInner {
     }
 
     pub(super) fn release(&self, floating: Floating<Live>) {
-        let Floating { inner: idle, guard } = floating.into_idle();
+        self.push_idle(floating.into_idle());
+    }
+
+    /// Put a connection in the idle queue, keeping the time it became idle.
+    fn push_idle(&self, floating: Floating<Idle>) {
+        let Floating { inner: idle, guard } = floating;
 
         if self.idle_conns.push(idle).is_err() {
             panic!("BUG: connection queue overflow in release()");
@@ -154,7 +321,11 @@ impl PoolInner {
                     .filter(|size| size <= &self.options.pool_max_connections)
             }) {
             // we successfully incremented the size
-            Ok(_) => Ok(DecrementSizeGuard::from_permit((*self).clone(), permit)),
+            Ok(_) => {
+                let mut guard = DecrementSizeGuard::from_permit((*self).clone(), permit);
+                guard.connected = false;
+                Ok(guard)
+            }
             // the pool is at max capacity or is closed
             Err(_) => Err(permit),
         }
@@ -165,9 +336,9 @@ impl PoolInner {
             return Err(Error::PoolClosed);
         }
 
-        let deadline = Instant::now() + self.options.pool_acquire_timeout;
+        let started = Instant::now();
 
-        tokio::time::timeout(
+        let acquired = tokio::time::timeout(
             self.options.pool_acquire_timeout,
             async {
                 loop {
@@ -178,7 +349,10 @@ impl PoolInner {
                     let guard = match self.pop_idle(permit) {
 
                         // Then, check that we can use it...
-                        Ok(conn) => return Ok(conn.into_live()),
+                        Ok(conn) => match self.check_idle(conn.into_live()).await {
+                            Some(conn) => return Ok(conn),
+                            None => continue,
+                        },
                         Err(permit) => if let Ok(guard) = self.try_increment_size(permit) {
                             // we can open a new connection
                             guard
@@ -196,31 +370,129 @@ impl PoolInner {
                     };
 
                     // Attempt to connect...
-                    return self.connect(deadline, guard).await;
+                    return self.connect(started, guard).await;
                 }
             }
         )
             .await
-            .map_err(|_| Error::PoolTimedOut)?
+            .map_err(|_| self.timed_out(started))
+            .and_then(|acquired| acquired);
+        if let Err(Error::PoolAcquireTimedOut { .. }) = acquired {
+            self.metrics.timed_out();
+        }
+        acquired
+    }
+
+    /// Check an idle connection before handing it out: it must not have outlived its
+    /// [`max_lifetime`](crate::Musq::max_lifetime), it must pass the
+    /// [`quick_check_before_acquire`](crate::Musq::quick_check_before_acquire) check if set, and the
+    /// [`before_acquire`](crate::Musq::before_acquire) hook must accept it. Returns `None`, after closing the
+    /// connection, if any check fails.
+    async fn check_idle(&self, mut conn: Floating<Live>) -> Option<Floating<Live>> {
+        if self.is_expired(&conn) {
+            conn.close().await;
+            return None;
+        }
+        if self.options.pool_quick_check {
+            let healthy = match conn.raw.quick_check().await {
+                Ok(errors) if errors.is_empty() => true,
+                Ok(errors) => {
+                    tracing::warn!(
+                        errors = errors.len(),
+                        "quick_check found problems; closing the connection"
+                    );
+                    false
+                }
+                Err(error) => {
+                    tracing::warn!(%error, "quick_check failed; closing the connection");
+                    false
+                }
+            };
+            if !healthy {
+                conn.close().await;
+                return None;
+            }
+        }
+        let Some(hook) = self.options.pool_before_acquire.clone() else {
+            return Some(conn);
+        };
+        let keep = hook(&mut conn.raw).await.unwrap_or_else(|error| {
+            tracing::warn!(%error, "before_acquire hook failed; closing the connection");
+            false
+        });
+        if keep {
+            return Some(conn);
+        }
+        conn.close().await;
+        None
+    }
+
+    /// Open connections in parallel until the pool has `n` of them, or as many as it may have, and leave them idle.
+    /// Returns the number of connections opened. Stops early, without error, if the pool runs out of permits because other tasks
+    /// are opening connections at the same time.
+    pub(super) async fn warm_up(self: &Arc<Self>, n: u32) -> Result<u32> {
+        if self.is_closed() {
+            return Err(Error::PoolClosed);
+        }
+        let n = n.min(self.options.pool_max_connections);
+        let started = Instant::now();
+        let mut guards = Vec::new();
+        while self.size() < n {
+            let Ok(permit) = self.semaphore.try_acquire_many(1) else {
+                break;
+            };
+            let Ok(guard) = self.try_increment_size(permit) else {
+                break;
+            };
+            guards.push(guard);
+        }
+
+        let connecting = guards.into_iter().map(|guard| self.connect(started, guard));
+        let mut opened = 0;
+        let mut errors = Vec::new();
+        for result in future::join_all(connecting).await {
+            match result {
+                Ok(conn) if self.is_closed() => conn.close().await,
+                Ok(conn) => {
+                    self.release(conn);
+                    opened += 1;
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+        if errors.is_empty() {
+            Ok(opened)
+        } else {
+            Err(Error::WarmUpFailed { opened, errors })
+        }
     }
 
+    /// Open a new connection, giving up when the acquire timeout has elapsed since `started`.
     async fn connect(
         self: &Arc<Self>,
-        deadline: Instant,
+        started: Instant,
         guard: DecrementSizeGuard,
     ) -> Result<Floating<Live>> {
         if self.is_closed() {
             return Err(Error::PoolClosed);
         }
-        let timeout = deadline_as_timeout(deadline)?;
+        let timeout = (started + self.options.pool_acquire_timeout)
+            .checked_duration_since(Instant::now())
+            .ok_or_else(|| self.timed_out(started))?;
 
         // result here is `Result<Result<C, Error>, TimeoutError>`
         // if this block does not return, sleep for the backoff timeout and try again
         match tokio::time::timeout(timeout, self.options.connect()).await {
-            Ok(Ok(raw)) => Ok(Floating::new_live(raw, guard)),
+            Ok(Ok(raw)) => {
+                if let Ok(mut workers) = self.workers.lock() {
+                    workers.push(Arc::downgrade(&raw.worker.shared));
+                }
+                self.metrics.opened();
+                Ok(Floating::new_live(raw, guard))
+            }
             Ok(Err(e)) => Err(e),
             // timed out
-            Err(_) => Err(Error::PoolTimedOut),
+            Err(_) => Err(self.timed_out(started)),
         }
     }
 }
@@ -238,6 +510,8 @@ impl Drop for PoolInner {
 pub(in crate::pool) struct DecrementSizeGuard {
     pub(crate) pool: Arc<PoolInner>,
     cancelled: bool,
+    /// Whether the guard is for an open connection, rather than one that is being opened.
+    connected: bool,
 }
 
 impl DecrementSizeGuard {
@@ -246,6 +520,7 @@ impl DecrementSizeGuard {
         Self {
             pool,
             cancelled: false,
+            connected: true,
         }
     }
 
@@ -266,15 +541,25 @@ impl DecrementSizeGuard {
     pub fn cancel(mut self) {
         self.cancelled = true;
     }
+
+    /// Mark the guard as being for an open connection.
+    pub fn connected(mut self) -> Self {
+        self.connected = true;
+        self
+    }
 }
 
 impl Drop for DecrementSizeGuard {
     fn drop(&mut self) {
         if !self.cancelled {
             self.pool.size.fetch_sub(1, Ordering::AcqRel);
+            if self.connected {
+                self.pool.metrics.closed();
+            }
 
             // and here we release the permit we got on construction
             self.pool.semaphore.add_permits(1);
+            self.pool.replenish.notify_one();
         }
     }
 }
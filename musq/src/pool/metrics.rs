@@ -0,0 +1,124 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// The upper bounds of the buckets of [`WaitHistogram`]. Waits longer than the last bound are counted in a final,
+/// unbounded bucket.
+pub const WAIT_BUCKETS: [Duration; 10] = [
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// A snapshot of a pool's counters, returned by [`Pool::metrics`](crate::Pool::metrics). The counters start at zero
+/// when the pool is opened and only increase, so they map directly onto Prometheus counters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolMetrics {
+    /// The number of connections handed out by [`acquire`](crate::Pool::acquire) and
+    /// [`try_acquire`](crate::Pool::try_acquire).
+    pub acquires: u64,
+    /// How long successful acquires waited for a connection.
+    pub acquire_wait: WaitHistogram,
+    /// The number of acquires that failed because [`acquire_timeout`](crate::Musq::acquire_timeout) elapsed.
+    pub timeouts: u64,
+    /// The number of connections the pool has opened.
+    pub opened: u64,
+    /// The number of connections that have left the pool, by being closed or detached.
+    pub closed: u64,
+    /// The number of open connections that are not in use.
+    pub idle: usize,
+    /// The number of connections that are in use, or being opened.
+    pub active: u32,
+}
+
+/// A histogram of wait times, with the buckets of [`WAIT_BUCKETS`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitHistogram {
+    /// The number of waits in each bucket, not cumulative. The first count is of waits up to `WAIT_BUCKETS[0]`, each
+    /// following one of waits longer than the previous bound and up to the next, and the last of waits longer than
+    /// every bound.
+    pub counts: [u64; WAIT_BUCKETS.len() + 1],
+    /// The total of all the waits.
+    pub sum: Duration,
+}
+
+impl WaitHistogram {
+    /// The number of waits.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The cumulative counts of waits up to each bound, as Prometheus `le` buckets. The count for `+Inf` is
+    /// [`count`](Self::count).
+    pub fn cumulative(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        WAIT_BUCKETS
+            .iter()
+            .zip(self.counts.iter())
+            .scan(0, |total, (bound, n)| {
+                *total += n;
+                Some((*bound, *total))
+            })
+    }
+}
+
+/// The counters behind [`PoolMetrics`], updated as the pool runs.
+#[derive(Debug, Default)]
+pub(super) struct MetricsRecorder {
+    acquires: AtomicU64,
+    wait_counts: [AtomicU64; WAIT_BUCKETS.len() + 1],
+    /// The total wait, in nanoseconds.
+    wait_sum: AtomicU64,
+    timeouts: AtomicU64,
+    opened: AtomicU64,
+    closed: AtomicU64,
+}
+
+impl MetricsRecorder {
+    pub(super) fn acquired(&self, wait: Duration) {
+        let bucket = WAIT_BUCKETS
+            .iter()
+            .position(|bound| wait <= *bound)
+            .unwrap_or(WAIT_BUCKETS.len());
+        self.acquires.fetch_add(1, Ordering::Relaxed);
+        self.wait_counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.wait_sum.fetch_add(
+            u64::try_from(wait.as_nanos()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    pub(super) fn timed_out(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn opened(&self) {
+        self.opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn closed(&self) {
+        self.closed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn snapshot(&self, size: u32, idle: usize) -> PoolMetrics {
+        PoolMetrics {
+            acquires: self.acquires.load(Ordering::Relaxed),
+            acquire_wait: WaitHistogram {
+                counts: std::array::from_fn(|i| self.wait_counts[i].load(Ordering::Relaxed)),
+                sum: Duration::from_nanos(self.wait_sum.load(Ordering::Relaxed)),
+            },
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            opened: self.opened.load(Ordering::Relaxed),
+            closed: self.closed.load(Ordering::Relaxed),
+            idle,
+            active: size.saturating_sub(u32::try_from(idle).unwrap_or(u32::MAX)),
+        }
+    }
+}
@@ -13,6 +13,7 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Instant,
 };
 
 use event_listener::EventListener;
@@ -20,7 +21,14 @@ use futures_core::FusedFuture;
 use futures_util::FutureExt;
 
 use self::inner::PoolInner;
-use crate::{transaction::Transaction, Error, Result};
+use crate::{
+    query, query_scalar,
+    schema::quote_identifier,
+    sqlite::ChangeTracker,
+    transaction::{Transaction, TransactionBehavior},
+    writer::Writer,
+    ActiveQuery, Error, QueryResult, QueueMetrics, Result,
+};
 
 #[macro_use]
 mod executor;
@@ -30,8 +38,12 @@ pub mod maybe;
 
 mod connection;
 mod inner;
+mod metrics;
 
-pub use self::connection::PoolConnection;
+pub use self::{
+    connection::PoolConnection,
+    metrics::{PoolMetrics, WaitHistogram, WAIT_BUCKETS},
+};
 
 #[doc(hidden)]
 pub use self::maybe::MaybePoolConnection;
@@ -76,8 +88,27 @@ impl Pool {
         let inner = PoolInner::new_arc(options);
         let conn = inner.acquire().await?;
         inner.release(conn);
+        inner.warm_up(inner.options.pool_min_connections).await?;
+        inner.spawn_maintenance();
+        inner.spawn_reaper();
         Ok(Pool(inner))
     }
+
+    /// The change tracker shared by the pool's connections, if change tracking is enabled.
+    pub(crate) fn change_tracker(&self) -> Option<Arc<ChangeTracker>> {
+        self.0.options.change_tracker.clone()
+    }
+}
+
+/// A snapshot of a pool's connection counts, returned by [`Pool::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// The number of open connections, including idle ones.
+    pub size: u32,
+    /// The number of open connections that are not in use.
+    pub idle: usize,
+    /// The most connections the pool may open.
+    pub max_connections: u32,
 }
 
 /// A future that resolves when the pool is closed.
@@ -91,8 +122,9 @@ impl Pool {
     /// Retrieves a connection from the pool.
     ///
     /// The total time this method is allowed to execute is capped by
-    /// [`PoolOptions::acquire_timeout`].
-    /// If that timeout elapses, this will return [`Error::PoolClosed`].
+    /// [`Musq::acquire_timeout`](crate::Musq::acquire_timeout).
+    /// If that timeout elapses, this will return [`Error::PoolAcquireTimedOut`]. If the pool is closed, it returns
+    /// [`Error::PoolClosed`].
     ///
     /// ### Note: Cancellation/Timeout May Drop Connections
     /// If `acquire` is cancelled or times out after it acquires a connection from the idle queue or
@@ -113,7 +145,32 @@ impl Pool {
     /// returning it.
     pub fn acquire(&self) -> impl Future<Output = Result<PoolConnection>> + 'static {
         let shared = self.0.clone();
-        async move { shared.acquire().await.map(|conn| conn.reattach()) }
+        let started = Instant::now();
+        #[cfg(feature = "tracing-spans")]
+        let span = tracing::info_span!(
+            "musq.acquire",
+            wait = tracing::field::Empty,
+            connection = tracing::field::Empty
+        );
+        #[cfg(feature = "tracing-spans")]
+        let record = span.clone();
+        let acquire = async move {
+            let conn = shared
+                .acquire()
+                .await
+                .map(|conn| conn.reattach().acquired(started));
+            #[cfg(feature = "tracing-spans")]
+            {
+                record.record("wait", tracing::field::debug(started.elapsed()));
+                if let Ok(conn) = &conn {
+                    record.record("connection", conn.id());
+                }
+            }
+            conn
+        };
+        #[cfg(feature = "tracing-spans")]
+        let acquire = tracing::Instrument::instrument(acquire, span);
+        acquire
     }
 
     /// Attempts to retrieve a connection from the pool if there is one available.
@@ -121,7 +178,21 @@ impl Pool {
     /// Returns `None` immediately if there are no idle connections available in the pool
     /// or there are tasks waiting for a connection which have yet to wake.
     pub fn try_acquire(&self) -> Option<PoolConnection> {
-        self.0.try_acquire().map(|conn| conn.into_live().reattach())
+        let started = Instant::now();
+        self.0
+            .try_acquire()
+            .map(|conn| conn.into_live().reattach().acquired(started))
+    }
+
+    /// Open connections until the pool has `n` of them, so that the first queries don't wait for connections to be
+    /// established. Call this before the service starts taking traffic. Each connection is set up exactly as one
+    /// opened by [`acquire`](Self::acquire), and left idle in the pool. Returns the number of connections opened.
+    ///
+    /// The connections are opened in parallel, and `n` is capped at
+    /// [`Musq::max_connections`](crate::Musq::max_connections). If any connection can't be opened, fails with
+    /// [`Error::WarmUpFailed`], keeping the connections that were.
+    pub async fn warm_up(&self, n: u32) -> Result<u32> {
+        self.0.warm_up(n).await
     }
 
     /// Retrieves a connection and immediately begins a new transaction.
@@ -129,6 +200,16 @@ impl Pool {
         Transaction::begin(MaybePoolConnection::PoolConnection(self.acquire().await?)).await
     }
 
+    /// Retrieves a connection and immediately begins a new transaction that takes the write lock up front. See
+    /// [`TransactionBehavior::Immediate`].
+    pub async fn begin_immediate(&self) -> Result<Transaction<'static>> {
+        Transaction::begin_with(
+            MaybePoolConnection::PoolConnection(self.acquire().await?),
+            TransactionBehavior::Immediate,
+        )
+        .await
+    }
+
     /// Attempts to retrieve a connection and immediately begins a new transaction if successful.
     pub async fn try_begin(&self) -> Result<Option<Transaction<'static>>> {
         match self.try_acquire() {
@@ -192,6 +273,98 @@ impl Pool {
         // be in a consistent state, which may never happen at high levels of churn.
         self.0.num_idle()
     }
+
+    /// A snapshot of the pool's connection counts.
+    pub fn stats(&self) -> PoolStats {
+        self.0.stats()
+    }
+
+    /// A snapshot of the pool's counters, for exporting to a metrics system. See [`PoolMetrics`].
+    pub fn metrics(&self) -> PoolMetrics {
+        self.0.metrics()
+    }
+
+    /// A snapshot of the queries running on the pool's connections, oldest first. Use this to find out what a pool
+    /// is stuck on, for instance from a diagnostics endpoint.
+    ///
+    /// A query stays active until its connection has produced all its results, so a query whose results are not
+    /// being consumed is reported as [`QueryState::Streaming`](crate::QueryState::Streaming). Queries are reported
+    /// without their SQL unless the pool was opened with [`Musq::capture_query_sql`](crate::Musq::capture_query_sql).
+    pub fn active_queries(&self) -> Vec<ActiveQuery> {
+        self.0.active_queries()
+    }
+
+    /// How full the command and row buffers of each of the pool's open connections are, ordered by connection id.
+    /// See [`QueueMetrics`].
+    pub fn queue_metrics(&self) -> Vec<QueueMetrics> {
+        self.0.queue_metrics()
+    }
+
+    /// A handle to the pool's writer, which executes writes one at a time on a single task, so that writes made through
+    /// it never contend with each other for the database lock. Writes made through the pool directly still contend
+    /// with them. Every call returns a handle to the same writer while any handle is alive.
+    ///
+    /// The writer's task holds a reference to the pool, and stops when the pool is [closed](Self::close). See
+    /// [`Writer`].
+    pub fn writer(&self) -> Writer {
+        Writer::for_pool(self)
+    }
+
+    /// Execute `sql` on a connection from the pool without caching its prepared statements. See
+    /// [`Connection::execute_uncached`](crate::Connection::execute_uncached).
+    pub async fn execute_uncached(&self, sql: &str) -> Result<QueryResult> {
+        self.acquire().await?.execute_uncached(sql).await
+    }
+
+    /// Run `ANALYZE` on every table that has had at least `threshold` rows inserted, updated or deleted since it was
+    /// last analyzed by this method, keeping the query planner's statistics fresh without manual scheduling. Returns
+    /// the tables that were analyzed, as `(schema, table)` pairs.
+    ///
+    /// Writes are counted by committed transaction across all the pool's connections, so the pool must be opened with
+    /// [`Musq::track_changes`](crate::Musq::track_changes), and [`Error::Configuration`] is returned otherwise. Counts
+    /// start from zero when the pool is opened. Writes to temporary tables are not counted.
+    ///
+    /// Rows are counted from SQLite's update hook, which doesn't see rows in `WITHOUT ROWID` tables, nor those removed
+    /// by a `DELETE` without a `WHERE` clause. Such rows are counted from the number of rows the statement changed
+    /// when it writes a single table, but those written by triggers are missed.
+    pub async fn maybe_analyze(&self, threshold: u64) -> Result<Vec<(String, String)>> {
+        let tracker = self.change_tracker().ok_or_else(|| {
+            Error::Configuration(
+                "maybe_analyze requires a pool opened with track_changes(true)".into(),
+            )
+        })?;
+        let churned = tracker.churned(threshold.max(1));
+        if churned.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut conn = self.acquire().await?;
+        let mut analyzed = Vec::new();
+        for (table, rows) in churned {
+            let (schema, name) = &table;
+            let exists: Option<i64> = query_scalar(&format!(
+                "SELECT 1 FROM {}.sqlite_schema WHERE type = 'table' AND name = ?",
+                quote_identifier(schema)
+            ))
+            .bind(name.as_str())
+            .fetch_optional(&mut *conn)
+            .await
+            // The schema may have been detached
+            .unwrap_or(None);
+            if exists.is_some() {
+                query(&format!(
+                    "ANALYZE {}.{}",
+                    quote_identifier(schema),
+                    quote_identifier(name)
+                ))
+                .execute(&mut *conn)
+                .await?;
+                analyzed.push(table.clone());
+            }
+            tracker.analyzed(&table, rows);
+        }
+        Ok(analyzed)
+    }
 }
 
 /// Returns a new [Pool] tied to the same shared connection pool.
@@ -1,12 +1,19 @@
+use std::{fmt, time::Duration};
+
 use either::Either;
 use futures_core::stream::BoxStream;
 use futures_util::{future, StreamExt, TryFutureExt, TryStreamExt};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
+    bulk::{BulkInsert, Values},
     encode::Encode,
     error::Error,
     executor::{Execute, Executor},
-    Arguments, IntoArguments, QueryResult, Row, Statement,
+    from_row::FromRow,
+    pool::Pool,
+    ArgumentValue, Arguments, IntoArguments, QueryKind, QueryResult, Row, SqliteDataType,
+    Statement,
 };
 
 /// Raw SQL query with bind parameters. Returned by [`query`][crate::query::query].
@@ -14,6 +21,55 @@ use crate::{
 pub struct Query<A> {
     pub(crate) statement: Either<String, Statement>,
     pub(crate) arguments: Option<A>,
+    pub(crate) limits: ResultLimits,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    pub(crate) row_buffer_size: Option<usize>,
+}
+
+/// Bounds on the size of a query's results, set with [`Query::max_rows`] and [`Query::max_result_bytes`].
+///
+/// The limits are checked as rows are produced, so a query that exceeds them stops without reading the rest of its
+/// results, and fails with [`Error::ResultLimitExceeded`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResultLimits {
+    /// The maximum number of rows, over all the statements of the query.
+    pub max_rows: Option<u64>,
+    /// The maximum number of bytes of column data. Text and blobs count their length, and numbers count 8 bytes.
+    pub max_bytes: Option<u64>,
+}
+
+impl ResultLimits {
+    /// Check running totals of rows and bytes against the limits.
+    pub(crate) fn check(&self, rows: u64, bytes: u64) -> Result<(), Error> {
+        if let Some(max) = self.max_rows.filter(|max| rows > *max) {
+            return Err(Error::ResultLimitExceeded {
+                limit: ResultLimit::Rows(max),
+            });
+        }
+        if let Some(max) = self.max_bytes.filter(|max| bytes > *max) {
+            return Err(Error::ResultLimitExceeded {
+                limit: ResultLimit::Bytes(max),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The limit that a query exceeded. See [`ResultLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultLimit {
+    Rows(u64),
+    Bytes(u64),
+}
+
+impl fmt::Display for ResultLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rows(n) => write!(f, "{n} rows"),
+            Self::Bytes(n) => write!(f, "{n} bytes"),
+        }
+    }
 }
 
 /// SQL query that will map its results to owned Rust types.
@@ -52,6 +108,22 @@ where
     fn take_arguments(&mut self) -> Option<Arguments> {
         self.arguments.take().map(IntoArguments::into_arguments)
     }
+
+    fn limits(&self) -> ResultLimits {
+        self.limits
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn cancellation(&self) -> Option<CancellationToken> {
+        self.cancellation.clone()
+    }
+
+    fn row_buffer_size(&self) -> Option<usize> {
+        self.row_buffer_size
+    }
 }
 
 impl<'q> Query<Arguments> {
@@ -65,6 +137,40 @@ impl<'q> Query<Arguments> {
         }
         self
     }
+
+    /// Bind a `NULL` with an explicit type, as with [`Arguments::add_typed`]. SQLite's `NULL` has no type, so this binds
+    /// the same value as `bind(None::<T>)`; it states the intended type where the value is bound.
+    pub fn bind_null_as(mut self, ty: SqliteDataType) -> Self {
+        if let Some(arguments) = &mut self.arguments {
+            arguments.add_typed(ArgumentValue::Null, ty);
+        }
+        self
+    }
+
+    /// Bind many rows of values to an `INSERT` statement, for a bulk insert. The query's SQL is the statement up to
+    /// and including `VALUES`, such as `INSERT INTO t (a, b) VALUES`, and each row is a tuple with a value for each
+    /// column. Values bound to the query itself are ignored.
+    ///
+    /// See [`BulkInsert::execute`] for how the rows are inserted.
+    pub fn bind_all<I>(self, rows: I) -> BulkInsert<I>
+    where
+        I: IntoIterator,
+        I::Item: Values,
+    {
+        BulkInsert::new(self.sql(), rows)
+    }
+
+    /// Bind a value to a named parameter, such as `:name`, `@name` or `$name`. The name includes its prefix. Binding
+    /// the same name again replaces the earlier value.
+    ///
+    /// Named and positional parameters can be mixed. When the query is executed, it fails if a named parameter in the
+    /// SQL has no value, or if a name bound here doesn't appear in the SQL.
+    pub fn bind_named<T: 'q + Send + Encode>(mut self, name: &str, value: T) -> Self {
+        if let Some(arguments) = &mut self.arguments {
+            arguments.add_named(name, value);
+        }
+        self
+    }
 }
 
 impl<'q, A: Send> Query<A>
@@ -100,6 +206,55 @@ where
         }
     }
 
+    /// Classify the query as a read, a write or a schema change. See [`Execute::classify`].
+    pub fn classify(&self) -> QueryKind {
+        Execute::classify(self)
+    }
+
+    /// Fail with [`Error::ResultLimitExceeded`] if the query returns more than `n` rows, instead of reading them all.
+    pub fn max_rows(mut self, n: u64) -> Self {
+        self.limits.max_rows = Some(n);
+        self
+    }
+
+    /// Fail with [`Error::ResultLimitExceeded`] if the query returns more than `n` bytes of column data, instead of
+    /// reading it all. See [`ResultLimits::max_bytes`] for how data is counted.
+    pub fn max_result_bytes(mut self, n: u64) -> Self {
+        self.limits.max_bytes = Some(n);
+        self
+    }
+
+    /// Abort the query with [`Error::QueryTimedOut`] if it is still running `timeout` after the connection starts on
+    /// it, so that a runaway query can't hold a connection forever.
+    ///
+    /// The deadline covers all the statements of the query, including time spent waiting for the caller to consume
+    /// rows. It is checked while SQLite is working, every thousand or so virtual machine instructions, so a query
+    /// blocked elsewhere, for instance on a lock within the [busy timeout](crate::Musq::busy_timeout), is only
+    /// aborted once it resumes. A write aborted inside an explicit transaction may roll back the whole transaction.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Abort the query with [`Error::Cancelled`] when `token` is cancelled, for instance because the request it serves
+    /// went away. A query whose token is already cancelled doesn't run at all.
+    ///
+    /// Like a [timeout](Self::timeout), the token is checked while SQLite is working and between rows, and a write
+    /// aborted inside an explicit transaction may roll back the whole transaction.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Let the connection produce up to `size` rows ahead of the caller for this query, instead of the connection's
+    /// [row buffer size](crate::Musq::row_buffer_size). A larger buffer keeps the worker busy while a large report is
+    /// consumed in bursts, and a smaller one bounds the memory held by a query with large rows.
+    /// [`QueueMetrics`](crate::QueueMetrics) shows how full the buffer is, and how often the worker waits on it.
+    pub fn row_buffer_size(mut self, size: usize) -> Self {
+        self.row_buffer_size = Some(size);
+        self
+    }
+
     /// Execute the query and return the total number of rows affected.
     pub async fn execute<'e, 'c: 'e, E>(self, executor: E) -> Result<QueryResult, Error>
     where
@@ -123,6 +278,37 @@ where
         executor.execute_many(self)
     }
 
+    /// Execute a statement with a `RETURNING` clause, and return both the rows it returns, decoded as `T`, and the
+    /// [`QueryResult`] with the number of rows affected and the last inserted rowid.
+    ///
+    /// ```rust,ignore
+    /// let (ids, result): (Vec<(i64,)>, _) = query("UPDATE users SET active = 0 WHERE seen < ? RETURNING id")
+    ///     .bind(cutoff)
+    ///     .execute_returning(&pool)
+    ///     .await?;
+    /// ```
+    pub async fn execute_returning<'e, 'c: 'e, E, T>(
+        self,
+        executor: E,
+    ) -> Result<(Vec<T>, QueryResult), Error>
+    where
+        'q: 'e,
+        A: 'e,
+        E: Executor<'c>,
+        T: for<'r> FromRow<'r>,
+    {
+        let mut stream = executor.fetch_many(self);
+        let mut rows = Vec::new();
+        let mut result = QueryResult::default();
+        while let Some(item) = stream.try_next().await? {
+            match item {
+                Either::Left(done) => result.extend([done]),
+                Either::Right(row) => rows.push(T::from_row("", &row)?),
+            }
+        }
+        Ok((rows, result))
+    }
+
     /// Execute the query and return the generated results as a stream.
     pub fn fetch<'e, 'c: 'e, E>(self, executor: E) -> BoxStream<'e, Result<Row, Error>>
     where
@@ -147,6 +333,42 @@ where
         executor.fetch_many(self)
     }
 
+    /// Execute the query on a connection from `pool`, and return the generated rows as a stream that borrows neither
+    /// the pool nor the query, so that it can be returned from functions or fed into long-lived stream pipelines.
+    ///
+    /// The connection is acquired when the stream is first polled, and held until the stream finishes or is dropped.
+    pub fn fetch_owned(self, pool: &Pool) -> BoxStream<'static, Result<Row, Error>>
+    where
+        A: 'static,
+    {
+        let pool = pool.clone();
+        Box::pin(try_stream! {
+            let mut conn = pool.acquire().await?;
+            let mut s = conn.fetch(self);
+
+            while let Some(row) = s.try_next().await? {
+                r#yield!(row);
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Execute the query and return the generated rows in pages of up to `page_size` rows. See
+    /// [`Executor::fetch_paged`].
+    pub fn fetch_paged<'e, 'c: 'e, E>(
+        self,
+        executor: E,
+        page_size: usize,
+    ) -> BoxStream<'e, Result<Vec<Row>, Error>>
+    where
+        'q: 'e,
+        A: 'e,
+        E: Executor<'c>,
+    {
+        executor.fetch_paged(self, page_size)
+    }
+
     /// Execute the query and return all the generated results, collected into a [`Vec`].
     pub async fn fetch_all<'e, 'c: 'e, E>(self, executor: E) -> Result<Vec<Row>, Error>
     where
@@ -193,6 +415,40 @@ where
     fn take_arguments(&mut self) -> Option<Arguments> {
         self.inner.take_arguments()
     }
+
+    fn limits(&self) -> ResultLimits {
+        self.inner.limits()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Execute::timeout(&self.inner)
+    }
+
+    fn cancellation(&self) -> Option<CancellationToken> {
+        self.inner.cancellation()
+    }
+
+    fn row_buffer_size(&self) -> Option<usize> {
+        Execute::row_buffer_size(&self.inner)
+    }
+}
+
+impl<'q, F> Map<F, Arguments> {
+    /// Bind a value for use with this SQL query.
+    ///
+    /// See [`Query::bind`](Query::bind).
+    pub fn bind<T: 'q + Send + Encode>(mut self, value: T) -> Self {
+        self.inner = self.inner.bind(value);
+        self
+    }
+
+    /// Bind a value to a named parameter.
+    ///
+    /// See [`Query::bind_named`](Query::bind_named).
+    pub fn bind_named<T: 'q + Send + Encode>(mut self, name: &str, value: T) -> Self {
+        self.inner = self.inner.bind_named(name, value);
+        self
+    }
 }
 
 impl<'q, F, O, A> Map<F, A>
@@ -201,6 +457,36 @@ where
     O: Send + Unpin,
     A: 'q + Send + IntoArguments,
 {
+    /// See [`Query::max_rows`].
+    pub fn max_rows(mut self, n: u64) -> Self {
+        self.inner = self.inner.max_rows(n);
+        self
+    }
+
+    /// See [`Query::max_result_bytes`].
+    pub fn max_result_bytes(mut self, n: u64) -> Self {
+        self.inner = self.inner.max_result_bytes(n);
+        self
+    }
+
+    /// See [`Query::timeout`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
+    }
+
+    /// See [`Query::with_cancellation`].
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.inner = self.inner.with_cancellation(token);
+        self
+    }
+
+    /// See [`Query::row_buffer_size`].
+    pub fn row_buffer_size(mut self, size: usize) -> Self {
+        self.inner = self.inner.row_buffer_size(size);
+        self
+    }
+
     /// Map each row in the result to another type.
     ///
     /// See [`try_map`](Map::try_map) for a fallible version of this method.
@@ -328,6 +614,10 @@ where
 pub fn query_statement(statement: &Statement) -> Query<Arguments> {
     Query {
         arguments: Some(Default::default()),
+        limits: ResultLimits::default(),
+        timeout: None,
+        cancellation: None,
+        row_buffer_size: None,
         statement: Either::Right(statement.clone()),
     }
 }
@@ -339,6 +629,10 @@ where
 {
     Query {
         arguments: Some(arguments),
+        limits: ResultLimits::default(),
+        timeout: None,
+        cancellation: None,
+        row_buffer_size: None,
         statement: Either::Right(statement.clone()),
     }
 }
@@ -347,6 +641,10 @@ where
 pub fn query(sql: &str) -> Query<Arguments> {
     Query {
         arguments: Some(Default::default()),
+        limits: ResultLimits::default(),
+        timeout: None,
+        cancellation: None,
+        row_buffer_size: None,
         statement: Either::Left(sql.to_string()),
     }
 }
@@ -358,6 +656,10 @@ where
 {
     Query {
         arguments: Some(arguments),
+        limits: ResultLimits::default(),
+        timeout: None,
+        cancellation: None,
+        row_buffer_size: None,
         statement: Either::Left(sql.to_string()),
     }
 }
@@ -1,16 +1,19 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, time::Duration};
 
 use either::Either;
 use futures_core::stream::BoxStream;
-use futures_util::{StreamExt, TryStreamExt};
+use futures_util::{future, StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     encode::Encode,
     error::Error,
     executor::{Execute, Executor},
     from_row::FromRow,
-    query::{query, query_statement, query_statement_with, query_with, Query},
-    Arguments, IntoArguments, QueryResult, Statement,
+    query::{query, query_statement, query_statement_with, query_with, Map, Query},
+    row::from_row_serde,
+    Arguments, IntoArguments, Pool, QueryResult, ResultLimits, Row, Statement,
 };
 
 /// Raw SQL query with bind parameters, mapped to a concrete type using [`FromRow`].
@@ -36,6 +39,22 @@ where
     fn take_arguments(&mut self) -> Option<Arguments> {
         self.inner.take_arguments()
     }
+
+    fn limits(&self) -> ResultLimits {
+        self.inner.limits()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Execute::timeout(&self.inner)
+    }
+
+    fn cancellation(&self) -> Option<CancellationToken> {
+        self.inner.cancellation()
+    }
+
+    fn row_buffer_size(&self) -> Option<usize> {
+        Execute::row_buffer_size(&self.inner)
+    }
 }
 
 impl<'q, O> QueryAs<O, Arguments> {
@@ -46,6 +65,14 @@ impl<'q, O> QueryAs<O, Arguments> {
         self.inner = self.inner.bind(value);
         self
     }
+
+    /// Bind a value to a named parameter.
+    ///
+    /// See [`Query::bind_named`](Query::bind_named).
+    pub fn bind_named<T: 'q + Send + Encode>(mut self, name: &str, value: T) -> Self {
+        self.inner = self.inner.bind_named(name, value);
+        self
+    }
 }
 
 // FIXME: This is very close, nearly 1:1 with `Map`
@@ -55,6 +82,36 @@ where
     A: 'q + IntoArguments,
     O: Send + Unpin + for<'r> FromRow<'r>,
 {
+    /// See [`Query::max_rows`].
+    pub fn max_rows(mut self, n: u64) -> Self {
+        self.inner = self.inner.max_rows(n);
+        self
+    }
+
+    /// See [`Query::max_result_bytes`].
+    pub fn max_result_bytes(mut self, n: u64) -> Self {
+        self.inner = self.inner.max_result_bytes(n);
+        self
+    }
+
+    /// See [`Query::timeout`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
+    }
+
+    /// See [`Query::with_cancellation`].
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.inner = self.inner.with_cancellation(token);
+        self
+    }
+
+    /// See [`Query::row_buffer_size`].
+    pub fn row_buffer_size(mut self, size: usize) -> Self {
+        self.inner = self.inner.row_buffer_size(size);
+        self
+    }
+
     /// Execute the query and return the generated results as a stream.
     pub fn fetch<'e, 'c: 'e, E>(self, executor: E) -> BoxStream<'e, Result<O, Error>>
     where
@@ -90,6 +147,19 @@ where
             .boxed()
     }
 
+    /// Execute the query on a connection from `pool`, and return the generated results as a `'static` stream. See
+    /// [`Query::fetch_owned`].
+    pub fn fetch_owned(self, pool: &Pool) -> BoxStream<'static, Result<O, Error>>
+    where
+        O: 'static,
+        A: 'static,
+    {
+        self.inner
+            .fetch_owned(pool)
+            .and_then(|row| future::ready(O::from_row("", &row)))
+            .boxed()
+    }
+
     /// Execute the query and return all the generated results, collected into a [`Vec`].
 
     pub async fn fetch_all<'e, 'c: 'e, E>(self, executor: E) -> Result<Vec<O>, Error>
@@ -144,6 +214,15 @@ where
     }
 }
 
+/// Make a SQL query that is mapped to a serde type using [`from_row_serde`], for types that don't implement
+/// [`FromRow`].
+pub fn query_as_serde<O>(sql: &str) -> Map<impl FnMut(Row) -> Result<O, Error> + Send, Arguments>
+where
+    O: DeserializeOwned + Send + Unpin,
+{
+    query(sql).try_map(|row| from_row_serde(&row))
+}
+
 /// Make a SQL query, with the given arguments, that is mapped to a concrete type
 /// using [`FromRow`].
 
@@ -0,0 +1,514 @@
+//! Build SQL queries dynamically.
+//!
+//! A [`QueryBuilder`] accumulates SQL text and bound arguments, for queries whose shape depends on runtime input:
+//! optional filters, variable column lists, and so on. Values are always bound as parameters rather than spliced into
+//! the SQL.
+//!
+//! ```rust,ignore
+//! let mut qb = QueryBuilder::new("SELECT id FROM events WHERE ");
+//! qb.push_json_extract("payload", &JsonPath::root().key("user").key("id"))
+//!     .push(" = ")
+//!     .push_bind(42);
+//! let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&pool).await?;
+//! ```
+//!
+//! A [`Fragment`] is a reusable piece of SQL with its own bound values, such as a filter or an `ORDER BY` clause
+//! shared by many queries. Fragments are usually written with the [`fragment!`](crate::fragment) macro, and are
+//! spliced into a builder or into other fragments:
+//!
+//! ```rust,ignore
+//! fn in_stock(min: i64) -> Fragment {
+//!     fragment!("stock >= {min}")
+//! }
+//!
+//! let filter = fragment!("price > {price} AND {stock:frag}", stock = in_stock(1));
+//! let mut qb = QueryBuilder::new("SELECT id FROM products WHERE ");
+//! qb.push_fragment(&filter);
+//! ```
+//!
+//! Lists are pushed through a [`Separated`], which puts a separator between items, and `IN` clauses with
+//! [`QueryBuilder::push_in`]:
+//!
+//! ```rust,ignore
+//! let mut qb = QueryBuilder::new("SELECT id FROM products WHERE ");
+//! let mut filters = qb.separated(" AND ");
+//! filters.push("stock > 0");
+//! if let Some(min) = min_price {
+//!     filters.push("price >= ").push_bind_unseparated(min);
+//! }
+//! qb.push(" AND ").push_in("category", ["books", "music"]);
+//! ```
+//!
+//! Sort orders chosen by users, such as a `?sort=-created,name` request parameter, are validated against an allowlist
+//! of columns with a [`SortSpec`] before they reach the SQL:
+//!
+//! ```rust,ignore
+//! let sort = SortSpec::for_table::<User>().parse(&params.sort)?;
+//! qb.push_order_by(&sort);
+//! ```
+use std::str::FromStr;
+
+use crate::{
+    bulk::Values,
+    encode::Encode,
+    query::{query_with, Query},
+    query_as::{query_as_with, QueryAs},
+    query_scalar::{query_scalar_with, QueryScalar},
+    schema::{quote_identifier, Table},
+    types::{fts5_escape, JsonPath, HIGHLIGHT_END, HIGHLIGHT_START},
+    ArgumentValue, Arguments, Error, FromRow, Result,
+};
+
+/// A piece of SQL with the values bound to its `?` placeholders. See the [module docs](self).
+///
+/// Composing fragments renumbers nothing, so a fragment's SQL should only use plain `?` placeholders, not numbered or
+/// named parameters.
+#[derive(Debug, Default, Clone)]
+pub struct Fragment {
+    sql: String,
+    values: Vec<ArgumentValue>,
+}
+
+impl Fragment {
+    /// A fragment of SQL text with no bound values.
+    pub fn new(sql: impl Into<String>) -> Self {
+        Self {
+            sql: sql.into(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Join fragments with a separator, such as `" AND "` or `", "`.
+    pub fn join(separator: &str, fragments: impl IntoIterator<Item = Fragment>) -> Self {
+        let mut joined = Self::default();
+        for (i, fragment) in fragments.into_iter().enumerate() {
+            if i > 0 {
+                joined.push(separator);
+            }
+            joined.push_fragment(&fragment);
+        }
+        joined
+    }
+
+    /// A `VALUES (?, ?), (?, ?)` list with a row of placeholders for each of `rows`, for row-value comparisons such
+    /// as `WHERE (a, b) IN (VALUES ...)`. A list that is too long for the connection's variable limit fails when the
+    /// query is prepared; [`bulk::fetch_in`](crate::bulk::fetch_in) splits long lists into chunks.
+    ///
+    /// `rows` must not be empty. SQL has no empty `VALUES` list, so an empty `rows` gives a fragment that fails to
+    /// prepare. Unlike [`QueryBuilder::push_in`], check for no rows before building the query.
+    pub fn values<I>(rows: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Values,
+    {
+        let placeholders = format!("({})", vec!["?"; I::Item::LEN].join(", "));
+        let mut arguments = Arguments::default();
+        let mut n = 0;
+        for row in rows {
+            row.add_to(&mut arguments);
+            n += 1;
+        }
+        Self {
+            sql: format!("VALUES {}", vec![placeholders.as_str(); n].join(", ")),
+            values: arguments.values,
+        }
+    }
+
+    /// Append SQL text verbatim. Never push untrusted input this way; bind it with [`push_bind`](Self::push_bind).
+    pub fn push(&mut self, sql: impl AsRef<str>) -> &mut Self {
+        self.sql.push_str(sql.as_ref());
+        self
+    }
+
+    /// Append a `?` placeholder, and bind `value` to it.
+    pub fn push_bind(&mut self, value: impl Encode) -> &mut Self {
+        self.sql.push('?');
+        self.values.push(value.encode());
+        self
+    }
+
+    /// Append another fragment, with its values.
+    pub fn push_fragment(&mut self, fragment: &Fragment) -> &mut Self {
+        self.sql.push_str(&fragment.sql);
+        self.values.extend(fragment.values.iter().cloned());
+        self
+    }
+
+    /// The SQL of the fragment.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sql.is_empty()
+    }
+
+    /// The number of values bound to the fragment.
+    pub(crate) fn bound(&self) -> usize {
+        self.values.len()
+    }
+}
+
+/// A SQL query built up piece by piece. See the [module docs](self).
+#[derive(Debug, Default)]
+pub struct QueryBuilder {
+    sql: String,
+    arguments: Arguments,
+}
+
+impl QueryBuilder {
+    /// Start a query with the given SQL.
+    pub fn new(init: impl Into<String>) -> Self {
+        Self {
+            sql: init.into(),
+            arguments: Arguments::default(),
+        }
+    }
+
+    /// Append SQL text verbatim. Never push untrusted input this way; bind it with [`push_bind`](Self::push_bind).
+    pub fn push(&mut self, sql: impl AsRef<str>) -> &mut Self {
+        self.sql.push_str(sql.as_ref());
+        self
+    }
+
+    /// Append a `?` placeholder, and bind `value` to it.
+    pub fn push_bind(&mut self, value: impl Encode) -> &mut Self {
+        self.sql.push('?');
+        self.arguments.add(value);
+        self
+    }
+
+    /// Append a quoted identifier. A dotted name such as `t.data` is quoted part by part.
+    pub fn push_identifier(&mut self, name: &str) -> &mut Self {
+        let quoted = name
+            .split('.')
+            .map(quote_identifier)
+            .collect::<Vec<_>>()
+            .join(".");
+        self.sql.push_str(&quoted);
+        self
+    }
+
+    /// Append an `ORDER BY` clause for `sort`, with a leading space. Appends nothing if `sort` has no terms.
+    pub fn push_order_by(&mut self, sort: &SortSpec) -> &mut Self {
+        for (i, (column, direction)) in sort.terms.iter().enumerate() {
+            self.push(if i == 0 { " ORDER BY " } else { ", " })
+                .push_identifier(column)
+                .push(match direction {
+                    SortDirection::Asc => " ASC",
+                    SortDirection::Desc => " DESC",
+                });
+        }
+        self
+    }
+
+    /// Append `json_extract(column, ?)`, binding `path`. `column` is quoted as by
+    /// [`push_identifier`](Self::push_identifier).
+    pub fn push_json_extract(&mut self, column: &str, path: &JsonPath) -> &mut Self {
+        self.push("json_extract(")
+            .push_identifier(column)
+            .push(", ")
+            .push_bind(path.clone())
+            .push(")")
+    }
+
+    /// Append `table MATCH ?`, binding `query` with its FTS5 syntax escaped by
+    /// [`fts5_escape`](crate::types::fts5_escape), so that it matches rows containing every term of `query`.
+    pub fn push_match(&mut self, table: &str, query: &str) -> &mut Self {
+        self.push_identifier(table)
+            .push(" MATCH ")
+            .push_bind(fts5_escape(query))
+    }
+
+    /// Append a call to the FTS5 `highlight()` function for column `column` of `table`, marking matches so that the
+    /// result decodes as an [`Fts5Highlight`](crate::types::Fts5Highlight).
+    pub fn push_highlight(&mut self, table: &str, column: i64) -> &mut Self {
+        self.push("highlight(")
+            .push_identifier(table)
+            .push(format!(", {column}, "))
+            .push_bind(HIGHLIGHT_START)
+            .push(", ")
+            .push_bind(HIGHLIGHT_END)
+            .push(")")
+    }
+
+    /// Append a call to the FTS5 `snippet()` function, which returns a fragment of up to `max_tokens` tokens of
+    /// column `column` of `table` around the matches, or of the best-matching column if `column` is -1. Text is
+    /// elided with `ellipsis`, and the result decodes as an [`Fts5Highlight`](crate::types::Fts5Highlight).
+    pub fn push_snippet(
+        &mut self,
+        table: &str,
+        column: i64,
+        ellipsis: &str,
+        max_tokens: u8,
+    ) -> &mut Self {
+        self.push("snippet(")
+            .push_identifier(table)
+            .push(format!(", {column}, "))
+            .push_bind(HIGHLIGHT_START)
+            .push(", ")
+            .push_bind(HIGHLIGHT_END)
+            .push(", ")
+            .push_bind(ellipsis)
+            .push(format!(", {max_tokens})"))
+    }
+
+    /// Append a [`Fragment`], binding its values.
+    pub fn push_fragment(&mut self, fragment: &Fragment) -> &mut Self {
+        self.sql.push_str(&fragment.sql);
+        self.arguments
+            .values
+            .extend(fragment.values.iter().cloned());
+        self
+    }
+
+    /// Start a list whose items are separated by `separator`, such as `", "` or `" AND "`. See [`Separated`].
+    pub fn separated<'sep>(&mut self, separator: &'sep str) -> Separated<'_, 'sep> {
+        Separated {
+            builder: self,
+            separator,
+            first: true,
+        }
+    }
+
+    /// Append `column IN (?, ?, ...)` with a placeholder for each of `values`, binding them. `column` is quoted as by
+    /// [`push_identifier`](Self::push_identifier). An empty list is `IN ()`, which SQLite accepts and which matches no
+    /// rows.
+    ///
+    /// A list that is too long for the connection's variable limit fails when the query is prepared;
+    /// [`bulk::fetch_in`](crate::bulk::fetch_in) splits long lists into chunks.
+    pub fn push_in<I>(&mut self, column: &str, values: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: Encode,
+    {
+        self.push_identifier(column).push(" IN (");
+        let mut list = self.separated(", ");
+        for value in values {
+            list.push_bind(value);
+        }
+        self.push(")")
+    }
+
+    /// Append a parenthesized tuple for each of `tuples`, separated by commas, as in `(?, ?), (?, ?)`. `push_tuple`
+    /// pushes the items of a tuple through a [`Separated`] that separates them with commas. Use this after `VALUES`
+    /// to insert many rows, or inside `IN (VALUES ...)` for a row-value comparison.
+    ///
+    /// ```rust,ignore
+    /// let mut qb = QueryBuilder::new("INSERT INTO users (name, email) ");
+    /// qb.push("VALUES ").push_tuples(&users, |mut b, user| {
+    ///     b.push_bind(user.name.as_str()).push_bind(user.email.as_str());
+    /// });
+    /// ```
+    pub fn push_tuples<I, F>(&mut self, tuples: I, mut push_tuple: F) -> &mut Self
+    where
+        I: IntoIterator,
+        F: FnMut(Separated<'_, 'static>, I::Item),
+    {
+        for (i, tuple) in tuples.into_iter().enumerate() {
+            self.push(if i == 0 { "(" } else { ", (" });
+            push_tuple(self.separated(", "), tuple);
+            self.push(")");
+        }
+        self
+    }
+
+    /// The SQL built so far.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Build the query, leaving the builder empty for reuse.
+    pub fn build(&mut self) -> Query<Arguments> {
+        let (sql, arguments) = self.take();
+        query_with(&sql, arguments)
+    }
+
+    /// Build a query that is mapped to a concrete type using [`FromRow`].
+    pub fn build_query_as<O>(&mut self) -> QueryAs<O, Arguments>
+    where
+        O: for<'r> FromRow<'r>,
+    {
+        let (sql, arguments) = self.take();
+        query_as_with(&sql, arguments)
+    }
+
+    /// Build a query that returns the first column of each row.
+    pub fn build_query_scalar<O>(&mut self) -> QueryScalar<O, Arguments>
+    where
+        (O,): for<'r> FromRow<'r>,
+    {
+        let (sql, arguments) = self.take();
+        query_scalar_with(&sql, arguments)
+    }
+
+    fn take(&mut self) -> (String, Arguments) {
+        (
+            std::mem::take(&mut self.sql),
+            std::mem::take(&mut self.arguments),
+        )
+    }
+}
+
+/// A list being pushed onto a [`QueryBuilder`], returned by [`QueryBuilder::separated`]. Each item after the first is
+/// preceded by the separator; the `_unseparated` methods continue the current item instead.
+#[derive(Debug)]
+pub struct Separated<'qb, 'sep> {
+    builder: &'qb mut QueryBuilder,
+    separator: &'sep str,
+    first: bool,
+}
+
+impl Separated<'_, '_> {
+    fn separate(&mut self) {
+        if !self.first {
+            self.builder.push(self.separator);
+        }
+        self.first = false;
+    }
+
+    /// Start an item with SQL text. Never push untrusted input this way; bind it with
+    /// [`push_bind`](Self::push_bind).
+    pub fn push(&mut self, sql: impl AsRef<str>) -> &mut Self {
+        self.separate();
+        self.builder.push(sql);
+        self
+    }
+
+    /// Append SQL text to the current item.
+    pub fn push_unseparated(&mut self, sql: impl AsRef<str>) -> &mut Self {
+        self.builder.push(sql);
+        self
+    }
+
+    /// Start an item with a `?` placeholder, and bind `value` to it.
+    pub fn push_bind(&mut self, value: impl Encode) -> &mut Self {
+        self.separate();
+        self.builder.push_bind(value);
+        self
+    }
+
+    /// Append a `?` placeholder to the current item, and bind `value` to it.
+    pub fn push_bind_unseparated(&mut self, value: impl Encode) -> &mut Self {
+        self.builder.push_bind(value);
+        self
+    }
+
+    /// Start an item with a [`Fragment`], binding its values.
+    pub fn push_fragment(&mut self, fragment: &Fragment) -> &mut Self {
+        self.separate();
+        self.builder.push_fragment(fragment);
+        self
+    }
+
+    /// Whether no item has been pushed yet.
+    pub fn is_empty(&self) -> bool {
+        self.first
+    }
+}
+
+impl From<Fragment> for QueryBuilder {
+    fn from(fragment: Fragment) -> Self {
+        Self {
+            sql: fragment.sql,
+            arguments: Arguments {
+                values: fragment.values,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// The direction of a term of a [`SortSpec`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl FromStr for SortDirection {
+    type Err = Error;
+
+    /// Parse `asc` or `desc`, ignoring case.
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("asc") {
+            Ok(Self::Asc)
+        } else if s.eq_ignore_ascii_case("desc") {
+            Ok(Self::Desc)
+        } else {
+            Err(Error::Protocol(format!("invalid sort direction: {s}")))
+        }
+    }
+}
+
+/// A sort order built from untrusted input, such as a request parameter, rendered with
+/// [`QueryBuilder::push_order_by`]. Column names are only accepted if they are in an allowlist, so that the input can't
+/// inject SQL or sort by columns that shouldn't be exposed.
+#[derive(Debug, Clone, Default)]
+pub struct SortSpec {
+    allowed: Vec<String>,
+    terms: Vec<(String, SortDirection)>,
+}
+
+impl SortSpec {
+    /// A sort order that may use the given columns. Dotted names such as `t.created` are allowed, and are quoted part
+    /// by part when rendered.
+    pub fn new<I>(allowed: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            allowed: allowed.into_iter().map(Into::into).collect(),
+            terms: Vec::new(),
+        }
+    }
+
+    /// A sort order that may use the columns of the [`Table`] type `T`.
+    pub fn for_table<T: Table>() -> Self {
+        Self::new(T::columns().into_iter().map(|c| c.name))
+    }
+
+    /// Add a term, sorting by `column` in `direction`. Column names are matched ignoring ASCII case, as SQLite does.
+    ///
+    /// Fails with [`Error::ColumnNotFound`] if the column isn't allowed.
+    pub fn by(mut self, column: &str, direction: SortDirection) -> Result<Self> {
+        let column = self
+            .allowed
+            .iter()
+            .find(|c| c.eq_ignore_ascii_case(column))
+            .ok_or_else(|| Error::ColumnNotFound(column.to_string()))?
+            .clone();
+        self.terms.push((column, direction));
+        Ok(self)
+    }
+
+    /// Add the terms of a comma-separated sort order, such as `-created, name` or `created desc, name asc`. A leading
+    /// `-` sorts a column in descending order, as does a trailing `desc`. Empty terms are ignored.
+    ///
+    /// Fails with [`Error::ColumnNotFound`] if a column isn't allowed, and [`Error::Protocol`] if a term is malformed.
+    pub fn parse(mut self, spec: &str) -> Result<Self> {
+        for term in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let invalid = || Error::Protocol(format!("invalid sort term: {term}"));
+            let words: Vec<&str> = term.split_whitespace().collect();
+            let (column, direction) = match words.as_slice() {
+                [column] => match column.strip_prefix('-') {
+                    Some(column) => (column, SortDirection::Desc),
+                    None => (*column, SortDirection::Asc),
+                },
+                [column, direction] if !column.starts_with('-') => (*column, direction.parse()?),
+                _ => return Err(invalid()),
+            };
+            if column.is_empty() {
+                return Err(invalid());
+            }
+            self = self.by(column, direction)?;
+        }
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
@@ -1,6 +1,9 @@
+use std::time::Duration;
+
 use either::Either;
 use futures_core::stream::BoxStream;
 use futures_util::{StreamExt, TryFutureExt, TryStreamExt};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     encode::Encode,
@@ -8,7 +11,7 @@ use crate::{
     executor::{Execute, Executor},
     from_row::FromRow,
     query_as::{query_as, query_as_with, query_statement_as, query_statement_as_with, QueryAs},
-    Arguments, IntoArguments, QueryResult, Statement,
+    Arguments, IntoArguments, Pool, QueryResult, ResultLimits, Statement,
 };
 
 /// Raw SQL query with bind parameters, mapped to a concrete type using [`FromRow`] on `(O,)`.
@@ -33,6 +36,22 @@ where
     fn take_arguments(&mut self) -> Option<Arguments> {
         self.inner.take_arguments()
     }
+
+    fn limits(&self) -> ResultLimits {
+        self.inner.limits()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Execute::timeout(&self.inner)
+    }
+
+    fn cancellation(&self) -> Option<CancellationToken> {
+        self.inner.cancellation()
+    }
+
+    fn row_buffer_size(&self) -> Option<usize> {
+        Execute::row_buffer_size(&self.inner)
+    }
 }
 
 impl<'q, O> QueryScalar<O, Arguments> {
@@ -43,6 +62,14 @@ impl<'q, O> QueryScalar<O, Arguments> {
         self.inner = self.inner.bind(value);
         self
     }
+
+    /// Bind a value to a named parameter.
+    ///
+    /// See [`Query::bind_named`](crate::query::Query::bind_named).
+    pub fn bind_named<T: 'q + Send + Encode>(mut self, name: &str, value: T) -> Self {
+        self.inner = self.inner.bind_named(name, value);
+        self
+    }
 }
 
 // FIXME: This is very close, nearly 1:1 with `Map`
@@ -53,6 +80,36 @@ where
     A: 'q + IntoArguments,
     (O,): Send + Unpin + for<'r> FromRow<'r>,
 {
+    /// See [`Query::max_rows`](crate::query::Query::max_rows).
+    pub fn max_rows(mut self, n: u64) -> Self {
+        self.inner = self.inner.max_rows(n);
+        self
+    }
+
+    /// See [`Query::max_result_bytes`](crate::query::Query::max_result_bytes).
+    pub fn max_result_bytes(mut self, n: u64) -> Self {
+        self.inner = self.inner.max_result_bytes(n);
+        self
+    }
+
+    /// See [`Query::timeout`](crate::query::Query::timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
+    }
+
+    /// See [`Query::with_cancellation`](crate::query::Query::with_cancellation).
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.inner = self.inner.with_cancellation(token);
+        self
+    }
+
+    /// See [`Query::row_buffer_size`](crate::query::Query::row_buffer_size).
+    pub fn row_buffer_size(mut self, size: usize) -> Self {
+        self.inner = self.inner.row_buffer_size(size);
+        self
+    }
+
     /// Execute the query and return the generated results as a stream.
 
     pub fn fetch<'e, 'c: 'e, E>(self, executor: E) -> BoxStream<'e, Result<O, Error>>
@@ -84,6 +141,16 @@ where
             .boxed()
     }
 
+    /// Execute the query on a connection from `pool`, and return the generated results as a `'static` stream. See
+    /// [`Query::fetch_owned`](crate::query::Query::fetch_owned).
+    pub fn fetch_owned(self, pool: &Pool) -> BoxStream<'static, Result<O, Error>>
+    where
+        O: 'static,
+        A: 'static,
+    {
+        self.inner.fetch_owned(pool).map_ok(|it| it.0).boxed()
+    }
+
     /// Execute the query and return all the generated results, collected into a [`Vec`].
 
     pub async fn fetch_all<'e, 'c: 'e, E>(self, executor: E) -> Result<Vec<O>, Error>
@@ -0,0 +1,245 @@
+//! A serde [`Deserializer`] over the columns of a [`Row`].
+use serde::{
+    de::{
+        value::{Error as DeError, SeqDeserializer},
+        DeserializeSeed, Error as _, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+    },
+    forward_to_deserialize_any, Deserializer,
+};
+
+use crate::{Row, SqliteDataType, Value};
+
+/// Deserializes a row as a map from column names to values, or as a sequence of values for tuples.
+pub(super) struct RowDeserializer<'r>(pub(super) &'r Row);
+
+impl<'de> Deserializer<'de> for RowDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_map(Columns {
+            row: self.0,
+            index: 0,
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_seq(Columns {
+            row: self.0,
+            index: 0,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit
+        unit_struct map struct enum identifier ignored_any
+    }
+}
+
+struct Columns<'r> {
+    row: &'r Row,
+    index: usize,
+}
+
+impl<'de> Columns<'de> {
+    fn next_value<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, DeError> {
+        let value = &self.row.values[self.index];
+        let name = self.row.columns[self.index].name();
+        self.index += 1;
+        seed.deserialize(ValueDeserializer(value))
+            .map_err(|e| DeError::custom(format!("column {name}: {e}")))
+    }
+}
+
+impl<'de> MapAccess<'de> for Columns<'de> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        match self.row.columns.get(self.index) {
+            Some(column) => seed
+                .deserialize(column.name().into_deserializer())
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        self.next_value(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.row.values.len() - self.index)
+    }
+}
+
+impl<'de> SeqAccess<'de> for Columns<'de> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeError> {
+        if self.index >= self.row.values.len() {
+            return Ok(None);
+        }
+        self.next_value(seed).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.row.values.len() - self.index)
+    }
+}
+
+/// Deserializes a single value. Integers deserialize into booleans as SQLite stores them, text deserializes into maps,
+/// sequences and structs by parsing it as JSON, and blobs deserialize into sequences of bytes.
+struct ValueDeserializer<'r>(&'r Value);
+
+impl<'de> ValueDeserializer<'de> {
+    fn text(&self) -> Result<&'de str, DeError> {
+        self.0.text().map_err(DeError::custom)
+    }
+
+    /// Deserialize a compound type from JSON text, or a sequence from the bytes of a blob.
+    fn compound<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if self.0.is_null() {
+            return self.deserialize_any(visitor);
+        }
+        match self.0.type_info() {
+            SqliteDataType::Text => {
+                let mut de = serde_json::Deserializer::from_str(self.text()?);
+                de.deserialize_any(visitor).map_err(DeError::custom)
+            }
+            SqliteDataType::Blob => {
+                visitor.visit_seq(SeqDeserializer::new(self.0.blob().iter().copied()))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if self.0.is_null() {
+            return visitor.visit_unit();
+        }
+        match self.0.type_info() {
+            SqliteDataType::Int | SqliteDataType::Int64 | SqliteDataType::Bool => {
+                visitor.visit_i64(self.0.int64())
+            }
+            SqliteDataType::Float => visitor.visit_f64(self.0.double()),
+            SqliteDataType::Blob => visitor.visit_borrowed_bytes(self.0.blob()),
+            _ => visitor.visit_borrowed_str(self.text()?),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if self.0.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.0.type_info() {
+            SqliteDataType::Int | SqliteDataType::Int64 => visitor.visit_bool(self.0.int64() != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        if self.0.is_null() {
+            return self.deserialize_any(visitor);
+        }
+        // Unit variants are stored by name; other variants as externally tagged JSON
+        let text = self.text()?;
+        if text.trim_start().starts_with('{') {
+            let mut de = serde_json::Deserializer::from_str(text);
+            return de
+                .deserialize_enum(name, variants, visitor)
+                .map_err(DeError::custom);
+        }
+        visitor.visit_enum(text.into_deserializer())
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.compound(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.compound(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.compound(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.compound(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.compound(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit unit_struct
+        identifier ignored_any
+    }
+}
@@ -0,0 +1,170 @@
+//! Rows returned by queries.
+use std::{collections::HashMap, sync::Arc};
+
+use indexmap::IndexMap;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    decode::Decode,
+    error::Error,
+    sqlite::{statement::StatementHandle, Value},
+    ustr::UStr,
+    Column, DecodeError, Result,
+};
+
+mod de;
+
+/// Implementation of [`Row`] for SQLite.
+pub struct Row {
+    pub values: Box<[Value]>,
+    pub columns: Arc<Vec<Column>>,
+    pub(crate) column_names: Arc<HashMap<UStr, usize>>,
+}
+
+// Accessing values from the statement object is
+// safe across threads as long as we don't call [sqlite3_step]
+
+// we block ourselves from doing that by only exposing
+// a set interface on [StatementHandle]
+
+unsafe impl Send for Row {}
+unsafe impl Sync for Row {}
+
+impl Row {
+    pub(crate) fn current(
+        statement: &StatementHandle,
+        columns: &Arc<Vec<Column>>,
+        column_names: &Arc<HashMap<UStr, usize>>,
+    ) -> Self {
+        let size = statement.column_count();
+        let mut values = Vec::with_capacity(size);
+
+        for i in 0..size {
+            let raw = statement.column_value(i);
+            values.push(unsafe { Value::new(raw, columns[i].type_info) });
+        }
+
+        Self {
+            values: values.into_boxed_slice(),
+            columns: Arc::clone(columns),
+            column_names: Arc::clone(column_names),
+        }
+    }
+
+    /// Returns `true` if this row has no columns.
+    pub fn is_empty(&self) -> bool {
+        self.columns.len() == 0
+    }
+
+    /// The number of columns in the row.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The columns of the row, in order.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// The undecoded value of the column at `index`.
+    pub fn try_get_raw(&self, index: usize) -> Result<&Value> {
+        self.values.get(index).ok_or(Error::ColumnIndexOutOfBounds {
+            index,
+            len: self.values.len(),
+        })
+    }
+
+    /// Iterate over the columns of the row with their undecoded values, in order. This lets generic code, such as
+    /// an exporter, walk rows of any shape; [`Value::type_info`] gives the type of each value.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&Column, &Value)> + '_ {
+        self.columns.iter().zip(self.values.iter())
+    }
+
+    /// Convert the row into a map from column names to values, in column order. If several columns have the same
+    /// name, the value of the last one is kept, in the position of the first.
+    pub fn into_map(self) -> IndexMap<String, Value> {
+        self.columns
+            .iter()
+            .map(|column| column.name().to_string())
+            .zip(self.values.into_vec())
+            .collect()
+    }
+
+    /// The row as a JSON object from column names to values, with each value converted by [`Value::to_json`]. If
+    /// several columns have the same name, the value of the last one is kept.
+    pub fn to_json(&self) -> serde_json::Value {
+        self.iter()
+            .map(|(column, value)| (column.name().to_string(), value.to_json()))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+
+    /// Get a single value from the row by column index.
+    pub fn get_value_idx<'r, T>(&'r self, index: usize) -> Result<T>
+    where
+        T: Decode<'r>,
+    {
+        let value = self.try_get_raw(index)?;
+        T::decode(value).map_err(|source| Error::ColumnDecode {
+            index: format!("{:?}", index),
+            source: match source {
+                DecodeError::UnexpectedNull { field, .. } => DecodeError::UnexpectedNull {
+                    column: self.columns[index].name.to_string(),
+                    field,
+                },
+                source => source,
+            },
+        })
+    }
+
+    /// Get a single value from the row by column name.
+    pub fn get_value<'r, T>(&'r self, column: &str) -> Result<T>
+    where
+        T: Decode<'r>,
+    {
+        self.get_value_idx(
+            *self
+                .column_names
+                .get(column)
+                .ok_or_else(|| Error::ColumnNotFound(column.into()))?,
+        )
+    }
+
+    /// Get a JSON value from the row by column name, and deserialize it. The column may hold JSON text, or a blob
+    /// containing JSON text; SQLite's binary JSONB format must first be converted with `json()` in the query. A `NULL`
+    /// column deserializes as JSON `null`.
+    pub fn get_json<T>(&self, column: &str) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let value: Option<&[u8]> = self.get_value(column)?;
+        serde_json::from_slice(value.unwrap_or(b"null")).map_err(|e| Error::ColumnDecode {
+            index: format!("{column:?}"),
+            source: DecodeError::Conversion(e.to_string()),
+        })
+    }
+}
+
+/// Deserialize a row into any serde type, as an alternative to deriving [`FromRow`](crate::FromRow).
+///
+/// Structs and maps are filled by column name, and tuples and sequences by column position. Integer columns
+/// deserialize into `bool`, text columns into nested structs, maps and sequences by parsing them as JSON, and blob
+/// columns into byte sequences. As with [`Row::get_value`], `&str` fields borrow from the row.
+///
+/// ```rust,ignore
+/// #[derive(Deserialize)]
+/// struct User {
+///     id: i64,
+///     name: String,
+/// }
+///
+/// let row = query("SELECT id, name FROM users").fetch_one(&pool).await?;
+/// let user: User = row::from_row_serde(&row)?;
+/// ```
+pub fn from_row_serde<'r, T>(row: &'r Row) -> Result<T>
+where
+    T: Deserialize<'r>,
+{
+    T::deserialize(de::RowDeserializer(row))
+        .map_err(|e| Error::Decode(DecodeError::Conversion(e.to_string())))
+}
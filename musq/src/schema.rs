@@ -0,0 +1,1332 @@
+//! Inspect and compare the schemas of live databases.
+//!
+//! The main entry points are [`compare`] and [`compare_with`], which produce a structured report of the differences
+//! between two databases. This is useful for verifying replication or synchronisation implementations built on musq.
+//!
+//! [`validate_schema`] checks a live database against the tables expected by types deriving
+//! [`FromRow`](crate::FromRow) with a `#[musq(table = "...")]` attribute, so that mismatches are reported at startup
+//! rather than at the first query that trips over them.
+//!
+//! Types that also derive `Insert` implement [`Insert`], which writes values of the type as new rows.
+//!
+//! [`tables`], [`table_columns`], [`table_indexes`] and [`table_foreign_keys`] describe the tables of a live database,
+//! and are also available as methods on [`Connection`](crate::Connection). [`diff`] compares two
+//! [`SchemaSnapshot`]s, and generates the statements that migrate one schema to the other.
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hasher,
+};
+
+use futures_core::future::BoxFuture;
+use futures_util::TryStreamExt;
+
+use crate::{
+    pool::Pool, query_as, query_with, ArgumentValue, Arguments, Connection, Executor, Musq, Result,
+};
+
+/// An object recorded in the `sqlite_schema` table: a table, index, view or trigger.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SchemaObject {
+    /// The object type, one of `table`, `index`, `view` or `trigger`.
+    pub kind: String,
+    /// The name of the object.
+    pub name: String,
+    /// The table or view the object is associated with. For tables and views this is the object's own name.
+    pub table: String,
+    /// The SQL used to create the object. This is `None` for automatically created indexes.
+    pub sql: Option<String>,
+}
+
+impl SchemaObject {
+    /// Two objects are considered equivalent if their SQL matches, ignoring differences in whitespace.
+    fn same_definition(&self, other: &SchemaObject) -> bool {
+        self.sql.as_deref().map(normalize_sql) == other.sql.as_deref().map(normalize_sql)
+    }
+}
+
+/// Row-level differences for a single table.
+///
+/// Rows are identified by their primary key (or `rowid` for tables without one), rendered as SQL literals by SQLite's
+/// `quote()` function. Composite keys are joined with `", "`. Each list is sorted by the rendered keys, as text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RowDiff {
+    /// The name of the table.
+    pub table: String,
+    /// Keys of rows present only in the first database.
+    pub only_in_a: Vec<String>,
+    /// Keys of rows present only in the second database.
+    pub only_in_b: Vec<String>,
+    /// Keys of rows present in both databases with differing content.
+    pub changed: Vec<String>,
+}
+
+impl RowDiff {
+    /// Returns `true` if no row differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A structured report of the differences between two databases. Returned by [`compare`] and [`compare_with`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Comparison {
+    /// Schema objects present only in the first database.
+    pub only_in_a: Vec<SchemaObject>,
+    /// Schema objects present only in the second database.
+    pub only_in_b: Vec<SchemaObject>,
+    /// Schema objects present in both databases with differing definitions, as `(a, b)` pairs.
+    pub changed: Vec<(SchemaObject, SchemaObject)>,
+    /// Row-level differences for each table requested in [`CompareOptions`]. Tables with no differences are
+    /// omitted.
+    pub rows: Vec<RowDiff>,
+}
+
+impl Comparison {
+    /// Returns `true` if the databases are identical in every respect that was compared.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty()
+            && self.only_in_b.is_empty()
+            && self.changed.is_empty()
+            && self.rows.is_empty()
+    }
+}
+
+/// Options for [`compare_with`].
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    row_tables: Vec<String>,
+}
+
+impl CompareOptions {
+    /// Options that compare schemas only. This is the same as [`CompareOptions::default`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also compare the contents of `table` row-by-row.
+    ///
+    /// Rows are streamed from both databases in key order and matched up by primary key, so tables of any size can be
+    /// compared without loading them into memory. Row comparison is skipped for tables that are missing from either
+    /// database or whose definitions differ, since these are already reported as schema differences.
+    pub fn rows(mut self, table: &str) -> Self {
+        self.row_tables.push(table.into());
+        self
+    }
+}
+
+/// Compare the schemas of two databases.
+pub async fn compare(a: &Pool, b: &Pool) -> Result<Comparison> {
+    compare_with(a, b, &CompareOptions::default()).await
+}
+
+/// Compare the schemas of two databases, and optionally the contents of selected tables.
+pub async fn compare_with(a: &Pool, b: &Pool, options: &CompareOptions) -> Result<Comparison> {
+    let objects_a = schema_objects(a).await?;
+    let objects_b = schema_objects(b).await?;
+
+    let mut by_name_b: HashMap<(&str, &str), &SchemaObject> = objects_b
+        .iter()
+        .map(|o| ((o.kind.as_str(), o.name.as_str()), o))
+        .collect();
+
+    let mut cmp = Comparison::default();
+    let mut identical_tables = HashSet::new();
+
+    for obj in &objects_a {
+        match by_name_b.remove(&(obj.kind.as_str(), obj.name.as_str())) {
+            Some(other) if obj.same_definition(other) => {
+                if obj.kind == "table" {
+                    identical_tables.insert(obj.name.as_str());
+                }
+            }
+            Some(other) => cmp.changed.push((obj.clone(), other.clone())),
+            None => cmp.only_in_a.push(obj.clone()),
+        }
+    }
+    cmp.only_in_b = objects_b
+        .iter()
+        .filter(|o| by_name_b.contains_key(&(o.kind.as_str(), o.name.as_str())))
+        .cloned()
+        .collect();
+
+    for table in &options.row_tables {
+        if !identical_tables.contains(table.as_str()) {
+            continue;
+        }
+        let diff = compare_rows(a, b, table).await?;
+        if !diff.is_empty() {
+            cmp.rows.push(diff);
+        }
+    }
+
+    Ok(cmp)
+}
+
+/// List the user-defined objects in the main schema of a database, ordered by type and name.
+async fn schema_objects<'c, E>(executor: E) -> Result<Vec<SchemaObject>>
+where
+    E: Executor<'c>,
+{
+    let rows: Vec<(String, String, String, Option<String>)> = query_as(
+        "SELECT type, name, tbl_name, sql FROM main.sqlite_schema
+        WHERE name NOT LIKE 'sqlite_%'
+        ORDER BY type, name",
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(kind, name, table, sql)| SchemaObject {
+            kind,
+            name,
+            table,
+            sql,
+        })
+        .collect())
+}
+
+async fn compare_rows(a: &Pool, b: &Pool, table: &str) -> Result<RowDiff> {
+    // Order by the rendered key rather than the key itself, so that both sides arrive in an order Rust can follow:
+    // SQLite compares text with memcmp, as `str` does.
+    let select = format!(
+        "SELECT * FROM ({}) ORDER BY 1",
+        row_hash_query(a, table).await?
+    );
+    let mut rows_a = query_as::<(String, String)>(&select).fetch(a);
+    let mut rows_b = query_as::<(String, String)>(&select).fetch(b);
+
+    let mut diff = RowDiff {
+        table: table.into(),
+        ..Default::default()
+    };
+    let (mut next_a, mut next_b) = (rows_a.try_next().await?, rows_b.try_next().await?);
+    loop {
+        let order = match (&next_a, &next_b) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((key_a, _)), Some((key_b, _))) => key_a.cmp(key_b),
+        };
+        match (order, next_a.take(), next_b.take()) {
+            (Ordering::Less, Some((key, _)), row_b) => {
+                diff.only_in_a.push(key);
+                next_a = rows_a.try_next().await?;
+                next_b = row_b;
+            }
+            (Ordering::Greater, row_a, Some((key, _))) => {
+                diff.only_in_b.push(key);
+                next_a = row_a;
+                next_b = rows_b.try_next().await?;
+            }
+            (_, Some((key, content_a)), Some((_, content_b))) => {
+                if content_a != content_b {
+                    diff.changed.push(key);
+                }
+                next_a = rows_a.try_next().await?;
+                next_b = rows_b.try_next().await?;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Build a query returning `(key, content)` pairs for every row in `table`, ordered by key. Both the key and the
+/// content are rendered with `quote()`, which gives a canonical, type-preserving representation of each value.
+pub(crate) async fn row_hash_query<'c, E>(executor: E, table: &str) -> Result<String>
+where
+    E: Executor<'c>,
+{
+    let columns: Vec<(String, i64)> =
+        query_as("SELECT name, pk FROM pragma_table_info(?) ORDER BY cid")
+            .bind(table.to_string())
+            .fetch_all(executor)
+            .await?;
+
+    let mut pk: Vec<&(String, i64)> = columns.iter().filter(|(_, pk)| *pk > 0).collect();
+    pk.sort_by_key(|(_, pk)| *pk);
+    let key_columns: Vec<String> = if pk.is_empty() {
+        vec!["rowid".into()]
+    } else {
+        pk.iter().map(|(name, _)| quote_identifier(name)).collect()
+    };
+
+    let render = |cols: &[String]| {
+        cols.iter()
+            .map(|c| format!("quote({})", c))
+            .collect::<Vec<_>>()
+            .join(" || ', ' || ")
+    };
+    let content_columns: Vec<String> = columns
+        .iter()
+        .map(|(name, _)| quote_identifier(name))
+        .collect();
+
+    Ok(format!(
+        "SELECT {}, {} FROM {} ORDER BY {}",
+        render(&key_columns),
+        if content_columns.is_empty() {
+            "''".into()
+        } else {
+            render(&content_columns)
+        },
+        quote_identifier(table),
+        key_columns.join(", ")
+    ))
+}
+
+/// How a [generated column](https://www.sqlite.org/gencol.html) is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Generated {
+    /// Computed when the row is read.
+    Virtual,
+    /// Computed when the row is written, and stored in the table.
+    Stored,
+}
+
+/// A column of a live table, as returned by [`table_columns`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnInfo {
+    pub name: String,
+    /// The declared type, which is empty if the column was declared without one.
+    pub declared_type: String,
+    pub notnull: bool,
+    /// The column's position in the primary key, starting from 1, or 0 if it isn't part of it.
+    pub pk: i64,
+    /// The default value, as SQL text.
+    pub default: Option<String>,
+    /// How the column is generated, or `None` for an ordinary column.
+    pub generated: Option<Generated>,
+}
+
+impl ColumnInfo {
+    /// Whether the column can be given a value by `INSERT` or `UPDATE`. Generated columns can't.
+    pub fn is_insertable(&self) -> bool {
+        self.generated.is_none()
+    }
+}
+
+/// List the columns of `table` in declaration order, including generated columns, which `PRAGMA table_info` omits.
+pub async fn table_columns<'c, E>(executor: E, table: &str) -> Result<Vec<ColumnInfo>>
+where
+    E: Executor<'c>,
+{
+    let rows: Vec<(String, String, bool, Option<String>, i64, i64)> = query_as(
+        r#"SELECT name, type, "notnull", dflt_value, pk, hidden FROM pragma_table_xinfo(?)
+        WHERE hidden != 1 ORDER BY cid"#,
+    )
+    .bind(table.to_string())
+    .fetch_all(executor)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(name, declared_type, notnull, default, pk, hidden)| ColumnInfo {
+                name,
+                declared_type,
+                notnull,
+                pk,
+                default,
+                generated: match hidden {
+                    2 => Some(Generated::Virtual),
+                    3 => Some(Generated::Stored),
+                    _ => None,
+                },
+            },
+        )
+        .collect())
+}
+
+/// A table of a live database, as returned by [`tables`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableInfo {
+    pub name: String,
+    /// The SQL used to create the table.
+    pub sql: String,
+    /// Whether the table was declared `WITHOUT ROWID`.
+    pub without_rowid: bool,
+    /// Whether the table was declared [`STRICT`](https://www.sqlite.org/stricttables.html).
+    pub strict: bool,
+    /// Whether this is a [virtual table](https://www.sqlite.org/vtab.html).
+    pub virtual_table: bool,
+}
+
+/// List the tables in the main schema, ordered by name. SQLite's internal tables, and the shadow tables that back
+/// virtual tables such as FTS5 indexes, are omitted.
+pub async fn tables<'c, E>(executor: E) -> Result<Vec<TableInfo>>
+where
+    E: Executor<'c>,
+{
+    let rows: Vec<(String, String, String, bool, bool)> = query_as(
+        r#"SELECT s.name, s.sql, l.type, l.wr, l.strict FROM main.sqlite_schema s
+        JOIN pragma_table_list l ON l.schema = 'main' AND l.name = s.name
+        WHERE s.type = 'table' AND s.name NOT LIKE 'sqlite_%' AND l.type != 'shadow'
+        ORDER BY s.name"#,
+    )
+    .fetch_all(executor)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(name, sql, kind, without_rowid, strict)| TableInfo {
+            name,
+            sql,
+            without_rowid,
+            strict,
+            virtual_table: kind == "virtual",
+        })
+        .collect())
+}
+
+/// Why an index exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexOrigin {
+    /// Created with `CREATE INDEX`.
+    CreateIndex,
+    /// Created automatically for a `UNIQUE` constraint.
+    Unique,
+    /// Created automatically for a `PRIMARY KEY` constraint.
+    PrimaryKey,
+}
+
+/// An index on a live table, as returned by [`table_indexes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableIndex {
+    pub name: String,
+    pub unique: bool,
+    pub origin: IndexOrigin,
+    /// Whether the index has a `WHERE` clause.
+    pub partial: bool,
+    /// The indexed columns in order. Expressions are `None`.
+    pub columns: Vec<Option<String>>,
+}
+
+/// List the indexes on `table`, including those SQLite creates for `UNIQUE` and `PRIMARY KEY` constraints.
+pub async fn table_indexes<'c, E>(executor: E, table: &str) -> Result<Vec<TableIndex>>
+where
+    E: Executor<'c>,
+{
+    let rows: Vec<(String, bool, String, bool, Option<String>)> = query_as(
+        r#"SELECT l.name, l."unique", l.origin, l.partial, i.name
+        FROM pragma_index_list(?) l JOIN pragma_index_info(l.name) i
+        ORDER BY l.name, i.seqno"#,
+    )
+    .bind(table.to_string())
+    .fetch_all(executor)
+    .await?;
+    let mut indexes: Vec<TableIndex> = Vec::new();
+    for (name, unique, origin, partial, column) in rows {
+        match indexes.last_mut() {
+            Some(index) if index.name == name => index.columns.push(column),
+            _ => indexes.push(TableIndex {
+                name,
+                unique,
+                origin: match origin.as_str() {
+                    "u" => IndexOrigin::Unique,
+                    "pk" => IndexOrigin::PrimaryKey,
+                    _ => IndexOrigin::CreateIndex,
+                },
+                partial,
+                columns: vec![column],
+            }),
+        }
+    }
+    Ok(indexes)
+}
+
+/// A foreign key declared on a live table, as returned by [`table_foreign_keys`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyInfo {
+    /// The foreign key's index in the output of `PRAGMA foreign_key_list`, as reported in
+    /// [`FkViolation::fkid`](crate::foreign_keys::FkViolation::fkid).
+    pub id: i64,
+    /// The table the foreign key refers to.
+    pub parent: String,
+    /// The columns of the child table, in order.
+    pub from: Vec<String>,
+    /// The columns of the parent table that `from` refers to. These are `None` when the foreign key refers to the
+    /// parent's primary key without naming its columns.
+    pub to: Vec<Option<String>>,
+    /// The `ON UPDATE` action, such as `CASCADE` or `NO ACTION`.
+    pub on_update: String,
+    /// The `ON DELETE` action.
+    pub on_delete: String,
+}
+
+/// List the foreign keys declared on `table`, ordered by id.
+pub async fn table_foreign_keys<'c, E>(executor: E, table: &str) -> Result<Vec<ForeignKeyInfo>>
+where
+    E: Executor<'c>,
+{
+    let rows: Vec<(i64, String, String, Option<String>, String, String)> = query_as(
+        r#"SELECT id, "table", "from", "to", on_update, on_delete FROM pragma_foreign_key_list(?)
+        ORDER BY id, seq"#,
+    )
+    .bind(table.to_string())
+    .fetch_all(executor)
+    .await?;
+    let mut keys: Vec<ForeignKeyInfo> = Vec::new();
+    for (id, parent, from, to, on_update, on_delete) in rows {
+        match keys.last_mut() {
+            Some(key) if key.id == id => {
+                key.from.push(from);
+                key.to.push(to);
+            }
+            _ => keys.push(ForeignKeyInfo {
+                id,
+                parent,
+                from: vec![from],
+                to: vec![to],
+                on_update,
+                on_delete,
+            }),
+        }
+    }
+    Ok(keys)
+}
+
+/// SQLite's [column affinities](https://www.sqlite.org/datatype3.html#type_affinity).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Affinity {
+    Integer,
+    Real,
+    Text,
+    Blob,
+    Numeric,
+}
+
+impl Affinity {
+    /// The affinity SQLite assigns to a column with the declared type `declared`.
+    pub fn of_declared(declared: &str) -> Affinity {
+        let declared = declared.to_ascii_uppercase();
+        if declared.contains("INT") {
+            Affinity::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|t| declared.contains(t))
+        {
+            Affinity::Text
+        } else if declared.is_empty() || declared.contains("BLOB") {
+            Affinity::Blob
+        } else if ["REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|t| declared.contains(t))
+        {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    /// Whether a column with affinity `self` is suitable for values expected to have affinity `expected`. Columns
+    /// with `BLOB` affinity store values unchanged, so they suit anything.
+    fn accepts(self, expected: Affinity) -> bool {
+        self == expected
+            || self == Affinity::Blob
+            || (self == Affinity::Numeric && matches!(expected, Affinity::Integer | Affinity::Real))
+    }
+}
+
+/// A column expected by a [`Table`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSpec {
+    pub name: &'static str,
+    /// The affinity the column should have, or `None` if it isn't checked.
+    pub affinity: Option<Affinity>,
+    /// Whether the field accepts `NULL`. Non-nullable fields require a `NOT NULL` column.
+    pub nullable: bool,
+    /// Whether the column may be missing, because the field has a default.
+    pub optional: bool,
+}
+
+/// A type that maps onto a database table, for use with [`validate_schema`].
+///
+/// This is implemented by `#[derive(FromRow)]` for structs with a table attribute. Expected affinities are inferred
+/// from field types: integers and `bool` expect `INTEGER`, floats `REAL`, strings `TEXT`, and byte vectors `BLOB`.
+/// Other types are only checked for presence and nullability. Skipped, flattened and prefixed fields are ignored.
+///
+/// ```rust,ignore
+/// #[derive(FromRow)]
+/// #[musq(table = "users")]
+/// struct User {
+///     id: i64,
+///     name: String,
+///     email: Option<String>,
+/// }
+/// ```
+pub trait Table {
+    /// The name of the table.
+    const NAME: &'static str;
+
+    /// The columns read by the type.
+    fn columns() -> Vec<ColumnSpec>;
+}
+
+/// A [`Table`] type whose values can be inserted as rows, implemented by `#[derive(Insert)]`.
+///
+/// Every column of the table type is inserted, except a field marked `#[musq(rowid)]` that is `None`, which lets
+/// SQLite choose the rowid. After the insert, the rowid field is set to the rowid of the new row, so that callers get
+/// the new ID without a second query. The field must be an `Option<i64>`, and should be the table's `INTEGER PRIMARY
+/// KEY`, which is an alias for the rowid.
+///
+/// ```rust,ignore
+/// #[derive(FromRow, Insert)]
+/// #[musq(table = "users")]
+/// struct User {
+///     #[musq(rowid)]
+///     id: Option<i64>,
+///     name: String,
+/// }
+///
+/// let mut user = User { id: None, name: "alice".into() };
+/// user.insert(&pool).await?;
+/// assert!(user.id.is_some());
+/// ```
+pub trait Insert: Table {
+    /// The columns to insert and their values.
+    fn values(&self) -> Vec<(&'static str, ArgumentValue)>;
+
+    /// Record the rowid of the row the value was inserted as. Does nothing for types without a rowid field.
+    fn set_rowid(&mut self, rowid: i64);
+
+    /// Insert the value as a new row, and return its rowid.
+    fn insert<'e, 'c: 'e, E>(&'e mut self, executor: E) -> BoxFuture<'e, Result<i64>>
+    where
+        E: 'e + Executor<'c>,
+        Self: Send,
+    {
+        Box::pin(async move {
+            let values = self.values();
+            let columns = values
+                .iter()
+                .map(|(c, _)| quote_identifier(c))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let sql = if values.is_empty() {
+                format!(
+                    "INSERT INTO {} DEFAULT VALUES",
+                    quote_identifier(Self::NAME)
+                )
+            } else {
+                format!(
+                    "INSERT INTO {} ({columns}) VALUES ({})",
+                    quote_identifier(Self::NAME),
+                    vec!["?"; values.len()].join(", ")
+                )
+            };
+            let arguments = Arguments {
+                values: values.into_iter().map(|(_, v)| v).collect(),
+                ..Default::default()
+            };
+            let rowid = executor
+                .execute(query_with(&sql, arguments))
+                .await?
+                .last_insert_rowid();
+            self.set_rowid(rowid);
+            Ok(rowid)
+        })
+    }
+}
+
+/// A set of [`Table`]s: a single table type, or a tuple of them.
+pub trait Tables {
+    fn tables() -> Vec<(&'static str, Vec<ColumnSpec>)>;
+}
+
+impl<T: Table> Tables for T {
+    fn tables() -> Vec<(&'static str, Vec<ColumnSpec>)> {
+        vec![(T::NAME, T::columns())]
+    }
+}
+
+macro_rules! impl_tables_for_tuple {
+    ($($T:ident),+) => {
+        impl<$($T: Table),+> Tables for ($($T,)+) {
+            fn tables() -> Vec<(&'static str, Vec<ColumnSpec>)> {
+                vec![$(($T::NAME, $T::columns())),+]
+            }
+        }
+    };
+}
+
+impl_tables_for_tuple!(T1);
+impl_tables_for_tuple!(T1, T2);
+impl_tables_for_tuple!(T1, T2, T3);
+impl_tables_for_tuple!(T1, T2, T3, T4);
+impl_tables_for_tuple!(T1, T2, T3, T4, T5);
+impl_tables_for_tuple!(T1, T2, T3, T4, T5, T6);
+impl_tables_for_tuple!(T1, T2, T3, T4, T5, T6, T7);
+impl_tables_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8);
+impl_tables_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9);
+impl_tables_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10);
+impl_tables_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11);
+impl_tables_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12);
+
+/// A difference between the schema expected by a [`Table`] and the live database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The table doesn't exist.
+    MissingTable { table: String },
+    /// A column read by the type doesn't exist.
+    MissingColumn { table: String, column: String },
+    /// The column's declared type has an affinity that doesn't suit the field.
+    Affinity {
+        table: String,
+        column: String,
+        expected: Affinity,
+        declared: String,
+    },
+    /// The column allows `NULL`, but the field can't hold it.
+    Nullable { table: String, column: String },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::MissingTable { table } => write!(f, "table {table} does not exist"),
+            Mismatch::MissingColumn { table, column } => {
+                write!(f, "column {table}.{column} does not exist")
+            }
+            Mismatch::Affinity {
+                table,
+                column,
+                expected,
+                declared,
+            } => write!(
+                f,
+                "column {table}.{column} is declared as {declared:?}, expected {expected:?} affinity"
+            ),
+            Mismatch::Nullable { table, column } => write!(
+                f,
+                "column {table}.{column} allows NULL, but the field is not an Option"
+            ),
+        }
+    }
+}
+
+/// The result of [`validate_schema`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaReport {
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl SchemaReport {
+    /// Returns `true` if no mismatches were found.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for SchemaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for m in &self.mismatches {
+            writeln!(f, "{m}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the live schema against the tables expected by `T`, which is a [`Table`] type or a tuple of them.
+///
+/// ```rust,ignore
+/// let report = musq::validate_schema::<(User, Post)>(&pool).await?;
+/// if !report.is_ok() {
+///     panic!("schema mismatch:\n{report}");
+/// }
+/// ```
+pub async fn validate_schema<T: Tables>(pool: &Pool) -> Result<SchemaReport> {
+    let mut report = SchemaReport::default();
+    for (table, expected) in T::tables() {
+        let columns = table_columns(pool, table).await?;
+        if columns.is_empty() {
+            report.mismatches.push(Mismatch::MissingTable {
+                table: table.into(),
+            });
+            continue;
+        }
+        let columns: HashMap<&str, (&str, bool, i64)> = columns
+            .iter()
+            .map(|c| (c.name.as_str(), (c.declared_type.as_str(), c.notnull, c.pk)))
+            .collect();
+
+        for spec in expected {
+            let Some(&(declared, notnull, pk)) = columns.get(spec.name) else {
+                if !spec.optional {
+                    report.mismatches.push(Mismatch::MissingColumn {
+                        table: table.into(),
+                        column: spec.name.into(),
+                    });
+                }
+                continue;
+            };
+            if let Some(expected) = spec.affinity {
+                if !Affinity::of_declared(declared).accepts(expected) {
+                    report.mismatches.push(Mismatch::Affinity {
+                        table: table.into(),
+                        column: spec.name.into(),
+                        expected,
+                        declared: declared.into(),
+                    });
+                }
+            }
+            // An INTEGER PRIMARY KEY aliases the rowid, which can't be NULL
+            let rowid = pk > 0 && declared.eq_ignore_ascii_case("INTEGER");
+            if !spec.nullable && !notnull && !rowid {
+                report.mismatches.push(Mismatch::Nullable {
+                    table: table.into(),
+                    column: spec.name.into(),
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// A table and its columns, as captured in a [`SchemaSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSnapshot {
+    pub table: TableInfo,
+    pub columns: Vec<ColumnInfo>,
+}
+
+/// The schema of a database at a point in time, for use with [`diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaSnapshot {
+    /// The tables, ordered by name.
+    pub tables: Vec<TableSnapshot>,
+    /// The indexes, views and triggers created with SQL, in the order they were created. Indexes that SQLite creates
+    /// for `UNIQUE` and `PRIMARY KEY` constraints are part of their table's definition, and are omitted.
+    pub objects: Vec<SchemaObject>,
+}
+
+impl SchemaSnapshot {
+    /// Capture the schema of the main database of `conn`.
+    pub async fn capture(conn: &mut Connection) -> Result<Self> {
+        let mut snapshot = Self::default();
+        for table in tables(&mut *conn).await? {
+            let columns = table_columns(&mut *conn, &table.name).await?;
+            snapshot.tables.push(TableSnapshot { table, columns });
+        }
+        let rows: Vec<(String, String, String, String)> = query_as(
+            "SELECT type, name, tbl_name, sql FROM main.sqlite_schema
+            WHERE type != 'table' AND sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+            ORDER BY rowid",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        snapshot.objects = rows
+            .into_iter()
+            .map(|(kind, name, table, sql)| SchemaObject {
+                kind,
+                name,
+                table,
+                sql: Some(sql),
+            })
+            .collect();
+        Ok(snapshot)
+    }
+
+    /// Capture the schema that `sql`, a series of `CREATE` statements, produces in an empty database.
+    pub async fn from_sql(sql: &str) -> Result<Self> {
+        let pool = Musq::new().max_connections(1).open_in_memory().await?;
+        let mut conn = pool.acquire().await?;
+        crate::query(sql).execute(&mut *conn).await?;
+        Self::capture(&mut conn).await
+    }
+
+    fn table(&self, name: &str) -> Option<&TableSnapshot> {
+        self.tables.iter().find(|t| t.table.name == name)
+    }
+
+    fn object(&self, kind: &str, name: &str) -> Option<&SchemaObject> {
+        self.objects
+            .iter()
+            .find(|o| o.kind == kind && o.name == name)
+    }
+}
+
+/// What a [`DdlStatement`] does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DdlAction {
+    Create,
+    Drop,
+    /// Add a column to a table with `ALTER TABLE ... ADD COLUMN`.
+    AddColumn,
+    /// Rebuild a table with a new definition, copying its rows across. See
+    /// [Making Other Kinds Of Table Schema Changes](https://www.sqlite.org/lang_altertable.html#otheralter).
+    Rebuild,
+}
+
+/// A step in changing a schema, produced by [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DdlStatement {
+    pub action: DdlAction,
+    /// The type of the object, one of `table`, `index`, `view` or `trigger`.
+    pub kind: String,
+    pub name: String,
+    /// The SQL to execute. A rebuild consists of several statements, separated by semicolons.
+    pub sql: String,
+}
+
+impl DdlStatement {
+    fn new(action: DdlAction, kind: &str, name: &str, sql: String) -> Self {
+        Self {
+            action,
+            kind: kind.into(),
+            name: name.into(),
+            sql,
+        }
+    }
+}
+
+impl fmt::Display for DdlStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.sql)
+    }
+}
+
+/// How a table that exists in both schemas changes.
+enum TableChange<'a> {
+    /// Append these column definitions.
+    AddColumns(Vec<&'a str>),
+    /// Rebuild the table from this definition.
+    Rebuild(TableDefinition<'a>),
+    /// Drop the table and create it again.
+    Replace,
+}
+
+/// The parts of a `CREATE TABLE` statement.
+struct TableDefinition<'a> {
+    columns: Vec<&'a str>,
+    constraints: Vec<&'a str>,
+    /// Everything after the closing parenthesis, such as `WITHOUT ROWID`.
+    options: &'a str,
+}
+
+impl<'a> TableDefinition<'a> {
+    fn parse(sql: &'a str) -> Option<Self> {
+        let (parts, options) = split_definition(sql)?;
+        let (constraints, columns) = parts.into_iter().partition(|part| {
+            let word = first_word(part);
+            ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+                .iter()
+                .any(|k| k.eq_ignore_ascii_case(word))
+        });
+        Some(Self {
+            columns,
+            constraints,
+            options,
+        })
+    }
+}
+
+/// Compute the statements that change the schema of `live` into `desired`, in the order they must be executed.
+///
+/// A table that only gains columns `ALTER TABLE ... ADD COLUMN` can add is altered in place. Any other change to a table
+/// rebuilds it: a table with the new definition is created, the columns both definitions share are copied across, and
+/// it replaces the old table. Indexes and triggers on a rebuilt table are created again, and if any table is rebuilt,
+/// views are dropped and created again around the rebuild, because views that refer to a missing table stop the
+/// replacement table being renamed. A changed virtual table is dropped and created again, losing its contents.
+///
+/// With foreign keys enforced, dropping a table deletes or rejects rows that refer to it, so turn `PRAGMA foreign_keys`
+/// off before applying the statements, and check for violations before committing them:
+///
+/// ```rust,ignore
+/// let desired = SchemaSnapshot::from_sql(include_str!("schema.sql")).await?;
+/// let live = SchemaSnapshot::capture(&mut conn).await?;
+/// query("PRAGMA foreign_keys = OFF").execute(&mut conn).await?;
+/// let mut tx = conn.begin().await?;
+/// for statement in schema::diff(&live, &desired) {
+///     query(&statement.sql).execute(&mut tx).await?;
+/// }
+/// assert!(tx.foreign_key_violations().await?.is_empty());
+/// tx.commit().await?;
+/// query("PRAGMA foreign_keys = ON").execute(&mut conn).await?;
+/// ```
+pub fn diff(live: &SchemaSnapshot, desired: &SchemaSnapshot) -> Vec<DdlStatement> {
+    let mut changes = Vec::new();
+    for table in &live.tables {
+        if let Some(wanted) = desired.table(&table.table.name) {
+            if normalize_sql(&table.table.sql) != normalize_sql(&wanted.table.sql) {
+                changes.push((table, wanted, table_change(table, wanted)));
+            }
+        }
+    }
+    let rebuilt = changes
+        .iter()
+        .any(|(_, _, change)| matches!(change, TableChange::Rebuild(_)));
+
+    // Objects on tables that are dropped go with them
+    let mut gone: HashSet<&str> = live
+        .tables
+        .iter()
+        .map(|t| t.table.name.as_str())
+        .filter(|name| desired.table(name).is_none())
+        .collect();
+    gone.extend(
+        changes
+            .iter()
+            .filter(|(_, _, change)| !matches!(change, TableChange::AddColumns(_)))
+            .map(|(table, _, _)| table.table.name.as_str()),
+    );
+    let unchanged = |o: &SchemaObject| {
+        desired
+            .object(&o.kind, &o.name)
+            .is_some_and(|other| o.same_definition(other))
+    };
+    let dropped_views: HashSet<&str> = live
+        .objects
+        .iter()
+        .filter(|o| o.kind == "view" && (rebuilt || !unchanged(o)))
+        .map(|o| o.name.as_str())
+        .collect();
+    gone.extend(&dropped_views);
+    let kept: Vec<&SchemaObject> = live
+        .objects
+        .iter()
+        .filter(|o| {
+            !gone.contains(o.name.as_str()) && !gone.contains(o.table.as_str()) && unchanged(o)
+        })
+        .collect();
+    let is_kept = |o: &SchemaObject| kept.iter().any(|k| k.kind == o.kind && k.name == o.name);
+
+    let mut out = Vec::new();
+    for o in live.objects.iter().rev() {
+        if o.kind != "index"
+            && !is_kept(o)
+            && (o.kind == "view" || !gone.contains(o.table.as_str()))
+        {
+            let sql = format!(
+                "DROP {} {}",
+                o.kind.to_uppercase(),
+                quote_identifier(&o.name)
+            );
+            out.push(DdlStatement::new(DdlAction::Drop, &o.kind, &o.name, sql));
+        }
+    }
+    for o in &live.objects {
+        if o.kind == "index" && !is_kept(o) && !gone.contains(o.table.as_str()) {
+            let sql = format!("DROP INDEX {}", quote_identifier(&o.name));
+            out.push(DdlStatement::new(DdlAction::Drop, "index", &o.name, sql));
+        }
+    }
+    for table in &live.tables {
+        let name = &table.table.name;
+        let replaced = changes
+            .iter()
+            .any(|(t, _, change)| t.table.name == *name && matches!(change, TableChange::Replace));
+        if desired.table(name).is_none() || replaced {
+            let sql = format!("DROP TABLE {}", quote_identifier(name));
+            out.push(DdlStatement::new(DdlAction::Drop, "table", name, sql));
+        }
+    }
+
+    for table in &desired.tables {
+        let name = &table.table.name;
+        let replaced = changes
+            .iter()
+            .any(|(t, _, change)| t.table.name == *name && matches!(change, TableChange::Replace));
+        if live.table(name).is_none() || replaced {
+            out.push(DdlStatement::new(
+                DdlAction::Create,
+                "table",
+                name,
+                table.table.sql.clone(),
+            ));
+        }
+    }
+    for (table, wanted, change) in &changes {
+        let name = &table.table.name;
+        match change {
+            TableChange::AddColumns(columns) => {
+                for column in columns {
+                    let sql = format!(
+                        "ALTER TABLE {} ADD COLUMN {}",
+                        quote_identifier(name),
+                        column
+                    );
+                    out.push(DdlStatement::new(DdlAction::AddColumn, "table", name, sql));
+                }
+            }
+            TableChange::Rebuild(definition) => {
+                let sql = rebuild_sql(table, wanted, definition);
+                out.push(DdlStatement::new(DdlAction::Rebuild, "table", name, sql));
+            }
+            TableChange::Replace => {}
+        }
+    }
+
+    // Indexes first, then views and triggers in the order they were created, since triggers can be defined on views
+    let (indexes, others): (Vec<&SchemaObject>, Vec<&SchemaObject>) =
+        desired.objects.iter().partition(|o| o.kind == "index");
+    for o in indexes.into_iter().chain(others) {
+        if !is_kept(o) {
+            let sql = o.sql.clone().unwrap_or_default();
+            out.push(DdlStatement::new(DdlAction::Create, &o.kind, &o.name, sql));
+        }
+    }
+    out
+}
+
+fn table_change<'a>(live: &'a TableSnapshot, desired: &'a TableSnapshot) -> TableChange<'a> {
+    if live.table.virtual_table || desired.table.virtual_table {
+        return TableChange::Replace;
+    }
+    let Some(wanted) = TableDefinition::parse(&desired.table.sql) else {
+        return TableChange::Replace;
+    };
+    let Some(current) = TableDefinition::parse(&live.table.sql) else {
+        return TableChange::Rebuild(wanted);
+    };
+    let same = |a: &[&str], b: &[&str]| {
+        a.len() == b.len()
+            && a.iter()
+                .zip(b)
+                .all(|(a, b)| normalize_sql(a) == normalize_sql(b))
+    };
+    let n = current.columns.len();
+    let appended = wanted.columns.len() >= n
+        && wanted.columns.len() == desired.columns.len()
+        && same(&current.columns, &wanted.columns[..n])
+        && same(&current.constraints, &wanted.constraints)
+        && normalize_sql(current.options) == normalize_sql(wanted.options)
+        && wanted.columns[n..]
+            .iter()
+            .zip(&desired.columns[n..])
+            .all(|(column, info)| can_add_column(column, info));
+    if appended {
+        TableChange::AddColumns(wanted.columns[n..].to_vec())
+    } else {
+        TableChange::Rebuild(wanted)
+    }
+}
+
+/// Whether `ALTER TABLE ... ADD COLUMN` accepts the column definition. See the restrictions listed under
+/// [ALTER TABLE ADD COLUMN](https://www.sqlite.org/lang_altertable.html#altertabaddcol).
+fn can_add_column(column: &str, info: &ColumnInfo) -> bool {
+    let has_word = |keyword: &str| {
+        column
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .any(|word| word.eq_ignore_ascii_case(keyword))
+    };
+    let default = info
+        .default
+        .as_deref()
+        .filter(|d| !d.eq_ignore_ascii_case("NULL"));
+    let computed_default = default.is_some_and(|d| {
+        d.starts_with('(')
+            || d.get(..8)
+                .is_some_and(|p| p.eq_ignore_ascii_case("CURRENT_"))
+    });
+    let not_null = info.notnull && default.is_none();
+    let unsupported = info.pk != 0
+        || info.generated == Some(Generated::Stored)
+        || has_word("PRIMARY")
+        || has_word("UNIQUE")
+        || not_null
+        || computed_default
+        || (has_word("REFERENCES") && default.is_some());
+    !unsupported
+}
+
+/// The statements that rebuild `live` with the definition of `desired`, following the procedure in
+/// [Making Other Kinds Of Table Schema Changes](https://www.sqlite.org/lang_altertable.html#otheralter).
+fn rebuild_sql(
+    live: &TableSnapshot,
+    desired: &TableSnapshot,
+    definition: &TableDefinition,
+) -> String {
+    let name = quote_identifier(&desired.table.name);
+    let temp = quote_identifier(&format!("_musq_new_{}", desired.table.name));
+    let parts: Vec<&str> = definition
+        .columns
+        .iter()
+        .chain(&definition.constraints)
+        .map(|part| part.trim())
+        .collect();
+    let mut statements = vec![format!(
+        "CREATE TABLE {temp} ({}){}",
+        parts.join(", "),
+        definition.options
+    )];
+    let copied: Vec<String> = desired
+        .columns
+        .iter()
+        .filter(|c| {
+            c.is_insertable()
+                && live
+                    .columns
+                    .iter()
+                    .any(|l| l.is_insertable() && l.name == c.name)
+        })
+        .map(|c| quote_identifier(&c.name))
+        .collect();
+    if !copied.is_empty() {
+        let copied = copied.join(", ");
+        statements.push(format!(
+            "INSERT INTO {temp} ({copied}) SELECT {copied} FROM {name}"
+        ));
+    }
+    statements.push(format!("DROP TABLE {name}"));
+    statements.push(format!("ALTER TABLE {temp} RENAME TO {name}"));
+    statements.join(";\n")
+}
+
+/// Split the body of a `CREATE TABLE` statement into its column definitions and table constraints, returning them
+/// along with the text after the closing parenthesis. Quoted identifiers, strings and comments are skipped over.
+fn split_definition(sql: &str) -> Option<(Vec<&str>, &str)> {
+    let bytes = sql.as_bytes();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'' | b'`' | b'[') => {
+                let close = if quote == b'[' { b']' } else { quote };
+                i += 1;
+                while i < bytes.len() && bytes[i] != close {
+                    i += 1;
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'(' => {
+                depth += 1;
+                if depth == 1 {
+                    start = i + 1;
+                }
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    parts.push(sql[start..i].trim());
+                    return Some((parts, &sql[i + 1..]));
+                }
+            }
+            b',' if depth == 1 => {
+                parts.push(sql[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The first word of `sql` after any leading whitespace and comments, up to the first character that can't be part of
+/// an unquoted identifier. Empty if `sql` starts with a quoted identifier.
+fn first_word(mut sql: &str) -> &str {
+    loop {
+        sql = sql.trim_start();
+        if let Some(rest) = sql.strip_prefix("--") {
+            sql = rest.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(rest) = sql.strip_prefix("/*") {
+            sql = rest.split_once("*/").map_or("", |(_, rest)| rest);
+        } else {
+            break;
+        }
+    }
+    let end = sql
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(sql.len());
+    &sql[..end]
+}
+
+/// Quote an SQL identifier, escaping embedded double quotes.
+pub(crate) fn quote_identifier(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Collapse runs of whitespace so that formatting differences don't register as schema changes.
+fn normalize_sql(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The 64-bit FNV-1a hash. This is stable across platforms and releases, unlike the hashers in `std`.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ u64::from(*b)).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[test]
+fn test_fnv1a() {
+    let hash = |data: &[u8]| {
+        let mut hasher = Fnv1a::default();
+        hasher.write(data);
+        hasher.finish()
+    };
+    assert_eq!(hash(b""), 0xcbf29ce484222325);
+    assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+    assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+}
+
+#[test]
+fn test_split_definition() {
+    let (parts, options) = split_definition(
+        "CREATE TABLE \"a (b\" (x INTEGER DEFAULT (1 + 2), y TEXT CHECK (y != ','), -- z, w
+        PRIMARY KEY (x, y)) WITHOUT ROWID",
+    )
+    .unwrap();
+    assert_eq!(
+        parts,
+        vec![
+            "x INTEGER DEFAULT (1 + 2)",
+            "y TEXT CHECK (y != ',')",
+            "-- z, w\n        PRIMARY KEY (x, y)"
+        ]
+    );
+    assert_eq!(options, " WITHOUT ROWID");
+    assert!(split_definition("CREATE TABLE t").is_none());
+}
+
+#[test]
+fn test_table_definition() {
+    let definition = TableDefinition::parse(
+        "CREATE TABLE t (unique_code TEXT, primary_email TEXT, check_date, foreign_id, constraint_x,
+        \"unique\" TEXT, -- a comment
+        UNIQUE (unique_code), /* another */ CONSTRAINT pk PRIMARY KEY (foreign_id), check(check_date > 0))",
+    )
+    .unwrap();
+    assert_eq!(
+        definition.columns,
+        vec![
+            "unique_code TEXT",
+            "primary_email TEXT",
+            "check_date",
+            "foreign_id",
+            "constraint_x",
+            "\"unique\" TEXT"
+        ]
+    );
+    assert_eq!(definition.constraints.len(), 3);
+}
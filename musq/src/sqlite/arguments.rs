@@ -1,11 +1,11 @@
-use crate::{encode::Encode, sqlite::statement::StatementHandle, Error};
+use crate::{encode::Encode, sqlite::statement::StatementHandle, Error, SqliteDataType};
 
 use atoi::atoi;
 use libsqlite3_sys::SQLITE_OK;
 
 use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ArgumentValue {
     Null,
     Text(Arc<String>),
@@ -18,6 +18,8 @@ pub enum ArgumentValue {
 #[derive(Default, Debug)]
 pub struct Arguments {
     pub(crate) values: Vec<ArgumentValue>,
+    /// Values for named parameters, keyed by the parameter's name including its prefix, as in `:name`.
+    pub(crate) named: Vec<(String, ArgumentValue)>,
 }
 
 impl IntoArguments for Arguments {
@@ -34,31 +36,62 @@ impl Arguments {
         self.values.push(value.encode());
     }
 
-    pub(super) fn bind(&self, handle: &mut StatementHandle, offset: usize) -> Result<usize, Error> {
+    /// Add a value, converted to the storage class of `ty`, so that `typeof()` checks and `STRICT` tables see the
+    /// intended type: `add_typed("42", SqliteDataType::Int)` binds the integer 42, and `add_typed(5,
+    /// SqliteDataType::Text)` binds the text `'5'`.
+    ///
+    /// Numbers convert to and from text, and text to and from blobs. A value that can't be represented in the target
+    /// type, such as text that isn't a number for an integer, is added unchanged, so that type checks in the database
+    /// still reject it. `NULL` stays `NULL`: SQLite's `NULL` has no type, and binding it is the same for every `ty`.
+    pub fn add_typed<T>(&mut self, value: T, ty: SqliteDataType)
+    where
+        T: Encode,
+    {
+        self.values.push(value.encode().cast(ty));
+    }
+
+    /// Add a value for the named parameter `name`, which includes the parameter's prefix: `:name`, `@name` or
+    /// `$name`. Adding the same name again replaces the earlier value.
+    pub fn add_named<T>(&mut self, name: &str, value: T)
+    where
+        T: Encode,
+    {
+        let value = value.encode();
+        match self.named.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None => self.named.push((name.to_string(), value)),
+        }
+    }
+
+    /// Bind the arguments to one statement of a query. `offset` is the number of positional arguments used by the
+    /// earlier statements, and `used` records which named arguments have been bound so far.
+    pub(super) fn bind(
+        &self,
+        handle: &mut StatementHandle,
+        offset: usize,
+        used: &mut [bool],
+    ) -> Result<usize, Error> {
         let mut arg_i = offset;
-        // for handle in &statement.handles {
 
         let cnt = handle.bind_parameter_count();
 
         for param_i in 1..=cnt {
             // figure out the index of this bind parameter into our argument tuple
             let n: usize = if let Some(name) = handle.bind_parameter_name(param_i) {
-                if let Some(name) = name.strip_prefix('?') {
+                if let Some(n) = name.strip_prefix('?') {
                     // parameter should have the form ?NNN
-                    atoi(name.as_bytes()).expect("parameter of the form ?NNN")
-                } else if let Some(name) = name.strip_prefix('$') {
-                    // parameter should have the form $NNN
-                    atoi(name.as_bytes()).ok_or_else(|| {
-                        Error::Protocol(format!(
-                            "parameters with non-integer names are not currently supported: {}",
-                            name
-                        ))
-                    })?
+                    atoi(n.as_bytes()).expect("parameter of the form ?NNN")
+                } else if let Some(n) = name.strip_prefix('$').and_then(|n| atoi(n.as_bytes())) {
+                    // parameter of the form $NNN
+                    n
                 } else {
-                    return Err(Error::Protocol(format!(
-                        "unsupported SQL parameter format: {}",
-                        name
-                    )));
+                    // a named parameter, bound below
+                    if !self.named.iter().any(|(n, _)| n == name) {
+                        return Err(Error::Protocol(format!(
+                            "no value bound for named parameter {name}"
+                        )));
+                    }
+                    continue;
                 }
             } else {
                 arg_i += 1;
@@ -75,11 +108,65 @@ impl Arguments {
             self.values[n - 1].bind(handle, param_i)?;
         }
 
+        for ((name, value), used) in self.named.iter().zip(used) {
+            let param_i = handle.bind_parameter_index(name);
+            if param_i > 0 {
+                value.bind(handle, param_i)?;
+                *used = true;
+            }
+        }
+
         Ok(arg_i - offset)
     }
+
+    /// Fail if a named argument was not used by any statement of the query.
+    pub(crate) fn check_named_used(&self, used: &[bool]) -> Result<(), Error> {
+        match self.named.iter().zip(used).find(|(_, used)| !**used) {
+            Some(((name, _), _)) => Err(Error::Protocol(format!(
+                "named parameter {name} does not appear in the query"
+            ))),
+            None => Ok(()),
+        }
+    }
 }
 
 impl ArgumentValue {
+    /// Convert the value to the storage class of `ty`, if it can be represented there without loss.
+    fn cast(self, ty: SqliteDataType) -> Self {
+        use ArgumentValue::*;
+
+        let text = |v: &str| Text(Arc::new(v.to_string()));
+        match ty {
+            SqliteDataType::Int | SqliteDataType::Int64 | SqliteDataType::Bool => match &self {
+                Int(v) => Int64(i64::from(*v)),
+                Double(v) if v.fract() == 0.0 && v.abs() < 9.2e18 => Int64(*v as i64),
+                Text(v) => v.trim().parse().map(Int64).unwrap_or(self),
+                _ => self,
+            },
+            SqliteDataType::Float => match &self {
+                Int(v) => Double(f64::from(*v)),
+                Int64(v) => Double(*v as f64),
+                Text(v) => v.trim().parse().map(Double).unwrap_or(self),
+                _ => self,
+            },
+            SqliteDataType::Text
+            | SqliteDataType::Date
+            | SqliteDataType::Time
+            | SqliteDataType::Datetime => match &self {
+                Int(v) => text(&v.to_string()),
+                Int64(v) => text(&v.to_string()),
+                Double(v) => text(&format!("{v:?}")),
+                Blob(v) => std::str::from_utf8(v).map(text).unwrap_or(self),
+                _ => self,
+            },
+            SqliteDataType::Blob => match self.cast(SqliteDataType::Text) {
+                Text(v) => Blob(Arc::new(v.as_bytes().to_vec())),
+                other => other,
+            },
+            SqliteDataType::Null | SqliteDataType::Numeric => self,
+        }
+    }
+
     fn bind(&self, handle: &mut StatementHandle, i: usize) -> Result<(), Error> {
         use ArgumentValue::*;
 
@@ -0,0 +1,93 @@
+use std::{
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+/// What a connection is doing with its current query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueryState {
+    /// Compiling a statement of the query.
+    Preparing,
+    /// Running a statement inside SQLite.
+    Stepping,
+    /// Waiting for the caller to take a row from a full result channel.
+    Streaming,
+}
+
+impl QueryState {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            0 => Self::Preparing,
+            1 => Self::Stepping,
+            _ => Self::Streaming,
+        }
+    }
+}
+
+/// A query that was running on one of a pool's connections, returned by
+/// [`Pool::active_queries`](crate::Pool::active_queries).
+#[derive(Debug, Clone)]
+pub struct ActiveQuery {
+    /// The [id](super::Connection::id) of the connection running the query.
+    pub connection: u64,
+    /// The query's SQL, if the pool was opened with [`Musq::capture_query_sql`](crate::Musq::capture_query_sql).
+    pub sql: Option<String>,
+    /// When the connection started on the query.
+    pub started: Instant,
+    pub state: QueryState,
+}
+
+/// The query a connection's worker is currently running, shared with the pool so that it can be inspected from other
+/// threads.
+#[derive(Debug)]
+pub(crate) struct Activity {
+    connection: u64,
+    capture_sql: bool,
+    current: Mutex<Option<(Option<String>, Instant)>>,
+    state: AtomicU8,
+}
+
+impl Activity {
+    pub(crate) fn new(connection: u64, capture_sql: bool) -> Self {
+        Self {
+            connection,
+            capture_sql,
+            current: Mutex::new(None),
+            state: AtomicU8::new(QueryState::Preparing as u8),
+        }
+    }
+
+    /// Record that the worker started on `sql`.
+    pub(crate) fn start(&self, sql: &str) {
+        self.set_state(QueryState::Preparing);
+        if let Ok(mut current) = self.current.lock() {
+            *current = Some((self.capture_sql.then(|| sql.to_string()), Instant::now()));
+        }
+    }
+
+    pub(crate) fn set_state(&self, state: QueryState) {
+        self.state.store(state as u8, Ordering::Release);
+    }
+
+    /// Record that the worker finished its query.
+    pub(crate) fn finish(&self) {
+        if let Ok(mut current) = self.current.lock() {
+            *current = None;
+        }
+    }
+
+    /// The query the worker is running, if any.
+    pub(crate) fn snapshot(&self) -> Option<ActiveQuery> {
+        let current = self.current.lock().ok()?;
+        let (sql, started) = current.as_ref()?;
+        Some(ActiveQuery {
+            connection: self.connection,
+            sql: sql.clone(),
+            started: *started,
+            state: QueryState::from_u8(self.state.load(Ordering::Acquire)),
+        })
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    any::Any,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+
+use crate::error::Error;
+
+/// A panic raised by a user callback, waiting to be surfaced as [`Error::CallbackPanicked`].
+#[derive(Debug, Clone)]
+struct CallbackPanic {
+    callback: String,
+    message: String,
+}
+
+/// Records panics raised by user callbacks on a connection. Shared between the connection state and every callback
+/// registered on it, so that it can be written from inside SQLite without taking the connection lock.
+#[derive(Debug, Default)]
+pub(crate) struct CallbackPanics(Mutex<Option<CallbackPanic>>);
+
+impl CallbackPanics {
+    fn record(&self, panic: CallbackPanic) {
+        if let Ok(mut slot) = self.0.lock() {
+            slot.get_or_insert(panic);
+        }
+    }
+
+    /// Discard any recorded panic.
+    pub(crate) fn clear(&self) {
+        if let Ok(mut slot) = self.0.lock() {
+            slot.take();
+        }
+    }
+
+    fn take(&self) -> Option<Error> {
+        let CallbackPanic { callback, message } = self.0.lock().ok()?.take()?;
+        Some(Error::CallbackPanicked { callback, message })
+    }
+
+    /// If a callback panicked during the current operation, replace `err` with [`Error::CallbackPanicked`]. SQLite
+    /// only sees a failed callback, so the error it reports is not useful on its own.
+    pub(crate) fn map_err(&self, err: Error) -> Error {
+        self.take().unwrap_or(err)
+    }
+
+    /// Like [`map_err`](Self::map_err), but also fail a successful operation if a callback panicked during it. Hooks
+    /// whose return value SQLite ignores, such as the update and rollback hooks, can't make the operation fail, so
+    /// this is the only way their panics are reported.
+    pub(crate) fn check<T>(&self, res: Result<T, Error>) -> Result<T, Error> {
+        match (res, self.take()) {
+            (_, Some(err)) => Err(err),
+            (res, None) => res,
+        }
+    }
+}
+
+/// A panic-catching shim around a user callback invoked from SQLite.
+///
+/// Unwinding across the FFI boundary is undefined behaviour, so every user callback handed to SQLite must be called
+/// through this shim. If the callback panics, the panic is caught and recorded, and the callback is poisoned: it is
+/// never invoked again, and every later attempt to use it fails the same way until it is replaced.
+pub(crate) struct Callback<F> {
+    name: String,
+    callback: F,
+    poisoned: Option<String>,
+    panics: Arc<CallbackPanics>,
+}
+
+impl<F> Callback<F> {
+    pub(crate) fn new(name: impl Into<String>, callback: F, panics: Arc<CallbackPanics>) -> Self {
+        Self {
+            name: name.into(),
+            callback,
+            poisoned: None,
+            panics,
+        }
+    }
+
+    /// Invoke the callback through `f`. Returns `None` if the callback panicked, either now or on a previous call; the
+    /// caller should then report failure to SQLite.
+    pub(crate) fn call<R>(&mut self, f: impl FnOnce(&mut F) -> R) -> Option<R> {
+        if self.poisoned.is_none() {
+            let callback = &mut self.callback;
+            match catch_unwind(AssertUnwindSafe(|| f(callback))) {
+                Ok(r) => return Some(r),
+                Err(payload) => self.poisoned = Some(panic_message(payload.as_ref())),
+            }
+        }
+        self.panics.record(CallbackPanic {
+            callback: self.name.clone(),
+            message: self.poisoned.clone().unwrap_or_default(),
+        });
+        None
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
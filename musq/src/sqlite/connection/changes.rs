@@ -0,0 +1,220 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use libsqlite3_sys::sqlite3_get_autocommit;
+
+use crate::sqlite::connection::ConnectionHandle;
+
+/// Records which tables were modified by committed transactions, across all the connections of a pool with change
+/// tracking enabled.
+///
+/// Every commit that modifies at least one table bumps a global version number, and stamps each modified table with
+/// it. A reader that notes the version before running a query can later tell whether any table it read has changed
+/// since.
+///
+/// The tracker also counts the rows written to each table since it was last analyzed, so that statistics can be
+/// refreshed once enough of a table has changed.
+#[derive(Debug, Default)]
+pub(crate) struct ChangeTracker {
+    version: AtomicU64,
+    tables: Mutex<HashMap<String, u64>>,
+    churn: Mutex<HashMap<TableName, u64>>,
+}
+
+/// A table, qualified by the name of its schema.
+pub(crate) type TableName = (String, String);
+
+impl ChangeTracker {
+    /// The current version. This changes every time a transaction that modified data commits.
+    pub(crate) fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// The version at which `table` was last modified, or 0 if it hasn't been modified since tracking began.
+    pub(crate) fn table_version(&self, table: &str) -> u64 {
+        self.tables
+            .lock()
+            .ok()
+            .and_then(|t| t.get(table).copied())
+            .unwrap_or_default()
+    }
+
+    /// Tables with at least `threshold` rows written since they were last analyzed, along with the number of rows.
+    pub(crate) fn churned(&self, threshold: u64) -> Vec<(TableName, u64)> {
+        self.churn
+            .lock()
+            .map(|churn| {
+                churn
+                    .iter()
+                    .filter(|(_, rows)| **rows >= threshold)
+                    .map(|(table, rows)| (table.clone(), *rows))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Forget `rows` writes to `table`, once it has been analyzed. Writes made since the churn was read are kept.
+    pub(crate) fn analyzed(&self, table: &TableName, rows: u64) {
+        if let Ok(mut churn) = self.churn.lock() {
+            if let Some(count) = churn.get_mut(table) {
+                *count = count.saturating_sub(rows);
+                if *count == 0 {
+                    churn.remove(table);
+                }
+            }
+        }
+    }
+
+    fn record(&self, modified: HashMap<String, HashMap<String, u64>>) {
+        let modified: HashMap<TableName, u64> = modified
+            .into_iter()
+            .flat_map(|(db, tables)| {
+                tables
+                    .into_iter()
+                    .map(move |(table, rows)| ((db.clone(), table), rows))
+            })
+            .collect();
+        if let Ok(mut tables) = self.tables.lock() {
+            let version = self.version.fetch_add(1, Ordering::AcqRel) + 1;
+            for (_, table) in modified.keys() {
+                tables.insert(table.clone(), version);
+            }
+        }
+        if let Ok(mut churn) = self.churn.lock() {
+            for (table, rows) in modified {
+                // Temporary tables belong to a single connection, and can't be analyzed from the pool
+                if table.0 != "temp" && rows > 0 {
+                    *churn.entry(table).or_default() += rows;
+                }
+            }
+        }
+    }
+}
+
+/// The per-connection half of change tracking, which collects the tables modified by the current transaction from
+/// SQLite's update and rollback hooks, to be published to the [`ChangeTracker`] once it commits. The hooks themselves
+/// are installed by [`Hooks`](super::hooks::Hooks).
+///
+/// The update hook misses rows deleted wholesale by `DELETE FROM t` without a `WHERE` clause, and every row of a
+/// `WITHOUT ROWID` table. To cover them, an authorizer records the tables each statement may write when it is
+/// prepared, and those tables are marked as modified whenever the statement runs.
+pub(crate) struct ChangeHooks {
+    tracker: Arc<ChangeTracker>,
+    /// The number of rows written to each table by the current transaction, by schema and table name.
+    pending: Mutex<HashMap<String, HashMap<String, u64>>>,
+    /// The tables reported to the authorizer since the last statement was prepared.
+    prepared: Mutex<Vec<TableName>>,
+    /// The rows reported to the update hook since the current statement started.
+    statement_rows: AtomicU64,
+}
+
+impl ChangeHooks {
+    pub(crate) fn new(tracker: Arc<ChangeTracker>) -> Self {
+        Self {
+            tracker,
+            pending: Mutex::default(),
+            prepared: Mutex::default(),
+            statement_rows: AtomicU64::default(),
+        }
+    }
+
+    /// Called before a statement is prepared and before it starts running, to forget the tables reported while
+    /// preparing earlier statements and the rows they wrote.
+    pub(crate) fn preparing(&self) {
+        self.statement_rows.store(0, Ordering::Relaxed);
+        if let Ok(mut prepared) = self.prepared.lock() {
+            prepared.clear();
+        }
+    }
+
+    /// Called from the authorizer for every table a statement being prepared may write to, including from the
+    /// triggers and foreign key actions it fires.
+    pub(crate) fn authorized(&self, db: &str, table: &str) {
+        if let Ok(mut prepared) = self.prepared.lock() {
+            if !prepared.iter().any(|(d, t)| d == db && t == table) {
+                prepared.push((db.to_string(), table.to_string()));
+            }
+        }
+    }
+
+    /// Add the tables reported to the authorizer since [`preparing`](Self::preparing) to `writes`, the tables a
+    /// statement may write. Called once the statement is prepared, and again once it has run, since SQLite prepares
+    /// statements afresh when the schema changes.
+    pub(crate) fn prepared(&self, writes: &mut Vec<TableName>) {
+        if let Ok(mut prepared) = self.prepared.lock() {
+            for table in prepared.drain(..) {
+                if !writes.contains(&table) {
+                    writes.push(table);
+                }
+            }
+        }
+    }
+
+    /// Mark `writes`, the tables a statement that has just run may have written, as modified by the current
+    /// transaction. `changes` is the number of rows the statement changed directly, which is counted towards its table
+    /// if the update hook saw none of them and the statement writes no other table. This counts the rows in `WITHOUT
+    /// ROWID` tables and those deleted wholesale, but not those written by triggers.
+    pub(crate) fn finished(&self, writes: &[TableName], changes: u64) {
+        let hooked = self.statement_rows.swap(0, Ordering::Relaxed);
+        let unhooked = match writes {
+            // The schema tables are written by DDL, after which `changes` is left over from an earlier statement
+            [(_, table)] if hooked == 0 && !table.starts_with("sqlite_") => changes,
+            _ => 0,
+        };
+        if writes.is_empty() {
+            return;
+        }
+        if let Ok(mut pending) = self.pending.lock() {
+            for (db, table) in writes {
+                *pending
+                    .entry(db.clone())
+                    .or_default()
+                    .entry(table.clone())
+                    .or_default() += unhooked;
+            }
+        }
+    }
+
+    /// Publish the tables modified by the last transaction, if it has committed. Called after every statement and
+    /// explicit commit.
+    pub(crate) fn flush(&self, handle: &ConnectionHandle) {
+        if unsafe { sqlite3_get_autocommit(handle.as_ptr()) } == 0 {
+            return;
+        }
+        let modified = match self.pending.lock() {
+            Ok(mut pending) if !pending.is_empty() => std::mem::take(&mut *pending),
+            _ => return,
+        };
+        self.tracker.record(modified);
+    }
+
+    /// Called from the update hook for every row written.
+    pub(crate) fn updated(&self, db: &str, table: &str) {
+        self.statement_rows.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut pending) = self.pending.lock() {
+            // Avoid allocating for every row once the table has been seen
+            let tables = match pending.get_mut(db) {
+                Some(tables) => tables,
+                None => pending.entry(db.to_string()).or_default(),
+            };
+            match tables.get_mut(table) {
+                Some(rows) => *rows += 1,
+                None => {
+                    tables.insert(table.to_string(), 1);
+                }
+            }
+        }
+    }
+
+    /// Called from the rollback hook.
+    pub(crate) fn rolled_back(&self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.clear();
+        }
+    }
+}
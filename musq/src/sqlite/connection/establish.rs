@@ -2,7 +2,10 @@ use std::{
     ffi::CString,
     io,
     ptr::{null, null_mut},
-    sync::atomic::{AtomicU64, Ordering},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
@@ -13,11 +16,16 @@ use libsqlite3_sys::{
 };
 
 use crate::{
+    functions::Function,
+    musq::CommandSaturation,
     sqlite::{
-        connection::{handle::ConnectionHandle, ConnectionState, LogSettings, StatementCache},
+        connection::{
+            handle::ConnectionHandle, CallbackPanics, ChangeHooks, ChangeTracker, ConnectionState,
+            Hooks, Interrupt, LogSettings, Progress, StatementCache, Watchdog,
+        },
         SqliteError,
     },
-    Error, Musq,
+    Error, Musq, RetryPolicy,
 };
 
 static THREAD_ID: AtomicU64 = AtomicU64::new(0);
@@ -26,9 +34,18 @@ pub struct EstablishParams {
     filename: CString,
     open_flags: i32,
     busy_timeout: Duration,
+    retry_policy: RetryPolicy,
+    watchdog: Option<Watchdog>,
     log_settings: LogSettings,
+    change_tracker: Option<Arc<ChangeTracker>>,
+    functions: Vec<Function>,
+    extensions: Vec<(CString, Option<CString>)>,
+    pub(crate) id: u64,
+    pub(crate) capture_query_sql: bool,
+    pub(crate) on_command_buffer_saturated: Option<CommandSaturation>,
     pub(crate) thread_name: String,
     pub(crate) command_channel_size: usize,
+    pub(crate) row_channel_size: usize,
 }
 
 impl EstablishParams {
@@ -94,13 +111,40 @@ impl EstablishParams {
             )
         })?;
 
+        let extensions = options
+            .extensions
+            .iter()
+            .map(|(path, entry_point)| {
+                let path = path.to_str().and_then(|p| CString::new(p).ok());
+                let entry_point = entry_point.as_deref().map(CString::new).transpose();
+                match (path, entry_point) {
+                    (Some(path), Ok(entry_point)) => Ok((path, entry_point)),
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "extension paths and entry points must be valid UTF-8, without nul bytes",
+                    )),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        let id = THREAD_ID.fetch_add(1, Ordering::AcqRel);
+
         Ok(Self {
             filename,
             open_flags: flags,
             busy_timeout: options.busy_timeout,
+            retry_policy: options.retry_policy,
+            watchdog: options.watchdog.clone(),
             log_settings: options.log_settings.clone(),
-            thread_name: (options.thread_name)(THREAD_ID.fetch_add(1, Ordering::AcqRel)),
+            change_tracker: options.change_tracker.clone(),
+            functions: options.functions.clone(),
+            extensions,
+            id,
+            capture_query_sql: options.capture_query_sql,
+            on_command_buffer_saturated: options.on_command_buffer_saturated.clone(),
+            thread_name: (options.thread_name)(id),
             command_channel_size: options.command_channel_size,
+            row_channel_size: options.row_channel_size,
         })
     }
 
@@ -122,7 +166,7 @@ impl EstablishParams {
 
         // SAFE: tested for NULL just above
         // This allows any returns below to close this handle with RAII
-        let handle = unsafe { ConnectionHandle::new(handle) };
+        let mut handle = unsafe { ConnectionHandle::new(handle) };
 
         if status != SQLITE_OK {
             return Err(Error::Sqlite(SqliteError::new(handle.as_ptr())));
@@ -149,12 +193,39 @@ impl EstablishParams {
             return Err(Error::Sqlite(SqliteError::new(handle.as_ptr())));
         }
 
+        handle.set_retry_policy(self.retry_policy);
+
+        #[cfg(feature = "encryption")]
+        handle.reserve_encryption_bytes("main")?;
+
+        for (path, entry_point) in &self.extensions {
+            handle.load_extension(path, entry_point.as_deref())?;
+        }
+
+        let callback_panics = Arc::new(CallbackPanics::default());
+        for function in &self.functions {
+            function.register(handle.as_ptr(), &callback_panics)?;
+        }
+
+        let changes = self
+            .change_tracker
+            .as_ref()
+            .map(|tracker| ChangeHooks::new(tracker.clone()));
+        let hooks = Hooks::install(&handle, changes, callback_panics.clone());
+
+        let interrupt = Arc::new(Interrupt::new(handle.as_non_null_ptr()));
+        let progress =
+            Progress::install(&handle, self.watchdog.clone(), self.id, interrupt.clone());
+
         Ok(ConnectionState {
             handle,
             statements: StatementCache::new(),
             transaction_depth: 0,
             log_settings: self.log_settings.clone(),
-            progress_handler_callback: None,
+            progress,
+            callback_panics,
+            hooks,
+            interrupt,
         })
     }
 }
@@ -1,7 +1,9 @@
 use crate::{
     logger::QueryLogger,
     sqlite::{
-        connection::{ConnectionHandle, ConnectionState},
+        connection::{
+            Activity, ChangeHooks, ConnectionHandle, ConnectionState, Progress, QueryState,
+        },
         statement::{CompoundStatement, StatementHandle},
         Arguments,
     },
@@ -12,12 +14,19 @@ pub struct ExecuteIter<'a> {
     handle: &'a mut ConnectionHandle,
     statement: &'a mut CompoundStatement,
     logger: QueryLogger<'a>,
+    change_hooks: Option<&'a ChangeHooks>,
+    progress: &'a Progress,
+    activity: &'a Activity,
+    sql: &'a str,
     args: Option<Arguments>,
 
     /// since a `VirtualStatement` can encompass multiple actual statements,
     /// this keeps track of the number of arguments so far
     args_used: usize,
 
+    /// which named arguments have been bound by the statements so far
+    named_used: Vec<bool>,
+
     goto_next: bool,
 }
 
@@ -25,44 +34,116 @@ pub(crate) fn iter<'a>(
     conn: &'a mut ConnectionState,
     query: &'a str,
     args: Option<Arguments>,
+    activity: &'a Activity,
 ) -> Result<ExecuteIter<'a>, Error> {
     // fetch the cached statement or allocate a new one
     let statement = conn.statements.get(query)?;
 
     let logger = QueryLogger::new(query, conn.log_settings.clone());
 
+    let named_used = vec![false; args.as_ref().map_or(0, |a| a.named.len())];
+
     Ok(ExecuteIter {
         handle: &mut conn.handle,
         statement,
         logger,
+        change_hooks: conn.hooks.changes(),
+        progress: &conn.progress,
+        activity,
+        sql: query,
         args,
         args_used: 0,
+        named_used,
         goto_next: true,
     })
 }
 
+/// Run every statement of `query`, without arguments, and discard any rows. The statements are prepared afresh and
+/// finalized afterwards, rather than being added to the statement cache.
+pub(crate) fn execute_uncached(
+    conn: &mut ConnectionState,
+    query: &str,
+    activity: &Activity,
+) -> Result<QueryResult, Error> {
+    let mut statement = CompoundStatement::new(query)?;
+    let mut logger = QueryLogger::new(query, conn.log_settings.clone());
+    let mut result = QueryResult::default();
+    let mut run = || -> Result<(), Error> {
+        let hooks = conn.hooks.changes();
+        while let Some(prepared) = statement.prepare_next(&mut conn.handle, hooks)? {
+            conn.progress.start(query);
+            activity.set_state(QueryState::Stepping);
+            let step = loop {
+                match prepared.handle.step(conn.handle.retry_policy()) {
+                    Ok(true) => logger.increment_rows_returned(),
+                    Ok(false) => break Ok(()),
+                    Err(e) => break Err(e),
+                }
+            };
+            conn.progress.finish();
+            if let Some(hooks) = hooks {
+                let changes = if step.is_ok() {
+                    prepared.handle.changes()
+                } else {
+                    0
+                };
+                hooks.finished(prepared.writes, changes);
+            }
+            step?;
+            let changes = prepared.handle.changes();
+            logger.increase_rows_affected(changes);
+            result.extend([QueryResult {
+                changes,
+                last_insert_rowid: conn.handle.last_insert_rowid(),
+            }]);
+        }
+        Ok(())
+    };
+    let outcome = run();
+    if let Some(hooks) = conn.hooks.changes() {
+        hooks.flush(&conn.handle);
+    }
+    outcome.map(|_| result)
+}
+
 fn bind(
     statement: &mut StatementHandle,
     arguments: &Option<Arguments>,
     offset: usize,
+    named_used: &mut [bool],
 ) -> Result<usize, Error> {
     let mut n = 0;
 
     if let Some(arguments) = arguments {
-        n = arguments.bind(statement, offset)?;
+        n = arguments.bind(statement, offset, named_used)?;
     }
 
     Ok(n)
 }
 
+/// Fail if a named argument doesn't appear in any statement of the query.
+fn check_named_used(arguments: &Option<Arguments>, named_used: &[bool]) -> Result<(), Error> {
+    match arguments {
+        Some(arguments) => arguments.check_named_used(named_used),
+        None => Ok(()),
+    }
+}
+
 impl Iterator for ExecuteIter<'_> {
     type Item = Result<Either<QueryResult, Row>, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let first_step = self.goto_next;
         let statement = if self.goto_next {
-            let statement = match self.statement.prepare_next(self.handle) {
+            self.activity.set_state(QueryState::Preparing);
+            let statement = match self.statement.prepare_next(self.handle, self.change_hooks) {
                 Ok(Some(statement)) => statement,
-                Ok(None) => return None,
+                // The last statement couldn't be recognised as such if the query ends with a comment
+                Ok(None) => {
+                    return check_named_used(&self.args, &self.named_used)
+                        .err()
+                        .map(Err)
+                }
                 Err(e) => return Some(Err(e)),
             };
 
@@ -75,17 +156,57 @@ impl Iterator for ExecuteIter<'_> {
 
             statement.handle.clear_bindings();
 
-            match bind(statement.handle, &self.args, self.args_used) {
+            match bind(
+                statement.handle,
+                &self.args,
+                self.args_used,
+                &mut self.named_used,
+            ) {
                 Ok(args_used) => self.args_used += args_used,
                 Err(e) => return Some(Err(e)),
             }
 
+            // Check for unknown names before running the last statement, so that a single-statement query fails
+            // without side effects
+            if statement.is_last {
+                if let Err(e) = check_named_used(&self.args, &self.named_used) {
+                    return Some(Err(e));
+                }
+            }
+
+            self.progress.start(self.sql);
             statement
         } else {
             self.statement.current()?
         };
 
-        match statement.handle.step() {
+        self.activity.set_state(QueryState::Stepping);
+        if let (Some(hooks), true) = (self.change_hooks, first_step) {
+            hooks.preparing();
+        }
+        let step = statement.handle.step(self.handle.retry_policy());
+        if !matches!(step, Ok(true)) {
+            self.progress.finish();
+        }
+
+        // Publish changes before returning the outcome, so that callers can't observe a stale change tracker once
+        // they have seen their write succeed.
+        if let Some(hooks) = self.change_hooks {
+            // The first step prepares the statement afresh if the schema has changed
+            if first_step {
+                hooks.prepared(statement.writes);
+            }
+            if !matches!(step, Ok(true)) {
+                let changes = match step {
+                    Ok(_) => statement.handle.changes(),
+                    Err(_) => 0,
+                };
+                hooks.finished(statement.writes, changes);
+                hooks.flush(self.handle);
+            }
+        }
+
+        match step {
             Ok(true) => {
                 self.logger.increment_rows_returned();
 
@@ -117,6 +238,7 @@ impl Iterator for ExecuteIter<'_> {
 
 impl Drop for ExecuteIter<'_> {
     fn drop(&mut self) {
+        self.progress.finish();
         self.statement.reset().ok();
     }
 }
@@ -2,6 +2,7 @@ use futures_core::future::BoxFuture;
 use futures_core::stream::BoxStream;
 use futures_util::{TryFutureExt, TryStreamExt};
 
+use super::worker::QueryOptions;
 use crate::{
     error::Error,
     executor::{Execute, Executor},
@@ -19,11 +20,38 @@ impl<'c> Executor<'c> for &'c mut Connection {
         E: Execute + 'q,
     {
         let arguments = query.take_arguments();
+        let options = QueryOptions::of(&query);
+        let sql: String = query.sql().into();
+
+        #[cfg(feature = "tracing-spans")]
+        let span = query_span(&sql);
+        let stream = Box::pin(
+            self.worker
+                .execute(sql, arguments, options, self.row_channel_size)
+                .map_ok(flume::Receiver::into_stream)
+                .try_flatten_stream(),
+        );
+        #[cfg(feature = "tracing-spans")]
+        let stream = Box::pin(spans::Instrumented::new(stream, span));
+        stream
+    }
+
+    fn fetch_paged<'e, 'q: 'e, E>(
+        self,
+        mut query: E,
+        page_size: usize,
+    ) -> BoxStream<'e, Result<Vec<Row>, Error>>
+    where
+        'c: 'e,
+        E: Execute + 'q,
+    {
+        let arguments = query.take_arguments();
+        let options = QueryOptions::of(&query);
         let sql = query.sql().into();
 
         Box::pin(
             self.worker
-                .execute(sql, arguments, self.row_channel_size)
+                .execute_paged(sql, arguments, options, page_size, self.row_channel_size)
                 .map_ok(flume::Receiver::into_stream)
                 .try_flatten_stream(),
         )
@@ -38,12 +66,15 @@ impl<'c> Executor<'c> for &'c mut Connection {
         E: Execute + 'q,
     {
         let arguments = query.take_arguments();
+        let options = QueryOptions::of(&query);
         let sql = query.sql().to_string();
 
-        Box::pin(async move {
+        #[cfg(feature = "tracing-spans")]
+        let span = query_span(&sql);
+        let fetch = async move {
             let stream = self
                 .worker
-                .execute(sql, arguments, self.row_channel_size)
+                .execute(sql, arguments, options, self.row_channel_size)
                 .map_ok(flume::Receiver::into_stream)
                 .try_flatten_stream();
 
@@ -56,7 +87,10 @@ impl<'c> Executor<'c> for &'c mut Connection {
             }
 
             Ok(None)
-        })
+        };
+        #[cfg(feature = "tracing-spans")]
+        let fetch = tracing::Instrument::instrument(fetch, span);
+        Box::pin(fetch)
     }
 
     fn prepare_with<'e, 'q: 'e>(
@@ -77,3 +111,55 @@ impl<'c> Executor<'c> for &'c mut Connection {
         })
     }
 }
+
+#[cfg(feature = "tracing-spans")]
+fn query_span(sql: &str) -> tracing::Span {
+    tracing::info_span!("musq.query", sql = %sql, rows_affected = tracing::field::Empty)
+}
+
+#[cfg(feature = "tracing-spans")]
+mod spans {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures_core::Stream;
+
+    use crate::{Either, QueryResult, Result, Row};
+
+    /// A query stream that enters its span while it is polled, and records the rows affected so far.
+    pub(super) struct Instrumented<S> {
+        inner: S,
+        span: tracing::Span,
+        rows_affected: u64,
+    }
+
+    impl<S> Instrumented<S> {
+        pub(super) fn new(inner: S, span: tracing::Span) -> Self {
+            Self {
+                inner,
+                span,
+                rows_affected: 0,
+            }
+        }
+    }
+
+    impl<S> Stream for Instrumented<S>
+    where
+        S: Stream<Item = Result<Either<QueryResult, Row>>> + Unpin,
+    {
+        type Item = S::Item;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = &mut *self;
+            let _enter = this.span.enter();
+            let next = Pin::new(&mut this.inner).poll_next(cx);
+            if let Poll::Ready(Some(Ok(Either::Left(result)))) = &next {
+                this.rows_affected += result.rows_affected();
+                this.span.record("rows_affected", this.rows_affected);
+            }
+            next
+        }
+    }
+}
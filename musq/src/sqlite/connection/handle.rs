@@ -1,22 +1,31 @@
 use std::{
-    ffi::CString,
+    ffi::{CStr, CString},
+    os::raw::{c_int, c_uint},
     ptr::{self, NonNull},
+    thread,
 };
 
 use libsqlite3_sys::{
-    sqlite3, sqlite3_close, sqlite3_exec, sqlite3_last_insert_rowid, SQLITE_LOCKED_SHAREDCACHE,
-    SQLITE_OK,
+    sqlite3, sqlite3_close, sqlite3_db_cacheflush, sqlite3_db_config, sqlite3_deserialize,
+    sqlite3_exec, sqlite3_file, sqlite3_file_control, sqlite3_free, sqlite3_int64,
+    sqlite3_last_insert_rowid, sqlite3_load_extension, sqlite3_malloc64, sqlite3_serialize,
+    SQLITE_BUSY, SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION, SQLITE_DESERIALIZE_FREEONCLOSE,
+    SQLITE_DESERIALIZE_RESIZEABLE, SQLITE_FCNTL_FILE_POINTER, SQLITE_FCNTL_JOURNAL_POINTER,
+    SQLITE_LOCKED_SHAREDCACHE, SQLITE_OK, SQLITE_SYNC_FULL,
 };
 
 use crate::{
     sqlite::{statement::unlock_notify, SqliteError},
-    Error,
+    Error, RetryPolicy,
 };
 
 /// Managed handle to the raw SQLite3 database handle.
 /// The database handle will be closed when this is dropped and no `ConnectionHandleRef`s exist.
 #[derive(Debug)]
-pub(crate) struct ConnectionHandle(NonNull<sqlite3>);
+pub(crate) struct ConnectionHandle {
+    ptr: NonNull<sqlite3>,
+    retry: RetryPolicy,
+}
 
 // A SQLite3 handle is safe to send between threads, provided not more than
 // one is accessing it at the same time. This is upheld as long as [SQLITE_CONFIG_MULTITHREAD] is
@@ -31,15 +40,27 @@ unsafe impl Send for ConnectionHandle {}
 
 impl ConnectionHandle {
     pub(super) unsafe fn new(ptr: *mut sqlite3) -> Self {
-        Self(NonNull::new_unchecked(ptr))
+        Self {
+            ptr: NonNull::new_unchecked(ptr),
+            retry: RetryPolicy::none(),
+        }
+    }
+
+    pub(super) fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    /// How to retry statements that fail with `SQLITE_BUSY`.
+    pub(crate) fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
     }
 
     pub(crate) fn as_ptr(&self) -> *mut sqlite3 {
-        self.0.as_ptr()
+        self.ptr.as_ptr()
     }
 
     pub(crate) fn as_non_null_ptr(&self) -> NonNull<sqlite3> {
-        self.0
+        self.ptr
     }
 
     pub(crate) fn last_insert_rowid(&self) -> i64 {
@@ -52,6 +73,7 @@ impl ConnectionHandle {
         let query =
             CString::new(query).map_err(|_| Error::Protocol("query contains nul bytes".into()))?;
 
+        let mut attempt = 1;
         // SAFETY: we have exclusive access to the database handle
         unsafe {
             loop {
@@ -69,22 +91,203 @@ impl ConnectionHandle {
                 match status {
                     SQLITE_OK => return Ok(()),
                     SQLITE_LOCKED_SHAREDCACHE => unlock_notify::wait(self.as_ptr())?,
+                    // A busy BEGIN or COMMIT leaves the connection as it was, so it can be retried
+                    s if s & 0xff == SQLITE_BUSY && attempt < self.retry.max_attempts => {
+                        thread::sleep(self.retry.delay(attempt));
+                        attempt += 1;
+                    }
                     _ => return Err(SqliteError::new(self.as_ptr()).into()),
                 }
             }
         }
     }
+
+    /// Write dirty pages in the page cache to the database file. Returns `SQLITE_BUSY` if a page couldn't be written
+    /// because of a lock held by another connection.
+    pub(crate) fn cacheflush(&self) -> Result<(), Error> {
+        // SAFETY: we have exclusive access to the database handle
+        match unsafe { sqlite3_db_cacheflush(self.as_ptr()) } {
+            SQLITE_OK => Ok(()),
+            _ => Err(SqliteError::new(self.as_ptr()).into()),
+        }
+    }
+
+    /// Copy the database attached as `schema` into memory. See
+    /// [`sqlite3_serialize`](https://www.sqlite.org/c3ref/serialize.html).
+    pub(crate) fn serialize(&self, schema: &str) -> Result<Vec<u8>, Error> {
+        let schema = CString::new(schema)
+            .map_err(|_| Error::Protocol("schema name contains nul bytes".into()))?;
+        let mut size: sqlite3_int64 = 0;
+        // SAFETY: we have exclusive access to the database handle, and free the copy SQLite allocates once we have
+        // copied it
+        unsafe {
+            let data = sqlite3_serialize(self.as_ptr(), schema.as_ptr(), &mut size, 0);
+            if data.is_null() {
+                return Err(Error::Protocol(format!(
+                    "failed to serialize database {}",
+                    schema.to_string_lossy()
+                )));
+            }
+            let bytes = std::slice::from_raw_parts(data, size as usize).to_vec();
+            sqlite3_free(data.cast());
+            Ok(bytes)
+        }
+    }
+
+    /// Replace the database attached as `schema` with an in-memory copy of `data`. See
+    /// [`sqlite3_deserialize`](https://www.sqlite.org/c3ref/deserialize.html).
+    pub(crate) fn deserialize(&self, schema: &str, data: &[u8]) -> Result<(), Error> {
+        let schema = CString::new(schema)
+            .map_err(|_| Error::Protocol("schema name contains nul bytes".into()))?;
+        let size = data.len() as sqlite3_int64;
+        // SAFETY: we have exclusive access to the database handle. SQLite takes ownership of the buffer, and frees it
+        // when the database is closed or if deserializing fails.
+        let rc = unsafe {
+            let buf = sqlite3_malloc64(data.len().max(1) as u64).cast::<u8>();
+            if buf.is_null() {
+                return Err(Error::Protocol("out of memory".into()));
+            }
+            ptr::copy_nonoverlapping(data.as_ptr(), buf, data.len());
+            sqlite3_deserialize(
+                self.as_ptr(),
+                schema.as_ptr(),
+                buf,
+                size,
+                size,
+                (SQLITE_DESERIALIZE_FREEONCLOSE | SQLITE_DESERIALIZE_RESIZEABLE) as c_uint,
+            )
+        };
+        match rc {
+            SQLITE_OK => Ok(()),
+            _ => Err(SqliteError::new(self.as_ptr()).into()),
+        }
+    }
+
+    /// Load the extension in the shared library at `path`, calling `entry_point`, or the entry point SQLite derives
+    /// from the file name if `None`. See [`sqlite3_load_extension`](https://www.sqlite.org/c3ref/load_extension.html).
+    ///
+    /// Extension loading is enabled for the C API only while the extension loads, and never for the SQL
+    /// `load_extension()` function.
+    pub(crate) fn load_extension(
+        &self,
+        path: &CStr,
+        entry_point: Option<&CStr>,
+    ) -> Result<(), Error> {
+        let mut msg = ptr::null_mut();
+        // SAFETY: we have exclusive access to the database handle, and free the error message SQLite allocates once
+        // we have copied it
+        let (rc, msg) = unsafe {
+            sqlite3_db_config(
+                self.as_ptr(),
+                SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION,
+                1 as c_int,
+                ptr::null_mut::<c_int>(),
+            );
+            let rc = sqlite3_load_extension(
+                self.as_ptr(),
+                path.as_ptr(),
+                entry_point.map_or(ptr::null(), CStr::as_ptr),
+                &mut msg,
+            );
+            sqlite3_db_config(
+                self.as_ptr(),
+                SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION,
+                0 as c_int,
+                ptr::null_mut::<c_int>(),
+            );
+            let text = (!msg.is_null()).then(|| CStr::from_ptr(msg).to_string_lossy().into_owned());
+            sqlite3_free(msg.cast());
+            (rc, text)
+        };
+        match rc {
+            SQLITE_OK => Ok(()),
+            _ => Err(SqliteError::from_code(
+                rc,
+                msg.unwrap_or_else(|| {
+                    format!("failed to load extension {}", path.to_string_lossy())
+                }),
+            )
+            .into()),
+        }
+    }
+
+    /// Fsync the journal of the main database, if open, and then the database file itself. In WAL mode the journal is
+    /// the WAL.
+    pub(crate) fn sync_files(&self) -> Result<(), Error> {
+        for op in [SQLITE_FCNTL_JOURNAL_POINTER, SQLITE_FCNTL_FILE_POINTER] {
+            let mut file: *mut sqlite3_file = ptr::null_mut();
+            // SAFETY: we have exclusive access to the database handle, and the file pointers stay valid while we
+            // hold it
+            unsafe {
+                let status = sqlite3_file_control(
+                    self.as_ptr(),
+                    c"main".as_ptr(),
+                    op,
+                    (&mut file as *mut *mut sqlite3_file).cast(),
+                );
+                if status != SQLITE_OK {
+                    return Err(SqliteError::new(self.as_ptr()).into());
+                }
+                if file.is_null() || (*file).pMethods.is_null() {
+                    continue;
+                }
+                if let Some(sync) = (*(*file).pMethods).xSync {
+                    let status: c_int = sync(file, SQLITE_SYNC_FULL);
+                    if status != SQLITE_OK {
+                        return Err(SqliteError::from_code(status, "fsync failed").into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reserve room for the nonce and tag at the end of every page of the database attached as `schema`, if it was
+    /// opened through an encrypted VFS. This only takes effect when the database is created. See
+    /// [`encryption`](crate::encryption).
+    #[cfg(feature = "encryption")]
+    pub(crate) fn reserve_encryption_bytes(&self, schema: &str) -> Result<(), Error> {
+        let schema = CString::new(schema)
+            .map_err(|_| Error::Protocol("schema name contains nul bytes".into()))?;
+        let mut vfs: *mut libsqlite3_sys::sqlite3_vfs = ptr::null_mut();
+        // SAFETY: we have exclusive access to the database handle, and VFSs are never unregistered
+        unsafe {
+            let status = sqlite3_file_control(
+                self.as_ptr(),
+                schema.as_ptr(),
+                libsqlite3_sys::SQLITE_FCNTL_VFS_POINTER,
+                (&mut vfs as *mut *mut libsqlite3_sys::sqlite3_vfs).cast(),
+            );
+            if status != SQLITE_OK
+                || vfs.is_null()
+                || !crate::encryption::is_encrypted_vfs(CStr::from_ptr((*vfs).zName))
+            {
+                return Ok(());
+            }
+            let mut bytes = crate::encryption::RESERVED_BYTES as c_int;
+            let status = sqlite3_file_control(
+                self.as_ptr(),
+                schema.as_ptr(),
+                libsqlite3_sys::SQLITE_FCNTL_RESERVE_BYTES,
+                (&mut bytes as *mut c_int).cast(),
+            );
+            if status != SQLITE_OK {
+                return Err(SqliteError::new(self.as_ptr()).into());
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Drop for ConnectionHandle {
     fn drop(&mut self) {
         unsafe {
             // https://sqlite.org/c3ref/close.html
-            let status = sqlite3_close(self.0.as_ptr());
+            let status = sqlite3_close(self.ptr.as_ptr());
             if status != SQLITE_OK {
                 // this should *only* happen due to an internal bug in SQLite where we left
                 // SQLite handles open
-                panic!("{}", SqliteError::new(self.0.as_ptr()));
+                panic!("{}", SqliteError::new(self.ptr.as_ptr()));
             }
         }
     }
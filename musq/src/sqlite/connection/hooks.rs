@@ -0,0 +1,207 @@
+use std::{
+    ffi::CStr,
+    os::raw::{c_char, c_int, c_void},
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+use libsqlite3_sys::{
+    sqlite3_commit_hook, sqlite3_int64, sqlite3_rollback_hook, sqlite3_set_authorizer,
+    sqlite3_update_hook, SQLITE_DELETE, SQLITE_INSERT, SQLITE_OK, SQLITE_UPDATE,
+};
+
+use super::{Callback, CallbackPanics, ChangeHooks, ConnectionHandle};
+
+/// The kind of row change reported to an update hook, set with
+/// [`LockedSqliteHandle::set_update_hook`](super::LockedSqliteHandle::set_update_hook).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpdateOp {
+    Insert,
+    Update,
+    Delete,
+}
+
+type UpdateHook = dyn FnMut(UpdateOp, &str, &str, i64) + Send + 'static;
+type CommitHook = dyn FnMut() -> bool + Send + 'static;
+type RollbackHook = dyn FnMut() + Send + 'static;
+
+/// The update, commit and rollback hooks of a connection. SQLite allows only one of each per connection, so the hooks
+/// installed here dispatch both to change tracking and to the user's callbacks. Change tracking also installs an
+/// authorizer.
+///
+/// The hooks are called through a shared reference while a statement is stepping, so the callbacks live behind locks.
+pub(crate) struct Hooks {
+    changes: Option<ChangeHooks>,
+    panics: Arc<CallbackPanics>,
+    update: Mutex<Option<Callback<Box<UpdateHook>>>>,
+    commit: Mutex<Option<Callback<Box<CommitHook>>>>,
+    rollback: Mutex<Option<Callback<Box<RollbackHook>>>>,
+}
+
+impl Hooks {
+    /// Create the hooks for a connection. The returned box must outlive the hooks; call
+    /// [`uninstall`](Self::uninstall) before dropping it.
+    pub(crate) fn install(
+        handle: &ConnectionHandle,
+        changes: Option<ChangeHooks>,
+        panics: Arc<CallbackPanics>,
+    ) -> Box<Self> {
+        let hooks = Box::new(Self {
+            changes,
+            panics,
+            update: Mutex::default(),
+            commit: Mutex::default(),
+            rollback: Mutex::default(),
+        });
+        hooks.sync(handle);
+        hooks.reset_authorizer(handle);
+        hooks
+    }
+
+    /// Install the authorizer used by change tracking, replacing any other, or remove the authorizer if change
+    /// tracking is disabled.
+    pub(crate) fn reset_authorizer(&self, handle: &ConnectionHandle) {
+        let data = self as *const Self as *mut c_void;
+        unsafe {
+            match self.changes {
+                Some(_) => sqlite3_set_authorizer(handle.as_ptr(), Some(authorizer), data),
+                None => sqlite3_set_authorizer(handle.as_ptr(), None, ptr::null_mut()),
+            };
+        }
+    }
+
+    pub(crate) fn changes(&self) -> Option<&ChangeHooks> {
+        self.changes.as_ref()
+    }
+
+    pub(crate) fn set_update(&self, handle: &ConnectionHandle, hook: Option<Box<UpdateHook>>) {
+        if let Ok(mut slot) = self.update.lock() {
+            *slot = hook.map(|f| Callback::new("update hook", f, self.panics.clone()));
+        }
+        self.sync(handle);
+    }
+
+    pub(crate) fn set_commit(&self, handle: &ConnectionHandle, hook: Option<Box<CommitHook>>) {
+        if let Ok(mut slot) = self.commit.lock() {
+            *slot = hook.map(|f| Callback::new("commit hook", f, self.panics.clone()));
+        }
+        self.sync(handle);
+    }
+
+    pub(crate) fn set_rollback(&self, handle: &ConnectionHandle, hook: Option<Box<RollbackHook>>) {
+        if let Ok(mut slot) = self.rollback.lock() {
+            *slot = hook.map(|f| Callback::new("rollback hook", f, self.panics.clone()));
+        }
+        self.sync(handle);
+    }
+
+    /// Register with SQLite exactly the hooks that have something to dispatch to, so that connections without hooks
+    /// pay nothing for them.
+    fn sync(&self, handle: &ConnectionHandle) {
+        let is_set = |set: bool| set || self.changes.is_some();
+        let update = is_set(self.update.lock().is_ok_and(|h| h.is_some()));
+        let rollback = is_set(self.rollback.lock().is_ok_and(|h| h.is_some()));
+        let commit = self.commit.lock().is_ok_and(|h| h.is_some());
+        let data = self as *const Self as *mut c_void;
+        let data_if = |set: bool| if set { data } else { ptr::null_mut() };
+        unsafe {
+            sqlite3_update_hook(
+                handle.as_ptr(),
+                update.then_some(update_hook as _),
+                data_if(update),
+            );
+            sqlite3_rollback_hook(
+                handle.as_ptr(),
+                rollback.then_some(rollback_hook as _),
+                data_if(rollback),
+            );
+            sqlite3_commit_hook(
+                handle.as_ptr(),
+                commit.then_some(commit_hook as _),
+                data_if(commit),
+            );
+        }
+    }
+
+    pub(crate) fn uninstall(&self, handle: &ConnectionHandle) {
+        unsafe {
+            sqlite3_update_hook(handle.as_ptr(), None, ptr::null_mut());
+            sqlite3_rollback_hook(handle.as_ptr(), None, ptr::null_mut());
+            sqlite3_commit_hook(handle.as_ptr(), None, ptr::null_mut());
+            sqlite3_set_authorizer(handle.as_ptr(), None, ptr::null_mut());
+        }
+    }
+}
+
+extern "C" fn authorizer(
+    data: *mut c_void,
+    action: c_int,
+    table: *const c_char,
+    _column: *const c_char,
+    db: *const c_char,
+    _trigger: *const c_char,
+) -> c_int {
+    let hooks = unsafe { &*(data as *const Hooks) };
+    if let Some(changes) = &hooks.changes {
+        if matches!(action, SQLITE_INSERT | SQLITE_UPDATE | SQLITE_DELETE)
+            && !table.is_null()
+            && !db.is_null()
+        {
+            let db = unsafe { CStr::from_ptr(db) }.to_string_lossy();
+            let table = unsafe { CStr::from_ptr(table) }.to_string_lossy();
+            changes.authorized(&db, &table);
+        }
+    }
+    SQLITE_OK
+}
+
+extern "C" fn update_hook(
+    data: *mut c_void,
+    op: c_int,
+    db: *const c_char,
+    table: *const c_char,
+    rowid: sqlite3_int64,
+) {
+    let hooks = unsafe { &*(data as *const Hooks) };
+    let db = unsafe { CStr::from_ptr(db) }.to_string_lossy();
+    let table = unsafe { CStr::from_ptr(table) }.to_string_lossy();
+    if let Some(changes) = &hooks.changes {
+        changes.updated(&db, &table);
+    }
+    if let Ok(mut hook) = hooks.update.lock() {
+        if let Some(hook) = hook.as_mut() {
+            let op = match op {
+                SQLITE_INSERT => UpdateOp::Insert,
+                SQLITE_DELETE => UpdateOp::Delete,
+                SQLITE_UPDATE => UpdateOp::Update,
+                _ => return,
+            };
+            hook.call(|f| f(op, &db, &table, rowid));
+        }
+    }
+}
+
+extern "C" fn commit_hook(data: *mut c_void) -> c_int {
+    let hooks = unsafe { &*(data as *const Hooks) };
+    let proceed = match hooks.commit.lock() {
+        Ok(mut hook) => match hook.as_mut() {
+            Some(hook) => hook.call(|f| f()).unwrap_or(false),
+            None => true,
+        },
+        Err(_) => true,
+    };
+    // A non-zero return turns the commit into a rollback
+    c_int::from(!proceed)
+}
+
+extern "C" fn rollback_hook(data: *mut c_void) {
+    let hooks = unsafe { &*(data as *const Hooks) };
+    if let Some(changes) = &hooks.changes {
+        changes.rolled_back();
+    }
+    if let Ok(mut hook) = hooks.rollback.lock() {
+        if let Some(hook) = hook.as_mut() {
+            hook.call(|f| f());
+        }
+    }
+}
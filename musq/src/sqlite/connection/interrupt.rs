@@ -0,0 +1,89 @@
+use std::{
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use libsqlite3_sys::{sqlite3, sqlite3_interrupt};
+
+use crate::{error::Error, sqlite::error::PrimaryErrCode};
+
+/// The state shared between a connection and its [`InterruptHandle`]s.
+#[derive(Debug)]
+pub(crate) struct Interrupt {
+    /// The database handle, or `None` once the connection has been closed.
+    db: Mutex<Option<NonNull<sqlite3>>>,
+    /// Whether an interrupt was requested during the current operation.
+    requested: AtomicBool,
+}
+
+// `sqlite3_interrupt` may be called from any thread, and the handle is only used while the lock guarantees it's open.
+unsafe impl Send for Interrupt {}
+unsafe impl Sync for Interrupt {}
+
+impl Interrupt {
+    pub(crate) fn new(db: NonNull<sqlite3>) -> Self {
+        Self {
+            db: Mutex::new(Some(db)),
+            requested: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn interrupt(&self) {
+        if let Ok(db) = self.db.lock() {
+            if let Some(db) = *db {
+                self.requested.store(true, Ordering::Release);
+                // SAFETY: the connection can't be closed while we hold the lock
+                unsafe { sqlite3_interrupt(db.as_ptr()) };
+            }
+        }
+    }
+
+    /// Forget any interrupt requested during a previous operation.
+    pub(crate) fn clear(&self) {
+        self.requested.store(false, Ordering::Release);
+    }
+
+    /// Called before the database handle is closed.
+    pub(crate) fn close(&self) {
+        if let Ok(mut db) = self.db.lock() {
+            db.take();
+        }
+    }
+
+    /// If `err` was caused by a requested interrupt, replace it with [`Error::Interrupted`]. Other interrupts, such as
+    /// those caused by a progress handler, are left as they are.
+    pub(crate) fn map_err(&self, err: Error) -> Error {
+        match err {
+            Error::Sqlite(e)
+                if e.primary == PrimaryErrCode::Interrupt
+                    && self.requested.load(Ordering::Acquire) =>
+            {
+                Error::Interrupted
+            }
+            err => err,
+        }
+    }
+}
+
+/// A handle for interrupting queries running on a [`Connection`](super::Connection), returned by
+/// [`Connection::interrupt_handle`](super::Connection::interrupt_handle).
+///
+/// The handle can be cloned and sent to other tasks or threads, which can then cancel a query while the connection
+/// itself is busy, for example while a `fetch_many` stream is being consumed. Using a handle after its connection has
+/// been closed does nothing.
+#[derive(Debug, Clone)]
+pub struct InterruptHandle(pub(crate) Arc<Interrupt>);
+
+impl InterruptHandle {
+    /// Interrupt the query currently running on the connection, which then fails with [`Error::Interrupted`]. See
+    /// [`sqlite3_interrupt`](https://www.sqlite.org/c3ref/interrupt.html).
+    ///
+    /// If no query is running, this has no effect. An interrupted write inside an explicit transaction may roll back
+    /// the whole transaction.
+    pub fn interrupt(&self) {
+        self.0.interrupt();
+    }
+}
@@ -1,32 +1,63 @@
 use std::{
     fmt::{self, Debug, Formatter, Write},
-    os::raw::{c_int, c_void},
-    panic::catch_unwind,
+    hash::Hasher,
+    path::Path,
     ptr::NonNull,
+    sync::Arc,
 };
 
+use bytes::Bytes;
 use futures_core::future::BoxFuture;
 use futures_intrusive::sync::MutexGuard;
-use futures_util::future;
-use libsqlite3_sys::{sqlite3, sqlite3_progress_handler};
+use futures_util::{future, TryStreamExt};
+use libsqlite3_sys::{sqlite3, sqlite3_get_autocommit};
+use tokio::io::{AsyncBufRead, AsyncWrite};
 
 use crate::{
+    backup::Backup,
+    blob::BlobReader,
+    describe::{self, Describe},
+    dump::{self, Restore},
     error::Error,
-    executor::Executor,
+    executor::{Execute, Executor},
+    explain::{self, QueryPlanNode},
+    foreign_keys::{self, FkViolation, Repair},
+    integrity::{self, IntegrityError},
     logger::LogSettings,
-    musq::{Musq, OptimizeOnClose},
+    musq::{Musq, OptimizeOnClose, ResetOnReturn},
+    schema,
     sqlite::connection::{establish::EstablishParams, worker::ConnectionWorker},
     statement_cache::StatementCache,
-    transaction::Transaction,
-    Result,
+    transaction::{ReadScope, Transaction, TransactionBehavior},
+    QueryResult, Result,
 };
 
+pub(crate) use activity::Activity;
+pub use activity::{ActiveQuery, QueryState};
+pub(crate) use callback::{Callback, CallbackPanics};
+pub(crate) use changes::{ChangeHooks, ChangeTracker, TableName};
 pub(crate) use handle::ConnectionHandle;
+pub(crate) use hooks::Hooks;
+pub use hooks::UpdateOp;
+pub(crate) use interrupt::Interrupt;
+pub use interrupt::InterruptHandle;
+pub(crate) use progress::{Progress, Watchdog};
+pub use progress::{SlowQuery, WatchdogAction};
+pub use temp_table::TempTable;
+use temp_table::TempTableState;
+pub(crate) use worker::WorkerSharedState;
+mod activity;
+mod callback;
+mod changes;
 pub(crate) mod establish;
 pub(crate) mod execute;
 
 mod executor;
 mod handle;
+mod hooks;
+mod interrupt;
+mod progress;
+mod temp_table;
 mod worker;
 
 /// A connection to an open [Sqlite] database.
@@ -40,20 +71,44 @@ mod worker;
 /// You can explicitly call [`.close()`][Self::close] to ensure the database is closed successfully
 /// or get an error otherwise.
 pub struct Connection {
+    id: u64,
     optimize_on_close: OptimizeOnClose,
     pub(crate) worker: ConnectionWorker,
     pub(crate) row_channel_size: usize,
+    /// Temporary tables created with `create_temp_table`, dropped when the connection returns to its pool.
+    temp_tables: Vec<Arc<TempTableState>>,
+    /// Whether a database was replaced with `deserialize`, in which case the connection is closed rather than
+    /// returned to its pool.
+    pub(crate) deserialized: bool,
+}
+
+/// A snapshot of how full a connection's buffers are, returned by [`Connection::queue_metrics`]. Use these to tune
+/// [`Musq::command_buffer_size`] and [`Musq::row_buffer_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueMetrics {
+    /// The [id](Connection::id) of the connection.
+    pub connection: u64,
+    /// The number of commands waiting for the connection's worker thread.
+    pub commands: usize,
+    pub command_capacity: usize,
+    /// The number of rows of the current query that the worker has produced but the caller hasn't yet received.
+    pub rows: usize,
+    /// The size of the current query's row buffer, or the connection's [`Musq::row_buffer_size`] when no query is
+    /// running. Queries may override the size with [`Query::row_buffer_size`](crate::query::Query::row_buffer_size).
+    pub row_capacity: usize,
+    /// The number of times the worker has waited for the caller to make room in a full row buffer, over the lifetime
+    /// of the connection. A count that grows quickly means that callers consume rows more slowly than SQLite produces
+    /// them, and a larger buffer lets the worker run further ahead.
+    pub row_stalls: u64,
 }
 
 pub struct LockedSqliteHandle<'a> {
     pub(crate) guard: MutexGuard<'a, ConnectionState>,
 }
 
-/// Represents a callback handler that will be shared with the underlying sqlite3 connection.
-pub(crate) struct Handler(NonNull<dyn FnMut() -> bool + Send + 'static>);
-unsafe impl Send for Handler {}
-
-pub(crate) struct ConnectionState {
+/// The state of a connection on its worker thread, passed to closures run with
+/// [`Connection::run_in_worker`].
+pub struct ConnectionState {
     pub(crate) handle: ConnectionHandle,
 
     // transaction status
@@ -63,26 +118,24 @@ pub(crate) struct ConnectionState {
 
     log_settings: LogSettings,
 
-    /// Stores the progress handler set on the current connection. If the handler returns `false`,
-    /// the query is interrupted.
-    progress_handler_callback: Option<Handler>,
-}
+    /// The progress handler, which enforces query timeouts and runs the watchdog and the user's progress handler. If
+    /// it returns `false`, the query is interrupted.
+    pub(crate) progress: Arc<Progress>,
 
-impl ConnectionState {
-    /// Drops the `progress_handler_callback` if it exists.
-    pub(crate) fn remove_progress_handler(&mut self) {
-        if let Some(mut handler) = self.progress_handler_callback.take() {
-            unsafe {
-                sqlite3_progress_handler(self.handle.as_ptr(), 0, None, std::ptr::null_mut());
-                let _ = { Box::from_raw(handler.0.as_mut()) };
-            }
-        }
-    }
+    /// Records panics raised by user callbacks registered on this connection.
+    pub(crate) callback_panics: Arc<CallbackPanics>,
+
+    /// The update, commit and rollback hooks, which feed the pool's change tracker and user callbacks.
+    pub(crate) hooks: Box<Hooks>,
+
+    /// Shared with the connection's [`InterruptHandle`]s.
+    pub(crate) interrupt: Arc<Interrupt>,
 }
 
 impl Debug for Connection {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("SqliteConnection")
+            .field("id", &self.id)
             .field("row_channel_size", &self.row_channel_size)
             .field("cached_statements_size", &self.cached_statements_size())
             .finish()
@@ -92,14 +145,41 @@ impl Debug for Connection {
 impl Connection {
     pub(crate) async fn establish(options: &Musq) -> Result<Self> {
         let params = EstablishParams::from_options(options)?;
+        let id = params.id;
         let worker = ConnectionWorker::establish(params).await?;
         Ok(Self {
+            id,
             optimize_on_close: options.optimize_on_close.clone(),
             worker,
             row_channel_size: options.row_channel_size,
+            temp_tables: Vec::new(),
+            deserialized: false,
         })
     }
 
+    /// A process-wide unique identifier for this connection. This is the same value that is passed to the
+    /// [`thread_name`](Musq::thread_name) generator.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Run `f` on the connection's worker thread, and return its result. Use this to make a batch of direct SQLite API
+    /// calls in one hop, without blocking the caller the way [`lock_handle`](Self::lock_handle) does. The statement
+    /// cache and the progress handler stay with the worker, and are available through the [`ConnectionState`].
+    ///
+    /// Commands queued behind `f` wait until it returns, so it shouldn't run for long. If `f` panics, the panic is
+    /// resumed in the caller, and the connection stays usable.
+    pub async fn run_in_worker<F, R>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut ConnectionState) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        match self.worker.run_in_worker(f).await? {
+            Ok(ret) => Ok(ret),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
     /// Lock the SQLite database handle out from the worker thread so direct SQLite API calls can
     /// be made safely.
     ///
@@ -110,6 +190,19 @@ impl Connection {
         Ok(LockedSqliteHandle { guard })
     }
 
+    /// Interrupt the query currently running on this connection, which then fails with [`Error::Interrupted`].
+    ///
+    /// Since running a query borrows the connection, this is mostly useful through an
+    /// [`interrupt_handle`](Self::interrupt_handle), which can be used from another task.
+    pub fn interrupt(&self) {
+        self.interrupt_handle().interrupt();
+    }
+
+    /// Get a handle that can interrupt queries running on this connection from another task or thread.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle(Arc::clone(&self.worker.shared.interrupt))
+    }
+
     /// Explicitly close this database connection.
     ///
     /// This notifies the database server that the connection is closing so that it can
@@ -159,6 +252,288 @@ impl Connection {
         Transaction::begin(self)
     }
 
+    /// Begin a new transaction with the given behavior, which controls when it takes its locks. If a transaction is
+    /// already active, this establishes a savepoint as [`begin`](Self::begin) does, and `behavior` is ignored: the
+    /// locks are those of the outer transaction.
+    pub fn begin_with(
+        &mut self,
+        behavior: TransactionBehavior,
+    ) -> BoxFuture<'_, Result<Transaction<'_>>> {
+        Transaction::begin_with(self, behavior)
+    }
+
+    /// Start a [`ReadScope`], which behaves like a nested transaction but only opens one when a query that could write
+    /// runs through it.
+    pub fn read_scope(&mut self) -> ReadScope<'_> {
+        ReadScope::new(self)
+    }
+
+    /// Compute a stable checksum of the contents of `table`.
+    ///
+    /// Rows are streamed in primary key order (or `rowid` order for tables without one) and every value is rendered
+    /// with SQLite's `quote()` function before hashing, so the checksum reflects value types as well as values. The
+    /// result is stable across platforms and releases, so it can be used to verify backups and replicas.
+    pub async fn table_checksum(&mut self, table: &str) -> Result<u64> {
+        let select = schema::row_hash_query(&mut *self, table).await?;
+        let mut hasher = schema::Fnv1a::default();
+        let mut rows = self.fetch(crate::query(&select));
+        while let Some(row) = rows.try_next().await? {
+            let content: &str = row.get_value_idx(1)?;
+            hasher.write(&(content.len() as u64).to_le_bytes());
+            hasher.write(content.as_bytes());
+        }
+        Ok(hasher.finish())
+    }
+
+    /// List the tables in the main schema. See [`schema::tables`].
+    pub async fn tables(&mut self) -> Result<Vec<schema::TableInfo>> {
+        schema::tables(self).await
+    }
+
+    /// List the columns of `table`. See [`schema::table_columns`].
+    pub async fn columns(&mut self, table: &str) -> Result<Vec<schema::ColumnInfo>> {
+        schema::table_columns(self, table).await
+    }
+
+    /// List the indexes on `table`. See [`schema::table_indexes`].
+    pub async fn indexes(&mut self, table: &str) -> Result<Vec<schema::TableIndex>> {
+        schema::table_indexes(self, table).await
+    }
+
+    /// List the foreign keys declared on `table`. See [`schema::table_foreign_keys`].
+    pub async fn foreign_keys(&mut self, table: &str) -> Result<Vec<schema::ForeignKeyInfo>> {
+        schema::table_foreign_keys(self, table).await
+    }
+
+    /// Create a temporary table with the given column definitions, e.g. `"id INTEGER PRIMARY KEY, v TEXT"`, and return
+    /// a guard that drops it. The table is given a unique name, available from [`TempTable::name`].
+    ///
+    /// Temporary tables are private to the connection that created them. Tables created this way are also dropped when
+    /// a pooled connection is returned to the pool, so they can't leak into later users of the connection.
+    pub async fn create_temp_table(&mut self, columns: &str) -> Result<TempTable> {
+        let state = Arc::new(TempTableState::new());
+        self.execute(crate::query(&format!(
+            "CREATE TEMP TABLE {} ({columns})",
+            state.name
+        )))
+        .await?;
+        self.temp_tables.retain(|t| !t.is_dropped());
+        self.temp_tables.push(Arc::clone(&state));
+        Ok(TempTable::new(state, self.worker.handle()))
+    }
+
+    /// Drop the temporary tables created with [`create_temp_table`](Self::create_temp_table) whose guards are still
+    /// alive.
+    pub(crate) async fn drop_temp_tables(&mut self) -> Result<()> {
+        for table in std::mem::take(&mut self.temp_tables) {
+            if table.claim() {
+                self.execute(crate::query(&format!(
+                    "DROP TABLE IF EXISTS temp.{}",
+                    table.name
+                )))
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether SQLite is outside of any transaction. A statement can end the transaction it runs in, for instance
+    /// through `RAISE(ROLLBACK)`, while musq still considers it open.
+    pub(crate) async fn is_autocommit(&mut self) -> Result<bool> {
+        self.worker
+            .run(|conn| unsafe { sqlite3_get_autocommit(conn.handle.as_ptr()) } != 0)
+            .await
+    }
+
+    /// Reset the connection's state according to `policy`, before handing it to another user. `pragmas` are the
+    /// configured pragmas, as returned by [`Musq::pragma_string`].
+    pub(crate) async fn reset(&mut self, policy: ResetOnReturn, pragmas: &str) -> Result<()> {
+        self.drop_temp_tables().await?;
+        if policy.rollback || policy.authorizer {
+            self.worker
+                .run(move |conn| {
+                    if policy.authorizer {
+                        conn.hooks.reset_authorizer(&conn.handle);
+                    }
+                    if policy.rollback
+                        && unsafe { sqlite3_get_autocommit(conn.handle.as_ptr()) } == 0
+                    {
+                        conn.handle.exec("ROLLBACK")?;
+                        conn.transaction_depth = 0;
+                    }
+                    Ok::<_, Error>(())
+                })
+                .await??;
+        }
+        if policy.temp_tables {
+            let objects: Vec<(String, String)> = crate::query_as(
+                "SELECT type, name FROM temp.sqlite_schema WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%'",
+            )
+            .fetch_all(&mut *self)
+            .await?;
+            for (kind, name) in objects {
+                let kind = if kind == "view" { "VIEW" } else { "TABLE" };
+                self.execute(crate::query(&format!(
+                    "DROP {kind} IF EXISTS temp.{}",
+                    schema::quote_identifier(&name)
+                )))
+                .await?;
+            }
+        }
+        if policy.pragmas && !pragmas.is_empty() {
+            self.execute(crate::query(pragmas)).await?;
+        }
+        Ok(())
+    }
+
+    /// List the rows that violate foreign key constraints. See the [`foreign_keys`](crate::foreign_keys) module.
+    pub async fn foreign_key_violations(&mut self) -> Result<Vec<FkViolation>> {
+        foreign_keys::violations(self, None).await
+    }
+
+    /// List the rows of `table` that violate its foreign key constraints. This is the check to run before committing a
+    /// transaction that [defers foreign keys](crate::Transaction::defer_foreign_keys).
+    pub async fn foreign_key_check(&mut self, table: &str) -> Result<Vec<FkViolation>> {
+        foreign_keys::violations(self, Some(table)).await
+    }
+
+    /// Check the whole database for corruption, reporting at most `max_errors` problems. An empty list means the
+    /// database is healthy. See the [`integrity`](crate::integrity) module.
+    pub async fn integrity_check(&mut self, max_errors: u32) -> Result<Vec<IntegrityError>> {
+        integrity::check(self, "integrity_check", max_errors).await
+    }
+
+    /// A faster version of [`integrity_check`](Self::integrity_check) that doesn't check indexes against their
+    /// tables, reporting at most 100 problems.
+    pub async fn quick_check(&mut self) -> Result<Vec<IntegrityError>> {
+        integrity::check(self, "quick_check", 100).await
+    }
+
+    /// Resolve foreign key violations by deleting or detaching the orphaned rows, `batch_size` violations per
+    /// transaction. Returns the number of violations resolved.
+    ///
+    /// Repairs can cascade: with `foreign_keys` off, deleting an orphan may orphan its own children, which are then
+    /// repaired in turn. Violations in `WITHOUT ROWID` tables can't be addressed, and are left in place.
+    pub async fn repair_foreign_keys(&mut self, repair: Repair, batch_size: u32) -> Result<u64> {
+        foreign_keys::repair(self, repair, batch_size).await
+    }
+
+    /// Prepare `sql` without running it, and describe its parameters and result columns. See the
+    /// [`describe`](crate::describe) module.
+    pub async fn describe(&mut self, sql: &str) -> Result<Describe> {
+        describe::describe(self, sql).await
+    }
+
+    /// Run [`EXPLAIN QUERY PLAN`](https://www.sqlite.org/eqp.html) for `query`, returning the top-level steps of its
+    /// plan. See the [`explain`](crate::explain) module.
+    pub async fn explain(&mut self, query: impl Execute) -> Result<Vec<QueryPlanNode>> {
+        explain::explain(self, query).await
+    }
+
+    /// Write any dirty pages held in the page cache out to the database file, without committing or ending the
+    /// current transaction. See [`sqlite3_db_cacheflush`](https://www.sqlite.org/c3ref/db_cacheflush.html).
+    ///
+    /// This does not fsync; pair it with [`fsync_barrier`](Self::fsync_barrier) if the data must survive a crash.
+    pub async fn cacheflush(&mut self) -> Result<()> {
+        self.worker.run(|conn| conn.handle.cacheflush()).await?
+    }
+
+    /// Ensure that every transaction committed so far on this connection is durable on disk before returning, for
+    /// applications with explicit durability points, such as before acknowledging an upstream message.
+    ///
+    /// This flushes the page cache, then fsyncs the journal and the database file, in that order. In WAL mode,
+    /// committed transactions are durable once the WAL is synced, so no checkpoint is needed. This makes commits
+    /// durable even with `synchronous = NORMAL` (which skips the fsync on commit in WAL mode) or `synchronous = OFF`,
+    /// letting applications trade per-commit fsyncs for explicit barriers.
+    pub async fn fsync_barrier(&mut self) -> Result<()> {
+        self.worker
+            .run(|conn| {
+                conn.handle.cacheflush()?;
+                conn.handle.sync_files()
+            })
+            .await?
+    }
+
+    /// Open the blob in `column` of the row of `table` with the given rowid, to read it a chunk at a time. See the
+    /// [`blob`](crate::blob) module.
+    ///
+    /// Fails if the row doesn't exist, or if the value isn't a blob or text.
+    pub async fn open_blob(
+        &mut self,
+        table: &str,
+        column: &str,
+        rowid: i64,
+    ) -> Result<BlobReader<'_>> {
+        BlobReader::open(self, table, column, rowid).await
+    }
+
+    /// Copy the main database into the file at `path`, replacing its contents, while the database stays live. See the
+    /// [`backup`](crate::backup) module.
+    pub fn backup_to(&mut self, path: impl AsRef<Path>) -> Backup<'_> {
+        Backup::to(self, path.as_ref())
+    }
+
+    /// Replace the contents of the main database with the database in the file at `path`. See the
+    /// [`backup`](crate::backup) module.
+    pub fn backup_from(&mut self, path: impl AsRef<Path>) -> Backup<'_> {
+        Backup::from(self, path.as_ref())
+    }
+
+    /// Write the main database to `writer` as SQL statements that recreate it. See the [`dump`](crate::dump) module.
+    pub async fn dump<W>(&mut self, writer: W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        dump::dump(self, writer).await
+    }
+
+    /// Execute the SQL dump read from `reader` in a single transaction. See the [`dump`](crate::dump) module.
+    pub fn restore_from_sql<R>(&mut self, reader: R) -> Restore<'_, R>
+    where
+        R: AsyncBufRead + Unpin + Send,
+    {
+        Restore::new(self, reader)
+    }
+
+    /// Copy the database attached as `schema`, such as `main`, into memory, in the same format as a database file.
+    /// Use this to snapshot an in-memory database, or to ship a small database over the network. See
+    /// [`sqlite3_serialize`](https://www.sqlite.org/c3ref/serialize.html).
+    pub async fn serialize(&mut self, schema: &str) -> Result<Bytes> {
+        let schema = schema.to_string();
+        let data = self
+            .worker
+            .run(move |conn| conn.handle.serialize(&schema))
+            .await??;
+        Ok(Bytes::from(data))
+    }
+
+    /// Replace the database attached as `schema` with `data`, as returned by [`serialize`](Self::serialize). The
+    /// database becomes an in-memory database, even if it was a file, and changes to it are not written anywhere.
+    /// Use this to restore snapshots, or to load test fixtures without touching the filesystem. See
+    /// [`sqlite3_deserialize`](https://www.sqlite.org/c3ref/deserialize.html).
+    ///
+    /// A pooled connection that has been deserialized into no longer sees the pool's database, so it is closed when
+    /// it is released instead of being returned to the pool.
+    ///
+    /// Fails if a transaction is open.
+    pub async fn deserialize(&mut self, schema: &str, data: impl AsRef<[u8]>) -> Result<()> {
+        let schema = schema.to_string();
+        let data = data.as_ref().to_vec();
+        self.worker
+            .run(move |conn| conn.handle.deserialize(&schema, &data))
+            .await??;
+        self.deserialized = true;
+        Ok(())
+    }
+
+    /// How full the connection's command and row buffers are. See [`QueueMetrics`].
+    ///
+    /// While a query's results are being read, the connection is borrowed, so use
+    /// [`Pool::queue_metrics`](crate::Pool::queue_metrics) to observe a busy connection.
+    pub fn queue_metrics(&self) -> QueueMetrics {
+        self.worker.shared.queues.metrics()
+    }
+
     pub fn cached_statements_size(&self) -> usize {
         self.worker
             .shared
@@ -166,6 +541,13 @@ impl Connection {
             .load(std::sync::atomic::Ordering::Acquire)
     }
 
+    /// Execute `sql` without caching its prepared statements, and return the total number of rows affected. Use this
+    /// for large, one-off statements such as migrations and maintenance, which would otherwise take up space in the
+    /// statement cache. `sql` may hold several statements, and takes no arguments.
+    pub async fn execute_uncached(&mut self, sql: &str) -> Result<QueryResult> {
+        self.worker.execute_uncached(sql.to_string()).await
+    }
+
     pub async fn clear_cached_statements(&mut self) -> Result<()> {
         self.worker.clear_cache().await?;
         Ok(())
@@ -217,6 +599,87 @@ impl Connection {
         }
     }
 
+    /// Attach the database at `path` under the schema name `schema`. Tables in the attached database are addressed as
+    /// `schema.table`, and unqualified names that aren't found in the main database are looked up in attached
+    /// databases too. See [`ATTACH DATABASE`](https://www.sqlite.org/lang_attach.html).
+    ///
+    /// The database is created if it doesn't exist and the connection was opened with
+    /// [`create_if_missing`](Musq::create_if_missing). Attachments last until [`detach`](Self::detach) is called or
+    /// the connection is closed; to attach a database to every connection in a pool, use [`Musq::attach`].
+    pub async fn attach(&mut self, path: impl AsRef<Path>, schema: &str) -> Result<()> {
+        crate::query(&format!(
+            "ATTACH DATABASE ? AS {}",
+            schema::quote_identifier(schema)
+        ))
+        .bind(path.as_ref().to_string_lossy().into_owned())
+        .execute(&mut *self)
+        .await?;
+        #[cfg(feature = "encryption")]
+        {
+            let schema = schema.to_string();
+            self.worker
+                .run(move |conn| conn.handle.reserve_encryption_bytes(&schema))
+                .await??;
+        }
+        Ok(())
+    }
+
+    /// Detach the database attached under the schema name `schema`. Fails if a transaction is open.
+    ///
+    /// Note that [`PoolConnection::detach`](crate::pool::PoolConnection::detach) shadows this method, so call it
+    /// through a `&mut Connection` when holding a pooled connection.
+    pub async fn detach(&mut self, schema: &str) -> Result<()> {
+        crate::query(&format!(
+            "DETACH DATABASE {}",
+            schema::quote_identifier(schema)
+        ))
+        .execute(&mut *self)
+        .await?;
+        Ok(())
+    }
+
+    /// Attach the database at `path` under the schema name `schema`, and execute the function inside a transaction
+    /// spanning both databases. Tables in the attached database are addressed as `schema.table`.
+    ///
+    /// As with [`transaction`](Self::transaction), the transaction is committed if the function succeeds and rolled
+    /// back if it fails. The database is detached afterwards in either case. This makes it possible to, say, move rows
+    /// from a hot database to an archive atomically:
+    ///
+    /// ```rust,ignore
+    /// conn.transaction_with_attached("archive.db", "archive", |tx| Box::pin(async move {
+    ///     query("INSERT INTO archive.events SELECT * FROM events WHERE ts < ?").bind(cutoff).execute(&mut *tx).await?;
+    ///     query("DELETE FROM events WHERE ts < ?").bind(cutoff).execute(&mut *tx).await?;
+    ///     Ok::<_, musq::Error>(())
+    /// })).await?;
+    /// ```
+    ///
+    /// SQLite only commits atomically across databases when the main database uses a rollback journal (`DELETE`,
+    /// `TRUNCATE` or `PERSIST`). In WAL mode each database commits atomically on its own, but a crash during commit can
+    /// leave one committed and the other not.
+    ///
+    /// Attaching fails if a transaction is already open on this connection.
+    pub async fn transaction_with_attached<'a, F, R, E>(
+        &'a mut self,
+        path: impl AsRef<Path>,
+        schema: &str,
+        callback: F,
+    ) -> Result<R, E>
+    where
+        for<'c> F:
+            FnOnce(&'c mut Transaction<'_>) -> BoxFuture<'c, Result<R, E>> + 'a + Send + Sync,
+        Self: Sized,
+        R: Send,
+        E: From<Error> + Send,
+    {
+        self.attach(path, schema).await?;
+        let ret = self.transaction(callback).await;
+        let detached = self.detach(schema).await;
+        match (ret, detached) {
+            (Ok(_), Err(e)) => Err(e.into()),
+            (ret, _) => ret,
+        }
+    }
+
     /// Establish a new database connection with the provided options.
     pub async fn connect_with(options: &Musq) -> Result<Self>
     where
@@ -226,18 +689,41 @@ impl Connection {
     }
 }
 
-/// Implements a C binding to a progress callback. The function returns `0` if the
-/// user-provided callback returns `true`, and `1` otherwise to signal an interrupt.
-extern "C" fn progress_callback<F>(callback: *mut c_void) -> c_int
-where
-    F: FnMut() -> bool,
-{
-    unsafe {
-        let r = catch_unwind(|| {
-            let callback: *mut F = callback.cast::<F>();
-            (*callback)()
-        });
-        c_int::from(!r.unwrap_or_default())
+impl ConnectionState {
+    /// Returns the underlying sqlite3* connection handle. See [`LockedSqliteHandle::as_raw_handle`].
+    pub fn as_raw_handle(&mut self) -> NonNull<sqlite3> {
+        self.handle.as_non_null_ptr()
+    }
+
+    /// Whether a transaction opened through musq is in progress.
+    pub fn in_transaction(&self) -> bool {
+        self.transaction_depth > 0
+    }
+
+    /// The number of statements in the statement cache.
+    pub fn cached_statements_size(&self) -> usize {
+        self.statements.len()
+    }
+
+    /// Remove all statements from the statement cache.
+    pub fn clear_cached_statements(&mut self) {
+        self.statements.clear();
+    }
+
+    /// See [`LockedSqliteHandle::set_progress_handler`].
+    pub fn set_progress_handler<F>(&mut self, num_ops: i32, callback: F)
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        let mut shim = Callback::new("progress handler", callback, self.callback_panics.clone());
+        let callback = move || shim.call(|f| f()).unwrap_or(false);
+        self.progress
+            .set_handler(&self.handle, num_ops, Some(Box::new(callback)));
+    }
+
+    /// See [`LockedSqliteHandle::remove_progress_handler`].
+    pub fn remove_progress_handler(&mut self) {
+        self.progress.set_handler(&self.handle, 0, None);
     }
 }
 
@@ -271,37 +757,103 @@ impl LockedSqliteHandle<'_> {
     /// The progress handler callback must not do anything that will modify the database connection that invoked
     /// the progress handler. Note that sqlite3_prepare_v2() and sqlite3_step() both modify their database connections
     /// in this context.
+    ///
+    /// If the callback panics, the operation is interrupted and fails with [`Error::CallbackPanicked`]. The handler
+    /// is then poisoned, and every later operation it would be invoked for fails the same way until it is replaced
+    /// or removed.
     pub fn set_progress_handler<F>(&mut self, num_ops: i32, callback: F)
     where
         F: FnMut() -> bool + Send + 'static,
     {
-        unsafe {
-            let callback_boxed = Box::new(callback);
-            // SAFETY: `Box::into_raw()` always returns a non-null pointer.
-            let callback = NonNull::new_unchecked(Box::into_raw(callback_boxed));
-            let handler = callback.as_ptr() as *mut _;
-            self.guard.remove_progress_handler();
-            self.guard.progress_handler_callback = Some(Handler(callback));
-
-            sqlite3_progress_handler(
-                self.as_raw_handle().as_mut(),
-                num_ops,
-                Some(progress_callback::<F>),
-                handler,
-            );
-        }
+        self.guard.set_progress_handler(num_ops, callback);
     }
 
     /// Removes the progress handler on a database connection. The method does nothing if no handler was set.
     pub fn remove_progress_handler(&mut self) {
         self.guard.remove_progress_handler();
     }
+
+    /// Sets a callback that is invoked for every row inserted, updated or deleted on this connection, with the kind of
+    /// change, the schema and table names, and the rowid of the row. See
+    /// [`sqlite3_update_hook`](https://www.sqlite.org/c3ref/update_hook.html).
+    ///
+    /// The hook sees changes as they are made, including those later rolled back, so pair it with a rollback hook to
+    /// discard them. It isn't invoked for `WITHOUT ROWID` tables, for system tables, or for rows deleted by the
+    /// truncate optimization of an unqualified `DELETE`. Setting a new hook replaces the old one.
+    ///
+    /// If the callback panics, the statement that triggered it still takes effect, but fails with
+    /// [`Error::CallbackPanicked`]. The hook is then poisoned, and every later statement that would have invoked it
+    /// fails the same way until it is replaced or removed.
+    pub fn set_update_hook<F>(&mut self, callback: F)
+    where
+        F: FnMut(UpdateOp, &str, &str, i64) + Send + 'static,
+    {
+        let guard = &*self.guard;
+        guard
+            .hooks
+            .set_update(&guard.handle, Some(Box::new(callback)));
+    }
+
+    /// Removes the update hook. The method does nothing if no hook was set.
+    pub fn remove_update_hook(&mut self) {
+        let guard = &*self.guard;
+        guard.hooks.set_update(&guard.handle, None);
+    }
+
+    /// Sets a callback that is invoked whenever a transaction is about to commit. If the callback returns `false`,
+    /// the commit is turned into a rollback, and fails. See
+    /// [`sqlite3_commit_hook`](https://www.sqlite.org/c3ref/commit_hook.html). Setting a new hook replaces the old one.
+    ///
+    /// If the callback panics, the transaction is rolled back and the commit fails with
+    /// [`Error::CallbackPanicked`]. The hook is then poisoned, and every later commit fails the same way until it is
+    /// replaced or removed.
+    pub fn set_commit_hook<F>(&mut self, callback: F)
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        let guard = &*self.guard;
+        guard
+            .hooks
+            .set_commit(&guard.handle, Some(Box::new(callback)));
+    }
+
+    /// Removes the commit hook. The method does nothing if no hook was set.
+    pub fn remove_commit_hook(&mut self) {
+        let guard = &*self.guard;
+        guard.hooks.set_commit(&guard.handle, None);
+    }
+
+    /// Sets a callback that is invoked whenever a transaction is rolled back, explicitly or because a commit hook
+    /// vetoed the commit. It isn't invoked when a connection closes with a transaction open. See
+    /// [`sqlite3_rollback_hook`](https://www.sqlite.org/c3ref/commit_hook.html). Setting a new hook replaces the old
+    /// one.
+    ///
+    /// If the callback panics, the transaction is still rolled back, but the rollback fails with
+    /// [`Error::CallbackPanicked`]. The hook is then poisoned, and every later rollback fails the same way until it is
+    /// replaced or removed. Panics during the rollback of a dropped [`Transaction`] are discarded.
+    pub fn set_rollback_hook<F>(&mut self, callback: F)
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let guard = &*self.guard;
+        guard
+            .hooks
+            .set_rollback(&guard.handle, Some(Box::new(callback)));
+    }
+
+    /// Removes the rollback hook. The method does nothing if no hook was set.
+    pub fn remove_rollback_hook(&mut self) {
+        let guard = &*self.guard;
+        guard.hooks.set_rollback(&guard.handle, None);
+    }
 }
 
 impl Drop for ConnectionState {
     fn drop(&mut self) {
         // explicitly drop statements before the connection handle is dropped
         self.statements.clear();
-        self.remove_progress_handler();
+        self.progress.uninstall(&self.handle);
+        self.hooks.uninstall(&self.handle);
+        self.interrupt.close();
     }
 }
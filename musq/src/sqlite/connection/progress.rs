@@ -0,0 +1,325 @@
+use std::{
+    fmt,
+    os::raw::{c_int, c_void},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use libsqlite3_sys::sqlite3_progress_handler;
+use tokio_util::sync::CancellationToken;
+
+use super::{ConnectionHandle, Interrupt};
+use crate::{error::Error, sqlite::error::PrimaryErrCode};
+
+/// How often, in virtual machine instructions, the watchdog, query timeouts and cancellation tokens are checked.
+const CHECK_OPS: c_int = 1000;
+
+/// What a watchdog does when a statement runs for longer than its threshold. Set with
+/// [`Musq::watchdog`](crate::Musq::watchdog).
+#[derive(Clone)]
+pub enum WatchdogAction {
+    /// Log a warning with the statement's SQL and how long it has been running.
+    Log,
+    /// Interrupt the statement, which then fails with [`Error::Interrupted`](crate::Error::Interrupted).
+    Interrupt,
+    /// Call a function with the slow statement. The function is called on the connection's worker thread while the
+    /// statement is paused, so it should return quickly. Panics are caught and logged.
+    Callback(Arc<dyn Fn(&SlowQuery) + Send + Sync + 'static>),
+}
+
+impl fmt::Debug for WatchdogAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Log => f.write_str("Log"),
+            Self::Interrupt => f.write_str("Interrupt"),
+            Self::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
+}
+
+/// A statement that ran for longer than the watchdog threshold, passed to [`WatchdogAction::Callback`].
+#[derive(Debug, Clone)]
+pub struct SlowQuery {
+    /// The [id](super::Connection::id) of the connection running the query.
+    pub connection: u64,
+    /// The SQL of the query the statement belongs to.
+    pub sql: String,
+    /// How long the statement had been running when the watchdog fired.
+    pub elapsed: Duration,
+}
+
+/// The watchdog configured with [`Musq::watchdog`](crate::Musq::watchdog).
+#[derive(Debug, Clone)]
+pub(crate) struct Watchdog {
+    pub(crate) threshold: Duration,
+    pub(crate) action: WatchdogAction,
+}
+
+/// A user progress handler, called every `every` invocations of the SQLite progress handler.
+struct UserHandler {
+    num_ops: c_int,
+    every: u32,
+    ticks: u32,
+    callback: Box<dyn FnMut() -> bool + Send + 'static>,
+}
+
+struct WatchdogState {
+    watchdog: Watchdog,
+    connection: u64,
+    interrupt: Arc<Interrupt>,
+    /// The SQL and start time of the running statement, and whether the watchdog has fired for it.
+    current: Mutex<Option<(String, Instant, bool)>>,
+}
+
+/// The progress handler of a connection. SQLite allows only one per connection, so the handler installed here
+/// enforces query timeouts and cancellation, and dispatches both to the watchdog and to the user's progress handler.
+///
+/// The handler is called through a shared reference while a statement is stepping, so its state lives behind locks.
+pub(crate) struct Progress {
+    handler: Mutex<Option<UserHandler>>,
+    watchdog: Option<WatchdogState>,
+    /// The deadline and timeout of the running query, if it has a timeout.
+    deadline: Mutex<Option<(Instant, Duration)>>,
+    timed_out: AtomicBool,
+    /// The cancellation token of the running query, if it has one.
+    cancellation: Mutex<Option<CancellationToken>>,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    /// Create the progress handler for a connection. The returned handler must outlive its registration with SQLite;
+    /// call [`uninstall`](Self::uninstall) before dropping it.
+    pub(crate) fn install(
+        handle: &ConnectionHandle,
+        watchdog: Option<Watchdog>,
+        connection: u64,
+        interrupt: Arc<Interrupt>,
+    ) -> Arc<Self> {
+        let progress = Arc::new(Self {
+            handler: Mutex::default(),
+            watchdog: watchdog.map(|watchdog| WatchdogState {
+                watchdog,
+                connection,
+                interrupt,
+                current: Mutex::default(),
+            }),
+            deadline: Mutex::default(),
+            timed_out: AtomicBool::new(false),
+            cancellation: Mutex::default(),
+            cancelled: AtomicBool::new(false),
+        });
+        progress.sync(handle);
+        progress
+    }
+
+    /// Set the user's progress handler, to be called every `num_ops` virtual machine instructions. A `num_ops` less
+    /// than one removes it.
+    pub(crate) fn set_handler(
+        &self,
+        handle: &ConnectionHandle,
+        num_ops: c_int,
+        callback: Option<Box<dyn FnMut() -> bool + Send + 'static>>,
+    ) {
+        let callback = callback.filter(|_| num_ops > 0);
+        if let Ok(mut slot) = self.handler.lock() {
+            *slot = callback.map(|callback| UserHandler {
+                num_ops,
+                every: 1,
+                ticks: 0,
+                callback,
+            });
+        }
+        self.sync(handle);
+    }
+
+    /// Abort the queries that follow with [`Error::QueryTimedOut`] once `timeout` has elapsed, and with
+    /// [`Error::Cancelled`] once `cancellation` is cancelled. Passing `None` for both stops guarding queries.
+    pub(crate) fn set_guards(
+        &self,
+        handle: &ConnectionHandle,
+        timeout: Option<Duration>,
+        cancellation: Option<CancellationToken>,
+    ) {
+        self.timed_out.store(false, Ordering::Release);
+        self.cancelled.store(false, Ordering::Release);
+        if let Ok(mut deadline) = self.deadline.lock() {
+            *deadline = timeout.map(|timeout| (Instant::now() + timeout, timeout));
+        }
+        if let Ok(mut slot) = self.cancellation.lock() {
+            *slot = cancellation;
+        }
+        self.sync(handle);
+    }
+
+    /// If `err` was caused by the query timing out or being cancelled, replace it with [`Error::QueryTimedOut`] or
+    /// [`Error::Cancelled`].
+    pub(crate) fn map_err(&self, err: Error) -> Error {
+        match err {
+            Error::Sqlite(e)
+                if e.primary == PrimaryErrCode::Interrupt
+                    && self.cancelled.load(Ordering::Acquire) =>
+            {
+                Error::Cancelled
+            }
+            Error::Sqlite(e)
+                if e.primary == PrimaryErrCode::Interrupt
+                    && self.timed_out.load(Ordering::Acquire) =>
+            {
+                let timeout = self
+                    .deadline
+                    .lock()
+                    .ok()
+                    .and_then(|d| d.map(|(_, timeout)| timeout))
+                    .unwrap_or_default();
+                Error::QueryTimedOut { timeout }
+            }
+            err => err,
+        }
+    }
+
+    /// Register with SQLite a progress handler frequent enough for the timeout, the watchdog and the user's handler,
+    /// or none if none is set.
+    fn sync(&self, handle: &ConnectionHandle) {
+        let timed = self.watchdog.is_some()
+            || self.deadline.lock().is_ok_and(|d| d.is_some())
+            || self.cancellation.lock().is_ok_and(|c| c.is_some());
+        let mut handler = self.handler.lock().ok();
+        let handler = handler.as_mut().and_then(|h| h.as_mut());
+        let ops = match (handler, timed) {
+            (Some(h), true) => {
+                h.every = (h.num_ops as u32).div_ceil(CHECK_OPS as u32);
+                h.num_ops.min(CHECK_OPS)
+            }
+            (Some(h), false) => {
+                h.every = 1;
+                h.num_ops
+            }
+            (None, true) => CHECK_OPS,
+            (None, false) => 0,
+        };
+        let data = self as *const Self as *mut c_void;
+        unsafe {
+            if ops > 0 {
+                sqlite3_progress_handler(handle.as_ptr(), ops, Some(progress_callback), data);
+            } else {
+                sqlite3_progress_handler(handle.as_ptr(), 0, None, ptr::null_mut());
+            }
+        }
+    }
+
+    pub(crate) fn uninstall(&self, handle: &ConnectionHandle) {
+        unsafe { sqlite3_progress_handler(handle.as_ptr(), 0, None, ptr::null_mut()) };
+    }
+
+    /// Record that a statement of the query `sql` is about to start.
+    pub(crate) fn start(&self, sql: &str) {
+        if let Some(watchdog) = &self.watchdog {
+            if let Ok(mut current) = watchdog.current.lock() {
+                *current = Some((sql.to_string(), Instant::now(), false));
+            }
+        }
+    }
+
+    /// Record that the running statement finished.
+    pub(crate) fn finish(&self) {
+        if let Some(watchdog) = &self.watchdog {
+            if let Ok(mut current) = watchdog.current.lock() {
+                *current = None;
+            }
+        }
+    }
+
+    /// Whether the running statement may continue.
+    fn proceed(&self) -> bool {
+        if let Ok(deadline) = self.deadline.lock() {
+            if deadline.is_some_and(|(deadline, _)| Instant::now() >= deadline) {
+                self.timed_out.store(true, Ordering::Release);
+                return false;
+            }
+        }
+        if let Ok(cancellation) = self.cancellation.lock() {
+            if cancellation.as_ref().is_some_and(|c| c.is_cancelled()) {
+                self.cancelled.store(true, Ordering::Release);
+                return false;
+            }
+        }
+        if let Some(watchdog) = &self.watchdog {
+            if !watchdog.check() {
+                return false;
+            }
+        }
+        match self.handler.lock() {
+            Ok(mut handler) => match handler.as_mut() {
+                Some(h) => {
+                    h.ticks += 1;
+                    if h.ticks < h.every {
+                        return true;
+                    }
+                    h.ticks = 0;
+                    (h.callback)()
+                }
+                None => true,
+            },
+            Err(_) => true,
+        }
+    }
+}
+
+impl WatchdogState {
+    /// Act if the running statement has just passed the threshold. Returns `false` if it should be interrupted.
+    fn check(&self) -> bool {
+        let slow = {
+            let Ok(mut current) = self.current.lock() else {
+                return true;
+            };
+            match current.as_mut() {
+                Some((sql, started, fired)) if !*fired => {
+                    let elapsed = started.elapsed();
+                    if elapsed < self.watchdog.threshold {
+                        return true;
+                    }
+                    *fired = true;
+                    SlowQuery {
+                        connection: self.connection,
+                        sql: sql.clone(),
+                        elapsed,
+                    }
+                }
+                _ => return true,
+            }
+        };
+        match &self.watchdog.action {
+            WatchdogAction::Log => {
+                tracing::warn!(
+                    connection = slow.connection,
+                    elapsed = ?slow.elapsed,
+                    sql = %slow.sql,
+                    "slow query"
+                );
+                true
+            }
+            WatchdogAction::Interrupt => {
+                self.interrupt.interrupt();
+                false
+            }
+            WatchdogAction::Callback(f) => {
+                if panic::catch_unwind(AssertUnwindSafe(|| f(&slow))).is_err() {
+                    tracing::warn!(connection = slow.connection, "watchdog callback panicked");
+                }
+                true
+            }
+        }
+    }
+}
+
+/// The callback must not unwind; user callbacks are wrapped in a [`Callback`](super::Callback) shim before they get
+/// here, and watchdog callbacks are called under `catch_unwind`.
+extern "C" fn progress_callback(data: *mut c_void) -> c_int {
+    let progress = unsafe { &*(data as *const Progress) };
+    c_int::from(!progress.proceed())
+}
@@ -0,0 +1,85 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use super::worker::WorkerHandle;
+
+static TEMP_TABLE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A temporary table created by [`Connection::create_temp_table`](super::Connection::create_temp_table), shared
+/// between its guard and the connection that owns it.
+#[derive(Debug)]
+pub(crate) struct TempTableState {
+    pub(crate) name: String,
+    dropped: AtomicBool,
+}
+
+impl TempTableState {
+    pub(crate) fn new() -> Self {
+        Self {
+            name: format!(
+                "musq_temp_{}",
+                TEMP_TABLE_ID.fetch_add(1, Ordering::Relaxed)
+            ),
+            dropped: AtomicBool::new(false),
+        }
+    }
+
+    /// Mark the table as dropped, returning `true` if the caller is responsible for dropping it.
+    pub(crate) fn claim(&self) -> bool {
+        !self.dropped.swap(true, Ordering::AcqRel)
+    }
+
+    pub(crate) fn is_dropped(&self) -> bool {
+        self.dropped.load(Ordering::Acquire)
+    }
+}
+
+/// A guard for a temporary table, returned by
+/// [`Connection::create_temp_table`](super::Connection::create_temp_table). The table is dropped when the guard is
+/// dropped, or when the connection is returned to its pool, whichever comes first.
+///
+/// The guard doesn't borrow the connection, so the connection can be used freely while the table exists. Its
+/// [`Display`] implementation renders the table name, for use in SQL.
+#[derive(Debug)]
+pub struct TempTable {
+    state: Arc<TempTableState>,
+    worker: WorkerHandle,
+}
+
+impl TempTable {
+    pub(crate) fn new(state: Arc<TempTableState>, worker: WorkerHandle) -> Self {
+        Self { state, worker }
+    }
+
+    /// The name of the table, which is unique within the process. The table lives in the `temp` schema.
+    pub fn name(&self) -> &str {
+        &self.state.name
+    }
+}
+
+impl Display for TempTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Drop for TempTable {
+    fn drop(&mut self) {
+        if self.state.claim() {
+            let sql = format!("DROP TABLE IF EXISTS temp.{}", self.state.name);
+            // If the worker is gone, so is the table
+            self.worker
+                .start_run(move |conn| {
+                    if let Err(error) = conn.handle.exec(sql) {
+                        tracing::warn!(%error, "failed to drop temporary table");
+                    }
+                })
+                .ok();
+        }
+    }
+}
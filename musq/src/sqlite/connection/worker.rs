@@ -1,21 +1,32 @@
+use std::fmt::{self, Debug, Formatter};
 use std::future::Future;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use futures_channel::oneshot;
 use futures_intrusive::sync::{Mutex, MutexGuard};
+use libsqlite3_sys::sqlite3_get_autocommit;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     error::Error,
+    executor::Execute,
+    musq::CommandSaturation,
     sqlite::{
-        connection::{establish::EstablishParams, execute, ConnectionState},
-        Arguments, Statement,
+        connection::{
+            establish::EstablishParams, execute, Activity, ConnectionState, Interrupt, QueryState,
+            QueueMetrics,
+        },
+        Arguments, Statement, Value,
     },
     transaction::{
         begin_ansi_transaction_sql, commit_ansi_transaction_sql, rollback_ansi_transaction_sql,
+        TransactionBehavior,
     },
-    Either, QueryResult, Row,
+    Either, QueryResult, ResultLimits, Row,
 };
 
 // Each SQLite connection has a dedicated thread.
@@ -25,14 +36,173 @@ use crate::{
 //       unlikely.
 
 pub(crate) struct ConnectionWorker {
+    id: u64,
     command_tx: flume::Sender<Command>,
     /// Mutex for locking access to the database.
     pub(crate) shared: Arc<WorkerSharedState>,
+    on_saturated: Option<CommandSaturation>,
+}
+
+/// Queues commands on a worker without borrowing its connection.
+#[derive(Clone)]
+pub(crate) struct WorkerHandle(flume::Sender<Command>);
+
+impl Debug for WorkerHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("WorkerHandle").finish()
+    }
+}
+
+impl WorkerHandle {
+    /// Queue `f` to run against the connection on the worker thread, without waiting for it.
+    pub(crate) fn start_run<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ConnectionState) + Send + 'static,
+    {
+        self.0
+            .send(Command::Run { f: Box::new(f) })
+            .map_err(|_| Error::WorkerCrashed)
+    }
 }
 
 pub(crate) struct WorkerSharedState {
     pub(crate) cached_statements_size: AtomicUsize,
     pub(crate) conn: Mutex<ConnectionState>,
+    pub(crate) interrupt: Arc<Interrupt>,
+    /// The query the worker is running, shared with the pool for [`Pool::active_queries`](crate::Pool::active_queries).
+    pub(crate) activity: Arc<Activity>,
+    pub(crate) queues: Queues,
+}
+
+/// How a query is run, as set on the [`Execute`] value it came from.
+pub(crate) struct QueryOptions {
+    pub(crate) limits: ResultLimits,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    pub(crate) row_buffer_size: Option<usize>,
+}
+
+impl QueryOptions {
+    pub(crate) fn of(query: &impl Execute) -> Self {
+        Self {
+            limits: query.limits(),
+            timeout: query.timeout(),
+            cancellation: query.cancellation(),
+            row_buffer_size: query.row_buffer_size(),
+        }
+    }
+}
+
+type RowResult = Result<Either<QueryResult, Row>, Error>;
+type PageResult = Result<Vec<Row>, Error>;
+
+/// Where the worker sends the results of a query.
+enum Results {
+    /// Each statement result and each row in a message of its own.
+    Rows(flume::Sender<RowResult>),
+    /// Rows batched into pages of up to `size` rows, so that large results take fewer channel messages. Statement
+    /// results are dropped.
+    Pages {
+        tx: flume::Sender<PageResult>,
+        size: usize,
+        page: Vec<Row>,
+    },
+}
+
+impl Results {
+    /// Send a result, or add it to the current page. Returns `false` if the receiver is gone. Each time the channel
+    /// is full and the send has to wait for the caller, `stalls` is incremented.
+    fn send(&mut self, res: RowResult, stalls: &AtomicU64) -> bool {
+        match self {
+            Self::Rows(tx) => send_counting_stalls(tx, res, stalls),
+            Self::Pages { tx, size, page } => match res {
+                Ok(Either::Left(_)) => true,
+                Ok(Either::Right(row)) => {
+                    page.push(row);
+                    page.len() < *size
+                        || send_counting_stalls(
+                            tx,
+                            Ok(std::mem::replace(page, Vec::with_capacity(*size))),
+                            stalls,
+                        )
+                }
+                Err(e) => {
+                    // Rows read before the error are still delivered
+                    (page.is_empty() || send_counting_stalls(tx, Ok(std::mem::take(page)), stalls))
+                        && tx.send(Err(e)).is_ok()
+                }
+            },
+        }
+    }
+
+    /// Send the last, partial page.
+    fn finish(self) {
+        if let Self::Pages { tx, page, .. } = self {
+            if !page.is_empty() {
+                tx.send(Ok(page)).ok();
+            }
+        }
+    }
+}
+
+fn send_counting_stalls<T>(tx: &flume::Sender<T>, value: T, stalls: &AtomicU64) -> bool {
+    match tx.try_send(value) {
+        Ok(()) => true,
+        Err(flume::TrySendError::Full(value)) => {
+            stalls.fetch_add(1, Ordering::Relaxed);
+            tx.send(value).is_ok()
+        }
+        Err(flume::TrySendError::Disconnected(_)) => false,
+    }
+}
+
+/// Weak handles on the worker's command channel and on the row channel of its current query, for
+/// [`QueueMetrics`]. Holding them doesn't keep the channels open.
+pub(crate) struct Queues {
+    connection: u64,
+    commands: flume::WeakSender<Command>,
+    rows: std::sync::Mutex<Option<WeakResults>>,
+    row_capacity: usize,
+    row_stalls: AtomicU64,
+}
+
+enum WeakResults {
+    Rows(flume::WeakSender<RowResult>),
+    Pages(flume::WeakSender<PageResult>, usize),
+}
+
+impl Queues {
+    fn set_rows(&self, results: &Results) {
+        if let Ok(mut rows) = self.rows.lock() {
+            *rows = Some(match results {
+                Results::Rows(tx) => WeakResults::Rows(tx.downgrade()),
+                Results::Pages { tx, size, .. } => WeakResults::Pages(tx.downgrade(), *size),
+            });
+        }
+    }
+
+    pub(crate) fn metrics(&self) -> QueueMetrics {
+        let commands = self.commands.upgrade();
+        // Queued pages are counted as full, which may overstate the rows in the last one
+        let rows = self.rows.lock().ok().and_then(|rows| match rows.as_ref()? {
+            WeakResults::Rows(tx) => {
+                let tx = tx.upgrade()?;
+                Some((tx.len(), tx.capacity()?))
+            }
+            WeakResults::Pages(tx, size) => {
+                let tx = tx.upgrade()?;
+                Some((tx.len() * size, tx.capacity()? * size))
+            }
+        });
+        QueueMetrics {
+            connection: self.connection,
+            commands: commands.as_ref().map_or(0, |tx| tx.len()),
+            command_capacity: commands.and_then(|tx| tx.capacity()).unwrap_or(0),
+            rows: rows.map_or(0, |(rows, _)| rows),
+            row_capacity: rows.map_or(self.row_capacity, |(_, capacity)| capacity),
+            row_stalls: self.row_stalls.load(Ordering::Relaxed),
+        }
+    }
 }
 
 enum Command {
@@ -43,9 +213,11 @@ enum Command {
     Execute {
         query: Box<str>,
         arguments: Option<Arguments>,
-        tx: flume::Sender<Result<Either<QueryResult, Row>, Error>>,
+        options: QueryOptions,
+        tx: Results,
     },
     Begin {
+        behavior: TransactionBehavior,
         tx: rendezvous_oneshot::Sender<Result<(), Error>>,
     },
     Commit {
@@ -55,6 +227,9 @@ enum Command {
         tx: Option<rendezvous_oneshot::Sender<Result<(), Error>>>,
     },
     UnlockDb,
+    Run {
+        f: Box<dyn FnOnce(&mut ConnectionState) + Send>,
+    },
     ClearCache {
         tx: oneshot::Sender<()>,
     },
@@ -71,6 +246,7 @@ impl ConnectionWorker {
             .name(params.thread_name.clone())
             .spawn(move || {
                 let (command_tx, command_rx) = flume::bounded(params.command_channel_size);
+                let activity = Arc::new(Activity::new(params.id, params.capture_query_sql));
 
                 let conn = match params.establish() {
                     Ok(conn) => conn,
@@ -82,6 +258,15 @@ impl ConnectionWorker {
 
                 let shared = Arc::new(WorkerSharedState {
                     cached_statements_size: AtomicUsize::new(0),
+                    interrupt: Arc::clone(&conn.interrupt),
+                    activity,
+                    queues: Queues {
+                        connection: params.id,
+                        commands: command_tx.downgrade(),
+                        rows: std::sync::Mutex::new(None),
+                        row_capacity: params.row_channel_size,
+                        row_stalls: AtomicU64::new(0),
+                    },
                     // note: must be fair because in `Command::UnlockDb` we unlock the mutex
                     // and then immediately try to relock it; an unfair mutex would immediately
                     // grant us the lock even if another task is waiting.
@@ -91,8 +276,10 @@ impl ConnectionWorker {
 
                 if establish_tx
                     .send(Ok(Self {
+                        id: params.id,
                         command_tx,
                         shared: Arc::clone(&shared),
+                        on_saturated: params.on_command_buffer_saturated.clone(),
                     }))
                     .is_err()
                 {
@@ -105,9 +292,16 @@ impl ConnectionWorker {
                 let mut ignore_next_start_rollback = false;
 
                 for cmd in command_rx {
+                    // Panics are attributed to the command during which they occurred, and reported when each
+                    // statement finishes, even if it succeeded.
+                    conn.callback_panics.clear();
+                    conn.interrupt.clear();
                     match cmd {
                         Command::Prepare { query, tx } => {
-                            tx.send(prepare(&mut conn, &query).map(|prepared| {
+                            shared.activity.start(&query);
+                            let res = prepare(&mut conn, &query);
+                            shared.activity.finish();
+                            tx.send(res.map(|prepared| {
                                 update_cached_statements_size(
                                     &conn,
                                     &shared.cached_statements_size,
@@ -119,33 +313,91 @@ impl ConnectionWorker {
                         Command::Execute {
                             query,
                             arguments,
-                            tx,
+                            options,
+                            mut tx,
                         } => {
-                            let iter = match execute::iter(&mut conn, &query, arguments)
-                            {
-                                Ok(iter) => iter,
-                                Err(e) => {
-                                    tx.send(Err(e)).ok();
-                                    continue;
+                            let QueryOptions {
+                                limits,
+                                timeout,
+                                cancellation,
+                                ..
+                            } = options;
+                            let panics = conn.callback_panics.clone();
+                            let interrupt = conn.interrupt.clone();
+                            let progress = conn.progress.clone();
+                            let map_err =
+                                |e| progress.map_err(interrupt.map_err(panics.map_err(e)));
+                            shared.activity.start(&query);
+                            shared.queues.set_rows(&tx);
+                            // Cache the statement before any results are sent, so that callers see the new cache size
+                            // as soon as they see a result
+                            if let Err(e) = conn.statements.get(&query) {
+                                shared.activity.finish();
+                                tx.send(Err(map_err(e)), &shared.queues.row_stalls);
+                                continue;
+                            }
+                            update_cached_statements_size(&conn, &shared.cached_statements_size);
+                            if cancellation.as_ref().is_some_and(|c| c.is_cancelled()) {
+                                shared.activity.finish();
+                                tx.send(Err(Error::Cancelled), &shared.queues.row_stalls);
+                                continue;
+                            }
+                            let guarded = timeout.is_some() || cancellation.is_some();
+                            if guarded {
+                                progress.set_guards(&conn.handle, timeout, cancellation.clone());
+                            }
+                            match execute::iter(&mut conn, &query, arguments, &shared.activity) {
+                                Ok(iter) => {
+                                    let (mut rows, mut bytes) = (0, 0);
+                                    for res in iter {
+                                        let res = panics.check(res.and_then(|res| {
+                                            if let Either::Right(row) = &res {
+                                                // Stop between rows too, for queries that SQLite runs quickly
+                                                // but whose rows the caller consumes slowly
+                                                if cancellation.as_ref().is_some_and(|c| c.is_cancelled()) {
+                                                    return Err(Error::Cancelled);
+                                                }
+                                                rows += 1;
+                                                bytes +=
+                                                    row.values.iter().map(Value::size).sum::<u64>();
+                                                limits.check(rows, bytes)?;
+                                            }
+                                            Ok(res)
+                                        }));
+                                        // Stepping a statement again after an error would re-run it from the
+                                        // start, which for an interrupted query could run forever
+                                        let failed = res.is_err();
+                                        shared.activity.set_state(QueryState::Streaming);
+                                        if !tx.send(res.map_err(map_err), &shared.queues.row_stalls)
+                                            || failed
+                                        {
+                                            break;
+                                        }
+                                    }
                                 }
-                            };
-
-                            for res in iter {
-                                if tx.send(res).is_err() {
-                                    break;
+                                Err(e) => {
+                                    tx.send(Err(map_err(e)), &shared.queues.row_stalls);
                                 }
                             }
+                            // Finish the activity before closing the results, so that a caller that has seen the
+                            // end of its results never sees its query as active
+                            shared.activity.finish();
+                            tx.finish();
+                            if guarded {
+                                progress.set_guards(&conn.handle, None, None);
+                            }
 
                             update_cached_statements_size(&conn, &shared.cached_statements_size);
                         }
-                        Command::Begin { tx } => {
+                        Command::Begin { behavior, tx } => {
                             let depth = conn.transaction_depth;
                             let res =
                                 conn.handle
-                                    .exec(begin_ansi_transaction_sql(depth))
+                                    .exec(begin_ansi_transaction_sql(depth, behavior))
                                     .map(|_| {
                                         conn.transaction_depth += 1;
-                                    });
+                                    })
+                                    .map_err(|e| conn.callback_panics.map_err(e));
                             let res_ok = res.is_ok();
 
                             if tx.blocking_send(res).is_err() && res_ok {
@@ -181,11 +433,17 @@ impl ConnectionWorker {
                                 Ok(())
                             };
                             let res_ok = res.is_ok();
+                            let res = conn.callback_panics.check(res);
+                            let reported_ok = res.is_ok();
+                            if let Some(hooks) = conn.hooks.changes() {
+                                hooks.flush(&conn.handle);
+                            }
 
-                            if tx.blocking_send(res).is_err() && res_ok {
-                                // The COMMIT was processed but not acknowledged. This means that
-                                // the `Transaction` doesn't know it was committed and will try to
-                                // rollback on drop. We need to ignore that rollback.
+                            if (tx.blocking_send(res).is_err() || !reported_ok) && res_ok {
+                                // The COMMIT was processed but not acknowledged, or reported as failed
+                                // because a callback panicked. This means that the `Transaction` doesn't
+                                // know it was committed and will try to rollback on drop. We need to
+                                // ignore that rollback.
                                 ignore_next_start_rollback = true;
                             }
                         }
@@ -197,7 +455,14 @@ impl ConnectionWorker {
 
                             let depth = conn.transaction_depth;
 
-                            let res = if depth > 0 {
+                            let res = if depth > 0
+                                && unsafe { sqlite3_get_autocommit(conn.handle.as_ptr()) } != 0
+                            {
+                                // SQLite has already rolled back the whole transaction, for instance through
+                                // RAISE(ROLLBACK), so there's nothing left to roll back
+                                conn.transaction_depth = 0;
+                                Ok(())
+                            } else if depth > 0 {
                                 conn.handle
                                     .exec(rollback_ansi_transaction_sql(depth))
                                     .map(|_| {
@@ -208,17 +473,20 @@ impl ConnectionWorker {
                             };
 
                             let res_ok = res.is_ok();
+                            let res = conn.callback_panics.check(res);
+                            let reported_ok = res.is_ok();
 
                             if let Some(tx) = tx {
-                                if tx.blocking_send(res).is_err() && res_ok {
-                                    // The ROLLBACK was processed but not acknowledged. This means
-                                    // that the `Transaction` doesn't know it was rolled back and
-                                    // will try to rollback again on drop. We need to ignore that
-                                    // rollback.
+                                if (tx.blocking_send(res).is_err() || !reported_ok) && res_ok {
+                                    // The ROLLBACK was processed but not acknowledged, or reported as
+                                    // failed because a callback panicked. This means that the
+                                    // `Transaction` doesn't know it was rolled back and will try to
+                                    // rollback again on drop. We need to ignore that rollback.
                                     ignore_next_start_rollback = true;
                                 }
                             }
                         }
+                        Command::Run { f } => f(&mut conn),
                         Command::ClearCache { tx } => {
                             conn.statements.clear();
                             update_cached_statements_size(&conn, &shared.cached_statements_size);
@@ -256,24 +524,76 @@ impl ConnectionWorker {
         &mut self,
         query: String,
         args: Option<Arguments>,
+        options: QueryOptions,
         chan_size: usize,
     ) -> Result<flume::Receiver<Result<Either<QueryResult, Row>, Error>>, Error> {
-        let (tx, rx) = flume::bounded(chan_size);
+        let (tx, rx) = flume::bounded(options.row_buffer_size.unwrap_or(chan_size));
 
-        self.command_tx
-            .send_async(Command::Execute {
-                query: query.into(),
-                arguments: args,
+        self.send(Command::Execute {
+            query: query.into(),
+            arguments: args,
+            options,
+            tx: Results::Rows(tx),
+        })
+        .await?;
+
+        Ok(rx)
+    }
+
+    /// Like [`execute`](Self::execute), but with rows sent in pages of up to `page_size` rows, and statement results
+    /// dropped.
+    pub(crate) async fn execute_paged(
+        &mut self,
+        query: String,
+        args: Option<Arguments>,
+        options: QueryOptions,
+        page_size: usize,
+        chan_size: usize,
+    ) -> Result<flume::Receiver<PageResult>, Error> {
+        // Bound the channel to about as many rows as a row channel holds
+        let size = page_size.max(1);
+        let chan_size = options.row_buffer_size.unwrap_or(chan_size);
+        let (tx, rx) = flume::bounded(chan_size.div_ceil(size).max(1));
+
+        self.send(Command::Execute {
+            query: query.into(),
+            arguments: args,
+            options,
+            tx: Results::Pages {
                 tx,
-            })
-            .await
-            .map_err(|_| Error::WorkerCrashed)?;
+                size,
+                page: Vec::with_capacity(size),
+            },
+        })
+        .await?;
 
         Ok(rx)
     }
 
-    pub(crate) async fn begin(&mut self) -> Result<(), Error> {
-        self.oneshot_cmd_with_ack(|tx| Command::Begin { tx })
+    /// Queue a command for the worker. If the command buffer is full, this waits for space, and reports waits longer
+    /// than the threshold set with [`Musq::on_command_buffer_saturated`](crate::Musq::on_command_buffer_saturated).
+    async fn send(&self, command: Command) -> Result<(), Error> {
+        let command = match self.command_tx.try_send(command) {
+            Ok(()) => return Ok(()),
+            Err(flume::TrySendError::Full(command)) => command,
+            Err(flume::TrySendError::Disconnected(_)) => return Err(Error::WorkerCrashed),
+        };
+        let started = Instant::now();
+        self.command_tx
+            .send_async(command)
+            .await
+            .map_err(|_| Error::WorkerCrashed)?;
+        if let Some(on_saturated) = &self.on_saturated {
+            let waited = started.elapsed();
+            if waited >= on_saturated.threshold {
+                (on_saturated.callback)(self.id, waited);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn begin(&mut self, behavior: TransactionBehavior) -> Result<(), Error> {
+        self.oneshot_cmd_with_ack(|tx| Command::Begin { behavior, tx })
             .await?
     }
 
@@ -287,22 +607,37 @@ impl ConnectionWorker {
             .await?
     }
 
+    pub(crate) fn handle(&self) -> WorkerHandle {
+        WorkerHandle(self.command_tx.clone())
+    }
+
     pub(crate) fn start_rollback(&mut self) -> Result<(), Error> {
         self.command_tx
             .send(Command::Rollback { tx: None })
             .map_err(|_| Error::WorkerCrashed)
     }
 
+    /// Run `sql` on the worker without waiting for it, for cleanup that has to start from `Drop`. Failures are
+    /// logged.
+    pub(crate) fn start_exec(&mut self, sql: String) -> Result<(), Error> {
+        self.command_tx
+            .send(Command::Run {
+                f: Box::new(move |conn| {
+                    if let Err(error) = conn.handle.exec(sql) {
+                        tracing::warn!(%error, "failed to run cleanup statement");
+                    }
+                }),
+            })
+            .map_err(|_| Error::WorkerCrashed)
+    }
+
     async fn oneshot_cmd<F, T>(&mut self, command: F) -> Result<T, Error>
     where
         F: FnOnce(oneshot::Sender<T>) -> Command,
     {
         let (tx, rx) = oneshot::channel();
 
-        self.command_tx
-            .send_async(command(tx))
-            .await
-            .map_err(|_| Error::WorkerCrashed)?;
+        self.send(command(tx)).await?;
 
         rx.await.map_err(|_| Error::WorkerCrashed)
     }
@@ -313,14 +648,55 @@ impl ConnectionWorker {
     {
         let (tx, rx) = rendezvous_oneshot::channel();
 
-        self.command_tx
-            .send_async(command(tx))
-            .await
-            .map_err(|_| Error::WorkerCrashed)?;
+        self.send(command(tx)).await?;
 
         rx.recv().await.map_err(|_| Error::WorkerCrashed)
     }
 
+    /// Run `f` against the connection on the worker thread, for blocking operations that shouldn't hold up the async
+    /// runtime.
+    pub(crate) async fn run<F, T>(&mut self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut ConnectionState) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.oneshot_cmd(|tx| Command::Run {
+            f: Box::new(move |conn| {
+                tx.send(f(conn)).ok();
+            }),
+        })
+        .await
+    }
+
+    /// Run a user closure, catching any panic so that it doesn't take down the worker.
+    pub(crate) async fn run_in_worker<F, T>(&mut self, f: F) -> Result<thread::Result<T>, Error>
+    where
+        F: FnOnce(&mut ConnectionState) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let shared = Arc::clone(&self.shared);
+        self.run(move |conn| {
+            let ret = panic::catch_unwind(AssertUnwindSafe(|| f(conn)));
+            update_cached_statements_size(conn, &shared.cached_statements_size);
+            ret
+        })
+        .await
+    }
+
+    /// Run `query` without adding its statements to the statement cache.
+    pub(crate) async fn execute_uncached(&mut self, query: String) -> Result<QueryResult, Error> {
+        let shared = Arc::clone(&self.shared);
+        self.run(move |conn| {
+            shared.activity.start(&query);
+            let res = execute::execute_uncached(conn, &query, &shared.activity);
+            shared.activity.finish();
+            conn.callback_panics
+                .check(res)
+                .map_err(|e| conn.progress.map_err(conn.interrupt.map_err(e)))
+        })
+        .await?
+    }
+
     pub(crate) async fn clear_cache(&mut self) -> Result<(), Error> {
         self.oneshot_cmd(|tx| Command::ClearCache { tx }).await
     }
@@ -329,11 +705,11 @@ impl ConnectionWorker {
         let (guard, res) = futures_util::future::join(
             // we need to join the wait queue for the lock before we send the message
             self.shared.conn.lock(),
-            self.command_tx.send_async(Command::UnlockDb),
+            self.send(Command::UnlockDb),
         )
         .await;
 
-        res.map_err(|_| Error::WorkerCrashed)?;
+        res?;
 
         Ok(guard)
     }
@@ -363,17 +739,23 @@ fn prepare(conn: &mut ConnectionState, query: &str) -> Result<Statement, Error>
     let statement = conn.statements.get(query)?;
 
     let mut columns = None;
+    let mut readonly = true;
+    let mut parameters = 0;
 
-    while let Some(statement) = statement.prepare_next(&mut conn.handle)? {
+    while let Some(statement) = statement.prepare_next(&mut conn.handle, conn.hooks.changes())? {
         // the first non-empty statement is chosen as the statement we pull columns from
         if !statement.columns.is_empty() && columns.is_none() {
             columns = Some(Arc::clone(statement.columns));
         }
+        readonly &= statement.handle.is_readonly();
+        parameters += statement.handle.bind_parameter_count();
     }
 
     Ok(Statement {
         sql: query.to_string(),
         columns: columns.unwrap_or_default(),
+        readonly,
+        parameters,
     })
 }
 
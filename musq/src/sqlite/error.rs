@@ -271,6 +271,15 @@ impl SqliteError {
             message,
         }
     }
+
+    /// An error for a result code that wasn't recorded on a connection handle, e.g. one returned by a VFS method.
+    pub(crate) fn from_code(code: c_int, message: impl Into<String>) -> Self {
+        Self {
+            extended: ExtendedErrCode::from_code(code),
+            primary: PrimaryErrCode::from_code(code),
+            message: message.into(),
+        }
+    }
 }
 
 impl Display for SqliteError {
@@ -1,5 +1,9 @@
 pub use arguments::{ArgumentValue, Arguments, IntoArguments};
-pub use connection::Connection;
+pub use connection::{
+    ActiveQuery, Connection, ConnectionState, InterruptHandle, QueryState, QueueMetrics, SlowQuery,
+    TempTable, UpdateOp, WatchdogAction,
+};
+pub(crate) use connection::{Callback, CallbackPanics, ChangeTracker, Watchdog, WorkerSharedState};
 pub use error::SqliteError;
 pub use statement::Statement;
 pub use type_info::SqliteDataType;
@@ -15,7 +15,11 @@ use smallvec::SmallVec;
 
 use crate::{
     error::Error,
-    sqlite::{connection::ConnectionHandle, statement::StatementHandle, SqliteError},
+    sqlite::{
+        connection::{ChangeHooks, ConnectionHandle, TableName},
+        statement::StatementHandle,
+        SqliteError,
+    },
     ustr::UStr,
     Column,
 };
@@ -44,12 +48,19 @@ pub struct CompoundStatement {
 
     // each set of column names
     column_names: SmallVec<[Arc<HashMap<UStr, usize>>; 1]>,
+
+    // the tables each statement may write, when change tracking is enabled
+    writes: SmallVec<[Vec<TableName>; 1]>,
 }
 
 pub struct PreparedStatement<'a> {
     pub(crate) handle: &'a mut StatementHandle,
     pub(crate) columns: &'a Arc<Vec<Column>>,
     pub(crate) column_names: &'a Arc<HashMap<UStr, usize>>,
+    /// The tables the statement may write, as reported to the change tracking authorizer.
+    pub(crate) writes: &'a mut Vec<TableName>,
+    /// Whether this is the last statement of the query, as far as is known without preparing the rest of it.
+    pub(crate) is_last: bool,
 }
 
 impl CompoundStatement {
@@ -69,12 +80,16 @@ impl CompoundStatement {
             index: None,
             columns: SmallVec::with_capacity(1),
             column_names: SmallVec::with_capacity(1),
+            writes: SmallVec::with_capacity(1),
         })
     }
 
+    /// Move on to the next statement, preparing it if it hasn't been already. `changes` are the change tracking hooks
+    /// of the connection, if any, which collect the tables the statement may write.
     pub(crate) fn prepare_next(
         &mut self,
         conn: &mut ConnectionHandle,
+        changes: Option<&ChangeHooks>,
     ) -> Result<Option<PreparedStatement<'_>>, Error> {
         // increment `self.index` up to `self.handles.len()`
         self.index = self
@@ -87,6 +102,9 @@ impl CompoundStatement {
                 return Ok(None);
             }
 
+            if let Some(changes) = changes {
+                changes.preparing();
+            }
             if let Some(statement) = prepare_all(conn.as_ptr(), &mut self.tail)? {
                 let num = statement.column_count();
 
@@ -111,6 +129,12 @@ impl CompoundStatement {
                 self.handles.push(statement);
                 self.columns.push(Arc::new(columns));
                 self.column_names.push(Arc::new(column_names));
+
+                let mut writes = Vec::new();
+                if let Some(changes) = changes {
+                    changes.prepared(&mut writes);
+                }
+                self.writes.push(writes);
             }
         }
 
@@ -118,12 +142,19 @@ impl CompoundStatement {
     }
 
     pub fn current(&mut self) -> Option<PreparedStatement<'_>> {
+        let last = self
+            .handles
+            .len()
+            .checked_sub(1)
+            .filter(|_| self.tail.is_empty());
         self.index
             .filter(|&idx| idx < self.handles.len())
             .map(move |idx| PreparedStatement {
+                is_last: last == Some(idx),
                 handle: &mut self.handles[idx],
                 columns: &self.columns[idx],
                 column_names: &self.column_names[idx],
+                writes: &mut self.writes[idx],
             })
     }
 
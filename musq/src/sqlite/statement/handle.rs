@@ -1,22 +1,26 @@
 use std::ffi::c_void;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 
 use std::os::raw::{c_char, c_int};
 use std::ptr::NonNull;
 use std::str::from_utf8_unchecked;
+use std::thread;
 
 use libsqlite3_sys::{
     sqlite3, sqlite3_bind_blob64, sqlite3_bind_double, sqlite3_bind_int, sqlite3_bind_int64,
-    sqlite3_bind_null, sqlite3_bind_parameter_count, sqlite3_bind_parameter_name,
-    sqlite3_bind_text64, sqlite3_changes, sqlite3_clear_bindings, sqlite3_column_count,
-    sqlite3_column_decltype, sqlite3_column_name, sqlite3_column_type, sqlite3_column_value,
-    sqlite3_db_handle, sqlite3_finalize, sqlite3_reset, sqlite3_step, sqlite3_stmt, sqlite3_value,
-    SQLITE_DONE, SQLITE_LOCKED_SHAREDCACHE, SQLITE_MISUSE, SQLITE_OK, SQLITE_ROW, SQLITE_TRANSIENT,
-    SQLITE_UTF8,
+    sqlite3_bind_null, sqlite3_bind_parameter_count, sqlite3_bind_parameter_index,
+    sqlite3_bind_parameter_name, sqlite3_bind_text64, sqlite3_changes, sqlite3_clear_bindings,
+    sqlite3_column_count, sqlite3_column_database_name, sqlite3_column_decltype,
+    sqlite3_column_name, sqlite3_column_origin_name, sqlite3_column_table_name,
+    sqlite3_column_type, sqlite3_column_value, sqlite3_db_handle, sqlite3_finalize,
+    sqlite3_get_autocommit, sqlite3_reset, sqlite3_step, sqlite3_stmt, sqlite3_stmt_readonly,
+    sqlite3_value, SQLITE_BUSY, SQLITE_DONE, SQLITE_LOCKED_SHAREDCACHE, SQLITE_MISUSE, SQLITE_OK,
+    SQLITE_ROW, SQLITE_TRANSIENT, SQLITE_UTF8,
 };
 
 use crate::sqlite::type_info::SqliteDataType;
 use crate::sqlite::SqliteError;
+use crate::RetryPolicy;
 
 use super::unlock_notify;
 
@@ -43,6 +47,12 @@ impl StatementHandle {
         SqliteError::new(unsafe { self.db_handle() })
     }
 
+    /// Whether the statement makes no direct changes to the database file. See
+    /// [`sqlite3_stmt_readonly`](https://www.sqlite.org/c3ref/stmt_readonly.html).
+    pub(crate) fn is_readonly(&self) -> bool {
+        unsafe { sqlite3_stmt_readonly(self.0.as_ptr()) != 0 }
+    }
+
     pub(crate) fn column_count(&self) -> usize {
         // https://sqlite.org/c3ref/column_count.html
         unsafe { sqlite3_column_count(self.0.as_ptr()) as usize }
@@ -70,6 +80,11 @@ impl StatementHandle {
     }
 
     pub(crate) fn column_decltype(&self, index: usize) -> Option<SqliteDataType> {
+        self.column_decltype_str(index)?.parse().ok()
+    }
+
+    /// The declared type of a column, as written in the table definition.
+    pub(crate) fn column_decltype_str(&self, index: usize) -> Option<&str> {
         unsafe {
             let decl = sqlite3_column_decltype(self.0.as_ptr(), index as c_int);
             if decl.is_null() {
@@ -78,10 +93,23 @@ impl StatementHandle {
                 return None;
             }
 
-            let decl = from_utf8_unchecked(CStr::from_ptr(decl).to_bytes());
-            let ty: SqliteDataType = decl.parse().ok()?;
+            Some(from_utf8_unchecked(CStr::from_ptr(decl).to_bytes()))
+        }
+    }
+
+    /// The database, table and column a result column is read from, or `None` if it is an expression or subquery.
+    /// See [`sqlite3_column_table_name`](https://www.sqlite.org/c3ref/column_database_name.html).
+    pub(crate) fn column_origin(&self, index: usize) -> Option<(&str, &str, &str)> {
+        unsafe {
+            let database = sqlite3_column_database_name(self.0.as_ptr(), index as c_int);
+            let table = sqlite3_column_table_name(self.0.as_ptr(), index as c_int);
+            let column = sqlite3_column_origin_name(self.0.as_ptr(), index as c_int);
+            if database.is_null() || table.is_null() || column.is_null() {
+                return None;
+            }
+            let str = |p| from_utf8_unchecked(CStr::from_ptr(p).to_bytes());
 
-            Some(ty)
+            Some((str(database), str(table), str(column)))
         }
     }
 
@@ -107,6 +135,16 @@ impl StatementHandle {
         }
     }
 
+    // Index Of A Parameter With A Given Name, or 0 if there is no such parameter.
+
+    pub(crate) fn bind_parameter_index(&self, name: &str) -> usize {
+        let Ok(name) = CString::new(name) else {
+            return 0;
+        };
+        // https://www.sqlite.org/c3ref/bind_parameter_index.html
+        unsafe { sqlite3_bind_parameter_index(self.0.as_ptr(), name.as_ptr()) as usize }
+    }
+
     // Binding Values To Prepared Statements
     // https://www.sqlite.org/c3ref/bind_blob.html
 
@@ -177,7 +215,10 @@ impl StatementHandle {
         Ok(())
     }
 
-    pub(crate) fn step(&mut self) -> Result<bool, SqliteError> {
+    /// Step the statement, returning whether it produced a row. Statements that fail with `SQLITE_BUSY` outside an
+    /// explicit transaction are retried according to `retry`.
+    pub(crate) fn step(&mut self, retry: &RetryPolicy) -> Result<bool, SqliteError> {
+        let mut attempt = 1;
         // SAFETY: we have exclusive access to the handle
         unsafe {
             loop {
@@ -193,6 +234,14 @@ impl StatementHandle {
                         // (https://www.sqlite.org/unlock_notify.html)
                         sqlite3_reset(self.0.as_ptr());
                     }
+                    // Inside a transaction, retrying could wait on a writer that is waiting on us
+                    s if s & 0xff == SQLITE_BUSY
+                        && attempt < retry.max_attempts
+                        && sqlite3_get_autocommit(self.db_handle()) != 0 =>
+                    {
+                        thread::sleep(retry.delay(attempt));
+                        attempt += 1;
+                    }
                     _ => return Err(SqliteError::new(self.db_handle())),
                 }
             }
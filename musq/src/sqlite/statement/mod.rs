@@ -22,6 +22,8 @@ pub(crate) use handle::StatementHandle;
 pub struct Statement {
     pub(crate) sql: String,
     pub columns: Arc<Vec<Column>>,
+    pub(crate) readonly: bool,
+    pub(crate) parameters: usize,
 }
 
 impl Statement {
@@ -33,6 +35,22 @@ impl Statement {
         &self.columns
     }
 
+    /// The number of parameters the statement takes. For SQL containing several statements, this is the total over
+    /// all of them.
+    pub fn parameter_count(&self) -> usize {
+        self.parameters
+    }
+
+    /// Returns `true` if the statement makes no direct changes to the database, as reported by
+    /// [`sqlite3_stmt_readonly`](https://www.sqlite.org/c3ref/stmt_readonly.html). For SQL containing several
+    /// statements, this is `true` only if all of them are read-only.
+    ///
+    /// Note that SQLite considers transaction control statements such as `BEGIN` and `COMMIT` read-only, since they
+    /// don't themselves modify the database.
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
     pub fn query(&self) -> query::Query<Arguments> {
         query::query_statement(self)
     }
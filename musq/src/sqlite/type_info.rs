@@ -96,9 +96,7 @@ impl FromStr for SqliteDataType {
                 SqliteDataType::Float
             }
 
-            _ => {
-                panic!("unknown type");
-            }
+            _ => return Err(crate::Error::Protocol(format!("unknown type {s}"))),
         })
     }
 }
@@ -134,5 +132,7 @@ fn test_data_type_from_str() -> Result<(), crate::Error> {
     assert_eq!(SqliteDataType::Time, "TIME".parse()?);
     assert_eq!(SqliteDataType::Date, "DATE".parse()?);
 
+    assert!("NUMERIC".parse::<SqliteDataType>().is_err());
+
     Ok(())
 }
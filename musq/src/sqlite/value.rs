@@ -1,9 +1,9 @@
-use std::{ptr::NonNull, slice::from_raw_parts, str::from_utf8, sync::Arc};
+use std::{fmt::Write, ptr::NonNull, slice::from_raw_parts, str::from_utf8, sync::Arc};
 
 use libsqlite3_sys::{
     sqlite3_value, sqlite3_value_blob, sqlite3_value_bytes, sqlite3_value_double,
     sqlite3_value_dup, sqlite3_value_free, sqlite3_value_int, sqlite3_value_int64,
-    sqlite3_value_type, SQLITE_NULL,
+    sqlite3_value_type, SQLITE_BLOB, SQLITE_FLOAT, SQLITE_INTEGER, SQLITE_NULL,
 };
 
 use crate::{error::DecodeError, sqlite::type_info::SqliteDataType};
@@ -78,6 +78,82 @@ impl Value {
     pub fn is_null(&self) -> bool {
         unsafe { sqlite3_value_type(self.handle.0.as_ptr()) == SQLITE_NULL }
     }
+
+    /// The value as JSON, by its storage class: `NULL` as `null`, integers and reals as numbers, text as a string, and
+    /// blobs as a string of their standard, padded base64 encoding. Reals that JSON can't represent, such as
+    /// infinities, become `null`, and text that isn't valid UTF-8 is converted lossily. Text is never parsed as JSON.
+    pub fn to_json(&self) -> serde_json::Value {
+        match unsafe { sqlite3_value_type(self.handle.0.as_ptr()) } {
+            SQLITE_NULL => serde_json::Value::Null,
+            SQLITE_INTEGER => self.int64().into(),
+            SQLITE_FLOAT => serde_json::Number::from_f64(self.double())
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            SQLITE_BLOB => base64(self.blob()).into(),
+            _ => String::from_utf8_lossy(self.blob()).into_owned().into(),
+        }
+    }
+
+    /// The value as an SQL literal that reads back as the same value: text is quoted, blobs are written as `X'..'`
+    /// hex literals, and reals are written with enough digits to round-trip. Text that isn't valid UTF-8 or contains a
+    /// NUL is written as a hex literal cast to text, so its bytes survive unchanged.
+    pub(crate) fn sql_literal(&self) -> String {
+        match unsafe { sqlite3_value_type(self.handle.0.as_ptr()) } {
+            SQLITE_NULL => "NULL".into(),
+            SQLITE_INTEGER => self.int64().to_string(),
+            SQLITE_FLOAT => match self.double() {
+                f if f.is_nan() => "NULL".into(),
+                f if f.is_infinite() => if f > 0.0 { "1e999" } else { "-1e999" }.into(),
+                f => format!("{f:?}"),
+            },
+            SQLITE_BLOB => hex_literal(self.blob()),
+            _ => match from_utf8(self.blob()) {
+                Ok(text) if !text.contains('\0') => format!("'{}'", text.replace('\'', "''")),
+                // A quoted literal would corrupt these bytes or end at the NUL, so cast them from a blob instead
+                _ => format!("CAST({} AS TEXT)", hex_literal(self.blob())),
+            },
+        }
+    }
+
+    /// The number of bytes of data in the value: the length of text and blobs, and 8 for numbers. Unlike
+    /// [`blob`](Self::blob), this doesn't convert numbers to text.
+    pub(crate) fn size(&self) -> u64 {
+        match unsafe { sqlite3_value_type(self.handle.0.as_ptr()) } {
+            SQLITE_NULL => 0,
+            SQLITE_INTEGER | SQLITE_FLOAT => 8,
+            _ => unsafe { sqlite3_value_bytes(self.handle.0.as_ptr()) as u64 },
+        }
+    }
+}
+
+/// Bytes as an SQL `X'..'` blob literal.
+fn hex_literal(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2 + 3);
+    out.push_str("X'");
+    for b in bytes {
+        write!(out, "{b:02X}").ok();
+    }
+    out.push('\'');
+    out
+}
+
+/// Standard base64, with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
 }
 
 impl Drop for ValueHandle {
@@ -3,9 +3,16 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use futures_core::future::BoxFuture;
+use either::Either;
+use futures_core::{future::BoxFuture, stream::BoxStream};
+use futures_util::TryStreamExt;
 
-use crate::{pool::MaybePoolConnection, Connection, Result};
+use crate::{
+    executor::{Execute, Executor},
+    pool::MaybePoolConnection,
+    schema::quote_identifier,
+    Connection, QueryResult, Result, Row, SqliteDataType, Statement,
+};
 
 /// An in-progress database transaction or savepoint.
 ///
@@ -25,34 +32,340 @@ use crate::{pool::MaybePoolConnection, Connection, Result};
 pub struct Transaction<'c> {
     connection: MaybePoolConnection<'c>,
     open: bool,
+    #[cfg(feature = "tracing-spans")]
+    span: tracing::Span,
+}
+
+/// When a transaction takes its locks, set with [`Connection::begin_with`]. See
+/// [Transactions](https://www.sqlite.org/lang_transaction.html).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TransactionBehavior {
+    /// Take no locks until the database is first read or written. A transaction that reads before it writes has to
+    /// upgrade its lock at the first write, which fails with `SQLITE_BUSY` if another connection wrote in the
+    /// meantime.
+    #[default]
+    Deferred,
+    /// Take the write lock immediately, so the transaction can't fail to upgrade later. Other connections can still
+    /// read, and in WAL mode this is the usual choice for transactions that write.
+    Immediate,
+    /// Take the write lock immediately, and in rollback journal modes also stop other connections from reading.
+    Exclusive,
 }
 
 impl<'c> Transaction<'c> {
     /// Begin a nested transaction
     pub fn begin(conn: impl Into<MaybePoolConnection<'c>>) -> BoxFuture<'c, Result<Self>> {
+        Self::begin_with(conn, TransactionBehavior::Deferred)
+    }
+
+    /// Begin a transaction with the given behavior, or a savepoint if a transaction is already active.
+    pub fn begin_with(
+        conn: impl Into<MaybePoolConnection<'c>>,
+        behavior: TransactionBehavior,
+    ) -> BoxFuture<'c, Result<Self>> {
         let mut conn = conn.into();
-        Box::pin(async move {
-            Box::pin(conn.worker.begin()).await?;
+        #[cfg(feature = "tracing-spans")]
+        let span = tracing::info_span!(
+            "musq.transaction",
+            behavior = ?behavior,
+            outcome = tracing::field::Empty
+        );
+        #[cfg(feature = "tracing-spans")]
+        let tx_span = span.clone();
+        let begin = async move {
+            Box::pin(conn.worker.begin(behavior)).await?;
             Ok(Self {
                 connection: conn,
                 open: true,
+                #[cfg(feature = "tracing-spans")]
+                span: tx_span,
             })
-        })
+        };
+        #[cfg(feature = "tracing-spans")]
+        let begin = tracing::Instrument::instrument(begin, span);
+        Box::pin(begin)
     }
 
     /// Commits this transaction or savepoint.
     pub async fn commit(mut self) -> Result<()> {
-        Box::pin(self.connection.worker.commit()).await?;
+        let commit = Box::pin(self.connection.worker.commit());
+        #[cfg(feature = "tracing-spans")]
+        let commit = tracing::Instrument::instrument(commit, self.span.clone());
+        commit.await?;
         self.open = false;
+        #[cfg(feature = "tracing-spans")]
+        self.span.record("outcome", "commit");
         Ok(())
     }
 
     /// Aborts this transaction or savepoint.
     pub async fn rollback(mut self) -> Result<()> {
-        Box::pin(self.connection.worker.rollback()).await?;
+        let rollback = Box::pin(self.connection.worker.rollback());
+        #[cfg(feature = "tracing-spans")]
+        let rollback = tracing::Instrument::instrument(rollback, self.span.clone());
+        rollback.await?;
         self.open = false;
+        #[cfg(feature = "tracing-spans")]
+        self.span.record("outcome", "rollback");
         Ok(())
     }
+
+    /// Create a named savepoint. Changes made through the returned guard can be rolled back without aborting the
+    /// transaction, by calling [`Savepoint::rollback`], or kept by calling [`Savepoint::release`].
+    pub async fn savepoint(&mut self, name: &str) -> Result<Savepoint<'_>> {
+        Savepoint::create(&mut self.connection, name).await
+    }
+
+    /// Defer foreign key enforcement until the outermost transaction commits, with
+    /// [`PRAGMA defer_foreign_keys`](https://www.sqlite.org/pragma.html#pragma_defer_foreign_keys). Rows can then be
+    /// written in any order, and the commit fails with a foreign key constraint error if violations remain. SQLite
+    /// turns the setting off again when the transaction ends.
+    ///
+    /// Use [`Connection::foreign_key_check`] to find the violations before committing.
+    pub async fn defer_foreign_keys(&mut self) -> Result<()> {
+        run(
+            &mut self.connection,
+            "PRAGMA defer_foreign_keys = ON".into(),
+        )
+        .await
+    }
+}
+
+/// A named savepoint inside a transaction, created with [`Transaction::savepoint`].
+///
+/// A savepoint should end with a call to [`release`](Self::release), which keeps its changes as part of the enclosing
+/// transaction, or [`rollback`](Self::rollback), which undoes them. Either way the enclosing transaction carries on.
+/// If neither is called, the savepoint is rolled back when it is dropped. Savepoints can be nested with
+/// [`savepoint`](Self::savepoint).
+pub struct Savepoint<'t> {
+    connection: &'t mut Connection,
+    name: String,
+    open: bool,
+}
+
+impl<'t> Savepoint<'t> {
+    async fn create(connection: &'t mut Connection, name: &str) -> Result<Self> {
+        let name = quote_identifier(name);
+        run(connection, format!("SAVEPOINT {name}")).await?;
+        Ok(Self {
+            connection,
+            name,
+            open: true,
+        })
+    }
+
+    /// Create a savepoint nested inside this one.
+    pub async fn savepoint(&mut self, name: &str) -> Result<Savepoint<'_>> {
+        Savepoint::create(self.connection, name).await
+    }
+
+    /// Keep the changes made since the savepoint was created, as part of the enclosing transaction.
+    pub async fn release(mut self) -> Result<()> {
+        run(self.connection, format!("RELEASE SAVEPOINT {}", self.name)).await?;
+        self.open = false;
+        Ok(())
+    }
+
+    /// Undo the changes made since the savepoint was created.
+    pub async fn rollback(mut self) -> Result<()> {
+        run(self.connection, rollback_savepoint_sql(&self.name)).await?;
+        self.open = false;
+        Ok(())
+    }
+}
+
+impl<'t> Debug for Savepoint<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Savepoint")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl<'t> Deref for Savepoint<'t> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection
+    }
+}
+
+impl<'t> DerefMut for Savepoint<'t> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.connection
+    }
+}
+
+impl<'t> Drop for Savepoint<'t> {
+    fn drop(&mut self) {
+        if self.open {
+            let sql = rollback_savepoint_sql(&self.name);
+            self.connection.worker.start_exec(sql).ok();
+        }
+    }
+}
+
+/// A scope that behaves like a nested transaction, but only opens one once it is needed. Created with
+/// [`Connection::read_scope`].
+///
+/// Queries run through the scope are checked with
+/// [`sqlite3_stmt_readonly`](https://www.sqlite.org/c3ref/stmt_readonly.html) before they run. As long as every query
+/// is read-only the scope issues no `BEGIN` or `SAVEPOINT` statements, and takes no locks of its own. The first query
+/// that could write opens a transaction, or a savepoint if a transaction is already active, and from then on the
+/// scope behaves like a [`Transaction`]: [`commit`](Self::commit) keeps the changes, and [`rollback`](Self::rollback)
+/// or dropping the scope undoes them.
+///
+/// Queries must be run through the scope itself, as `&mut scope`, to be checked.
+///
+/// ```rust,ignore
+/// let mut scope = conn.read_scope();
+/// let user = query_as::<User>("SELECT * FROM users WHERE id = ?").bind(id).fetch_one(&mut scope).await?;
+/// if user.stale {
+///     // Opens a savepoint
+///     query("UPDATE users SET stale = 0 WHERE id = ?").bind(id).execute(&mut scope).await?;
+/// }
+/// scope.commit().await?;
+/// ```
+pub struct ReadScope<'c> {
+    connection: &'c mut Connection,
+    open: bool,
+}
+
+impl<'c> ReadScope<'c> {
+    pub(crate) fn new(connection: &'c mut Connection) -> Self {
+        Self {
+            connection,
+            open: false,
+        }
+    }
+
+    /// Whether a query that could write has opened a transaction or savepoint.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Keep the changes made in the scope. Does nothing if every query was read-only.
+    pub async fn commit(mut self) -> Result<()> {
+        if self.open {
+            self.connection.worker.commit().await?;
+            self.open = false;
+        }
+        Ok(())
+    }
+
+    /// Undo the changes made in the scope. Does nothing if every query was read-only.
+    pub async fn rollback(mut self) -> Result<()> {
+        if self.open {
+            self.connection.worker.rollback().await?;
+            self.open = false;
+        }
+        Ok(())
+    }
+
+    /// What needs checking before running `query`: whether its statement is known to be read-only, and its SQL. This
+    /// is `None` if a transaction is already open. The query isn't held across the check, since it needn't be `Sync`.
+    fn pending_check(&self, query: &impl Execute) -> Option<(Option<bool>, String)> {
+        (!self.open).then(|| {
+            (
+                query.statement().map(Statement::is_readonly),
+                query.sql().to_string(),
+            )
+        })
+    }
+
+    /// Open a transaction or savepoint unless the query is read-only, preparing it to find out if need be.
+    async fn check(&mut self, pending: Option<(Option<bool>, String)>) -> Result<()> {
+        let Some((readonly, sql)) = pending else {
+            return Ok(());
+        };
+        let readonly = match readonly {
+            Some(readonly) => readonly,
+            None => self.connection.worker.prepare(&sql).await?.is_readonly(),
+        };
+        if !readonly {
+            self.connection
+                .worker
+                .begin(TransactionBehavior::Deferred)
+                .await?;
+            self.open = true;
+        }
+        Ok(())
+    }
+}
+
+impl<'c> Debug for ReadScope<'c> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadScope")
+            .field("open", &self.open)
+            .finish()
+    }
+}
+
+impl<'c> Drop for ReadScope<'c> {
+    fn drop(&mut self) {
+        if self.open {
+            self.connection.worker.start_rollback().ok();
+        }
+    }
+}
+
+impl<'c, 's> Executor<'c> for &'c mut ReadScope<'s> {
+    fn fetch_many<'e, 'q: 'e, E>(self, query: E) -> BoxStream<'e, Result<Either<QueryResult, Row>>>
+    where
+        'c: 'e,
+        E: Execute + 'q,
+    {
+        Box::pin(try_stream! {
+            let pending = self.pending_check(&query);
+            self.check(pending).await?;
+            let mut s = self.connection.fetch_many(query);
+
+            while let Some(v) = s.try_next().await? {
+                r#yield!(v);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(self, query: E) -> BoxFuture<'e, Result<Option<Row>>>
+    where
+        'c: 'e,
+        E: Execute + 'q,
+    {
+        Box::pin(async move {
+            let pending = self.pending_check(&query);
+            self.check(pending).await?;
+            self.connection.fetch_optional(query).await
+        })
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [SqliteDataType],
+    ) -> BoxFuture<'e, Result<Statement>>
+    where
+        'c: 'e,
+    {
+        self.connection.prepare_with(sql, parameters)
+    }
+}
+
+async fn run(connection: &mut Connection, sql: String) -> Result<()> {
+    connection
+        .worker
+        .run(move |conn| {
+            conn.handle
+                .exec(sql)
+                .map_err(|e| conn.callback_panics.map_err(e))
+        })
+        .await?
+}
+
+/// Roll back to a savepoint and then release it: `ROLLBACK TO` leaves the savepoint in place.
+fn rollback_savepoint_sql(name: &str) -> String {
+    format!("ROLLBACK TO SAVEPOINT {name}; RELEASE SAVEPOINT {name}")
 }
 
 impl<'c> Debug for Transaction<'c> {
@@ -79,18 +392,29 @@ impl<'c> DerefMut for Transaction<'c> {
 impl<'c> Drop for Transaction<'c> {
     fn drop(&mut self) {
         if self.open {
+            #[cfg(feature = "tracing-spans")]
+            self.span.record("outcome", "dropped");
             self.connection.worker.start_rollback().ok();
         }
     }
 }
 
-pub fn begin_ansi_transaction_sql(depth: usize) -> String {
-    // The first savepoint is equivalent to a BEGIN
-    format!("SAVEPOINT _sqlx_savepoint_{}", depth)
+pub fn begin_ansi_transaction_sql(depth: usize, behavior: TransactionBehavior) -> String {
+    match (depth, behavior) {
+        (0, TransactionBehavior::Immediate) => "BEGIN IMMEDIATE".into(),
+        (0, TransactionBehavior::Exclusive) => "BEGIN EXCLUSIVE".into(),
+        // The first savepoint is equivalent to a BEGIN
+        _ => format!("SAVEPOINT _sqlx_savepoint_{}", depth),
+    }
 }
 
 pub fn commit_ansi_transaction_sql(depth: usize) -> String {
-    format!("RELEASE SAVEPOINT _sqlx_savepoint_{}", depth - 1)
+    if depth == 1 {
+        // Releasing the outermost savepoint commits, but a transaction opened with BEGIN has no savepoint to release
+        "COMMIT".into()
+    } else {
+        format!("RELEASE SAVEPOINT _sqlx_savepoint_{}", depth - 1)
+    }
 }
 
 pub fn rollback_ansi_transaction_sql(depth: usize) -> String {
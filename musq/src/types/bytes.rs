@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc};
 
 use crate::{
     compatible, decode::Decode, encode::Encode, error::DecodeError, ArgumentValue, SqliteDataType,
@@ -43,3 +43,16 @@ impl<'r> Decode<'r> for Arc<Vec<u8>> {
         Ok(Arc::new(value.blob().to_owned()))
     }
 }
+
+impl<'q> Encode for Cow<'q, [u8]> {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Blob(Arc::new(self.into_owned()))
+    }
+}
+
+/// Borrows the bytes from the row's value, without copying them.
+impl<'r> Decode<'r> for Cow<'r, [u8]> {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        <&'r [u8]>::decode(value).map(Cow::Borrowed)
+    }
+}
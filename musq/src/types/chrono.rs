@@ -0,0 +1,137 @@
+//! Support for [`chrono`](https://docs.rs/chrono) dates and times, enabled by the `chrono` feature.
+//!
+//! Values are stored as text in the formats SQLite's date and time functions produce. Decoding accepts every text
+//! format those functions accept, with a space or `T` between date and time, optional seconds and fractional seconds,
+//! and an optional `Z` or `[+-]HH:MM` offset. Integers decode as Unix timestamps in seconds, and reals as Julian day
+//! numbers, as returned by `julianday()`.
+use std::sync::Arc;
+
+pub use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono::{FixedOffset, SecondsFormat, TimeZone};
+
+use crate::{
+    compatible,
+    decode::Decode,
+    encode::Encode,
+    error::DecodeError,
+    sqlite::{ArgumentValue, SqliteDataType},
+    Value,
+};
+
+/// The Julian day number of the Unix epoch.
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2440587.5;
+
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+const TIME_FORMATS: &[&str] = &["%H:%M:%S%.f", "%H:%M"];
+
+impl Encode for DateTime<Utc> {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Text(Arc::new(self.to_rfc3339_opts(SecondsFormat::AutoSi, true)))
+    }
+}
+
+impl Encode for NaiveDateTime {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Text(Arc::new(self.format("%Y-%m-%d %H:%M:%S%.f").to_string()))
+    }
+}
+
+impl Encode for NaiveDate {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Text(Arc::new(self.format("%Y-%m-%d").to_string()))
+    }
+}
+
+impl Encode for NaiveTime {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Text(Arc::new(self.format("%H:%M:%S%.f").to_string()))
+    }
+}
+
+impl<'r> Decode<'r> for DateTime<Utc> {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        decode_datetime(value)
+    }
+}
+
+impl<'r> Decode<'r> for NaiveDateTime {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        decode_datetime(value).map(|dt| dt.naive_utc())
+    }
+}
+
+impl<'r> Decode<'r> for NaiveDate {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        if value.type_info() == SqliteDataType::Text {
+            if let Ok(date) = NaiveDate::parse_from_str(value.text()?, "%Y-%m-%d") {
+                return Ok(date);
+            }
+        }
+        decode_datetime(value).map(|dt| dt.date_naive())
+    }
+}
+
+impl<'r> Decode<'r> for NaiveTime {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        compatible!(value, SqliteDataType::Text);
+        let value = value.text()?;
+        TIME_FORMATS
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+            .ok_or_else(|| format!("invalid time: {}", value).into())
+    }
+}
+
+fn decode_datetime(value: &Value) -> Result<DateTime<Utc>, DecodeError> {
+    compatible!(
+        value,
+        SqliteDataType::Text | SqliteDataType::Int64 | SqliteDataType::Int | SqliteDataType::Float
+    );
+    let dt = match value.type_info() {
+        SqliteDataType::Text => decode_datetime_from_text(value.text()?),
+        SqliteDataType::Int | SqliteDataType::Int64 => DateTime::from_timestamp(value.int64(), 0),
+        SqliteDataType::Float => {
+            let millis = ((value.double() - UNIX_EPOCH_JULIAN_DAY) * 86_400_000.0).round();
+            if millis.is_finite() && millis.abs() < i64::MAX as f64 {
+                let millis = millis as i64;
+                DateTime::from_timestamp(
+                    millis.div_euclid(1000),
+                    (millis.rem_euclid(1000) * 1_000_000) as u32,
+                )
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    dt.ok_or_else(|| format!("invalid datetime: {}", value.text().unwrap_or_default()).into())
+}
+
+fn decode_datetime_from_text(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    // A trailing `Z` is UTC, like no offset at all
+    let naive = value.strip_suffix('Z').unwrap_or(value);
+    for format in DATETIME_FORMATS {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(naive, format) {
+            return Some(Utc.from_utc_datetime(&dt));
+        }
+        if let Ok(dt) = DateTime::<FixedOffset>::parse_from_str(value, &format!("{format}%:z")) {
+            return Some(dt.with_timezone(&Utc));
+        }
+    }
+
+    // A date on its own is midnight
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|dt| Utc.from_utc_datetime(&dt))
+}
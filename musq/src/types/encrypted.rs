@@ -0,0 +1,178 @@
+//! Encrypted columns, enabled by the `encryption` feature.
+//!
+//! [`Encrypted<T>`] is a value sealed into a BLOB with a [`ColumnKey`], so that individual columns holding sensitive
+//! data are protected at rest without encrypting the whole database:
+//!
+//! ```rust,ignore
+//! let key = ColumnKey::from_provider(&load_key)?;
+//! query("INSERT INTO users (name, email) VALUES (?, ?)")
+//!     .bind(name)
+//!     .bind(Encrypted::seal(&key, "users.email", &email)?)
+//!     .execute(&pool)
+//!     .await?;
+//! let sealed: Encrypted<String> = query_scalar("SELECT email FROM users").fetch_one(&pool).await?;
+//! let email = sealed.open(&key, "users.email")?;
+//! ```
+//!
+//! Values are serialized to JSON and sealed with AES-256-GCM under a random nonce, so tampering is detected when they
+//! are opened, and equal values produce different ciphertexts. The flip side is that SQL can't compare, index or
+//! search encrypted columns, beyond testing for `NULL`. Store `Option<Encrypted<T>>` to keep `NULL`s visible to SQL.
+//!
+//! A value is sealed for a named column, conventionally `table.column`, which is authenticated along with it: a value
+//! copied into another column fails to open under that column's name. The key is passed in explicitly rather than
+//! held by the pool or the process, so that pools with different keys can't mix up their values.
+use std::{fmt, io, marker::PhantomData, sync::Arc};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    compatible,
+    decode::Decode,
+    encode::Encode,
+    encryption::KeyProvider,
+    error::DecodeError,
+    sqlite::{ArgumentValue, SqliteDataType},
+    Error, Value,
+};
+
+/// The first byte of every encrypted value, to allow the format to change.
+const VERSION: u8 = 2;
+
+const NONCE_LEN: usize = 12;
+
+/// A 256-bit key for sealing and opening [`Encrypted`] values.
+#[derive(Clone)]
+pub struct ColumnKey {
+    cipher: Aes256Gcm,
+}
+
+impl ColumnKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(&key.into()),
+        }
+    }
+
+    /// Fetch the key from `provider`.
+    pub fn from_provider(provider: &impl KeyProvider) -> io::Result<Self> {
+        provider.key().map(Self::new)
+    }
+}
+
+impl fmt::Debug for ColumnKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ColumnKey").finish_non_exhaustive()
+    }
+}
+
+/// A value of type `T`, sealed with a [`ColumnKey`]. See the [module docs](self).
+pub struct Encrypted<T> {
+    data: Arc<Vec<u8>>,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> Encrypted<T> {
+    /// The sealed value, as stored in the database.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl<T: Serialize> Encrypted<T> {
+    /// Seal `value` with `key`, for storage in `column`.
+    pub fn seal(key: &ColumnKey, column: &str, value: &T) -> Result<Self, Error> {
+        let plaintext = serde_json::to_vec(value)
+            .map_err(|e| Error::Protocol(format!("failed to serialize an encrypted value: {e}")))?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = key
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: column.as_bytes(),
+                },
+            )
+            .map_err(|_| Error::Protocol("failed to encrypt a value".into()))?;
+
+        let mut data = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+        data.push(VERSION);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(Self {
+            data: Arc::new(data),
+            value: PhantomData,
+        })
+    }
+}
+
+impl<T: DeserializeOwned> Encrypted<T> {
+    /// Open the value with `key`. Fails if the key is wrong, the value was sealed for a column other than `column`, or
+    /// the data has been tampered with.
+    pub fn open(&self, key: &ColumnKey, column: &str) -> Result<T, Error> {
+        let (nonce, ciphertext) = self.data[1..].split_at(NONCE_LEN);
+        let plaintext = key
+            .cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: column.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                Error::Decode(DecodeError::Conversion(format!(
+                    "failed to decrypt value for {column}, the key or column is wrong or the data is corrupt"
+                )))
+            })?;
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| Error::Decode(DecodeError::Conversion(e.to_string())))
+    }
+}
+
+impl<T> Clone for Encrypted<T> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            value: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Encrypted<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl<T> Eq for Encrypted<T> {}
+
+impl<T> fmt::Debug for Encrypted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Encrypted(<{} bytes>)", self.data.len())
+    }
+}
+
+impl<T> Encode for Encrypted<T> {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Blob(self.data)
+    }
+}
+
+impl<'r, T> Decode<'r> for Encrypted<T> {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        compatible!(value, SqliteDataType::Blob);
+        let data = value.blob();
+        if data.len() < 1 + NONCE_LEN || data[0] != VERSION {
+            return Err(DecodeError::Conversion("not an encrypted value".into()));
+        }
+        Ok(Self {
+            data: Arc::new(data.to_vec()),
+            value: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{decode::Decode, error::DecodeError, Value};
+
+/// The marker that [`QueryBuilder::push_highlight`](crate::QueryBuilder::push_highlight) and
+/// [`push_snippet`](crate::QueryBuilder::push_snippet) insert before each match.
+pub const HIGHLIGHT_START: &str = "\u{2}";
+
+/// The marker inserted after each match. See [`HIGHLIGHT_START`].
+pub const HIGHLIGHT_END: &str = "\u{3}";
+
+/// The `rank` column of an [FTS5](https://www.sqlite.org/fts5.html) table, by default the
+/// [bm25](https://www.sqlite.org/fts5.html#the_bm25_function) score of the row.
+///
+/// Better matches have lower ranks, so `ORDER BY rank` returns the best matches first.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Fts5Rank(pub f64);
+
+impl Fts5Rank {
+    /// The rank as a relevance score, where better matches score higher.
+    pub fn relevance(&self) -> f64 {
+        -self.0
+    }
+}
+
+impl<'r> Decode<'r> for Fts5Rank {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        f64::decode(value).map(Self)
+    }
+}
+
+/// Text returned by the FTS5 `highlight()` or `snippet()` functions, with the matches marked by [`HIGHLIGHT_START`]
+/// and [`HIGHLIGHT_END`]. Queries built with [`QueryBuilder::push_highlight`](crate::QueryBuilder::push_highlight)
+/// or [`push_snippet`](crate::QueryBuilder::push_snippet) use these markers.
+///
+/// ```rust,ignore
+/// let mut qb = QueryBuilder::new("SELECT ");
+/// qb.push_highlight("docs", 0).push(" FROM docs WHERE ").push_match("docs", "rust sqlite");
+/// for doc in qb.build_query_scalar::<Fts5Highlight>().fetch_all(&pool).await? {
+///     println!("{}", doc.render("<b>", "</b>"));
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fts5Highlight(String);
+
+impl Fts5Highlight {
+    /// The pieces of the text in order, each with whether it is a match.
+    pub fn segments(&self) -> impl Iterator<Item = (&str, bool)> + '_ {
+        let mut rest = self.0.as_str();
+        let mut highlighted = false;
+        std::iter::from_fn(move || loop {
+            if rest.is_empty() {
+                return None;
+            }
+            let marker = if highlighted {
+                HIGHLIGHT_END
+            } else {
+                HIGHLIGHT_START
+            };
+            let (segment, next) = match rest.split_once(marker) {
+                Some((segment, next)) => (segment, next),
+                None => (rest, ""),
+            };
+            let current = highlighted;
+            highlighted = !highlighted;
+            rest = next;
+            if !segment.is_empty() {
+                return Some((segment, current));
+            }
+        })
+    }
+
+    /// The text without markers.
+    pub fn text(&self) -> String {
+        self.segments().map(|(segment, _)| segment).collect()
+    }
+
+    /// The text with each match wrapped in `open` and `close`. The text itself is not escaped.
+    pub fn render(&self, open: &str, close: &str) -> String {
+        let mut out = String::with_capacity(self.0.len());
+        for (segment, highlighted) in self.segments() {
+            if highlighted {
+                out.push_str(open);
+                out.push_str(segment);
+                out.push_str(close);
+            } else {
+                out.push_str(segment);
+            }
+        }
+        out
+    }
+
+    /// The text with its markers.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'r> Decode<'r> for Fts5Highlight {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        String::decode(value).map(Self)
+    }
+}
+
+/// Quote every whitespace-separated term of `input` as an FTS5 string, so that the result matches rows containing all
+/// of the terms, and none of FTS5's query syntax in `input` takes effect.
+pub fn fts5_escape(input: &str) -> String {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        "\"\"".to_string()
+    } else {
+        terms.join(" ")
+    }
+}
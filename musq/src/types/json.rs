@@ -0,0 +1,66 @@
+use std::{fmt, sync::Arc};
+
+use crate::{encode::Encode, ArgumentValue};
+
+/// A path into a JSON document, for SQLite's [JSON functions](https://www.sqlite.org/json1.html#path_arguments).
+///
+/// Paths are built from the root, and encode as their text form, so they can be bound as parameters:
+///
+/// ```rust,ignore
+/// let path = JsonPath::root().key("tags").index(0);
+/// assert_eq!(path.as_str(), "$.tags[0]");
+/// query("SELECT json_extract(data, ?) FROM t").bind(path);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonPath(String);
+
+impl JsonPath {
+    /// The path to the whole document, `$`.
+    pub fn root() -> Self {
+        Self("$".to_string())
+    }
+
+    /// The member `key` of an object. Keys that aren't plain identifiers are quoted. SQLite's path syntax has no
+    /// escapes, so a key can't contain a double quote.
+    pub fn key(mut self, key: &str) -> Self {
+        let plain = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if plain {
+            self.0.push('.');
+            self.0.push_str(key);
+        } else {
+            self.0.push_str(".\"");
+            self.0.push_str(key);
+            self.0.push('"');
+        }
+        self
+    }
+
+    /// The element at `index` of an array.
+    pub fn index(mut self, index: usize) -> Self {
+        self.0.push_str(&format!("[{index}]"));
+        self
+    }
+
+    /// The element `n` places from the end of an array, so that `from_end(1)` is the last element.
+    pub fn from_end(mut self, n: usize) -> Self {
+        self.0.push_str(&format!("[#-{n}]"));
+        self
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Encode for JsonPath {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Text(Arc::new(self.0))
+    }
+}
@@ -12,15 +12,30 @@
 //! | `u8`                                  | INTEGER             |
 //! | `u16`                                 | INTEGER             |
 //! | `u32`                                 | INTEGER             |
+//! | [`U64BitCast`]                        | INTEGER             |
+//! | `NonZeroI8` ... `NonZeroI64`          | INTEGER             |
+//! | `NonZeroU8` ... `NonZeroU32`          | INTEGER             |
 //! | `f32`                                 | REAL                |
 //! | `f64`                                 | REAL                |
-//! | `&str`, [`String`]                    | TEXT                |
-//! | `&[u8]`, `Vec<u8>`                    | BLOB                |
+//! | `&str`, `Cow<str>`, [`String`]        | TEXT                |
+//! | `&[u8]`, `Cow<[u8]>`, `Vec<u8>`       | BLOB                |
+//! | `std::time::SystemTime`               | INTEGER             |
+//! | [`Rfc3339Time`]                       | DATETIME            |
+//! | `std::time::Duration`                 | INTEGER             |
 //! | `time::PrimitiveDateTime`             | DATETIME            |
 //! | `time::OffsetDateTime`                | DATETIME            |
 //! | `time::Date`                          | DATE                |
 //! | `time::Time`                          | TIME                |
+//! | `chrono::NaiveDateTime`               | DATETIME            |
+//! | `chrono::DateTime<Utc>`               | DATETIME            |
+//! | `chrono::NaiveDate`                   | DATE                |
+//! | `chrono::NaiveTime`                   | TIME                |
+//! | `rust_decimal::Decimal`               | TEXT                |
 //! | `bstr::BString`                       | BLOB                |
+//! | `Encrypted<T>`                        | BLOB                |
+//! | [`JsonPath`]                          | TEXT                |
+//! | [`Fts5Rank`]                          | REAL                |
+//! | [`Fts5Highlight`]                     | TEXT                |
 //!
 //! #### Note: Unsigned Integers
 //!
@@ -34,23 +49,63 @@
 //! Bit-casting it to `i64` or storing it as `REAL`, `BLOB` or `TEXT` would change the semantics of the value in SQL and
 //! so violates the principle of least surprise.
 //!
+//! Where the semantics don't matter, for instance for IDs that are only compared for equality, wrap the value in
+//! [`U64BitCast`] to store it bit-cast to `i64` explicitly.
+//!
+//! #### Note: Non-zero Integers
+//!
+//! The `std::num::NonZero*` types are stored as their primitive type. Decoding a zero into one is a
+//! [`DecodeError::Conversion`](crate::DecodeError::Conversion).
+//!
+//! #### Note: Standard Library Times
+//!
+//! A `SystemTime` is stored as whole seconds since the Unix epoch, rounded down. Wrap it in [`Rfc3339Time`] to store
+//! it as text with its fractional seconds instead. Either decodes from integer seconds, real seconds or any text
+//! accepted by `time::OffsetDateTime`. A `Duration` is stored as whole milliseconds.
+//!
+//! # Borrowing
+//!
+//! `&str`, `&[u8]` and their `Cow` forms decode by borrowing from the [`Row`](crate::Row) they are read from, so
+//! reading them doesn't copy the value. The borrow lasts as long as the row, and a [`FromRow`](crate::FromRow) struct
+//! with a lifetime parameter may hold borrowed fields.
+//!
 //! # Nullable
 //!
 //! `Option<T>` is supported where `T` implements `Encode` or `Decode`. An `Option<T>` represents a potentially `NULL`
 //! value from SQLite.
 pub mod bstr;
+#[cfg(feature = "chrono")]
+pub mod chrono;
+#[cfg(feature = "encryption")]
+pub mod encrypted;
+#[cfg(feature = "rust_decimal")]
+pub mod rust_decimal;
 pub mod time;
 
 mod bool;
 mod bytes;
 mod float;
+mod fts5;
 mod int;
+mod json;
+mod nonzero;
+mod std_time;
 mod str;
 mod uint;
 
+#[cfg(feature = "encryption")]
+pub use encrypted::{ColumnKey, Encrypted};
+pub use fts5::{fts5_escape, Fts5Highlight, Fts5Rank, HIGHLIGHT_END, HIGHLIGHT_START};
+pub use json::JsonPath;
+pub use std_time::Rfc3339Time;
+pub use uint::U64BitCast;
+
 #[macro_export]
 macro_rules! compatible {
     ($x:expr, $($y:path)|+) => {
+        if $x.is_null() {
+            return Err(DecodeError::unexpected_null());
+        }
         let t = $x.type_info();
         if !t.is_null() && !matches!(t, $($y)|+) {
             return Err(DecodeError::DataType(t))
@@ -0,0 +1,34 @@
+use std::num::{NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU16, NonZeroU32, NonZeroU8};
+
+use crate::{decode::Decode, encode::Encode, error::DecodeError, sqlite::ArgumentValue, Value};
+
+/// Implement `Encode` and `Decode` for a non-zero integer through its primitive type, rejecting zeros on decode.
+macro_rules! nonzero {
+    ($($ty:ty => $prim:ty),+ $(,)?) => {
+        $(
+            impl Encode for $ty {
+                fn encode(self) -> ArgumentValue {
+                    self.get().encode()
+                }
+            }
+
+            impl<'r> Decode<'r> for $ty {
+                fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+                    <$ty>::new(<$prim>::decode(value)?).ok_or_else(|| {
+                        DecodeError::Conversion(concat!("zero decoded into ", stringify!($ty)).into())
+                    })
+                }
+            }
+        )+
+    };
+}
+
+nonzero!(
+    NonZeroI8 => i8,
+    NonZeroI16 => i16,
+    NonZeroI32 => i32,
+    NonZeroI64 => i64,
+    NonZeroU8 => u8,
+    NonZeroU16 => u16,
+    NonZeroU32 => u32,
+);
@@ -0,0 +1,47 @@
+//! Support for [`rust_decimal`](https://docs.rs/rust_decimal) decimals, enabled by the `rust_decimal` feature.
+//!
+//! Decimals are stored as text, so that they keep their exact value and scale, which SQLite's `REAL` would round.
+//! Text compares lexically in SQL, so `ORDER BY` and comparisons on a decimal column don't follow numeric order, and
+//! SQLite's arithmetic and aggregate functions such as `SUM()` convert the text to floating point. Integers and reals
+//! also decode, for columns written by other code.
+use std::{str::FromStr, sync::Arc};
+
+pub use rust_decimal::Decimal;
+
+use crate::{
+    compatible,
+    decode::Decode,
+    encode::Encode,
+    error::DecodeError,
+    sqlite::{ArgumentValue, SqliteDataType},
+    Value,
+};
+
+impl Encode for Decimal {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Text(Arc::new(self.to_string()))
+    }
+}
+
+impl<'r> Decode<'r> for Decimal {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        compatible!(
+            value,
+            SqliteDataType::Text
+                | SqliteDataType::Int64
+                | SqliteDataType::Int
+                | SqliteDataType::Float
+        );
+        match value.type_info() {
+            SqliteDataType::Int | SqliteDataType::Int64 => Ok(Decimal::from(value.int64())),
+            SqliteDataType::Float => Decimal::try_from(value.double())
+                .map_err(|e| DecodeError::Conversion(e.to_string())),
+            _ => {
+                let text = value.text()?.trim();
+                Decimal::from_str(text)
+                    .or_else(|_| Decimal::from_scientific(text))
+                    .map_err(|e| DecodeError::Conversion(format!("invalid decimal {text:?}: {e}")))
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! [`SystemTime`] and [`Duration`] from the standard library, for applications that don't use a date and time crate.
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    compatible,
+    decode::Decode,
+    encode::Encode,
+    error::DecodeError,
+    sqlite::{ArgumentValue, SqliteDataType},
+    Value,
+};
+
+/// A [`SystemTime`] stored as RFC3339 text in UTC, rather than as Unix seconds.
+///
+/// A bare `SystemTime` is stored as whole seconds since the Unix epoch, which is compact and sorts numerically, but
+/// drops fractional seconds. This wrapper keeps them, and stores a value that SQLite's date and time functions and
+/// people reading the database understand directly. Both decode from either representation.
+///
+/// Times outside the years 0000 to 9999, which RFC3339 can't express, are stored as Unix seconds instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rfc3339Time(pub SystemTime);
+
+impl From<SystemTime> for Rfc3339Time {
+    fn from(value: SystemTime) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Rfc3339Time> for SystemTime {
+    fn from(value: Rfc3339Time) -> Self {
+        value.0
+    }
+}
+
+impl Encode for SystemTime {
+    fn encode(self) -> ArgumentValue {
+        // Round down, so that times before the epoch land on the second they fall in
+        let secs = match self.duration_since(UNIX_EPOCH) {
+            Ok(d) => i64::try_from(d.as_secs()).unwrap_or(i64::MAX),
+            Err(e) => {
+                let d = e.duration();
+                let secs = i64::try_from(d.as_secs()).unwrap_or(i64::MAX);
+                -secs - i64::from(d.subsec_nanos() > 0)
+            }
+        };
+        ArgumentValue::Int64(secs)
+    }
+}
+
+impl Encode for Rfc3339Time {
+    fn encode(self) -> ArgumentValue {
+        // Times that `time` can't represent or RFC 3339 can't format fall back to Unix seconds
+        match rfc3339(self.0) {
+            Some(text) => ArgumentValue::Text(Arc::new(text)),
+            None => self.0.encode(),
+        }
+    }
+}
+
+fn rfc3339(time: SystemTime) -> Option<String> {
+    let time = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => OffsetDateTime::UNIX_EPOCH.checked_add(d.try_into().ok()?),
+        Err(e) => OffsetDateTime::UNIX_EPOCH.checked_sub(e.duration().try_into().ok()?),
+    }?;
+    time.format(&Rfc3339).ok()
+}
+
+impl<'r> Decode<'r> for SystemTime {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        compatible!(
+            value,
+            SqliteDataType::Text
+                | SqliteDataType::Int64
+                | SqliteDataType::Int
+                | SqliteDataType::Float
+        );
+        let time = match value.type_info() {
+            SqliteDataType::Int | SqliteDataType::Int64 => {
+                let secs = value.int64();
+                let offset = Duration::from_secs(secs.unsigned_abs());
+                if secs < 0 {
+                    UNIX_EPOCH.checked_sub(offset)
+                } else {
+                    UNIX_EPOCH.checked_add(offset)
+                }
+            }
+            SqliteDataType::Float => {
+                let secs = value.double();
+                Duration::try_from_secs_f64(secs.abs())
+                    .ok()
+                    .and_then(|offset| {
+                        if secs < 0.0 {
+                            UNIX_EPOCH.checked_sub(offset)
+                        } else {
+                            UNIX_EPOCH.checked_add(offset)
+                        }
+                    })
+            }
+            _ => return OffsetDateTime::decode(value).map(SystemTime::from),
+        };
+        time.ok_or_else(|| {
+            format!("invalid timestamp: {}", value.text().unwrap_or_default()).into()
+        })
+    }
+}
+
+impl<'r> Decode<'r> for Rfc3339Time {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        SystemTime::decode(value).map(Self)
+    }
+}
+
+/// Durations are stored as whole milliseconds, saturating at `i64::MAX`.
+impl Encode for Duration {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Int64(i64::try_from(self.as_millis()).unwrap_or(i64::MAX))
+    }
+}
+
+impl<'r> Decode<'r> for Duration {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        compatible!(value, SqliteDataType::Int | SqliteDataType::Int64);
+        let millis = value.int64();
+        u64::try_from(millis)
+            .map(Duration::from_millis)
+            .map_err(|_| DecodeError::Conversion(format!("negative duration: {millis}")))
+    }
+}
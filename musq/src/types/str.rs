@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc};
 
 use crate::{
     compatible, decode::Decode, encode::Encode, error::DecodeError, ArgumentValue, SqliteDataType,
@@ -43,3 +43,16 @@ impl<'r> Decode<'r> for Arc<String> {
         value.text().map(|x| Arc::new(x.to_owned()))
     }
 }
+
+impl<'q> Encode for Cow<'q, str> {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Text(Arc::new(self.into_owned()))
+    }
+}
+
+/// Borrows the text from the row's value, without copying it.
+impl<'r> Decode<'r> for Cow<'r, str> {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        <&'r str>::decode(value).map(Cow::Borrowed)
+    }
+}
@@ -44,3 +44,38 @@ impl<'r> Decode<'r> for u32 {
         Ok(value.int64().try_into()?)
     }
 }
+
+/// A `u64` stored by reinterpreting its bits as an `i64`, for values such as hashes or externally assigned IDs that
+/// use the full `u64` range.
+///
+/// Values above `i64::MAX` are stored as negative integers, so SQL sees a different number than Rust does: they sort
+/// before smaller values in `ORDER BY`, compare as less than them in `WHERE` clauses, and turn negative in arithmetic
+/// and aggregates. Use this only for columns that are compared for equality. Decoding reverses the cast, so a value
+/// always round-trips unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct U64BitCast(pub u64);
+
+impl From<u64> for U64BitCast {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<U64BitCast> for u64 {
+    fn from(value: U64BitCast) -> Self {
+        value.0
+    }
+}
+
+impl Encode for U64BitCast {
+    fn encode(self) -> ArgumentValue {
+        ArgumentValue::Int64(self.0 as i64)
+    }
+}
+
+impl<'r> Decode<'r> for U64BitCast {
+    fn decode(value: &'r Value) -> Result<Self, DecodeError> {
+        compatible!(value, SqliteDataType::Int | SqliteDataType::Int64);
+        Ok(Self(value.int64() as u64))
+    }
+}
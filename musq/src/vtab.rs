@@ -0,0 +1,500 @@
+//! Eponymous virtual tables implemented in Rust.
+//!
+//! A [`VirtualTable`] exposes data that lives outside the database, such as an in-memory collection or an API, as a
+//! table that SQL can query. Tables are registered on the [`Musq`](crate::Musq) builder with
+//! [`register_vtab`](crate::Musq::register_vtab), and exist on every connection it opens, without a
+//! `CREATE VIRTUAL TABLE` statement. They are read-only.
+//!
+//! SQLite asks the table how it would answer a query in [`best_index`](VirtualTable::best_index), opens a cursor,
+//! and then walks it with [`filter`](VirtualCursor::filter), [`next`](VirtualCursor::next) and
+//! [`column`](VirtualCursor::column). Columns declared `HIDDEN` act as the arguments of a table-valued function:
+//!
+//! ```rust,ignore
+//! struct Series;
+//!
+//! impl VirtualTable for Series {
+//!     type Cursor = SeriesCursor;
+//!
+//!     fn schema(&self) -> String {
+//!         "CREATE TABLE x(value, stop HIDDEN)".into()
+//!     }
+//!
+//!     fn best_index(&self, info: &mut IndexInfo) -> Result<(), String> {
+//!         let constraints = info.constraints();
+//!         match constraints.iter().position(|c| c.column == 1 && c.op == ConstraintOp::Eq) {
+//!             Some(i) if constraints[i].usable => info.use_constraint(i, 0, true),
+//!             Some(_) => info.reject(),
+//!             None => return Err("series needs a stop".into()),
+//!         }
+//!         Ok(())
+//!     }
+//!
+//!     fn open(&self) -> Result<SeriesCursor, String> {
+//!         Ok(SeriesCursor::default())
+//!     }
+//! }
+//!
+//! let pool = Musq::new().register_vtab("series", Series).open_in_memory().await?;
+//! let values: Vec<i64> = query_scalar("SELECT value FROM series(3)").fetch_all(&pool).await?;
+//! ```
+//!
+//! Callbacks run on the connection's worker thread, inside the query that uses the table. If one panics, the query
+//! fails with [`Error::CallbackPanicked`](crate::Error::CallbackPanicked), and the table is poisoned on that
+//! connection, as for [user-defined functions](crate::functions).
+use std::{
+    ffi::CString,
+    mem,
+    os::raw::{c_char, c_int, c_void},
+    sync::Arc,
+};
+
+use libsqlite3_sys::{
+    sqlite3, sqlite3_context, sqlite3_create_module_v2, sqlite3_declare_vtab, sqlite3_free,
+    sqlite3_index_info, sqlite3_int64, sqlite3_module, sqlite3_mprintf, sqlite3_result_error,
+    sqlite3_value, sqlite3_vtab, sqlite3_vtab_cursor, SQLITE_CONSTRAINT, SQLITE_ERROR,
+    SQLITE_INDEX_CONSTRAINT_EQ, SQLITE_INDEX_CONSTRAINT_GE, SQLITE_INDEX_CONSTRAINT_GLOB,
+    SQLITE_INDEX_CONSTRAINT_GT, SQLITE_INDEX_CONSTRAINT_IS, SQLITE_INDEX_CONSTRAINT_ISNOT,
+    SQLITE_INDEX_CONSTRAINT_ISNOTNULL, SQLITE_INDEX_CONSTRAINT_ISNULL, SQLITE_INDEX_CONSTRAINT_LE,
+    SQLITE_INDEX_CONSTRAINT_LIKE, SQLITE_INDEX_CONSTRAINT_LIMIT, SQLITE_INDEX_CONSTRAINT_LT,
+    SQLITE_INDEX_CONSTRAINT_MATCH, SQLITE_INDEX_CONSTRAINT_NE, SQLITE_INDEX_CONSTRAINT_OFFSET,
+    SQLITE_INDEX_CONSTRAINT_REGEXP, SQLITE_OK,
+};
+
+use crate::{
+    functions::{self, Function},
+    sqlite::{Callback, CallbackPanics},
+    ArgumentValue, Error, SqliteError, Value,
+};
+
+/// A read-only virtual table. See the [module docs](self).
+pub trait VirtualTable: Send + Sync + 'static {
+    /// The cursor that walks the rows of a query.
+    type Cursor: VirtualCursor;
+
+    /// The `CREATE TABLE` statement that declares the table's columns to SQLite. The table name is ignored.
+    fn schema(&self) -> String;
+
+    /// Choose how to answer a query, by marking the constraints the cursor will apply and recording the choice in
+    /// the index number passed to [`VirtualCursor::filter`]. May be called several times while a query is planned,
+    /// with different sets of usable constraints. An error fails the query, for instance when a required argument is
+    /// missing.
+    ///
+    /// By default the table is scanned in full, with every constraint checked by SQLite.
+    fn best_index(&self, info: &mut IndexInfo) -> Result<(), String> {
+        let _ = info;
+        Ok(())
+    }
+
+    /// Open a cursor for a query.
+    fn open(&self) -> Result<Self::Cursor, String>;
+}
+
+/// A cursor over the rows of a [`VirtualTable`].
+pub trait VirtualCursor {
+    /// Start a scan, positioned on the first row. `index_num` is the number chosen by
+    /// [`best_index`](VirtualTable::best_index), and `args` holds the values of the constraints it used, in the order
+    /// it gave them.
+    fn filter(&mut self, index_num: i32, args: &[Value]) -> Result<(), String>;
+
+    /// Advance to the next row.
+    fn next(&mut self) -> Result<(), String>;
+
+    /// Whether the cursor has moved past the last row.
+    fn eof(&self) -> bool;
+
+    /// The value of column `index` of the current row.
+    fn column(&self, index: usize) -> Result<ArgumentValue, String>;
+
+    /// The rowid of the current row. Fails by default.
+    fn rowid(&self) -> Result<i64, String> {
+        Err("virtual table has no rowid".into())
+    }
+}
+
+/// The operator of a constraint passed to [`VirtualTable::best_index`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintOp {
+    Eq,
+    Gt,
+    Le,
+    Lt,
+    Ge,
+    Match,
+    Like,
+    Glob,
+    Regexp,
+    Ne,
+    IsNot,
+    IsNotNull,
+    IsNull,
+    Is,
+    Limit,
+    Offset,
+    /// An operator overloaded by a function, or one added by a later version of SQLite.
+    Other(u8),
+}
+
+impl ConstraintOp {
+    fn from_code(code: u8) -> Self {
+        match c_int::from(code) {
+            SQLITE_INDEX_CONSTRAINT_EQ => Self::Eq,
+            SQLITE_INDEX_CONSTRAINT_GT => Self::Gt,
+            SQLITE_INDEX_CONSTRAINT_LE => Self::Le,
+            SQLITE_INDEX_CONSTRAINT_LT => Self::Lt,
+            SQLITE_INDEX_CONSTRAINT_GE => Self::Ge,
+            SQLITE_INDEX_CONSTRAINT_MATCH => Self::Match,
+            SQLITE_INDEX_CONSTRAINT_LIKE => Self::Like,
+            SQLITE_INDEX_CONSTRAINT_GLOB => Self::Glob,
+            SQLITE_INDEX_CONSTRAINT_REGEXP => Self::Regexp,
+            SQLITE_INDEX_CONSTRAINT_NE => Self::Ne,
+            SQLITE_INDEX_CONSTRAINT_ISNOT => Self::IsNot,
+            SQLITE_INDEX_CONSTRAINT_ISNOTNULL => Self::IsNotNull,
+            SQLITE_INDEX_CONSTRAINT_ISNULL => Self::IsNull,
+            SQLITE_INDEX_CONSTRAINT_IS => Self::Is,
+            SQLITE_INDEX_CONSTRAINT_LIMIT => Self::Limit,
+            SQLITE_INDEX_CONSTRAINT_OFFSET => Self::Offset,
+            _ => Self::Other(code),
+        }
+    }
+}
+
+/// A constraint of the `WHERE` clause on a column of a virtual table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexConstraint {
+    /// The column, counting from 0, or -1 for the rowid.
+    pub column: i32,
+    pub op: ConstraintOp,
+    /// Whether the constraint can be used in this plan. A plan that needs an unusable constraint should be
+    /// [rejected](IndexInfo::reject).
+    pub usable: bool,
+}
+
+/// A term of the `ORDER BY` clause of a query on a virtual table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexOrderBy {
+    /// The column, counting from 0, or -1 for the rowid.
+    pub column: i32,
+    pub desc: bool,
+}
+
+/// The query plan under consideration in [`VirtualTable::best_index`]. See
+/// [`xBestIndex`](https://www.sqlite.org/vtab.html#the_xbestindex_method).
+pub struct IndexInfo<'a> {
+    raw: &'a mut sqlite3_index_info,
+    rejected: bool,
+}
+
+impl IndexInfo<'_> {
+    /// The constraints of the query on the table.
+    pub fn constraints(&self) -> Vec<IndexConstraint> {
+        (0..self.raw.nConstraint as usize)
+            .map(|i| {
+                // SAFETY: SQLite passes `nConstraint` constraints
+                let c = unsafe { &*self.raw.aConstraint.add(i) };
+                IndexConstraint {
+                    column: c.iColumn,
+                    op: ConstraintOp::from_code(c.op),
+                    usable: c.usable != 0,
+                }
+            })
+            .collect()
+    }
+
+    /// The `ORDER BY` terms of the query.
+    pub fn order_by(&self) -> Vec<IndexOrderBy> {
+        (0..self.raw.nOrderBy as usize)
+            .map(|i| {
+                // SAFETY: SQLite passes `nOrderBy` terms
+                let o = unsafe { &*self.raw.aOrderBy.add(i) };
+                IndexOrderBy {
+                    column: o.iColumn,
+                    desc: o.desc != 0,
+                }
+            })
+            .collect()
+    }
+
+    /// Pass the value of constraint `constraint` to [`VirtualCursor::filter`] as `args[arg]`. If `omit` is true,
+    /// SQLite trusts the cursor to apply the constraint, and doesn't check it again.
+    ///
+    /// The constraints used must be given consecutive argument positions, starting at 0.
+    pub fn use_constraint(&mut self, constraint: usize, arg: usize, omit: bool) {
+        assert!(constraint < self.raw.nConstraint as usize);
+        // SAFETY: SQLite passes a usage slot for every constraint
+        let usage = unsafe { &mut *self.raw.aConstraintUsage.add(constraint) };
+        usage.argvIndex = arg as c_int + 1;
+        usage.omit = omit.into();
+    }
+
+    /// Set the index number passed to [`VirtualCursor::filter`].
+    pub fn set_index_num(&mut self, num: i32) {
+        self.raw.idxNum = num;
+    }
+
+    /// Tell SQLite that the cursor returns rows in the order of the `ORDER BY` clause, so it needn't sort them.
+    pub fn set_order_by_consumed(&mut self, consumed: bool) {
+        self.raw.orderByConsumed = consumed.into();
+    }
+
+    /// The estimated cost of the plan. SQLite picks the cheapest of the plans it considers.
+    pub fn set_estimated_cost(&mut self, cost: f64) {
+        self.raw.estimatedCost = cost;
+    }
+
+    /// Reject this plan, so that SQLite tries another. SQLite fails the query if it has no plan left.
+    pub fn reject(&mut self) {
+        self.rejected = true;
+    }
+
+    /// The estimated number of rows the plan returns.
+    pub fn set_estimated_rows(&mut self, rows: i64) {
+        self.raw.estimatedRows = rows;
+    }
+}
+
+/// The module registered with SQLite, which owns the table. SQLite keeps a pointer to `module` until it calls
+/// `destroy_module`.
+struct Module<T> {
+    module: sqlite3_module,
+    table: Callback<Arc<T>>,
+}
+
+/// A connection's instance of a table. `base` must come first, as SQLite treats a pointer to this as a pointer to
+/// `sqlite3_vtab`.
+#[repr(C)]
+struct Table<T> {
+    base: sqlite3_vtab,
+    module: *mut Module<T>,
+}
+
+#[repr(C)]
+struct Cursor<T: VirtualTable> {
+    base: sqlite3_vtab_cursor,
+    cursor: T::Cursor,
+}
+
+/// A registration of the virtual table `table` under `name`, applied to each new connection.
+pub(crate) fn module<T: VirtualTable>(name: &str, table: T) -> Function {
+    let name = name.to_string();
+    let table = Arc::new(table);
+    Function::new(move |db: *mut sqlite3, panics: &Arc<CallbackPanics>| {
+        let c_name = CString::new(name.as_str())
+            .map_err(|_| Error::Protocol("virtual table name contains nul bytes".into()))?;
+        // SAFETY: a zeroed module has no methods, and we fill in the ones we implement. Leaving `xCreate` unset
+        // makes the table eponymous-only.
+        let mut module: sqlite3_module = unsafe { mem::zeroed() };
+        module.iVersion = 1;
+        module.xConnect = Some(connect::<T>);
+        module.xBestIndex = Some(best_index::<T>);
+        module.xDisconnect = Some(disconnect::<T>);
+        module.xOpen = Some(open::<T>);
+        module.xClose = Some(close::<T>);
+        module.xFilter = Some(filter::<T>);
+        module.xNext = Some(next::<T>);
+        module.xEof = Some(eof::<T>);
+        module.xColumn = Some(column::<T>);
+        module.xRowid = Some(rowid::<T>);
+        let data = Box::into_raw(Box::new(Module {
+            module,
+            table: Callback::new(
+                format!("virtual table {name}"),
+                table.clone(),
+                panics.clone(),
+            ),
+        }));
+        // SQLite takes ownership of `data`, and frees it with `destroy_module` when the module is replaced or the
+        // connection closes, even if registration fails
+        let rc = unsafe {
+            sqlite3_create_module_v2(
+                db,
+                c_name.as_ptr(),
+                &(*data).module,
+                data.cast(),
+                Some(destroy_module::<T>),
+            )
+        };
+        if rc != SQLITE_OK {
+            return Err(SqliteError::new(db).into());
+        }
+        Ok(())
+    })
+}
+
+/// A copy of `msg` allocated by SQLite, for it to free.
+unsafe fn sqlite_string(msg: &str) -> *mut c_char {
+    let msg = CString::new(msg.replace('\0', "")).unwrap_or_default();
+    sqlite3_mprintf(c"%s".as_ptr(), msg.as_ptr())
+}
+
+/// Report `msg` as the error of the last call on `vtab`.
+unsafe fn set_error(vtab: *mut sqlite3_vtab, msg: &str) -> c_int {
+    sqlite3_free((*vtab).zErrMsg.cast());
+    (*vtab).zErrMsg = sqlite_string(msg);
+    SQLITE_ERROR
+}
+
+/// The module of a table.
+unsafe fn module_of<'a, T>(vtab: *mut sqlite3_vtab) -> &'a mut Module<T> {
+    &mut *(*vtab.cast::<Table<T>>()).module
+}
+
+/// Call `f` with a cursor, through its table's panic shim, and report the outcome to SQLite.
+unsafe fn with_cursor<T: VirtualTable>(
+    cursor: *mut sqlite3_vtab_cursor,
+    f: impl FnOnce(&mut T::Cursor) -> Result<(), String>,
+) -> c_int {
+    let vtab = (*cursor).pVtab;
+    let cursor = &mut (*cursor.cast::<Cursor<T>>()).cursor;
+    match module_of::<T>(vtab).table.call(|_| f(cursor)) {
+        Some(Ok(())) => SQLITE_OK,
+        Some(Err(msg)) => set_error(vtab, &msg),
+        None => set_error(vtab, "virtual table panicked"),
+    }
+}
+
+unsafe extern "C" fn connect<T: VirtualTable>(
+    db: *mut sqlite3,
+    aux: *mut c_void,
+    _argc: c_int,
+    _argv: *const *const c_char,
+    vtab: *mut *mut sqlite3_vtab,
+    err: *mut *mut c_char,
+) -> c_int {
+    let module = aux.cast::<Module<T>>();
+    let schema = match (*module).table.call(|table| table.schema()) {
+        Some(schema) => schema,
+        None => {
+            *err = sqlite_string("virtual table panicked");
+            return SQLITE_ERROR;
+        }
+    };
+    let Ok(schema) = CString::new(schema) else {
+        *err = sqlite_string("virtual table schema contains nul bytes");
+        return SQLITE_ERROR;
+    };
+    let rc = sqlite3_declare_vtab(db, schema.as_ptr());
+    if rc != SQLITE_OK {
+        return rc;
+    }
+    *vtab = Box::into_raw(Box::new(Table {
+        // SAFETY: SQLite fills in the base
+        base: mem::zeroed(),
+        module,
+    }))
+    .cast();
+    SQLITE_OK
+}
+
+unsafe extern "C" fn best_index<T: VirtualTable>(
+    vtab: *mut sqlite3_vtab,
+    info: *mut sqlite3_index_info,
+) -> c_int {
+    let mut info = IndexInfo {
+        raw: &mut *info,
+        rejected: false,
+    };
+    match module_of::<T>(vtab)
+        .table
+        .call(|table| table.best_index(&mut info))
+    {
+        Some(Ok(())) if info.rejected => SQLITE_CONSTRAINT,
+        Some(Ok(())) => SQLITE_OK,
+        Some(Err(msg)) => set_error(vtab, &msg),
+        None => set_error(vtab, "virtual table panicked"),
+    }
+}
+
+unsafe extern "C" fn disconnect<T>(vtab: *mut sqlite3_vtab) -> c_int {
+    sqlite3_free((*vtab).zErrMsg.cast());
+    drop(Box::from_raw(vtab.cast::<Table<T>>()));
+    SQLITE_OK
+}
+
+unsafe extern "C" fn open<T: VirtualTable>(
+    vtab: *mut sqlite3_vtab,
+    cursor: *mut *mut sqlite3_vtab_cursor,
+) -> c_int {
+    match module_of::<T>(vtab).table.call(|table| table.open()) {
+        Some(Ok(inner)) => {
+            *cursor = Box::into_raw(Box::new(Cursor::<T> {
+                // SAFETY: SQLite fills in the base
+                base: mem::zeroed(),
+                cursor: inner,
+            }))
+            .cast();
+            SQLITE_OK
+        }
+        Some(Err(msg)) => set_error(vtab, &msg),
+        None => set_error(vtab, "virtual table panicked"),
+    }
+}
+
+unsafe extern "C" fn close<T: VirtualTable>(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    drop(Box::from_raw(cursor.cast::<Cursor<T>>()));
+    SQLITE_OK
+}
+
+unsafe extern "C" fn filter<T: VirtualTable>(
+    cursor: *mut sqlite3_vtab_cursor,
+    index_num: c_int,
+    _index_str: *const c_char,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) -> c_int {
+    let args = functions::values(argc, argv);
+    with_cursor::<T>(cursor, |cursor| cursor.filter(index_num, &args))
+}
+
+unsafe extern "C" fn next<T: VirtualTable>(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    with_cursor::<T>(cursor, VirtualCursor::next)
+}
+
+unsafe extern "C" fn eof<T: VirtualTable>(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    let vtab = (*cursor).pVtab;
+    let inner = &(*cursor.cast::<Cursor<T>>()).cursor;
+    // A panicking cursor has no more rows; the query fails with the panic
+    match module_of::<T>(vtab).table.call(|_| inner.eof()) {
+        Some(eof) => eof.into(),
+        None => 1,
+    }
+}
+
+unsafe extern "C" fn column<T: VirtualTable>(
+    cursor: *mut sqlite3_vtab_cursor,
+    ctx: *mut sqlite3_context,
+    index: c_int,
+) -> c_int {
+    let vtab = (*cursor).pVtab;
+    let inner = &(*cursor.cast::<Cursor<T>>()).cursor;
+    match module_of::<T>(vtab)
+        .table
+        .call(|_| inner.column(index as usize))
+    {
+        Some(Ok(value)) => {
+            functions::set_result(ctx, &value);
+            SQLITE_OK
+        }
+        Some(Err(msg)) => {
+            sqlite3_result_error(ctx, msg.as_ptr().cast(), msg.len() as c_int);
+            SQLITE_ERROR
+        }
+        None => {
+            sqlite3_result_error(ctx, c"virtual table panicked".as_ptr(), -1);
+            SQLITE_ERROR
+        }
+    }
+}
+
+unsafe extern "C" fn rowid<T: VirtualTable>(
+    cursor: *mut sqlite3_vtab_cursor,
+    rowid: *mut sqlite3_int64,
+) -> c_int {
+    with_cursor::<T>(cursor, |cursor| {
+        *rowid = cursor.rowid()?;
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn destroy_module<T>(data: *mut c_void) {
+    drop(Box::from_raw(data.cast::<Module<T>>()));
+}
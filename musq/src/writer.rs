@@ -0,0 +1,92 @@
+//! Serialize writes through a single task.
+//!
+//! SQLite allows one writer at a time. When many tasks write through a pool at once, each write's connection waits
+//! on the database lock, and under heavy contention writes fail with `SQLITE_BUSY` once the
+//! [busy timeout](crate::Musq::busy_timeout) runs out. A [`Writer`] avoids this contention among the writes made
+//! through it: writes are queued, and a single task executes them one after another on one connection. Writes made
+//! through the pool directly, or by other processes, still contend with the writer for the lock.
+//!
+//! ```rust,ignore
+//! let writer = pool.writer();
+//! writer
+//!     .execute(query("INSERT INTO events (kind) VALUES (?)").bind("click"))
+//!     .await?;
+//! ```
+//!
+//! Unlike a [`WriteBatcher`](crate::batch::WriteBatcher), each write is committed on its own, so the writer adds no
+//! latency, but doesn't save on commits either.
+use futures_channel::oneshot;
+
+use crate::{query::Query, Arguments, Error, Pool, QueryResult, Result};
+
+/// The number of writes that may be queued before [`Writer::execute`] applies backpressure.
+const QUEUE_SIZE: usize = 1024;
+
+pub(crate) struct Write {
+    query: Query<Arguments>,
+    tx: oneshot::Sender<Result<QueryResult>>,
+}
+
+/// A handle to a pool's writer task, returned by [`Pool::writer`]. See the [module docs](self).
+///
+/// Writes are executed in the order they were submitted. The task holds a connection from the pool while it has
+/// writes queued, and returns it when the queue is empty. It exits once every handle has been dropped and the queue is
+/// drained, or when the pool is closed; writes that are queued then, or submitted afterwards, fail with
+/// [`Error::PoolClosed`].
+#[derive(Debug, Clone)]
+pub struct Writer {
+    tx: flume::Sender<Write>,
+}
+
+impl Writer {
+    /// The pool's writer, starting its task if no handle to it is alive.
+    pub(crate) fn for_pool(pool: &Pool) -> Self {
+        let mut shared = pool.0.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(tx) = shared.as_ref().and_then(flume::WeakSender::upgrade) {
+            return Self { tx };
+        }
+        let (tx, rx) = flume::bounded(QUEUE_SIZE);
+        *shared = Some(tx.downgrade());
+        pool.0.options.spawn(run(pool.clone(), rx));
+        Self { tx }
+    }
+
+    /// Queue a write, and wait for it to be executed.
+    pub async fn execute(&self, query: Query<Arguments>) -> Result<QueryResult> {
+        let (tx, rx) = oneshot::channel();
+        // The task only stops while handles are alive when the pool is closed
+        self.tx
+            .send_async(Write { query, tx })
+            .await
+            .map_err(|_| Error::PoolClosed)?;
+        rx.await.map_err(|_| Error::WorkerCrashed)?
+    }
+}
+
+async fn run(pool: Pool, rx: flume::Receiver<Write>) {
+    // Stop when the pool is closed, rather than keeping it alive until every handle is dropped
+    let mut closed = pool.close_event();
+    while let Ok(Ok(first)) = closed.do_until(rx.recv_async()).await {
+        let mut conn = match pool.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                first.tx.send(Err(e)).ok();
+                continue;
+            }
+        };
+        let mut next = Some(first);
+        while let Some(write) = next.take().or_else(|| rx.try_recv().ok()) {
+            // Return the connection as soon as the pool starts closing, so that closing doesn't wait on the queue
+            let result = if pool.is_closed() {
+                Err(Error::PoolClosed)
+            } else {
+                write.query.execute(&mut *conn).await
+            };
+            write.tx.send(result).ok();
+        }
+    }
+    drop(pool);
+    for write in rx.drain() {
+        write.tx.send(Err(Error::PoolClosed)).ok();
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::{Arc, Mutex};
+
+use musq::{
+    archive::{Archiver, Destination, Period, Progress, TimeFormat},
+    query, query_scalar, Error, JournalMode, Musq,
+};
+
+#[tokio::test]
+async fn it_archives_into_tables() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    query("CREATE TABLE samples (id INTEGER PRIMARY KEY, ts TEXT NOT NULL, v INTEGER)")
+        .execute(&pool)
+        .await?;
+    for (ts, v) in [
+        ("2024-01-05", 1),
+        ("2024-01-20", 2),
+        ("2024-01-31 23:59:59", 3),
+        ("2024-02-10", 4),
+        ("2024-03-01", 5),
+        ("not a date", 6),
+    ] {
+        query("INSERT INTO samples (ts, v) VALUES (?, ?)")
+            .bind(ts)
+            .bind(v)
+            .execute(&pool)
+            .await?;
+    }
+
+    let progress = Arc::new(Mutex::new(vec![]));
+    let p = progress.clone();
+    let moved = Archiver::new(pool.clone(), "samples", "ts")
+        .batch_size(2)
+        .on_progress(move |x: &Progress| p.lock().unwrap().push(x.clone()))
+        .run("2024-03-01")
+        .await?;
+    assert_eq!(moved, 4);
+
+    let progress: Vec<_> = progress
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|p| (p.period.clone(), p.archived, p.total))
+        .collect();
+    assert_eq!(
+        progress,
+        vec![
+            ("2024-01".to_string(), 2, 2),
+            ("2024-01".to_string(), 3, 3),
+            ("2024-02".to_string(), 1, 4),
+        ]
+    );
+
+    let sum: i64 = query_scalar("SELECT sum(v) FROM samples_2024_01")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(sum, 6);
+    let left: Vec<i64> = query_scalar("SELECT v FROM samples ORDER BY v")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(left, vec![5, 6]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_archives_into_files() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-archive")?;
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .open(dir.path().join("live.db"))
+        .await?;
+    query("CREATE TABLE samples (ts INTEGER NOT NULL, v INTEGER)")
+        .execute(&pool)
+        .await?;
+    // 2023-12-31, 2024-01-01 and 2024-01-02, in seconds
+    for ts in [1704000000, 1704067200, 1704153600] {
+        query("INSERT INTO samples VALUES (?, ?)")
+            .bind(ts)
+            .bind(1)
+            .execute(&pool)
+            .await?;
+    }
+
+    let moved = Archiver::new(pool.clone(), "samples", "ts")
+        .period(Period::Year)
+        .time_format(TimeFormat::UnixSeconds)
+        .destination(Destination::Files(dir.path().join("archive")))
+        .run(1704153600)
+        .await?;
+    assert_eq!(moved, 2);
+
+    for (year, n) in [("2023", 1), ("2024", 1)] {
+        let archive = Musq::new()
+            .open(dir.path().join(format!("archive/samples-{year}.db")))
+            .await?;
+        let count: i64 = query_scalar("SELECT count(*) FROM samples")
+            .fetch_one(&archive)
+            .await?;
+        assert_eq!(count, n);
+    }
+    let count: i64 = query_scalar("SELECT count(*) FROM samples")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(count, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_refuses_to_archive_into_files_in_wal_mode() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-archive")?;
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .journal_mode(JournalMode::Wal)
+        .open(dir.path().join("live.db"))
+        .await?;
+    query("CREATE TABLE samples (ts INTEGER NOT NULL, v INTEGER)")
+        .execute(&pool)
+        .await?;
+    query("INSERT INTO samples VALUES (1704000000, 1)")
+        .execute(&pool)
+        .await?;
+
+    let err = Archiver::new(pool.clone(), "samples", "ts")
+        .time_format(TimeFormat::UnixSeconds)
+        .destination(Destination::Files(dir.path().join("archive")))
+        .run(1704153600)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Configuration(_)), "{err:?}");
+
+    let count: i64 = query_scalar("SELECT count(*) FROM samples")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(count, 1);
+    // The connection is returned to the pool without the archive attached
+    let attached: i64 =
+        query_scalar("SELECT count(*) FROM pragma_database_list WHERE name = 'archive'")
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(attached, 0);
+    Ok(())
+}
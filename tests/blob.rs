@@ -0,0 +1,45 @@
+use futures::TryStreamExt;
+use musq::{query, Error, Musq};
+
+#[tokio::test]
+async fn it_streams_blobs() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    let mut conn = pool.acquire().await?;
+    query("CREATE TABLE files (id INTEGER PRIMARY KEY, data BLOB)")
+        .execute(&mut *conn)
+        .await?;
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    query("INSERT INTO files (id, data) VALUES (1, ?), (2, x'')")
+        .bind(data.clone())
+        .execute(&mut *conn)
+        .await?;
+
+    let mut blob = conn.open_blob("files", "data", 1).await?;
+    assert_eq!(blob.len(), data.len() as u64);
+    let first = blob.read_chunk(10).await?.unwrap();
+    assert_eq!(first, data[..10]);
+    let chunks: Vec<Vec<u8>> = blob.into_stream(30_000).try_collect().await?;
+    assert_eq!(
+        chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![30_000, 30_000, 30_000, 9_990]
+    );
+    assert_eq!(chunks.concat(), data[10..]);
+
+    let mut empty = conn.open_blob("files", "data", 2).await?;
+    assert!(empty.is_empty());
+    assert_eq!(empty.read_chunk(10).await?, None);
+    drop(empty);
+
+    assert!(matches!(
+        conn.open_blob("files", "data", 3).await,
+        Err(Error::Sqlite(_))
+    ));
+
+    // A blob left open is closed when its reader is dropped, so the connection can keep going
+    let blob = conn.open_blob("files", "data", 1).await?;
+    drop(blob);
+    query("DELETE FROM files WHERE id = 1")
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
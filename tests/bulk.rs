@@ -0,0 +1,94 @@
+use musq::{bulk, fragment, query, query_as, query_scalar, Fragment, Musq, QueryBuilder};
+
+#[tokio::test]
+async fn it_inserts_rows_in_bulk() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    let mut conn = pool.acquire().await?;
+    query("CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT NOT NULL, score REAL)")
+        .execute(&mut *conn)
+        .await?;
+
+    // Enough values to need several statements
+    let n = 25_000;
+    let rows = (0..n).map(|i| {
+        (
+            i,
+            format!("name {i}"),
+            (i % 2 == 0).then_some(i as f64 / 2.0),
+        )
+    });
+    let inserted = query("INSERT INTO t (id, name, score) VALUES")
+        .bind_all(rows)
+        .execute(&mut conn)
+        .await?;
+    assert_eq!(inserted, n as u64);
+    let (count, last): (i64, String) =
+        query_as("SELECT count(*), max(name) FROM t WHERE score IS NULL")
+            .fetch_one(&mut *conn)
+            .await?;
+    assert_eq!((count, last.as_str()), (n / 2, "name 9999"));
+
+    assert_eq!(
+        query("INSERT INTO t (id, name) VALUES")
+            .bind_all(Vec::<(i64, String)>::new())
+            .execute(&mut conn)
+            .await?,
+        0
+    );
+
+    // A failure in a later statement rolls back the earlier ones
+    let rows = (n..n * 3).map(|i| (i.min(n * 3 - 2), "dup"));
+    assert!(query("INSERT INTO t (id, name) VALUES")
+        .bind_all(rows)
+        .execute(&mut conn)
+        .await
+        .is_err());
+    let count: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut *conn)
+        .await?;
+    assert_eq!(count, n);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_fetches_rows_by_composite_keys() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    let mut conn = pool.acquire().await?;
+    query("CREATE TABLE t (a INTEGER, b TEXT, v INTEGER, PRIMARY KEY (a, b))")
+        .execute(&mut *conn)
+        .await?;
+    let n = 20_000;
+    query("INSERT INTO t (a, b, v) VALUES")
+        .bind_all((0..n).map(|i| (i, format!("k{i}"), i * 10)))
+        .execute(&mut conn)
+        .await?;
+
+    // Two values per key, so more keys than fit in one statement
+    let keys: Vec<(i64, String)> = (0..n).map(|i| (i, format!("k{}", i % 2 * i))).collect();
+    let min = 5;
+    let rows: Vec<(i64, i64)> = bulk::fetch_in(&mut conn, &keys, |values| {
+        fragment!("SELECT a, v FROM t WHERE v >= {min} AND (a, b) IN ({values:frag}) ORDER BY a")
+    })
+    .await?;
+    // Only odd keys and 0 match, and 0 is filtered out by `min`
+    assert_eq!(rows.len(), n as usize / 2);
+    assert_eq!(rows[0], (1, 10));
+    assert!(rows.iter().all(|(a, v)| a % 2 == 1 && *v == a * 10));
+
+    let none: Vec<(i64, i64)> = bulk::fetch_in(&mut conn, Vec::<(i64, String)>::new(), |values| {
+        fragment!("SELECT a, v FROM t WHERE (a, b) IN ({values:frag})")
+    })
+    .await?;
+    assert!(none.is_empty());
+
+    // An empty VALUES list isn't valid SQL, so the query fails rather than matching nothing
+    let values = Fragment::values(Vec::<(i64, String)>::new());
+    assert!(QueryBuilder::from(fragment!(
+        "SELECT a, v FROM t WHERE (a, b) IN ({values:frag})"
+    ))
+    .build_query_as::<(i64, i64)>()
+    .fetch_all(&mut *conn)
+    .await
+    .is_err());
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use musq::{cache::QueryCache, query, Error, Musq};
+
+#[tokio::test]
+async fn it_caches_until_tables_change() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-cache")?;
+    let path = dir.path().join("db.sqlite");
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .track_changes(true)
+        .open(&path)
+        .await?;
+    // Writes through a separate pool bypass change tracking, which lets us see whether a result came from the cache
+    let other = Musq::new().open(&path).await?;
+
+    query(
+        "CREATE TABLE a (id INTEGER PRIMARY KEY, v TEXT); CREATE TABLE b (id INTEGER PRIMARY KEY)",
+    )
+    .execute(&pool)
+    .await?;
+    query("INSERT INTO a (v) VALUES ('x'), ('y')")
+        .execute(&pool)
+        .await?;
+
+    let cache = QueryCache::new(&pool, 10)?;
+    let fetch = || {
+        cache
+            .query_as::<(i64, String)>("SELECT id, v FROM a WHERE id >= ? ORDER BY id")
+            .bind(1)
+            .fetch_all()
+    };
+    assert_eq!(fetch().await?.len(), 2);
+
+    query("DELETE FROM a WHERE id = 1").execute(&other).await?;
+    assert_eq!(fetch().await?.len(), 2);
+
+    // Changes to other tables leave the entry alone
+    query("INSERT INTO b DEFAULT VALUES").execute(&pool).await?;
+    assert_eq!(fetch().await?.len(), 2);
+
+    // Bound arguments are part of the key
+    let one = cache
+        .query_as::<(i64, String)>("SELECT id, v FROM a WHERE id >= ? ORDER BY id")
+        .bind(2)
+        .fetch_one()
+        .await?;
+    assert_eq!(one, (2, "y".to_string()));
+    assert_eq!(cache.len(), 2);
+
+    // A rolled-back write doesn't invalidate
+    let mut tx = pool.begin().await?;
+    query("INSERT INTO a (v) VALUES ('z')")
+        .execute(&mut *tx)
+        .await?;
+    tx.rollback().await?;
+    assert_eq!(fetch().await?.len(), 2);
+
+    // A committed write does
+    let mut tx = pool.begin().await?;
+    query("UPDATE a SET v = 'w' WHERE id = 2")
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    assert_eq!(fetch().await?, vec![(2, "w".to_string())]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_invalidates_joins() -> anyhow::Result<()> {
+    let pool = Musq::new().track_changes(true).open_in_memory().await?;
+    query(
+        "CREATE TABLE a (id INTEGER PRIMARY KEY); CREATE TABLE b (a INTEGER, v INTEGER);
+        INSERT INTO a VALUES (1); INSERT INTO b VALUES (1, 10);",
+    )
+    .execute(&pool)
+    .await?;
+
+    let cache = QueryCache::new(&pool, 10)?;
+    let fetch = || {
+        cache
+            .query_as::<(i64,)>("SELECT b.v FROM a JOIN b ON b.a = a.id")
+            .fetch_optional()
+    };
+    assert_eq!(fetch().await?, Some((10,)));
+
+    query("UPDATE b SET v = 20").execute(&pool).await?;
+    assert_eq!(fetch().await?, Some((20,)));
+
+    cache.clear();
+    assert!(cache.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_invalidates_writes_missed_by_the_update_hook() -> anyhow::Result<()> {
+    let pool = Musq::new().track_changes(true).open_in_memory().await?;
+    query(
+        "CREATE TABLE a (id INTEGER PRIMARY KEY);
+        CREATE TABLE w (k TEXT PRIMARY KEY, v INTEGER) WITHOUT ROWID;
+        CREATE TABLE log (id INTEGER PRIMARY KEY);
+        CREATE TABLE l (k TEXT PRIMARY KEY) WITHOUT ROWID;
+        CREATE TRIGGER logged AFTER INSERT ON log BEGIN INSERT INTO l VALUES (new.id); END;
+        INSERT INTO a VALUES (1), (2);
+        INSERT INTO w VALUES ('x', 1);",
+    )
+    .execute(&pool)
+    .await?;
+    let cache = QueryCache::new(&pool, 10)?;
+
+    // Deleting every row takes a shortcut that skips the update hook
+    let count = || {
+        cache
+            .query_as::<(i64,)>("SELECT count(*) FROM a")
+            .fetch_one()
+    };
+    assert_eq!(count().await?, (2,));
+    query("DELETE FROM a").execute(&pool).await?;
+    assert_eq!(count().await?, (0,));
+
+    // So do writes to WITHOUT ROWID tables, whether direct or from a trigger
+    let value = || cache.query_as::<(i64,)>("SELECT v FROM w").fetch_one();
+    assert_eq!(value().await?, (1,));
+    query("UPDATE w SET v = 2").execute(&pool).await?;
+    assert_eq!(value().await?, (2,));
+
+    let logged = || {
+        cache
+            .query_as::<(i64,)>("SELECT count(*) FROM l")
+            .fetch_one()
+    };
+    assert_eq!(logged().await?, (0,));
+    query("INSERT INTO log DEFAULT VALUES")
+        .execute(&pool)
+        .await?;
+    assert_eq!(logged().await?, (1,));
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_requires_change_tracking() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    assert!(matches!(
+        QueryCache::new(&pool, 10),
+        Err(Error::Configuration(_))
+    ));
+    Ok(())
+}
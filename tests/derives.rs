@@ -34,6 +34,13 @@ enum ReprEnum {
     Bar = 2,
 }
 
+#[derive(Debug, PartialEq, Codec)]
+#[repr(i64)]
+enum NativeReprEnum {
+    Low = -1,
+    High = 1 << 40,
+}
+
 #[derive(Debug, PartialEq, Codec)]
 struct NewtypeStruct(i32);
 
@@ -125,6 +132,31 @@ test_type!(origin_enum<ReprEnum>(
     "2" == ReprEnum::Bar,
 ));
 
+test_type!(native_repr_enum<NativeReprEnum>(
+    "-1" == NativeReprEnum::Low,
+    "1099511627776" == NativeReprEnum::High,
+));
+
+#[tokio::test]
+async fn it_stores_repr_enums_as_integers() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    let kind: String = musq::query_scalar("SELECT typeof(?)")
+        .bind(NativeReprEnum::High)
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(kind, "integer");
+
+    let err = musq::query_scalar::<NativeReprEnum>("SELECT 2")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(matches!(&err, musq::Error::ColumnDecode { .. }), "{err}");
+    assert!(err
+        .to_string()
+        .contains("invalid value 2 for enum NativeReprEnum"));
+    Ok(())
+}
+
 test_type!(newtype_struct<NewtypeStruct>(
     "1" == NewtypeStruct(1),
 ));
@@ -135,3 +167,183 @@ test_type!(json_type<JsonType>(
         b: 1,
     },
 ));
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Positional(
+    i64,
+    #[musq(skip)] Vec<String>,
+    String,
+    #[musq(try_from = "i64")] u8,
+);
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Wrapper<T>(T);
+
+#[derive(Debug, PartialEq, FromRow)]
+#[musq(positional)]
+struct Joined {
+    left_id: i64,
+    left_name: String,
+    #[musq(skip)]
+    note: String,
+    right_id: i64,
+    right_name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Entity<T, D> {
+    id: T,
+    #[musq(flatten)]
+    data: D,
+    #[musq(skip)]
+    cached: Option<T>,
+}
+
+#[tokio::test]
+async fn it_derives_fromrow_tuple_and_generic() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+
+    let row: Positional = musq::query_as("SELECT 1, 'a', 7")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(row, Positional(1, vec![], "a".into(), 7));
+    let err = musq::query_as::<Positional>("SELECT 1, NULL, 7")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("field `2`"), "{err}");
+
+    // Colliding column names decode by position
+    let row: Joined = musq::query_as(
+        "SELECT * FROM (SELECT 1 AS id, 'a' AS name) JOIN (SELECT 2 AS id, NULL AS name)",
+    )
+    .fetch_one(&mut conn)
+    .await?;
+    assert_eq!(
+        row,
+        Joined {
+            left_id: 1,
+            left_name: "a".into(),
+            note: String::new(),
+            right_id: 2,
+            right_name: None,
+        }
+    );
+
+    let row: Wrapper<String> = musq::query_as("SELECT 'x'").fetch_one(&mut conn).await?;
+    assert_eq!(row, Wrapper("x".to_string()));
+
+    let row: Entity<i64, Flattened> = musq::query_as("SELECT 3 AS id, 'f' AS f, 4 AS g")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(
+        row,
+        Entity {
+            id: 3,
+            data: Flattened {
+                f: "f".into(),
+                g: 4
+            },
+            cached: None,
+        }
+    );
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Address {
+    city: String,
+    zip: Option<String>,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Post {
+    id: i64,
+    title: String,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Author {
+    id: i64,
+    name: String,
+    #[musq(flatten, prefix = "addr_")]
+    address: Option<Address>,
+    #[musq(skip)]
+    posts: Vec<Post>,
+}
+
+#[tokio::test]
+async fn it_groups_joined_rows() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    musq::query(
+        r"
+        CREATE TABLE authors (id INTEGER PRIMARY KEY, name TEXT NOT NULL, city TEXT, zip TEXT);
+        CREATE TABLE posts (id INTEGER PRIMARY KEY, author_id INTEGER, title TEXT NOT NULL);
+        INSERT INTO authors VALUES (1, 'ann', 'paris', NULL), (2, 'bob', NULL, NULL), (3, 'cy', 'rome', '00100');
+        INSERT INTO posts VALUES (10, 1, 'first'), (11, 3, 'hello'), (12, 1, 'second');
+        ",
+    )
+    .execute(&mut conn)
+    .await?;
+
+    let rows = musq::query(
+        r"
+        SELECT a.id, a.name, a.city AS addr_city, a.zip AS addr_zip, p.id AS post_id, p.title AS post_title
+        FROM authors a LEFT JOIN posts p ON p.author_id = a.id
+        ORDER BY a.id, p.id
+        ",
+    )
+    .fetch_all(&mut conn)
+    .await?;
+    let authors = musq::group_rows_by::<i64, Author, Post>(&rows, "id", "post_", |a| &mut a.posts)?;
+
+    assert_eq!(authors.len(), 3);
+    assert_eq!(
+        authors[0].address,
+        Some(Address {
+            city: "paris".into(),
+            zip: None
+        })
+    );
+    let titles: Vec<&str> = authors[0].posts.iter().map(|p| p.title.as_str()).collect();
+    assert_eq!(titles, ["first", "second"]);
+    assert_eq!(authors[1].name, "bob");
+    assert_eq!(authors[1].address, None);
+    assert!(authors[1].posts.is_empty());
+    assert_eq!(
+        authors[2].posts,
+        [Post {
+            id: 11,
+            title: "hello".into()
+        }]
+    );
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Borrowed<'a> {
+    name: std::borrow::Cow<'a, str>,
+    tag: &'a str,
+    data: std::borrow::Cow<'a, [u8]>,
+}
+
+#[tokio::test]
+async fn it_derives_fromrow_borrowed() -> anyhow::Result<()> {
+    use std::borrow::Cow;
+
+    let mut conn = connection().await?;
+    let row = musq::query("SELECT 'alice' AS name, 'x' AS tag, X'0102' AS data, NULL AS missing")
+        .fetch_one(&mut conn)
+        .await?;
+
+    // Borrowed fields point into the row's values
+    let borrowed = <Borrowed as musq::FromRow>::from_row("", &row)?;
+    assert!(matches!(borrowed.name, Cow::Borrowed("alice")));
+    assert!(matches!(borrowed.data, Cow::Borrowed([1, 2])));
+    assert_eq!(borrowed.tag, "x");
+    let name: &str = row.get_value("name")?;
+    assert_eq!(name.as_ptr(), borrowed.name.as_ptr());
+
+    assert_eq!(row.get_value::<Option<Cow<str>>>("missing")?, None);
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use musq::{docs::Collection, query_as, Musq};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Address {
+    city: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct User {
+    name: String,
+    admin: bool,
+    address: Address,
+}
+
+fn user(name: &str, admin: bool, city: &str) -> User {
+    User {
+        name: name.into(),
+        admin,
+        address: Address { city: city.into() },
+    }
+}
+
+#[tokio::test]
+async fn it_stores_documents() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    let users = Collection::<User>::open(&pool, "users").await?;
+    users.create_index("$.address.city").await?;
+    users.create_index("$.address.city").await?;
+
+    let a = users.insert(&user("a", true, "Dunedin")).await?;
+    let b = users.insert(&user("b", false, "Dunedin")).await?;
+    let c = users.insert(&user("c", false, "Oamaru")).await?;
+    assert_eq!(users.get(b).await?, Some(user("b", false, "Dunedin")));
+    assert_eq!(users.get(100).await?, None);
+
+    let found = users.find("$.address.city", "Dunedin").await?;
+    assert_eq!(
+        found
+            .iter()
+            .map(|d| (d.id, d.doc.name.as_str()))
+            .collect::<Vec<_>>(),
+        vec![(a, "a"), (b, "b")]
+    );
+    let admins = users.find("$.admin", true).await?;
+    assert_eq!(admins.len(), 1);
+    assert_eq!(admins[0].id, a);
+
+    // Lookups on an indexed path use the index
+    let plan: Vec<(i64, i64, i64, String)> = query_as(
+        "EXPLAIN QUERY PLAN SELECT id, doc FROM \"users\" \
+        WHERE json_extract(doc, '$.address.city') = ? ORDER BY id",
+    )
+    .bind("Dunedin")
+    .fetch_all(&pool)
+    .await?;
+    assert!(plan.iter().any(|p| p.3.contains("USING INDEX")), "{plan:?}");
+
+    assert!(users.update(c, &user("c", false, "Dunedin")).await?);
+    assert!(!users.update(100, &user("x", false, "")).await?);
+    assert_eq!(users.find("$.address.city", "Dunedin").await?.len(), 3);
+
+    assert!(users.delete(a).await?);
+    assert!(!users.delete(a).await?);
+    assert!(users.find("$.admin", true).await?.is_empty());
+
+    assert!(users.find("address.city", "Dunedin").await.is_err());
+    Ok(())
+}
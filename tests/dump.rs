@@ -0,0 +1,132 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use musq::{query, query_scalar, schema::compare, Musq, Pool};
+
+async fn source() -> anyhow::Result<Pool> {
+    let pool = Musq::new().max_connections(1).open_in_memory().await?;
+    query(
+        "CREATE TABLE posts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user INTEGER REFERENCES users,
+            title TEXT NOT NULL,
+            len INTEGER AS (length(title)),
+            data BLOB,
+            score REAL
+        );
+        CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+        CREATE TABLE tags (post INTEGER, tag TEXT, PRIMARY KEY (post, tag)) WITHOUT ROWID;
+        CREATE INDEX idx_posts_user ON posts (user);
+        CREATE VIEW titles AS SELECT title FROM posts;
+        CREATE TRIGGER posts_tag AFTER INSERT ON posts BEGIN
+            INSERT INTO tags VALUES (new.id, 'new');
+        END;
+        CREATE VIRTUAL TABLE docs USING fts5(body);
+        INSERT INTO users VALUES (1, 'ann');
+        INSERT INTO posts (user, title, data, score) VALUES
+            (1, 'it''s here;
+            on two lines', x'00ff10', 0.1),
+            (1, 'plain', NULL, 1e300),
+            (NULL, '', x'', -1.5);
+        DELETE FROM posts WHERE title = 'plain';",
+    )
+    .execute(&pool)
+    .await?;
+    Ok(pool)
+}
+
+#[tokio::test]
+async fn it_dumps_and_restores() -> anyhow::Result<()> {
+    let src = source().await?;
+    let mut out = Vec::new();
+    src.acquire().await?.dump(&mut out).await?;
+    let text = String::from_utf8(out.clone())?;
+    assert!(text.starts_with("PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n"));
+    assert!(text.ends_with("COMMIT;\n"));
+    assert!(text.contains("VALUES(1,1,'it''s here;\n            on two lines',X'00FF10',0.1);"));
+
+    let dst = Musq::new().max_connections(1).open_in_memory().await?;
+    let seen = Arc::new(AtomicU64::new(0));
+    let counter = seen.clone();
+    let n = dst
+        .acquire()
+        .await?
+        .restore_from_sql(out.as_slice())
+        .on_progress(move |p| counter.store(p.statements, Ordering::SeqCst))
+        .run()
+        .await?;
+    assert_eq!(n, seen.load(Ordering::SeqCst));
+
+    assert!(compare(&src, &dst).await?.is_empty());
+    let mut a = src.acquire().await?;
+    let mut b = dst.acquire().await?;
+    for table in ["posts", "users", "tags"] {
+        assert_eq!(
+            a.table_checksum(table).await?,
+            b.table_checksum(table).await?
+        );
+    }
+    drop(a);
+
+    // The autoincrement counter carries over, so deleted ids aren't reused
+    query("INSERT INTO posts (title) VALUES ('next')")
+        .execute(&mut b)
+        .await?;
+    let id: i64 = query_scalar("SELECT max(id) FROM posts")
+        .fetch_one(&mut b)
+        .await?;
+    assert_eq!(id, 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_dumps_text_that_is_not_plain_utf8() -> anyhow::Result<()> {
+    let src = Musq::new().max_connections(1).open_in_memory().await?;
+    query(
+        "CREATE TABLE t (x TEXT);
+        INSERT INTO t VALUES (CAST(x'61ff62' AS TEXT)), (CAST(x'610062' AS TEXT)), ('ok');",
+    )
+    .execute(&src)
+    .await?;
+    let mut out = Vec::new();
+    src.acquire().await?.dump(&mut out).await?;
+    let text = String::from_utf8(out.clone())?;
+    assert!(text.contains("VALUES(CAST(X'61FF62' AS TEXT));"));
+
+    let dst = Musq::new().max_connections(1).open_in_memory().await?;
+    dst.acquire()
+        .await?
+        .restore_from_sql(out.as_slice())
+        .run()
+        .await?;
+    let sql = "SELECT group_concat(typeof(x) || ':' || hex(x), ',') FROM (SELECT x FROM t ORDER BY rowid)";
+    let expected: String = query_scalar(sql).fetch_one(&src).await?;
+    assert_eq!(expected, "text:61FF62,text:610062,text:6F6B");
+    assert_eq!(query_scalar::<String>(sql).fetch_one(&dst).await?, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_rolls_back_failed_restores() -> anyhow::Result<()> {
+    let dst = Musq::new().max_connections(1).open_in_memory().await?;
+    let mut conn = dst.acquire().await?;
+    let dump = "BEGIN TRANSACTION;
+        CREATE TABLE t (x);
+        INSERT INTO t VALUES (1);
+        INSERT INTO nope VALUES (2);
+        COMMIT;";
+    assert!(conn.restore_from_sql(dump.as_bytes()).run().await.is_err());
+    assert!(conn.tables().await?.is_empty());
+
+    // Foreign keys are checked before the restore commits
+    let dump = "CREATE TABLE parent (id INTEGER PRIMARY KEY);
+        CREATE TABLE child (parent INTEGER REFERENCES parent);
+        INSERT INTO child VALUES (1);";
+    assert!(conn.restore_from_sql(dump.as_bytes()).run().await.is_err());
+    assert!(conn.tables().await?.is_empty());
+    let dump = format!("{dump}\nINSERT INTO parent VALUES (1);");
+    assert_eq!(conn.restore_from_sql(dump.as_bytes()).run().await?, 4);
+    Ok(())
+}
@@ -0,0 +1,179 @@
+#![cfg(feature = "encryption")]
+
+use musq::{
+    query, query_scalar,
+    types::{ColumnKey, Encrypted},
+    JournalMode, Musq,
+};
+
+fn key(byte: u8) -> impl Fn() -> std::io::Result<[u8; 32]> + Send + Sync + 'static {
+    move || Ok([byte; 32])
+}
+
+#[tokio::test]
+async fn it_encrypts_at_rest() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-encryption")?;
+    let path = dir.path().join("db.sqlite");
+
+    for journal in ["DELETE", "WAL"] {
+        let _ = std::fs::remove_file(&path);
+        let pool = Musq::new()
+            .create_if_missing(true)
+            .encrypted_vfs(key(1))
+            .open(&path)
+            .await?;
+        query(&format!("PRAGMA journal_mode = {journal}"))
+            .execute(&pool)
+            .await?;
+        query("CREATE TABLE t (v TEXT)").execute(&pool).await?;
+        let mut tx = pool.begin().await?;
+        for _ in 0..100 {
+            query("INSERT INTO t VALUES ('plaintext secret')")
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        pool.close().await;
+
+        let raw = std::fs::read(&path)?;
+        assert!(!raw.starts_with(b"SQLite format 3"));
+        assert!(!raw
+            .windows(b"plaintext secret".len())
+            .any(|w| w == b"plaintext secret"));
+
+        let pool = Musq::new().encrypted_vfs(key(1)).open(&path).await?;
+        let n: i64 = query_scalar("SELECT count(*) FROM t")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(n, 100);
+        pool.close().await;
+
+        // Pages sealed under another key fail to authenticate
+        let r = match Musq::new().encrypted_vfs(key(2)).open(&path).await {
+            Ok(pool) => {
+                let r: musq::Result<i64> = query_scalar("SELECT count(*) FROM t")
+                    .fetch_one(&pool)
+                    .await;
+                pool.close().await;
+                r
+            }
+            Err(e) => Err(e),
+        };
+        assert!(r.is_err());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_authenticates_pages() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-encryption")?;
+    let path = dir.path().join("db.sqlite");
+    let mut files = Vec::new();
+    for name in ["copy.sqlite", "db.sqlite"] {
+        let pool = Musq::new()
+            .create_if_missing(true)
+            .encrypted_vfs(key(1))
+            .journal_mode(JournalMode::Persist)
+            .pragma("cache_size", "2")
+            .open(dir.path().join(name))
+            .await?;
+        query("CREATE TABLE t (v TEXT)").execute(&pool).await?;
+        query("INSERT INTO t VALUES ('plaintext secret')")
+            .execute(&pool)
+            .await?;
+        files.push(std::fs::read(dir.path().join(name))?);
+        pool.close().await;
+    }
+    // Identical pages are sealed under different nonces
+    assert_eq!(files[0].len(), files[1].len());
+    assert_ne!(files[0], files[1]);
+
+    let pool = Musq::new()
+        .encrypted_vfs(key(1))
+        .journal_mode(JournalMode::Persist)
+        .pragma("cache_size", "2")
+        .open(&path)
+        .await?;
+
+    // A transaction that spills pages before rolling back restores them from the journal, which stays encrypted
+    let mut tx = pool.begin().await?;
+    for _ in 0..500 {
+        query("INSERT INTO t VALUES ('plaintext secret')")
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.rollback().await?;
+    let n: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(n, 1);
+    let journal = std::fs::read(dir.path().join("db.sqlite-journal"))?;
+    assert!(!journal
+        .windows(b"plaintext secret".len())
+        .any(|w| w == b"plaintext secret"));
+    pool.close().await;
+
+    // A modified page fails to read
+    let mut raw = std::fs::read(&path)?;
+    let page_size = raw.len() / 2;
+    raw[page_size + 100] ^= 1;
+    std::fs::write(&path, raw)?;
+    let pool = Musq::new().encrypted_vfs(key(1)).open(&path).await?;
+    let r: musq::Result<i64> = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&pool)
+        .await;
+    assert!(r.is_err());
+    pool.close().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_encrypts_columns() -> anyhow::Result<()> {
+    let key = ColumnKey::from_provider(&key(7))?;
+    let pool = Musq::new().open_in_memory().await?;
+    query("CREATE TABLE users (name TEXT, email BLOB, phone BLOB)")
+        .execute(&pool)
+        .await?;
+    query("INSERT INTO users VALUES (?, ?, ?)")
+        .bind("alice")
+        .bind(Encrypted::seal(&key, "users.email", &"alice@example.com")?)
+        .bind(None::<Encrypted<String>>)
+        .execute(&pool)
+        .await?;
+
+    let raw: Vec<u8> = query_scalar("SELECT email FROM users")
+        .fetch_one(&pool)
+        .await?;
+    assert!(!raw.windows(5).any(|w| w == b"alice"));
+
+    let (email, phone): (Encrypted<String>, Option<Encrypted<String>>) =
+        musq::query_as("SELECT email, phone FROM users")
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(email.open(&key, "users.email")?, "alice@example.com");
+    assert_eq!(phone, None);
+
+    // The column and key are authenticated along with the value
+    assert!(email.open(&key, "users.phone").is_err());
+    let other_key = ColumnKey::new([8; 32]);
+    assert!(email.open(&other_key, "users.email").is_err());
+
+    // Equal values encrypt differently
+    let other = Encrypted::seal(&key, "users.email", &"alice@example.com")?;
+    assert_ne!(raw, other.as_bytes());
+
+    // Tampered data doesn't open, and unencrypted data doesn't decode
+    let mut tampered = raw.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    let tampered: Encrypted<String> = query_scalar("SELECT ?")
+        .bind(tampered)
+        .fetch_one(&pool)
+        .await?;
+    assert!(tampered.open(&key, "users.email").is_err());
+    let r: musq::Result<Encrypted<String>> = query_scalar("SELECT ?")
+        .bind(b"alice".to_vec())
+        .fetch_one(&pool)
+        .await;
+    assert!(r.is_err());
+    Ok(())
+}
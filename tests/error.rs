@@ -1,4 +1,4 @@
-use musq::{query, Error, ExtendedErrCode, PrimaryErrCode};
+use musq::{query, query_as, DecodeError, Error, ExtendedErrCode, FromRow, PrimaryErrCode};
 use musq_test::tdb;
 
 #[tokio::test]
@@ -73,3 +73,43 @@ async fn it_fails_with_check_violation() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn it_fails_with_unexpected_null() -> anyhow::Result<()> {
+    let mut conn = tdb().await?;
+
+    let err =
+        query_as::<(i64, i64)>("SELECT id, owner_id FROM tweet_reply UNION ALL SELECT 2, NULL")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::ColumnDecode {
+            source: DecodeError::UnexpectedNull { ref column, field: None },
+            ..
+        } if column == "owner_id"
+    ));
+
+    #[derive(FromRow, Debug)]
+    struct Reply {
+        #[allow(dead_code)]
+        id: i64,
+        #[musq(rename = "owner_id")]
+        #[allow(dead_code)]
+        owner: i64,
+    }
+    let err = query_as::<Reply>("SELECT id, NULL AS owner_id FROM tweet_reply")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "error occurred while decoding column 1: unexpected NULL in column \"owner_id\" for field `owner`"
+    );
+
+    // NULLs decode into options
+    let (owner,): (Option<i64>,) = query_as("SELECT NULL").fetch_one(&mut conn).await?;
+    assert_eq!(owner, None);
+    Ok(())
+}
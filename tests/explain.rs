@@ -0,0 +1,73 @@
+use musq::{explain::QueryPlanNode, query, Musq};
+use musq_test::{assert_plan_snapshot, check_plan_snapshot, render_plan};
+
+#[tokio::test]
+async fn it_explains_query_plans() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    let mut conn = pool.acquire().await?;
+    query(
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT, name TEXT);
+         CREATE INDEX users_email ON users (email);",
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let plan = conn
+        .explain(query("SELECT * FROM users WHERE email = ?").bind("a@example.com"))
+        .await?;
+    assert_eq!(plan.len(), 1);
+    assert!(plan[0].detail.contains("USING INDEX users_email"));
+    assert!(!plan[0].full_scan);
+
+    let plan = conn.explain("SELECT * FROM users WHERE name = 'a'").await?;
+    assert!(plan[0].full_scan);
+
+    // Subqueries are nested under the step that uses them
+    let plan = conn
+        .explain(
+            "SELECT * FROM users WHERE id IN (SELECT id FROM users WHERE name = 'a') AND email = 'b'",
+        )
+        .await?;
+    let nodes: Vec<&QueryPlanNode> = plan.iter().flat_map(QueryPlanNode::nodes).collect();
+    assert!(nodes.iter().any(|n| n.parent != 0));
+    assert!(nodes
+        .iter()
+        .filter(|n| n.parent != 0)
+        .all(|n| nodes.iter().any(|p| p.id == n.parent)));
+    assert_eq!(nodes.iter().filter(|n| n.full_scan).count(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_snapshots_query_plans() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    let mut conn = pool.acquire().await?;
+    query(
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT, name TEXT);
+         CREATE INDEX users_email ON users (email);",
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    assert_plan_snapshot!(
+        conn,
+        "users_by_email",
+        query("SELECT * FROM users WHERE email = ?").bind("a@example.com")
+    );
+
+    // A changed plan fails against the existing snapshot
+    let dir = tempdir::TempDir::new("musq-plans")?;
+    let indexed = conn
+        .explain("SELECT * FROM users WHERE email = 'a'")
+        .await?;
+    let scan = conn.explain("SELECT * FROM users WHERE name = 'a'").await?;
+    check_plan_snapshot(dir.path(), "plan", &indexed);
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("plan.plan"))?,
+        render_plan(&indexed)
+    );
+    check_plan_snapshot(dir.path(), "plan", &indexed);
+    let changed = std::panic::catch_unwind(|| check_plan_snapshot(dir.path(), "plan", &scan));
+    assert!(changed.is_err());
+    Ok(())
+}
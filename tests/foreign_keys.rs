@@ -0,0 +1,99 @@
+use musq::{foreign_keys::Repair, query, query_scalar, ExtendedErrCode, Musq};
+
+async fn orphaned() -> anyhow::Result<musq::Pool> {
+    let pool = Musq::new()
+        .foreign_keys(false)
+        .max_connections(1)
+        .open_in_memory()
+        .await?;
+    query(
+        "CREATE TABLE parent (id INTEGER PRIMARY KEY);
+        CREATE TABLE child (id INTEGER PRIMARY KEY, parent INTEGER REFERENCES parent(id), v TEXT);
+        INSERT INTO parent VALUES (1);
+        INSERT INTO child VALUES (1, 1, 'ok'), (2, 2, 'orphan'), (3, 3, 'orphan'), (4, NULL, 'none');",
+    )
+    .execute(&pool)
+    .await?;
+    Ok(pool)
+}
+
+#[tokio::test]
+async fn it_lists_violations() -> anyhow::Result<()> {
+    let pool = orphaned().await?;
+    let mut conn = pool.acquire().await?;
+    let violations = conn.foreign_key_violations().await?;
+    assert_eq!(violations.len(), 2);
+    assert_eq!(violations[0].table, "child");
+    assert_eq!(violations[0].rowid, Some(2));
+    assert_eq!(violations[0].parent, "parent");
+    assert_eq!(violations[0].fkid, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_repairs_violations() -> anyhow::Result<()> {
+    let pool = orphaned().await?;
+    let mut conn = pool.acquire().await?;
+    assert_eq!(conn.repair_foreign_keys(Repair::SetNull, 1).await?, 2);
+    assert!(conn.foreign_key_violations().await?.is_empty());
+    let n: i64 = query_scalar("SELECT count(*) FROM child WHERE parent IS NULL")
+        .fetch_one(&mut *conn)
+        .await?;
+    assert_eq!(n, 3);
+
+    let pool = orphaned().await?;
+    let mut conn = pool.acquire().await?;
+    assert_eq!(conn.repair_foreign_keys(Repair::Delete, 10).await?, 2);
+    let ids: Vec<i64> = query_scalar("SELECT id FROM child ORDER BY id")
+        .fetch_all(&mut *conn)
+        .await?;
+    assert_eq!(ids, vec![1, 4]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_defers_and_checks_foreign_keys() -> anyhow::Result<()> {
+    let pool = Musq::new().max_connections(1).open_in_memory().await?;
+    query(
+        "CREATE TABLE parent (id INTEGER PRIMARY KEY);
+        CREATE TABLE child (id INTEGER PRIMARY KEY, parent INTEGER REFERENCES parent(id));
+        CREATE TABLE other (id INTEGER PRIMARY KEY, parent INTEGER REFERENCES parent(id));",
+    )
+    .execute(&pool)
+    .await?;
+
+    // Children can be inserted before their parents
+    let mut tx = pool.begin().await?;
+    tx.defer_foreign_keys().await?;
+    query("INSERT INTO child VALUES (1, 1), (2, 2)")
+        .execute(&mut tx)
+        .await?;
+    query("INSERT INTO other VALUES (1, 3)")
+        .execute(&mut tx)
+        .await?;
+    let violations = tx.foreign_key_check("child").await?;
+    assert_eq!(violations.len(), 2);
+    assert!(violations
+        .iter()
+        .all(|v| v.table == "child" && v.parent == "parent"));
+    assert_eq!(tx.foreign_key_check("other").await?.len(), 1);
+    query("INSERT INTO parent VALUES (1), (2), (3)")
+        .execute(&mut tx)
+        .await?;
+    assert!(tx.foreign_key_check("child").await?.is_empty());
+    tx.commit().await?;
+
+    // Violations that remain fail the commit
+    let mut tx = pool.begin().await?;
+    tx.defer_foreign_keys().await?;
+    query("INSERT INTO child VALUES (3, 4)")
+        .execute(&mut tx)
+        .await?;
+    let err = tx.commit().await.unwrap_err().into_sqlite_error().unwrap();
+    assert_eq!(err.extended, ExtendedErrCode::ConstraintForeignKey);
+    let n: i64 = query_scalar("SELECT count(*) FROM child")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(n, 2);
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use musq::{
+    encode::Encode, query, query_scalar, AggregateFunction, ArgumentValue, Error, FunctionFlags,
+    Musq, Value,
+};
+
+#[derive(Default)]
+struct Median(Vec<f64>);
+
+impl AggregateFunction for Median {
+    fn step(&mut self, args: &[Value]) {
+        if !args[0].is_null() {
+            self.0.push(args[0].double());
+        }
+    }
+
+    fn finalize(mut self) -> ArgumentValue {
+        self.0.sort_by(f64::total_cmp);
+        self.0.get(self.0.len() / 2).copied().encode()
+    }
+}
+
+struct Concat(String);
+
+impl AggregateFunction for Concat {
+    fn step(&mut self, args: &[Value]) {
+        if args[0].int() < 0 {
+            panic!("negative value");
+        }
+        for arg in args {
+            self.0.push_str(arg.text().unwrap_or_default());
+        }
+    }
+
+    fn finalize(self) -> ArgumentValue {
+        self.0.encode()
+    }
+}
+
+#[tokio::test]
+async fn it_calls_aggregate_functions() -> anyhow::Result<()> {
+    let pool = Musq::new()
+        .create_aggregate_function("median", 1, Median::default)
+        .create_aggregate_function("concat_all", -1, || Concat(String::new()))
+        .max_connections(1)
+        .open_in_memory()
+        .await?;
+    query(
+        "CREATE TABLE t (g TEXT, v REAL);
+        INSERT INTO t VALUES ('a', 1), ('a', 5), ('a', 3), ('b', 10), ('b', NULL);",
+    )
+    .execute(&pool)
+    .await?;
+
+    let medians: Vec<(String, f64)> =
+        musq::query_as("SELECT g, median(v) FROM t GROUP BY g ORDER BY g")
+            .fetch_all(&pool)
+            .await?;
+    assert_eq!(medians, vec![("a".into(), 3.0), ("b".into(), 10.0)]);
+
+    // An empty group is finalized without any steps
+    let empty: Option<f64> = query_scalar("SELECT median(v) FROM t WHERE 0")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(empty, None);
+
+    let concat: String = query_scalar("SELECT concat_all(v, g, '.') FROM t WHERE v IS NOT NULL")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(concat, "1.0a.5.0a.3.0a.10.0b.");
+
+    // A panic fails the query, and poisons the function
+    let err = query_scalar::<String>("SELECT concat_all(-1)")
+        .fetch_one(&pool)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::CallbackPanicked { .. }), "{err}");
+    assert!(query_scalar::<String>("SELECT concat_all(1)")
+        .fetch_one(&pool)
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_sorts_with_collations() -> anyhow::Result<()> {
+    let pool = Musq::new()
+        .create_collation("nocase_rev", |a, b| b.to_lowercase().cmp(&a.to_lowercase()))
+        .open_in_memory()
+        .await?;
+    query(
+        "CREATE TABLE t (v TEXT COLLATE nocase_rev);
+        INSERT INTO t VALUES ('b'), ('C'), ('a'), ('Ä');",
+    )
+    .execute(&pool)
+    .await?;
+
+    // The collation is registered on every connection in the pool
+    let mut conns = vec![pool.acquire().await?, pool.acquire().await?];
+    for conn in &mut conns {
+        let sorted: Vec<String> = query_scalar("SELECT v FROM t ORDER BY v")
+            .fetch_all(&mut **conn)
+            .await?;
+        assert_eq!(sorted, vec!["Ä", "C", "b", "a"]);
+        let matched: i64 = query_scalar("SELECT count(*) FROM t WHERE v = 'c'")
+            .fetch_one(&mut **conn)
+            .await?;
+        assert_eq!(matched, 1);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_calls_scalar_functions() -> anyhow::Result<()> {
+    let pool = Musq::new()
+        .create_scalar_function("double", 1, FunctionFlags::DETERMINISTIC, |args| {
+            Ok((args[0].int64() * 2).encode())
+        })
+        .create_scalar_function("fail", 0, FunctionFlags::NONE, |_| {
+            Err("no good".to_string())
+        })
+        .create_scalar_function("local", 0, FunctionFlags::DIRECT_ONLY, |_| Ok(1.encode()))
+        .open_in_memory()
+        .await?;
+
+    let v: i64 = query_scalar("SELECT double(21)").fetch_one(&pool).await?;
+    assert_eq!(v, 42);
+    let err = query("SELECT fail()").execute(&pool).await.unwrap_err();
+    assert!(err.to_string().contains("no good"), "{err}");
+
+    // Only deterministic functions may be used in generated columns and indexes
+    query("CREATE TABLE t (a INTEGER, b INTEGER AS (double(a)))")
+        .execute(&pool)
+        .await?;
+    query("INSERT INTO t (a) VALUES (4)").execute(&pool).await?;
+    let v: i64 = query_scalar("SELECT b FROM t").fetch_one(&pool).await?;
+    assert_eq!(v, 8);
+
+    // Direct-only functions can't be called from views
+    query("CREATE VIEW v AS SELECT local()")
+        .execute(&pool)
+        .await?;
+    assert!(query("SELECT * FROM v").execute(&pool).await.is_err());
+    let v: i64 = query_scalar("SELECT local()").fetch_one(&pool).await?;
+    assert_eq!(v, 1);
+    Ok(())
+}
+
+#[cfg(feature = "helper-functions")]
+#[tokio::test]
+async fn it_registers_helper_functions() -> anyhow::Result<()> {
+    let pool = Musq::new().with_helper_functions().open_in_memory().await?;
+
+    let v: String = query_scalar("SELECT regexp_replace('a1b22c333', '[0-9]+', '#')")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(v, "a#b#c#");
+    let v: String = query_scalar("SELECT regexp_replace('john smith', '(\\w+) (\\w+)', '$2, $1')")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(v, "smith, john");
+    assert!(query("SELECT regexp_replace('a', '(', '')")
+        .execute(&pool)
+        .await
+        .is_err());
+
+    let v: String = query_scalar("SELECT uuid_blob_to_text(x'67e5504410b1426f9247bb680e5fe0c8')")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(v, "67e55044-10b1-426f-9247-bb680e5fe0c8");
+    assert!(query("SELECT uuid_blob_to_text(x'0102')")
+        .execute(&pool)
+        .await
+        .is_err());
+
+    let v: String = query_scalar("SELECT unix_to_iso8601(1700000000)")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(v, "2023-11-14T22:13:20Z");
+    let v: String = query_scalar("SELECT unix_to_iso8601(1.5)")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(v, "1970-01-01T00:00:01.5Z");
+
+    let v: Option<String> = query_scalar("SELECT unix_to_iso8601(NULL)")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(v, None);
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use musq::{query, Musq};
+
+#[tokio::test]
+async fn it_checks_integrity() -> anyhow::Result<()> {
+    let pool = Musq::new().max_connections(1).open_in_memory().await?;
+    query(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, x INTEGER, y INTEGER);
+        CREATE INDEX idx_t ON t (x);
+        INSERT INTO t VALUES (1, 1, 10), (2, 2, 20), (3, 3, 30);",
+    )
+    .execute(&pool)
+    .await?;
+
+    let mut conn = pool.acquire().await?;
+    assert!(conn.integrity_check(100).await?.is_empty());
+    assert!(conn.quick_check().await?.is_empty());
+
+    // Point the index at another column, so its entries no longer match the table
+    query(
+        "PRAGMA writable_schema = ON;
+        UPDATE sqlite_schema SET sql = 'CREATE INDEX idx_t ON t (y)' WHERE name = 'idx_t';
+        PRAGMA writable_schema = RESET;",
+    )
+    .execute(&mut *conn)
+    .await?;
+    let errors = conn.integrity_check(100).await?;
+    assert!(!errors.is_empty());
+    assert!(
+        errors.iter().all(|e| e.index.as_deref() == Some("idx_t")),
+        "{errors:?}"
+    );
+    assert!(errors.iter().any(|e| e.rowid == Some(1)), "{errors:?}");
+    assert_eq!(conn.integrity_check(1).await?.len(), 1);
+
+    // The quick check doesn't compare indexes with their tables
+    assert!(conn.quick_check().await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_quick_checks_before_acquire() -> anyhow::Result<()> {
+    let pool = Musq::new()
+        .quick_check_before_acquire(true)
+        .max_connections(1)
+        .open_in_memory()
+        .await?;
+    query("CREATE TABLE t (x INTEGER)").execute(&pool).await?;
+    for _ in 0..3 {
+        let mut conn = pool.acquire().await?;
+        query("INSERT INTO t VALUES (1)").execute(&mut conn).await?;
+    }
+    assert_eq!(pool.metrics().closed, 0);
+
+    // A NULL in a column declared NOT NULL fails the check, and the connection is replaced
+    let mut conn = pool.acquire().await?;
+    query(
+        "INSERT INTO t VALUES (NULL);
+        PRAGMA writable_schema = ON;
+        UPDATE sqlite_schema SET sql = 'CREATE TABLE t (x INTEGER NOT NULL)' WHERE name = 't';
+        PRAGMA writable_schema = RESET;",
+    )
+    .execute(&mut conn)
+    .await?;
+    let errors = conn.quick_check().await?;
+    assert_eq!(errors.len(), 1);
+    assert!(
+        errors[0].message.contains("NULL value in t.x"),
+        "{errors:?}"
+    );
+    drop(conn);
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let conn = pool.acquire().await?;
+    assert_eq!(pool.metrics().closed, 1);
+    assert_eq!(pool.metrics().opened, 2);
+    drop(conn);
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use musq::{
+    jobs::{self, Enqueue, JobQueue},
+    Executor, Musq,
+};
+
+#[tokio::test]
+async fn it_claims_jobs_by_priority() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    jobs::create_tables(&pool).await?;
+
+    jobs::enqueue(&pool, "q", "low").await?;
+    jobs::enqueue_with(&pool, "q", "high", &Enqueue::new().priority(10)).await?;
+    jobs::enqueue_with(
+        &pool,
+        "q",
+        "later",
+        &Enqueue::new().delay(Duration::from_secs(60)),
+    )
+    .await?;
+    jobs::enqueue(&pool, "other", "elsewhere").await?;
+
+    let queue = JobQueue::new(pool.clone(), "q");
+    let first = queue.claim().await?.unwrap();
+    assert_eq!(first.payload, "high");
+    let second = queue.claim().await?.unwrap();
+    assert_eq!(second.payload, "low");
+
+    // Claimed jobs are invisible, and delayed jobs aren't ready yet
+    assert!(queue.claim().await?.is_none());
+
+    queue.complete(&first).await?;
+    queue.complete(&second).await?;
+    let remaining: i64 = pool.fetch_scalar("SELECT count(*) FROM musq_jobs").await?;
+    assert_eq!(remaining, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_retries_and_dead_letters_jobs() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    jobs::create_tables(&pool).await?;
+    jobs::enqueue(&pool, "q", "job").await?;
+
+    let queue = JobQueue::new(pool.clone(), "q")
+        .retry_delay(Duration::ZERO)
+        .max_attempts(2);
+
+    let job = queue.claim().await?.unwrap();
+    assert_eq!(job.attempts, 1);
+    queue.fail(&job, "boom").await?;
+
+    let job = queue.claim().await?.unwrap();
+    assert_eq!(job.attempts, 2);
+    queue.fail(&job, "boom again").await?;
+
+    assert!(queue.claim().await?.is_none());
+    let (attempts, error): (i64, String) =
+        musq::query_as("SELECT attempts, last_error FROM musq_jobs_dead")
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(attempts, 2);
+    assert_eq!(error, "boom again");
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_dead_letters_jobs_whose_rowid_would_be_reused() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    jobs::create_tables(&pool).await?;
+    let queue = JobQueue::new(pool.clone(), "q").max_attempts(1);
+
+    // Each job is the only one in the table when it is enqueued, so a reused rowid would collide with the last
+    // dead-lettered job
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        jobs::enqueue(&pool, "q", "job").await?;
+        let job = queue.claim().await?.unwrap();
+        queue.fail(&job, "boom").await?;
+        ids.push(job.id);
+    }
+    assert_ne!(ids[0], ids[1]);
+    assert!(queue.claim().await?.is_none());
+    let dead: i64 = pool
+        .fetch_scalar("SELECT count(*) FROM musq_jobs_dead")
+        .await?;
+    assert_eq!(dead, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_retries_jobs_after_visibility_timeout() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    jobs::create_tables(&pool).await?;
+    jobs::enqueue(&pool, "q", "job").await?;
+
+    let queue = JobQueue::new(pool.clone(), "q")
+        .visibility_timeout(Duration::ZERO)
+        .max_attempts(2);
+
+    // Simulate workers that die without completing or failing the job
+    assert_eq!(queue.claim().await?.unwrap().attempts, 1);
+    assert_eq!(queue.claim().await?.unwrap().attempts, 2);
+    assert!(queue.claim().await?.is_none());
+
+    let dead: i64 = pool
+        .fetch_scalar("SELECT count(*) FROM musq_jobs_dead")
+        .await?;
+    assert_eq!(dead, 1);
+    Ok(())
+}
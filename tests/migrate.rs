@@ -0,0 +1,69 @@
+use musq::{
+    migrate::{Migration, Migrator},
+    query, Error, Musq,
+};
+use musq_test::connection;
+
+static MIGRATOR: Migrator = musq::migrations!("tests/migrations");
+
+#[tokio::test]
+async fn it_runs_embedded_migrations() -> anyhow::Result<()> {
+    let versions: Vec<_> = MIGRATOR.migrations().iter().map(|m| m.version).collect();
+    assert_eq!(versions, [1, 2]);
+    assert_eq!(MIGRATOR.migrations()[1].description, "add user names");
+
+    let mut conn = connection().await?;
+    assert_eq!(MIGRATOR.run(&mut conn).await?, [1, 2]);
+    query("INSERT INTO users (email, name) VALUES ('a@example.com', 'a')")
+        .execute(&mut conn)
+        .await?;
+
+    // Applied migrations are skipped
+    assert!(MIGRATOR.run(&mut conn).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_rejects_changed_migrations() -> anyhow::Result<()> {
+    static FIRST: [Migration; 1] = [Migration::new(1, "create", "CREATE TABLE t (v)")];
+    static EDITED: [Migration; 2] = [
+        Migration::new(1, "create", "CREATE TABLE t (v, w)"),
+        Migration::new(2, "index", "CREATE INDEX t_v ON t (v)"),
+    ];
+
+    let mut conn = connection().await?;
+    Migrator::new(&FIRST).run(&mut conn).await?;
+    let err = Migrator::new(&EDITED).run(&mut conn).await.unwrap_err();
+    assert!(matches!(err, Error::Protocol(msg) if msg.contains("changed")));
+    let err = Migrator::new(&[]).run(&mut conn).await.unwrap_err();
+    assert!(matches!(err, Error::Protocol(msg) if msg.contains("missing")));
+
+    // A failing migration is rolled back
+    static FAILING: [Migration; 2] = [
+        Migration::new(1, "create", "CREATE TABLE t (v)"),
+        Migration::new(2, "broken", "CREATE TABLE u (v); SELEC 1"),
+    ];
+    let mut conn = connection().await?;
+    assert!(Migrator::new(&FAILING).run(&mut conn).await.is_err());
+    assert!(query("SELECT * FROM u").execute(&mut conn).await.is_err());
+    assert_eq!(
+        Migrator::new(&FIRST).run(&mut conn).await?,
+        Vec::<i64>::new()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_applies_migrations_once_when_run_concurrently() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-migrate")?;
+    let path = dir.path().join("db.sqlite");
+    let a = Musq::new().create_if_missing(true).open(&path).await?;
+    let b = Musq::new().open(&path).await?;
+    let (mut a, mut b) = (a.acquire().await?, b.acquire().await?);
+
+    let (a, b) = tokio::join!(MIGRATOR.run(&mut a), MIGRATOR.run(&mut b));
+    let mut applied = [a?, b?].concat();
+    applied.sort();
+    assert_eq!(applied, [1, 2]);
+    Ok(())
+}
@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN name TEXT;
+CREATE INDEX users_email ON users (email);
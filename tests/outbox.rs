@@ -0,0 +1,77 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use musq::{
+    outbox::{self, Outbox},
+    query, Executor, Musq,
+};
+
+#[tokio::test]
+async fn it_dispatches_outbox_messages() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    outbox::create_table(&pool).await?;
+
+    let mut tx = pool.begin().await?;
+    outbox::enqueue(&mut *tx, "a", "1").await?;
+    outbox::enqueue(&mut *tx, "b", "2").await?;
+    tx.commit().await?;
+
+    // Messages enqueued in a rolled-back transaction are never sent
+    let mut tx = pool.begin().await?;
+    outbox::enqueue(&mut *tx, "c", "3").await?;
+    tx.rollback().await?;
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let outbox = Outbox::new(pool.clone()).retry_delay(Duration::ZERO);
+    let handler = |msg: outbox::Message| {
+        let seen = seen.clone();
+        async move {
+            seen.lock().unwrap().push((msg.topic.clone(), msg.attempts));
+            if msg.topic == "b" && msg.attempts < 2 {
+                Err("transient failure")
+            } else {
+                Ok(())
+            }
+        }
+    };
+
+    assert_eq!(outbox.dispatch(handler).await?, 2);
+    let err: Option<String> = pool
+        .fetch_scalar("SELECT last_error FROM musq_outbox WHERE topic = 'b'")
+        .await?;
+    assert_eq!(err.as_deref(), Some("transient failure"));
+
+    assert_eq!(outbox.dispatch(handler).await?, 1);
+    assert_eq!(outbox.dispatch(handler).await?, 0);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [("a".into(), 1), ("b".into(), 1), ("b".into(), 2)]
+    );
+    let remaining: i64 = pool
+        .fetch_scalar("SELECT count(*) FROM musq_outbox")
+        .await?;
+    assert_eq!(remaining, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_abandons_outbox_messages_after_max_attempts() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    outbox::create_table(&pool).await?;
+    outbox::enqueue(&pool, "a", "1").await?;
+
+    let outbox = Outbox::new(pool.clone())
+        .retry_delay(Duration::ZERO)
+        .max_attempts(2);
+    let handler = |_| async { Err("permanent failure") };
+    assert_eq!(outbox.dispatch(handler).await?, 1);
+    assert_eq!(outbox.dispatch(handler).await?, 1);
+    assert_eq!(outbox.dispatch(handler).await?, 0);
+
+    let attempts: i64 = query("SELECT attempts FROM musq_outbox")
+        .fetch_one(&pool)
+        .await?
+        .get_value_idx(0)?;
+    assert_eq!(attempts, 2);
+    Ok(())
+}
@@ -0,0 +1,538 @@
+use musq::{
+    patch::Patch,
+    query, query_as,
+    schema::{
+        compare, compare_with, diff, table_columns, Affinity, CompareOptions, DdlAction, Generated,
+        IndexOrigin, Mismatch, SchemaSnapshot,
+    },
+    validate_schema, Error, FromRow, Musq, Pool,
+};
+use musq_test::connection;
+
+async fn pool(sql: &str) -> anyhow::Result<Pool> {
+    let pool = Musq::new().open_in_memory().await?;
+    query(sql).execute(&pool).await?;
+    Ok(pool)
+}
+
+#[tokio::test]
+async fn it_compares_identical_schemas() -> anyhow::Result<()> {
+    let schema = "CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT);
+                  CREATE INDEX t_v ON t (v);";
+    let a = pool(schema).await?;
+    let b = pool(schema).await?;
+    assert!(compare(&a, &b).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_reports_schema_differences() -> anyhow::Result<()> {
+    let a = pool(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT);
+         CREATE TABLE only_a (id INTEGER);
+         CREATE VIEW view AS SELECT id FROM t;",
+    )
+    .await?;
+    let b = pool(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT, w INTEGER);
+         CREATE TABLE   only_b (id INTEGER);
+         CREATE VIEW view AS   SELECT id   FROM t;",
+    )
+    .await?;
+
+    let cmp = compare(&a, &b).await?;
+    assert_eq!(
+        cmp.only_in_a.iter().map(|o| &o.name).collect::<Vec<_>>(),
+        ["only_a"]
+    );
+    assert_eq!(
+        cmp.only_in_b.iter().map(|o| &o.name).collect::<Vec<_>>(),
+        ["only_b"]
+    );
+    assert_eq!(cmp.changed.len(), 1);
+    assert_eq!(cmp.changed[0].0.name, "t");
+    assert_eq!(cmp.changed[0].0.kind, "table");
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_reports_row_differences() -> anyhow::Result<()> {
+    let schema = "CREATE TABLE t (a INTEGER, b TEXT, v, PRIMARY KEY (a, b));
+                  CREATE TABLE norowid (v);";
+    let a = pool(schema).await?;
+    let b = pool(schema).await?;
+
+    query(
+        "INSERT INTO t VALUES (1, 'x', 1), (2, 'y', 2), (3, 'z', 3);
+         INSERT INTO norowid VALUES (1), (2);",
+    )
+    .execute(&a)
+    .await?;
+    query(
+        "INSERT INTO t VALUES (1, 'x', 1), (2, 'y', '2'), (4, 'w', 4);
+         INSERT INTO norowid VALUES (1), (2);",
+    )
+    .execute(&b)
+    .await?;
+
+    let opts = CompareOptions::new().rows("t").rows("norowid");
+    let cmp = compare_with(&a, &b, &opts).await?;
+    assert!(cmp.changed.is_empty());
+    assert_eq!(cmp.rows.len(), 1);
+    let diff = &cmp.rows[0];
+    assert_eq!(diff.table, "t");
+    assert_eq!(diff.only_in_a, ["3, 'z'"]);
+    assert_eq!(diff.only_in_b, ["4, 'w'"]);
+    assert_eq!(diff.changed, ["2, 'y'"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_merges_interleaved_rows() -> anyhow::Result<()> {
+    let schema = "CREATE TABLE t (id INTEGER PRIMARY KEY, v)";
+    let a = pool(schema).await?;
+    let b = pool(schema).await?;
+    let fill = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000)
+                INSERT INTO t SELECT i, i FROM n WHERE i % ? != 0";
+    query(fill).bind(7).execute(&a).await?;
+    query(fill).bind(11).execute(&b).await?;
+    query("UPDATE t SET v = -v WHERE id % 13 = 0")
+        .execute(&b)
+        .await?;
+
+    let cmp = compare_with(&a, &b, &CompareOptions::new().rows("t")).await?;
+    let diff = &cmp.rows[0];
+    let multiples = |n: i64, not: i64| (1..=1000).filter(|i| i % n == 0 && i % not != 0).count();
+    assert_eq!(diff.only_in_a.len(), multiples(11, 7));
+    assert_eq!(diff.only_in_b.len(), multiples(7, 11));
+    assert_eq!(
+        diff.changed.len(),
+        (1..=1000)
+            .filter(|i| i % 13 == 0 && i % 7 != 0 && i % 11 != 0)
+            .count()
+    );
+    assert!(diff.only_in_a.contains(&"11".to_string()));
+    assert!(diff.only_in_b.contains(&"7".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_computes_table_checksums() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    query(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, v);
+         CREATE TABLE u (id INTEGER PRIMARY KEY, v);
+         INSERT INTO t VALUES (1, 'a'), (2, 2);
+         INSERT INTO u VALUES (2, 2), (1, 'a');",
+    )
+    .execute(&mut conn)
+    .await?;
+
+    let t = conn.table_checksum("t").await?;
+    assert_eq!(t, conn.table_checksum("u").await?);
+
+    // The checksum distinguishes between types
+    query("UPDATE u SET v = '2' WHERE id = 2")
+        .execute(&mut conn)
+        .await?;
+    assert_ne!(t, conn.table_checksum("u").await?);
+
+    assert!(conn.table_checksum("missing").await.is_err());
+    Ok(())
+}
+
+#[derive(FromRow)]
+#[musq(table = "users")]
+#[allow(dead_code)]
+struct User {
+    id: i64,
+    name: String,
+    email: Option<String>,
+    #[musq(default)]
+    nickname: Option<String>,
+    #[musq(skip)]
+    cached: u32,
+}
+
+#[derive(FromRow)]
+#[musq(table = "posts", rename_all = "pascal_case")]
+#[allow(dead_code)]
+struct Post {
+    post_id: i64,
+    body: String,
+    score: f64,
+    raw: Vec<u8>,
+}
+
+#[tokio::test]
+async fn it_validates_schema() -> anyhow::Result<()> {
+    let db = pool(
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, email VARCHAR(100));
+        CREATE TABLE posts (PostId INTEGER PRIMARY KEY, Body TEXT NOT NULL, Score NUMERIC NOT NULL, Raw NOT NULL);",
+    )
+    .await?;
+    let report = validate_schema::<(User, Post)>(&db).await?;
+    assert!(report.is_ok(), "{report}");
+
+    let db = pool("CREATE TABLE users (id INTEGER PRIMARY KEY, name INTEGER, email TEXT);").await?;
+    let report = validate_schema::<(User, Post)>(&db).await?;
+    assert_eq!(
+        report.mismatches,
+        vec![
+            Mismatch::Affinity {
+                table: "users".into(),
+                column: "name".into(),
+                expected: Affinity::Text,
+                declared: "INTEGER".into(),
+            },
+            Mismatch::Nullable {
+                table: "users".into(),
+                column: "name".into(),
+            },
+            Mismatch::MissingTable {
+                table: "posts".into()
+            },
+        ]
+    );
+
+    let db = pool("CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT);").await?;
+    let report = validate_schema::<User>(&db).await?;
+    assert_eq!(report.to_string(), "column users.name does not exist\n");
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_reflects_generated_columns() -> anyhow::Result<()> {
+    let db = pool(
+        "CREATE TABLE users (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            email VARCHAR(100) DEFAULT 'none',
+            upper TEXT GENERATED ALWAYS AS (upper(name)) VIRTUAL,
+            len INTEGER AS (length(name)) STORED
+        );",
+    )
+    .await?;
+    let columns = table_columns(&db, "users").await?;
+    assert_eq!(
+        columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.generated, c.is_insertable()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("id", None, true),
+            ("name", None, true),
+            ("email", None, true),
+            ("upper", Some(Generated::Virtual), false),
+            ("len", Some(Generated::Stored), false),
+        ]
+    );
+    assert_eq!(columns[0].pk, 1);
+    assert!(columns[1].notnull);
+    assert_eq!(columns[2].default.as_deref(), Some("'none'"));
+
+    // Generated columns can be read by a table type
+    #[derive(FromRow)]
+    #[musq(table = "users")]
+    #[allow(dead_code)]
+    struct WithUpper {
+        id: i64,
+        upper: Option<String>,
+    }
+    let report = validate_schema::<WithUpper>(&db).await?;
+    assert!(report.is_ok(), "{report}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_introspects_tables() -> anyhow::Result<()> {
+    let db = pool(
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT UNIQUE, name TEXT) STRICT;
+        CREATE INDEX idx_users_name ON users (name, lower(email)) WHERE name IS NOT NULL;
+        CREATE TABLE tags (user INTEGER, tag TEXT, PRIMARY KEY (user, tag)) WITHOUT ROWID;
+        CREATE TABLE posts (
+            id INTEGER PRIMARY KEY,
+            user INTEGER REFERENCES users ON DELETE CASCADE,
+            a INTEGER,
+            b TEXT,
+            FOREIGN KEY (a, b) REFERENCES tags (user, tag)
+        );
+        CREATE VIRTUAL TABLE docs USING fts5(body);
+        CREATE VIEW names AS SELECT name FROM users;",
+    )
+    .await?;
+    let mut conn = db.acquire().await?;
+
+    let tables = conn.tables().await?;
+    assert_eq!(
+        tables
+            .iter()
+            .map(|t| (t.name.as_str(), t.strict, t.without_rowid, t.virtual_table))
+            .collect::<Vec<_>>(),
+        vec![
+            ("docs", false, false, true),
+            ("posts", false, false, false),
+            ("tags", false, true, false),
+            ("users", true, false, false),
+        ]
+    );
+    assert!(tables[3].sql.starts_with("CREATE TABLE users"));
+
+    let columns = conn.columns("users").await?;
+    assert_eq!(
+        columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+        vec!["id", "email", "name"]
+    );
+
+    let indexes = conn.indexes("users").await?;
+    assert_eq!(indexes.len(), 2);
+    assert_eq!(indexes[0].name, "idx_users_name");
+    assert_eq!(indexes[0].origin, IndexOrigin::CreateIndex);
+    assert!(indexes[0].partial && !indexes[0].unique);
+    assert_eq!(indexes[0].columns, vec![Some("name".to_string()), None]);
+    assert_eq!(indexes[1].origin, IndexOrigin::Unique);
+    assert!(indexes[1].unique);
+    assert_eq!(indexes[1].columns, vec![Some("email".to_string())]);
+    assert_eq!(
+        conn.indexes("tags").await?[0].origin,
+        IndexOrigin::PrimaryKey
+    );
+
+    let keys = conn.foreign_keys("posts").await?;
+    assert_eq!(keys.len(), 2);
+    let to_users = keys.iter().find(|k| k.parent == "users").unwrap();
+    assert_eq!(to_users.from, vec!["user"]);
+    assert_eq!(to_users.to, vec![None]);
+    assert_eq!(to_users.on_delete, "CASCADE");
+    let to_tags = keys.iter().find(|k| k.parent == "tags").unwrap();
+    assert_eq!(to_tags.from, vec!["a", "b"]);
+    assert_eq!(
+        to_tags.to,
+        vec![Some("user".to_string()), Some("tag".to_string())]
+    );
+    assert!(conn.foreign_keys("users").await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_diffs_schemas() -> anyhow::Result<()> {
+    let db = Musq::new()
+        .foreign_keys(false)
+        .max_connections(1)
+        .open_in_memory()
+        .await?;
+    query(
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+        CREATE TABLE posts (id INTEGER PRIMARY KEY, user INTEGER REFERENCES users, title TEXT, body TEXT);
+        CREATE TABLE old (x);
+        CREATE INDEX idx_posts_user ON posts (user);
+        CREATE INDEX idx_users_name ON users (name);
+        CREATE VIEW titles AS SELECT title FROM posts;
+        INSERT INTO users VALUES (1, 'ann'), (2, 'bob');
+        INSERT INTO posts VALUES (1, 1, 'hello', 'first'), (2, 2, 'again', 'second');",
+    )
+    .execute(&db)
+    .await?;
+    let desired = SchemaSnapshot::from_sql(
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, email TEXT NOT NULL DEFAULT '');
+        CREATE TABLE posts (id INTEGER PRIMARY KEY, user INTEGER REFERENCES users, title TEXT NOT NULL);
+        CREATE TABLE tags (post INTEGER REFERENCES posts, tag TEXT);
+        CREATE INDEX idx_posts_user ON posts (user);
+        CREATE INDEX idx_users_name ON users (name, email);
+        CREATE VIEW titles AS SELECT title FROM posts;
+        CREATE TRIGGER titles_insert INSTEAD OF INSERT ON titles BEGIN
+            INSERT INTO posts (title) VALUES (new.title);
+        END;",
+    )
+    .await?;
+
+    let mut conn = db.acquire().await?;
+    let live = SchemaSnapshot::capture(&mut conn).await?;
+    let statements = diff(&live, &desired);
+    assert_eq!(
+        statements
+            .iter()
+            .map(|s| (s.action, s.kind.as_str(), s.name.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (DdlAction::Drop, "view", "titles"),
+            (DdlAction::Drop, "index", "idx_users_name"),
+            (DdlAction::Drop, "table", "old"),
+            (DdlAction::Create, "table", "tags"),
+            (DdlAction::Rebuild, "table", "posts"),
+            (DdlAction::AddColumn, "table", "users"),
+            (DdlAction::Create, "index", "idx_posts_user"),
+            (DdlAction::Create, "index", "idx_users_name"),
+            (DdlAction::Create, "view", "titles"),
+            (DdlAction::Create, "trigger", "titles_insert"),
+        ]
+    );
+
+    let mut tx = conn.begin().await?;
+    for statement in &statements {
+        query(&statement.sql).execute(&mut tx).await?;
+    }
+    assert!(tx.foreign_key_violations().await?.is_empty());
+    tx.commit().await?;
+
+    let live = SchemaSnapshot::capture(&mut conn).await?;
+    assert!(diff(&live, &desired).is_empty());
+    let posts: Vec<(i64, i64, String)> = query_as("SELECT id, user, title FROM posts ORDER BY id")
+        .fetch_all(&mut conn)
+        .await?;
+    assert_eq!(posts, vec![(1, 1, "hello".into()), (2, 2, "again".into())]);
+    let emails: Vec<(String,)> = query_as("SELECT email FROM users")
+        .fetch_all(&mut conn)
+        .await?;
+    assert_eq!(emails, vec![("".into(),), ("".into(),)]);
+    query("INSERT INTO titles VALUES ('via view')")
+        .execute(&mut conn)
+        .await?;
+    assert_eq!(conn.indexes("posts").await?[0].name, "idx_posts_user");
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_adds_columns_to_tables_with_keyword_prefixed_columns() -> anyhow::Result<()> {
+    let db = Musq::new().max_connections(1).open_in_memory().await?;
+    query("CREATE TABLE t (unique_code TEXT, id INTEGER)")
+        .execute(&db)
+        .await?;
+    let desired =
+        SchemaSnapshot::from_sql("CREATE TABLE t (unique_code TEXT, id INTEGER, extra TEXT)")
+            .await?;
+
+    let mut conn = db.acquire().await?;
+    let live = SchemaSnapshot::capture(&mut conn).await?;
+    let statements = diff(&live, &desired);
+    assert_eq!(
+        statements.iter().map(|s| s.action).collect::<Vec<_>>(),
+        [DdlAction::AddColumn]
+    );
+    for statement in &statements {
+        query(&statement.sql).execute(&mut conn).await?;
+    }
+
+    let live = SchemaSnapshot::capture(&mut conn).await?;
+    assert!(diff(&live, &desired).is_empty());
+    let columns: Vec<String> = table_columns(&mut conn, "t")
+        .await?
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+    assert_eq!(columns, ["unique_code", "id", "extra"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_applies_patches() -> anyhow::Result<()> {
+    let db = pool(
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, email TEXT);
+        INSERT INTO users VALUES (1, 'a', 'a@example.com'), (2, 'b', NULL);",
+    )
+    .await?;
+
+    let patch = Patch::<User>::new()
+        .set("name", "x")
+        .set_opt("email", None::<String>)
+        .set("name", "aa");
+    assert_eq!(patch.columns().collect::<Vec<_>>(), ["name"]);
+    assert!(patch.apply_update(&db, 1).await?);
+    assert!(
+        Patch::<User>::new()
+            .set_opt("email", Some("b@example.com"))
+            .apply_update(&db, 2)
+            .await?
+    );
+    assert!(
+        !Patch::<User>::new()
+            .set("name", "c")
+            .apply_update(&db, 3)
+            .await?
+    );
+    assert!(Patch::<User>::new().apply_update(&db, 2).await?);
+    assert!(!Patch::<User>::new().apply_update(&db, 3).await?);
+
+    let rows: Vec<(i64, String, Option<String>)> =
+        query_as("SELECT id, name, email FROM users ORDER BY id")
+            .fetch_all(&db)
+            .await?;
+    assert_eq!(
+        rows,
+        vec![
+            (1, "aa".into(), Some("a@example.com".into())),
+            (2, "b".into(), Some("b@example.com".into())),
+        ]
+    );
+
+    // Columns outside the table type are rejected
+    let err = Patch::<User>::new()
+        .set("cached", 1)
+        .apply_update(&db, 1)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::ColumnNotFound(c) if c == "cached"));
+    Ok(())
+}
+
+#[test]
+fn it_computes_affinity() {
+    for (declared, affinity) in [
+        ("INT", Affinity::Integer),
+        ("BIGINT", Affinity::Integer),
+        ("VARCHAR(10)", Affinity::Text),
+        ("", Affinity::Blob),
+        ("DOUBLE PRECISION", Affinity::Real),
+        ("DECIMAL(10,5)", Affinity::Numeric),
+        ("BOOLEAN", Affinity::Numeric),
+    ] {
+        assert_eq!(Affinity::of_declared(declared), affinity, "{declared}");
+    }
+}
+
+#[tokio::test]
+async fn it_inserts_rows_with_rowids() -> anyhow::Result<()> {
+    use musq::{schema::Insert, Insert};
+
+    #[derive(Debug, PartialEq, FromRow, Insert)]
+    #[musq(table = "users")]
+    struct NewUser {
+        #[musq(rowid)]
+        id: Option<i64>,
+        name: String,
+        email: Option<String>,
+        #[musq(skip)]
+        cached: u32,
+    }
+
+    let db =
+        pool("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, email TEXT)").await?;
+    let mut a = NewUser {
+        id: None,
+        name: "a".into(),
+        email: None,
+        cached: 7,
+    };
+    assert_eq!(a.insert(&db).await?, 1);
+    assert_eq!(a.id, Some(1));
+
+    // An explicit rowid is inserted as given
+    let mut b = NewUser {
+        id: Some(10),
+        name: "b".into(),
+        email: Some("b@example.com".into()),
+        cached: 0,
+    };
+    assert_eq!(b.insert(&db).await?, 10);
+    // Inserting again reuses the rowid, which is taken
+    assert!(a.insert(&db).await.is_err());
+
+    let rows: Vec<NewUser> = query_as("SELECT id, name, email FROM users ORDER BY id")
+        .fetch_all(&db)
+        .await?;
+    assert_eq!(
+        rows,
+        vec![NewUser { cached: 0, ..a }, NewUser { cached: 0, ..b }]
+    );
+    Ok(())
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "tracing-spans")]
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use musq::{query, Musq};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+type Spans = Arc<Mutex<Vec<(&'static str, Vec<String>)>>>;
+
+/// A subscriber that records the name of every span, and the fields recorded on it, as `field=value` strings.
+#[derive(Default, Clone)]
+struct Recorder {
+    spans: Spans,
+}
+
+impl Recorder {
+    fn fields(&self, name: &str) -> Vec<Vec<String>> {
+        let spans = self.spans.lock().unwrap();
+        spans
+            .iter()
+            .filter(|(n, _)| *n == name)
+            .map(|(_, fields)| fields.clone())
+            .collect()
+    }
+}
+
+struct Fields<'a>(&'a mut Vec<String>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push(format!("{}={:?}", field.name(), value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push(format!("{}={}", field.name(), value));
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+        let mut fields = Vec::new();
+        attrs.record(&mut Fields(&mut fields));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push((attrs.metadata().name(), fields));
+        span::Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+        values.record(&mut Fields(fields));
+    }
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, _: &Event<'_>) {}
+
+    fn enter(&self, _: &span::Id) {}
+
+    fn exit(&self, _: &span::Id) {}
+}
+
+#[tokio::test]
+async fn it_emits_spans() -> anyhow::Result<()> {
+    let recorder = Recorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let pool = Musq::new().open_in_memory().await?;
+    let mut conn = pool.acquire().await?;
+    query("CREATE TABLE t (v INTEGER)")
+        .execute(&mut *conn)
+        .await?;
+    let mut tx = conn.begin().await?;
+    query("INSERT INTO t VALUES (1), (2)")
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    conn.begin().await?.rollback().await?;
+
+    let acquires = recorder.fields("musq.acquire");
+    assert_eq!(acquires.len(), 1);
+    assert!(acquires[0].iter().any(|f| f.starts_with("wait=")));
+    assert!(acquires[0].contains(&format!("connection={}", conn.id())));
+
+    let insert = recorder
+        .fields("musq.query")
+        .into_iter()
+        .find(|f| f.contains(&"sql=INSERT INTO t VALUES (1), (2)".to_string()))
+        .unwrap();
+    assert!(insert.contains(&"rows_affected=2".to_string()));
+
+    let outcomes: Vec<_> = recorder
+        .fields("musq.transaction")
+        .into_iter()
+        .map(|f| f.into_iter().find(|f| f.starts_with("outcome=")))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            Some("outcome=commit".into()),
+            Some("outcome=rollback".into())
+        ]
+    );
+    Ok(())
+}
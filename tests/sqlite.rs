@@ -1,12 +1,13 @@
 use futures::TryStreamExt;
 use musq::{
-    query, query_as, query_scalar, Connection, Error, Executor, ExtendedErrCode, Musq,
-    PrimaryErrCode, Row,
+    batch::{WriteBatcher, WriteBatcherOptions},
+    query, query_as, query_scalar, ActiveQuery, Connection, Error, Executor, ExtendedErrCode, Musq,
+    PrimaryErrCode, QueryEvent, QueryKind, QueryState, ResetOnReturn, ResultLimit, Row,
 };
-use musq_test::{connection, tdb};
+use musq_test::{assert_rows_eq, connection, tdb, TestDb};
 use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 #[tokio::test]
 async fn it_connects() -> anyhow::Result<()> {
@@ -14,6 +15,29 @@ async fn it_connects() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn it_builds_reproducible_test_databases() -> anyhow::Result<()> {
+    let mut db = TestDb::new()
+        .test_schema()
+        .schema("INSERT INTO tweet (id, text) VALUES (2, 'three')")
+        .connect()
+        .await?;
+    assert_rows_eq!(
+        &mut *db,
+        "SELECT id, text FROM tweet ORDER BY id",
+        [(1, "two"), (2, "three")]
+    );
+
+    // The same seed generates the same data
+    let text = db.random_text(16);
+    assert_eq!(text.len(), 16);
+    let mut other = TestDb::new().seed(db.seed()).connect().await?;
+    assert_eq!(other.random_text(16), text);
+    let mut other = TestDb::new().seed(db.seed() + 1).connect().await?;
+    assert_ne!(other.random_text(16), text);
+    Ok(())
+}
+
 #[tokio::test]
 async fn it_fetches_and_inflates_row() -> anyhow::Result<()> {
     let mut conn = connection().await?;
@@ -181,6 +205,70 @@ async fn it_executes_with_pool() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn it_fetches_scalars() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    assert_eq!(conn.fetch_scalar::<i64>("SELECT 1 + 1").await?, 2);
+    assert_eq!(
+        conn.fetch_scalar::<String>("PRAGMA journal_mode").await?,
+        "memory"
+    );
+    assert!(matches!(
+        conn.fetch_scalar::<i64>("SELECT 1 WHERE 0").await,
+        Err(Error::RowNotFound)
+    ));
+
+    let pool = Musq::new().open_in_memory().await?;
+    assert_eq!(pool.fetch_scalar::<i32>("SELECT 42").await?, 42);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_calls_pool_instrumentation_callbacks() -> anyhow::Result<()> {
+    let acquired = Arc::new(std::sync::Mutex::new(vec![]));
+    let released = Arc::new(std::sync::Mutex::new(vec![]));
+
+    let (a, r) = (acquired.clone(), released.clone());
+    let pool = Musq::new()
+        .max_connections(1)
+        .on_acquire(move |id, _| a.lock().unwrap().push(id))
+        .on_release(move |id, _| r.lock().unwrap().push(id))
+        .open_in_memory()
+        .await?;
+
+    let conn = pool.acquire().await?;
+    let id = conn.id();
+    drop(conn);
+    pool.fetch_all("SELECT 1").await?;
+
+    assert_eq!(*acquired.lock().unwrap(), [id, id]);
+    assert_eq!(*released.lock().unwrap(), [id, id]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_spawns_pool_tasks_with_the_spawner() -> anyhow::Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let spawned = Arc::new(AtomicUsize::new(0));
+    let s = spawned.clone();
+    let pool = Musq::new()
+        .max_connections(1)
+        .spawner(move |task| {
+            s.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(task);
+        })
+        .open_in_memory()
+        .await?;
+
+    drop(pool.acquire().await?);
+    // The connection is returned by the spawned task, so it can be acquired again
+    pool.fetch_all("SELECT 1").await?;
+    assert!(spawned.load(Ordering::SeqCst) >= 1);
+    Ok(())
+}
+
 #[tokio::test]
 async fn it_opens_in_memory() -> anyhow::Result<()> {
     // If the filename is ":memory:", then a private, temporary in-memory database
@@ -254,6 +342,53 @@ async fn it_binds_dollar_parameters() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn it_binds_named_parameters() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+
+    let v: (i32, String, i32, i32) = query_as("SELECT :a, @b, $c, ?")
+        .bind_named("@b", "x")
+        .bind_named(":a", 1_i32)
+        .bind(4_i32)
+        .bind_named("$c", 2_i32)
+        .bind_named("$c", 3_i32)
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(v, (1, "x".into(), 3, 4));
+
+    // Names can be shared between statements
+    query("CREATE TEMP TABLE t (v); INSERT INTO t VALUES (:v); INSERT INTO t VALUES (:v + 1)")
+        .bind_named(":v", 10_i32)
+        .execute(&mut conn)
+        .await?;
+    let sum: i32 = query_scalar("SELECT sum(v) FROM t")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(sum, 21);
+
+    let err = query_scalar::<i32>("SELECT :a + :b")
+        .bind_named(":a", 1_i32)
+        .fetch_one(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains(":b"), "{err}");
+
+    // An unknown name fails before the statement runs
+    let err = query("INSERT INTO t VALUES (:v)")
+        .bind_named(":v", 1_i32)
+        .bind_named(":w", 2_i32)
+        .execute(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains(":w"), "{err}");
+    let n: i32 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(n, 2);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn it_executes_queries() -> anyhow::Result<()> {
     let mut conn = connection().await?;
@@ -343,15 +478,86 @@ SELECT id, text FROM _musq_test;
 
     let row = cursor.try_next().await?.unwrap();
 
-    let id: i64 = row.get_value("id")?;
+    // `INT PRIMARY KEY` is not an alias for the rowid, so the id is NULL
+    let id: Option<i64> = row.get_value("id")?;
     let text: &str = row.get_value("text")?;
 
-    assert_eq!(0, id);
+    assert_eq!(None, id);
     assert_eq!("this is a test", text);
 
     Ok(())
 }
 
+#[tokio::test]
+async fn it_borrows_text_and_blobs_from_rows() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    let row = conn.fetch_one("SELECT 'text' AS t, x'0102' AS b").await?;
+
+    // Borrowed values point into the row, so decoding twice yields the same buffer
+    let (a, b): (&str, &str) = (row.get_value("t")?, row.get_value("t")?);
+    assert_eq!(a, "text");
+    assert_eq!(a.as_ptr(), b.as_ptr());
+    let (a, b): (&[u8], &[u8]) = (row.get_value("b")?, row.get_value("b")?);
+    assert_eq!(a, [1, 2]);
+    assert_eq!(a.as_ptr(), b.as_ptr());
+
+    // Clones share the value
+    let copy = row.values[1].clone();
+    assert_eq!(copy.blob(), a);
+    assert_eq!(copy.blob().as_ptr(), a.as_ptr());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_executes_with_returning() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    query("CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT NOT NULL, n INTEGER DEFAULT 0)")
+        .execute(&mut conn)
+        .await?;
+
+    let (rows, result): (Vec<(i64, String)>, _) =
+        query("INSERT INTO t (name) VALUES ('a'), ('b') RETURNING id, name")
+            .execute_returning(&mut conn)
+            .await?;
+    assert_eq!(rows, [(1, "a".to_string()), (2, "b".to_string())]);
+    assert_eq!(result.rows_affected(), 2);
+    assert_eq!(result.last_insert_rowid(), 2);
+
+    let (rows, result): (Vec<(i64,)>, _) =
+        query("UPDATE t SET n = n + 1 WHERE name = ? RETURNING n")
+            .bind("b")
+            .execute_returning(&mut conn)
+            .await?;
+    assert_eq!(rows, [(1,)]);
+    assert_eq!(result.rows_affected(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_executes_without_caching_statements() -> anyhow::Result<()> {
+    let pool = Musq::new().max_connections(1).open_in_memory().await?;
+    let result = pool
+        .execute_uncached(
+            "CREATE TABLE t (v INTEGER);
+             INSERT INTO t VALUES (1), (2);
+             UPDATE t SET v = v + 1;",
+        )
+        .await?;
+    assert_eq!(result.rows_affected(), 4);
+    assert_eq!(result.last_insert_rowid(), 2);
+
+    // Uncached statements leave the cache as it was
+    let mut conn = pool.acquire().await?;
+    let cached = conn.cached_statements_size();
+    conn.execute_uncached("DELETE FROM t").await?;
+    assert_eq!(conn.cached_statements_size(), cached);
+    conn.execute("DELETE FROM t").await?;
+    assert_eq!(conn.cached_statements_size(), cached + 1);
+
+    assert!(conn.execute_uncached("SELEC 1").await.is_err());
+    Ok(())
+}
+
 #[tokio::test]
 async fn it_caches_statements() -> anyhow::Result<()> {
     let mut conn = connection().await?;
@@ -430,6 +636,101 @@ async fn it_can_prepare_then_execute() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn it_fetches_rows_in_pages() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    let sql = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000) SELECT i FROM n";
+
+    let pages: Vec<Vec<Row>> = query(sql).fetch_paged(&mut conn, 300).try_collect().await?;
+    assert_eq!(
+        pages.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![300, 300, 300, 100]
+    );
+    let values = pages
+        .iter()
+        .flatten()
+        .map(|row| row.get_value_idx::<i64>(0))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(values, (1..=1000).collect::<Vec<_>>());
+
+    let pages: Vec<Vec<Row>> = query("SELECT 1 WHERE 0")
+        .fetch_paged(&mut conn, 10)
+        .try_collect()
+        .await?;
+    assert!(pages.is_empty());
+    let pages: Vec<Vec<Row>> = query("SELECT 1")
+        .fetch_paged(&mut conn, 0)
+        .try_collect()
+        .await?;
+    assert_eq!(pages.len(), 1);
+
+    // Rows read before an error are delivered before it
+    let mut pages = query(
+        "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5)
+         SELECT CASE WHEN i < 5 THEN i ELSE json('x') END FROM n",
+    )
+    .fetch_paged(&mut conn, 3);
+    assert_eq!(pages.try_next().await?.map(|p| p.len()), Some(3));
+    assert_eq!(pages.try_next().await?.map(|p| p.len()), Some(1));
+    assert!(pages.try_next().await.is_err());
+    drop(pages);
+
+    let pool = Musq::new().open_in_memory().await?;
+    let pages: Vec<Vec<Row>> = query(sql).fetch_paged(&pool, 512).try_collect().await?;
+    assert_eq!(
+        pages.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![512, 488]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_describes_statements() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    query("CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT NOT NULL, note TEXT)")
+        .execute(&mut conn)
+        .await?;
+
+    let statement = (&mut conn)
+        .prepare("SELECT id, name FROM t WHERE id = ? OR name = ?; DELETE FROM t WHERE id = ?")
+        .await?;
+    assert_eq!(statement.parameter_count(), 3);
+    assert_eq!(statement.columns().len(), 2);
+
+    let described = conn
+        .describe("SELECT id, name, note, length(name) AS len FROM t WHERE id = ?")
+        .await?;
+    assert_eq!(described.parameters, 1);
+    let columns: Vec<_> = described
+        .columns
+        .iter()
+        .map(|c| (c.name.as_str(), c.declared_type.as_deref(), c.nullable))
+        .collect();
+    assert_eq!(
+        columns,
+        [
+            ("id", Some("INTEGER"), Some(false)),
+            ("name", Some("TEXT"), Some(false)),
+            ("note", Some("TEXT"), Some(true)),
+            ("len", None, None),
+        ]
+    );
+    let origin = described.columns[0].origin.as_ref().unwrap();
+    assert_eq!(
+        (
+            origin.database.as_str(),
+            origin.table.as_str(),
+            origin.column.as_str()
+        ),
+        ("main", "t", "id")
+    );
+    assert!(origin.primary_key);
+    assert!(described.columns[3].origin.is_none());
+
+    assert!(conn.describe("SELECT nope FROM t").await.is_err());
+    Ok(())
+}
+
 #[tokio::test]
 async fn it_resets_prepared_statement_after_fetch_one() -> anyhow::Result<()> {
     let mut conn = connection().await?;
@@ -744,6 +1045,65 @@ async fn test_multiple_set_progress_handler_calls_drop_old_handler() -> anyhow::
     Ok(())
 }
 
+#[tokio::test]
+async fn it_runs_closures_in_the_worker() -> anyhow::Result<()> {
+    let mut conn = Connection::connect_with(&Musq::new()).await?;
+    query("SELECT 1").execute(&mut conn).await?;
+    let size = conn.cached_statements_size();
+    assert!(size > 0);
+
+    let (cached, in_tx) = conn
+        .run_in_worker(|state| {
+            let cached = state.cached_statements_size();
+            state.clear_cached_statements();
+            (cached, state.in_transaction())
+        })
+        .await?;
+    assert_eq!((cached, in_tx), (size, false));
+    assert_eq!(conn.cached_statements_size(), 0);
+
+    // Progress handlers set in the worker interrupt queries
+    conn.run_in_worker(|state| state.set_progress_handler(1, || false))
+        .await?;
+    assert!(query("SELECT 1").execute(&mut conn).await.is_err());
+    conn.run_in_worker(|state| state.remove_progress_handler())
+        .await?;
+
+    // A panic is resumed in the caller, and the connection survives it
+    let panicked = futures::FutureExt::catch_unwind(std::panic::AssertUnwindSafe(
+        conn.run_in_worker(|_| panic!("boom")),
+    ))
+    .await;
+    assert!(panicked.is_err());
+    let v: i64 = query_scalar("SELECT 2").fetch_one(&mut conn).await?;
+    assert_eq!(v, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_progress_handler_panic_poisons_handler() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    conn.lock_handle()
+        .await?
+        .set_progress_handler(1, || panic!("progress canary"));
+
+    for _ in 0..2 {
+        match query("SELECT 'hello' AS title").fetch_all(&mut conn).await {
+            Err(Error::CallbackPanicked { callback, message }) => {
+                assert_eq!(callback, "progress handler");
+                assert_eq!(message, "progress canary");
+            }
+            _ => panic!("expected a callback panic"),
+        }
+    }
+
+    conn.lock_handle().await?.remove_progress_handler();
+    query("SELECT 'hello' AS title")
+        .fetch_all(&mut conn)
+        .await?;
+    Ok(())
+}
+
 #[tokio::test]
 async fn it_binds_strings() -> anyhow::Result<()> {
     let mut conn = connection().await?;
@@ -761,3 +1121,2214 @@ async fn it_binds_strings() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn it_batches_writes() -> anyhow::Result<()> {
+    let pool = Musq::new().open_in_memory().await?;
+    pool.execute("CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT NOT NULL)")
+        .await?;
+
+    let batcher = WriteBatcher::new(
+        pool.clone(),
+        WriteBatcherOptions::new()
+            .max_batch(10)
+            .max_delay(std::time::Duration::from_millis(50)),
+    );
+
+    let writes = (0..25).map(|i| {
+        let batcher = batcher.clone();
+        tokio::spawn(async move {
+            let kind = if i == 7 {
+                None
+            } else {
+                Some(format!("kind-{i}"))
+            };
+            batcher
+                .execute(
+                    query("INSERT INTO events (id, kind) VALUES (?, ?)")
+                        .bind(i)
+                        .bind(kind),
+                )
+                .await
+        })
+    });
+    let results = futures::future::join_all(writes).await;
+
+    for (i, res) in results.into_iter().enumerate() {
+        let res = res?;
+        if i == 7 {
+            assert!(res.is_err());
+        } else {
+            assert_eq!(res?.rows_affected(), 1);
+        }
+    }
+
+    let count: i64 = pool.fetch_scalar("SELECT count(*) FROM events").await?;
+    assert_eq!(count, 24);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_fails_batches_whose_transaction_is_rolled_back() -> anyhow::Result<()> {
+    let pool = Musq::new().max_connections(1).open_in_memory().await?;
+    pool.execute(
+        "CREATE TABLE events (kind TEXT NOT NULL);
+        CREATE TRIGGER veto BEFORE INSERT ON events WHEN new.kind = 'veto'
+            BEGIN SELECT RAISE(ROLLBACK, 'vetoed'); END;",
+    )
+    .await?;
+    let batcher = WriteBatcher::new(
+        pool.clone(),
+        WriteBatcherOptions::new()
+            .max_batch(3)
+            .max_delay(Duration::from_secs(5)),
+    );
+
+    let writes = ["a", "veto", "b"].map(|kind| {
+        let batcher = batcher.clone();
+        tokio::spawn(async move {
+            batcher
+                .execute(query("INSERT INTO events VALUES (?)").bind(kind))
+                .await
+        })
+    });
+    for res in futures::future::join_all(writes).await {
+        assert!(matches!(res?, Err(musq::Error::BatchFailed(_))));
+    }
+    let count: i64 = pool.fetch_scalar("SELECT count(*) FROM events").await?;
+    assert_eq!(count, 0);
+
+    // The connection is left outside of any transaction, ready for the next batch
+    let writes = ["c", "d", "e"]
+        .map(|kind| batcher.execute(query("INSERT INTO events VALUES (?)").bind(kind)));
+    for res in futures::future::join_all(writes).await {
+        res?;
+    }
+    let count: i64 = pool.fetch_scalar("SELECT count(*) FROM events").await?;
+    assert_eq!(count, 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_serializes_writes_through_the_writer() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-writer")?;
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .busy_timeout(Duration::from_millis(1))
+        .max_connections(8)
+        .open(dir.path().join("db.sqlite"))
+        .await?;
+    pool.execute("CREATE TABLE events (id INTEGER PRIMARY KEY, n INTEGER NOT NULL)")
+        .await?;
+
+    let tasks: Vec<_> = (0..8)
+        .map(|i| {
+            let writer = pool.writer();
+            tokio::spawn(async move {
+                for j in 0..50 {
+                    writer
+                        .execute(query("INSERT INTO events (n) VALUES (?)").bind(i * 50 + j))
+                        .await?;
+                }
+                Ok::<_, Error>(())
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await??;
+    }
+    let count: i64 = query_scalar("SELECT count(*) FROM events")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(count, 400);
+
+    // Failed writes only fail their caller
+    let writer = pool.writer();
+    assert!(writer
+        .execute(query("INSERT INTO nope VALUES (1)"))
+        .await
+        .is_err());
+    let done = writer
+        .execute(query("DELETE FROM events WHERE n < 100"))
+        .await?;
+    assert_eq!(done.rows_affected(), 100);
+
+    // Closing the pool stops the writer
+    pool.close().await;
+    assert!(matches!(
+        writer.execute(query("DELETE FROM events")).await,
+        Err(Error::PoolClosed)
+    ));
+    assert!(matches!(
+        pool.writer().execute(query("DELETE FROM events")).await,
+        Err(Error::PoolClosed)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_flushes_and_syncs() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-sync")?;
+    let path = dir.path().join("db.sqlite");
+    let musq = Musq::new()
+        .create_if_missing(true)
+        .synchronous(musq::Synchronous::Off)
+        .filename(&path);
+    let mut conn = Connection::connect_with(&musq).await?;
+    query("PRAGMA journal_mode = WAL")
+        .execute(&mut conn)
+        .await?;
+    query("CREATE TABLE t (v INTEGER)")
+        .execute(&mut conn)
+        .await?;
+
+    let mut tx = conn.begin().await?;
+    query("INSERT INTO t VALUES (1)").execute(&mut *tx).await?;
+    tx.cacheflush().await?;
+    tx.commit().await?;
+    conn.fsync_barrier().await?;
+
+    let mut other = Connection::connect_with(&musq).await?;
+    let n: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut other)
+        .await?;
+    assert_eq!(n, 1);
+
+    let mut conn = connection().await?;
+    conn.cacheflush().await?;
+    conn.fsync_barrier().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_transacts_across_attached_databases() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-attach")?;
+    let musq = Musq::new()
+        .create_if_missing(true)
+        .filename(dir.path().join("hot.db"));
+    let archive = dir.path().join("archive.db");
+    let mut conn = Connection::connect_with(&musq).await?;
+    query("CREATE TABLE events (id INTEGER PRIMARY KEY); INSERT INTO events VALUES (1), (2);")
+        .execute(&mut conn)
+        .await?;
+
+    conn.transaction_with_attached(&archive, "archive", |tx| {
+        Box::pin(async move {
+            query("CREATE TABLE archive.events (id INTEGER PRIMARY KEY)")
+                .execute(&mut **tx)
+                .await?;
+            Ok::<_, Error>(())
+        })
+    })
+    .await?;
+
+    // A failing transaction leaves both databases untouched, and is still detached
+    let ret = conn
+        .transaction_with_attached(&archive, "archive", |tx| {
+            Box::pin(async move {
+                query("INSERT INTO archive.events SELECT * FROM events")
+                    .execute(&mut **tx)
+                    .await?;
+                query("DELETE FROM events").execute(&mut **tx).await?;
+                query("SELECT * FROM nonexistent")
+                    .execute(&mut **tx)
+                    .await?;
+                Ok::<_, Error>(())
+            })
+        })
+        .await;
+    assert!(ret.is_err());
+    let n: i64 = query_scalar("SELECT count(*) FROM events")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(n, 2);
+
+    let moved = conn
+        .transaction_with_attached(&archive, "archive", |tx| {
+            Box::pin(async move {
+                query("INSERT INTO archive.events SELECT * FROM events WHERE id = 1")
+                    .execute(&mut **tx)
+                    .await?;
+                let r = query("DELETE FROM events WHERE id = 1")
+                    .execute(&mut **tx)
+                    .await?;
+                Ok::<_, Error>(r.rows_affected())
+            })
+        })
+        .await?;
+    assert_eq!(moved, 1);
+
+    let schemas: Vec<(i64, String, String)> = query_as("PRAGMA database_list")
+        .fetch_all(&mut conn)
+        .await?;
+    assert_eq!(schemas.len(), 1);
+
+    conn.transaction_with_attached(&archive, "archive", |tx| {
+        Box::pin(async move {
+            let n: i64 = query_scalar("SELECT count(*) FROM archive.events")
+                .fetch_one(&mut **tx)
+                .await?;
+            assert_eq!(n, 1);
+            Ok::<_, Error>(())
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_classifies_queries() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    query("CREATE TABLE t (a INTEGER)")
+        .execute(&mut conn)
+        .await?;
+
+    let select = (&mut conn).prepare("SELECT a FROM t").await?;
+    assert!(select.is_readonly());
+    assert_eq!(select.query().classify(), QueryKind::Read);
+
+    let insert = (&mut conn).prepare("INSERT INTO t VALUES (1)").await?;
+    assert!(!insert.is_readonly());
+    assert_eq!(insert.query().classify(), QueryKind::Write);
+
+    // Compound SQL is read-only only if every statement is
+    let compound = (&mut conn)
+        .prepare("SELECT a FROM t; UPDATE t SET a = 2")
+        .await?;
+    assert!(!compound.is_readonly());
+    assert_eq!(compound.query().classify(), QueryKind::Write);
+
+    assert_eq!(query("DROP TABLE t").classify(), QueryKind::Ddl);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_interrupts_running_queries() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    let interrupt = conn.interrupt_handle();
+
+    // An endless query, cancelled from another task while its stream is in flight
+    let mut rows = query(
+        "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c",
+    )
+    .fetch_many(&mut conn);
+    let canceller = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        interrupt.interrupt();
+    });
+    let err = loop {
+        match rows.try_next().await {
+            Err(e) => break e,
+            Ok(Some(_)) => {}
+            Ok(None) => panic!("expected an interrupt"),
+        }
+    };
+    drop(rows);
+    canceller.await?;
+    assert!(matches!(err, Error::Interrupted), "{err:?}");
+
+    // The connection is still usable
+    let n: i64 = query_scalar("SELECT 1").fetch_one(&mut conn).await?;
+    assert_eq!(n, 1);
+
+    // Interrupting an idle connection does nothing
+    conn.interrupt();
+    let n: i64 = query_scalar("SELECT 1").fetch_one(&mut conn).await?;
+    assert_eq!(n, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_limits_result_sizes() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    let series = "WITH RECURSIVE s(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM s) \
+        SELECT n, 'abcd' FROM s";
+
+    // The limit stops an unbounded query
+    let err = query_as::<(i64, String)>(series)
+        .max_rows(100)
+        .fetch_all(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::ResultLimitExceeded {
+                limit: ResultLimit::Rows(100)
+            }
+        ),
+        "{err:?}"
+    );
+
+    // Each row is 8 bytes for the integer and 4 for the text
+    let sql = format!("{series} LIMIT 10");
+    let rows = query(&sql)
+        .max_result_bytes(120)
+        .fetch_all(&mut conn)
+        .await?;
+    assert_eq!(rows.len(), 10);
+    let err = query_as::<(i64, String)>(&sql)
+        .max_result_bytes(119)
+        .fetch_all(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::ResultLimitExceeded {
+                limit: ResultLimit::Bytes(119)
+            }
+        ),
+        "{err:?}"
+    );
+
+    // Limits count rows over all statements, and don't apply to writes
+    let n: i64 = query_scalar("SELECT 1; SELECT 2")
+        .max_rows(1)
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(n, 1);
+    assert!(query("SELECT 1; SELECT 2")
+        .max_rows(1)
+        .fetch_all(&mut conn)
+        .await
+        .is_err());
+    query("CREATE TEMP TABLE t (v); INSERT INTO t VALUES (1), (2), (3)")
+        .max_rows(0)
+        .execute(&mut conn)
+        .await?;
+
+    // The connection is still usable
+    let n: i64 = query_scalar("SELECT count(*) FROM t")
+        .max_rows(1)
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(n, 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_reports_active_queries() -> anyhow::Result<()> {
+    let pool = Musq::new().capture_query_sql(true).open_in_memory().await?;
+    let endless = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c)";
+    let wait_for = |pred: fn(&[ActiveQuery]) -> bool| {
+        let pool = pool.clone();
+        async move {
+            for _ in 0..500 {
+                let queries = pool.active_queries();
+                if pred(&queries) {
+                    return queries;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            panic!("timed out waiting for active queries");
+        }
+    };
+    assert!(pool.active_queries().is_empty());
+
+    // A query running inside SQLite
+    let mut conn = pool.acquire().await?;
+    let id = conn.id();
+    let interrupt = conn.interrupt_handle();
+    let counting = format!("{endless} SELECT count(*) FROM c");
+    let sql = counting.clone();
+    let task = tokio::spawn(async move { query_scalar::<i64>(&sql).fetch_one(&mut *conn).await });
+    let queries = wait_for(|q| q.len() == 1 && q[0].state == QueryState::Stepping).await;
+    assert_eq!(queries[0].connection, id);
+    assert_eq!(queries[0].sql.as_deref(), Some(counting.as_str()));
+    interrupt.interrupt();
+    assert!(matches!(task.await?, Err(Error::Interrupted)));
+    wait_for(|q| q.is_empty()).await;
+
+    // A query whose results aren't being consumed
+    let mut conn = pool.acquire().await?;
+    let mut rows = query(&format!("{endless} SELECT x FROM c")).fetch(&mut *conn);
+    rows.try_next().await?;
+    wait_for(|q| q.len() == 1 && q[0].state == QueryState::Streaming).await;
+    drop(rows);
+    wait_for(|q| q.is_empty()).await;
+    drop(conn);
+
+    // SQL is only captured on request
+    let pool = Musq::new().open_in_memory().await?;
+    let mut conn = pool.acquire().await?;
+    let mut rows = query(&format!("{endless} SELECT x FROM c")).fetch(&mut *conn);
+    rows.try_next().await?;
+    let queries = pool.active_queries();
+    assert_eq!(queries.len(), 1);
+    assert_eq!(queries[0].sql, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_reports_queue_metrics() -> anyhow::Result<()> {
+    let saturated = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let events = saturated.clone();
+    let pool = Musq::new()
+        .command_buffer_size(1)
+        .row_buffer_size(4)
+        .on_command_buffer_saturated(Duration::from_millis(20), move |id, waited| {
+            events.lock().unwrap().push((id, waited));
+        })
+        .open_in_memory()
+        .await?;
+    let mut conn = pool.acquire().await?;
+    let endless = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c)";
+
+    let metrics = conn.queue_metrics();
+    assert_eq!((metrics.commands, metrics.command_capacity), (0, 1));
+    assert_eq!((metrics.rows, metrics.row_capacity), (0, 4));
+
+    // The worker fills the row buffer while the caller isn't reading
+    let sql = format!("{endless} SELECT x FROM c");
+    let mut rows = conn.fetch(query(&sql));
+    rows.try_next().await?;
+    for _ in 0..500 {
+        if pool.queue_metrics()[0].rows == 4 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(pool.queue_metrics()[0].rows, 4);
+    drop(rows);
+
+    // Keep the worker busy, and fill the command buffer behind it
+    let interrupt = conn.interrupt_handle();
+    let sql = format!("{endless} SELECT count(*) FROM c");
+    for sql in [sql.as_str(), "SELECT 1"] {
+        let mut rows = conn.fetch(query(sql));
+        let _ = tokio::time::timeout(Duration::from_millis(10), rows.try_next()).await;
+    }
+    assert_eq!(conn.queue_metrics().commands, 1);
+    assert!(saturated.lock().unwrap().is_empty());
+
+    // The next command waits until the worker is interrupted
+    let canceller = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        interrupt.interrupt();
+    });
+    let n: i64 = query_scalar("SELECT 2").fetch_one(&mut *conn).await?;
+    assert_eq!(n, 2);
+    canceller.await?;
+    let saturated = saturated.lock().unwrap();
+    assert_eq!(saturated.len(), 1);
+    assert_eq!(saturated[0].0, conn.id());
+    assert!(saturated[0].1 >= Duration::from_millis(20));
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_overrides_row_buffer_size_per_query() -> anyhow::Result<()> {
+    let pool = Musq::new().row_buffer_size(4).open_in_memory().await?;
+    let mut conn = pool.acquire().await?;
+    let sql = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT x FROM c";
+
+    // The worker runs ahead by the query's buffer size, and waits once it is full
+    let mut rows = conn.fetch(query(sql).row_buffer_size(16));
+    rows.try_next().await?;
+    for _ in 0..500 {
+        if pool.queue_metrics()[0].rows == 16 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let metrics = pool.queue_metrics()[0];
+    assert_eq!((metrics.rows, metrics.row_capacity), (16, 16));
+    assert!(metrics.row_stalls > 0);
+    drop(rows);
+
+    // Other queries use the connection's buffer size
+    let n: i64 = query_scalar("SELECT 1").fetch_one(&mut *conn).await?;
+    assert_eq!(n, 1);
+    let metrics = conn.queue_metrics();
+    assert_eq!((metrics.rows, metrics.row_capacity), (0, 4));
+
+    let values: Vec<i64> = query_scalar("SELECT value FROM json_each('[1, 2, 3]')")
+        .row_buffer_size(1)
+        .fetch_all(&mut *conn)
+        .await?;
+    assert_eq!(values, [1, 2, 3]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_logs_to_a_custom_sink() -> anyhow::Result<()> {
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let pool = Musq::new()
+        .log_sink(move |event: &QueryEvent<'_>| {
+            sink.lock().unwrap().push((
+                event.sql.to_string(),
+                event.level.to_string(),
+                event.rows_returned,
+            ));
+        })
+        .open_in_memory()
+        .await?;
+
+    events.lock().unwrap().clear();
+    query("SELECT 1 UNION ALL SELECT 2")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![(
+            "SELECT 1 UNION ALL SELECT 2".to_string(),
+            "DEBUG".to_string(),
+            2
+        )]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_attaches_databases_to_pool_connections() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-attach")?;
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .attach(dir.path().join("a.db"), "a")
+        .attach(dir.path().join("b.db"), "b")
+        .open(dir.path().join("main.db"))
+        .await?;
+    query(
+        "CREATE TABLE a.t (v INTEGER); CREATE TABLE b.t (v INTEGER); INSERT INTO a.t VALUES (1);",
+    )
+    .execute(&pool)
+    .await?;
+
+    // Every connection opened by the pool has the attachments
+    let mut conns = Vec::new();
+    for _ in 0..3 {
+        let mut conn = pool.acquire().await?;
+        let n: i64 = query_scalar("SELECT count(*) FROM a.t")
+            .fetch_one(&mut *conn)
+            .await?;
+        assert_eq!(n, 1);
+        conns.push(conn);
+    }
+
+    // `PoolConnection::detach` takes a connection out of the pool, so go through the `Connection`
+    let conn: &mut Connection = &mut conns[0];
+    conn.detach("b").await?;
+    assert!(query("SELECT * FROM b.t")
+        .execute(&mut *conn)
+        .await
+        .is_err());
+    conn.attach(dir.path().join("b.db"), "b").await?;
+    query("SELECT * FROM b.t").execute(&mut *conn).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_loads_extensions_on_connect() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-extension")?;
+    let missing = dir.path().join("missing.so");
+
+    // A missing extension fails opening the pool, with SQLite's message
+    let err = Musq::new()
+        .extension(&missing)
+        .open_in_memory()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Sqlite(_)));
+    assert!(err.to_string().contains("missing"), "{err}");
+    assert!(Musq::new()
+        .extension_with_entrypoint(&missing, "sqlite3_ext_init")
+        .open_in_memory()
+        .await
+        .is_err());
+
+    // Loading through SQL stays disabled
+    let pool = Musq::new().open_in_memory().await?;
+    let loaded = query("SELECT load_extension(?)")
+        .bind(missing.to_string_lossy().into_owned())
+        .execute(&pool)
+        .await;
+    assert!(loaded.unwrap_err().to_string().contains("not authorized"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_backs_up_live_databases() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-backup")?;
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .journal_mode(musq::JournalMode::Wal)
+        .open(dir.path().join("live.db"))
+        .await?;
+    query("CREATE TABLE t (v BLOB)").execute(&pool).await?;
+    for _ in 0..50 {
+        query("INSERT INTO t VALUES (randomblob(4096))")
+            .execute(&pool)
+            .await?;
+    }
+
+    let snapshot = dir.path().join("snapshot.db");
+    let steps = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let progress = steps.clone();
+    pool.acquire()
+        .await?
+        .backup_to(&snapshot)
+        .pages_per_step(10)
+        .on_progress(move |p| progress.lock().unwrap().push(p))
+        .run()
+        .await?;
+    let steps = steps.lock().unwrap().clone();
+    assert!(steps.len() > 1);
+    assert_eq!(steps.last().unwrap().remaining, 0);
+
+    // Restore the snapshot into a fresh database
+    let mut conn = Connection::connect_with(
+        &Musq::new()
+            .filename(dir.path().join("restored.db"))
+            .create_if_missing(true),
+    )
+    .await?;
+    conn.backup_from(&snapshot).run().await?;
+    let n: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(n, 50);
+    assert_eq!(
+        conn.table_checksum("t").await?,
+        pool.acquire().await?.table_checksum("t").await?
+    );
+
+    // A missing source fails cleanly
+    assert!(conn
+        .backup_from(dir.path().join("missing.db"))
+        .run()
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_serializes_databases() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    query("CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT); INSERT INTO t (name) VALUES ('a'), ('b')")
+        .execute(&mut conn)
+        .await?;
+    let snapshot = conn.serialize("main").await?;
+    assert_eq!(&snapshot[..16], b"SQLite format 3\0");
+
+    query("DELETE FROM t").execute(&mut conn).await?;
+    conn.deserialize("main", &snapshot).await?;
+    let names: Vec<String> = query_scalar("SELECT name FROM t ORDER BY id")
+        .fetch_all(&mut conn)
+        .await?;
+    assert_eq!(names, ["a", "b"]);
+
+    // Restored databases can be written to, and loaded into other connections
+    query("INSERT INTO t (name) VALUES ('c')")
+        .execute(&mut conn)
+        .await?;
+    let mut other = connection().await?;
+    other
+        .deserialize("main", conn.serialize("main").await?)
+        .await?;
+    let count: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut other)
+        .await?;
+    assert_eq!(count, 3);
+
+    assert!(conn.serialize("nope").await.is_err());
+    // SQLite only notices that the data isn't a database when it's read
+    assert!(conn.deserialize("main", b"not a database").await.is_ok());
+    assert!(query("SELECT * FROM t").execute(&mut conn).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_closes_deserialized_pool_connections() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-deserialize")?;
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .max_connections(1)
+        .open(dir.path().join("db.sqlite"))
+        .await?;
+    query("CREATE TABLE t (v)").execute(&pool).await?;
+
+    let mut conn = pool.acquire().await?;
+    let id = conn.id();
+    let snapshot = conn.serialize("main").await?;
+    query("INSERT INTO t VALUES (1)")
+        .execute(&mut *conn)
+        .await?;
+    conn.deserialize("main", &snapshot).await?;
+    drop(conn);
+
+    // The pool hands out a new connection to the database file, rather than the in-memory copy
+    let mut conn = pool.acquire().await?;
+    assert_ne!(conn.id(), id);
+    let count: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut *conn)
+        .await?;
+    assert_eq!(count, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_drops_temp_tables() -> anyhow::Result<()> {
+    let pool = Musq::new().max_connections(1).open_in_memory().await?;
+    let mut conn = pool.acquire().await?;
+
+    let table = conn
+        .create_temp_table("id INTEGER PRIMARY KEY, v TEXT")
+        .await?;
+    query(&format!("INSERT INTO {table} (v) VALUES ('a'), ('b')"))
+        .execute(&mut *conn)
+        .await?;
+    let n: i64 = query_scalar(&format!("SELECT count(*) FROM {table}"))
+        .fetch_one(&mut *conn)
+        .await?;
+    assert_eq!(n, 2);
+
+    let count = "SELECT count(*) FROM temp.sqlite_schema WHERE type = 'table'";
+    drop(table);
+    let n: i64 = query_scalar(count).fetch_one(&mut *conn).await?;
+    assert_eq!(n, 0);
+
+    // Tables still alive when the connection goes back to the pool are dropped too
+    let table = conn.create_temp_table("v TEXT").await?;
+    let name = table.name().to_string();
+    drop(conn);
+    let mut conn = pool.acquire().await?;
+    let n: i64 = query_scalar(count).fetch_one(&mut *conn).await?;
+    assert_eq!(n, 0);
+
+    // A guard outliving its connection's lease leaves the next user alone
+    query(&format!("CREATE TEMP TABLE {name} (v TEXT)"))
+        .execute(&mut *conn)
+        .await?;
+    drop(table);
+    let n: i64 = query_scalar(count).fetch_one(&mut *conn).await?;
+    assert_eq!(n, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_resets_connections_on_return() -> anyhow::Result<()> {
+    let pool = Musq::new()
+        .max_connections(1)
+        .reset_on_return(ResetOnReturn::all())
+        .open_in_memory()
+        .await?;
+    let mut conn = pool.acquire().await?;
+    query("BEGIN; CREATE TEMP TABLE scratch (v); CREATE TEMP VIEW seen AS SELECT 1; PRAGMA foreign_keys = OFF;")
+        .execute(&mut *conn)
+        .await?;
+    query("CREATE TABLE t (v)").execute(&mut *conn).await?;
+    drop(conn);
+
+    let mut conn = pool.acquire().await?;
+    let temp: i64 = query_scalar("SELECT count(*) FROM temp.sqlite_schema")
+        .fetch_one(&mut *conn)
+        .await?;
+    assert_eq!(temp, 0);
+    let fk: i64 = query_scalar("PRAGMA foreign_keys")
+        .fetch_one(&mut *conn)
+        .await?;
+    assert_eq!(fk, 1);
+    // The open transaction was rolled back, taking the table with it
+    let tables: i64 = query_scalar("SELECT count(*) FROM sqlite_schema")
+        .fetch_one(&mut *conn)
+        .await?;
+    assert_eq!(tables, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_analyzes_churned_tables() -> anyhow::Result<()> {
+    let series = |n: u32| {
+        format!("WITH RECURSIVE n(v) AS (SELECT 1 UNION ALL SELECT v + 1 FROM n WHERE v < {n}) SELECT v FROM n")
+    };
+    let pool = Musq::new().track_changes(true).open_in_memory().await?;
+    assert!(Musq::new()
+        .open_in_memory()
+        .await?
+        .maybe_analyze(1)
+        .await
+        .is_err());
+
+    query("CREATE TABLE t (a INTEGER); CREATE TABLE u (a INTEGER)")
+        .execute(&pool)
+        .await?;
+    query(&format!("INSERT INTO t {}", series(10)))
+        .execute(&pool)
+        .await?;
+    assert!(pool.maybe_analyze(50).await?.is_empty());
+
+    // Rolled back writes don't count
+    let mut tx = pool.begin().await?;
+    query(&format!("INSERT INTO u {}", series(100)))
+        .execute(&mut *tx)
+        .await?;
+    tx.rollback().await?;
+
+    query("UPDATE t SET a = a + 1").execute(&pool).await?;
+    query("DELETE FROM t WHERE a > 5").execute(&pool).await?;
+    let analyzed = pool.maybe_analyze(20).await?;
+    assert_eq!(analyzed, vec![("main".to_string(), "t".to_string())]);
+    let stats: i64 = query_scalar("SELECT count(*) FROM sqlite_stat1 WHERE tbl = 't'")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(stats, 1);
+
+    // Analyzing resets the count
+    assert!(pool.maybe_analyze(1).await?.is_empty());
+
+    // Rows the update hook doesn't see are counted too
+    query("CREATE TABLE w (a INTEGER PRIMARY KEY) WITHOUT ROWID")
+        .execute(&pool)
+        .await?;
+    query(&format!("INSERT INTO w {}", series(60)))
+        .execute(&pool)
+        .await?;
+    query(&format!("INSERT INTO u {}", series(30)))
+        .execute(&pool)
+        .await?;
+    pool.maybe_analyze(1000).await?;
+    query("DELETE FROM u").execute(&pool).await?;
+    let mut analyzed = pool.maybe_analyze(50).await?;
+    analyzed.sort();
+    assert_eq!(
+        analyzed,
+        vec![
+            ("main".to_string(), "u".to_string()),
+            ("main".to_string(), "w".to_string())
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_calls_commit_rollback_and_update_hooks() -> anyhow::Result<()> {
+    // User hooks coexist with the hooks used for change tracking
+    let pool = Musq::new()
+        .track_changes(true)
+        .max_connections(1)
+        .open_in_memory()
+        .await?;
+    let mut conn = pool.acquire().await?;
+    query("CREATE TABLE t (a INTEGER)")
+        .execute(&mut *conn)
+        .await?;
+
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let allow = Arc::new(std::sync::atomic::AtomicBool::new(true));
+    {
+        let mut handle = conn.lock_handle().await?;
+        let e = events.clone();
+        handle.set_update_hook(move |op, db, table, rowid| {
+            e.lock()
+                .unwrap()
+                .push(format!("{op:?} {db}.{table} {rowid}"));
+        });
+        let (e, a) = (events.clone(), allow.clone());
+        handle.set_commit_hook(move || {
+            e.lock().unwrap().push("commit".into());
+            a.load(std::sync::atomic::Ordering::SeqCst)
+        });
+        let e = events.clone();
+        handle.set_rollback_hook(move || e.lock().unwrap().push("rollback".into()));
+    }
+    let take = || std::mem::take(&mut *events.lock().unwrap());
+
+    query("INSERT INTO t VALUES (1), (2)")
+        .execute(&mut *conn)
+        .await?;
+    query("UPDATE t SET a = 3 WHERE rowid = 2")
+        .execute(&mut *conn)
+        .await?;
+    query("DELETE FROM t WHERE a = 1")
+        .execute(&mut *conn)
+        .await?;
+    assert_eq!(
+        take(),
+        vec![
+            "Insert main.t 1",
+            "Insert main.t 2",
+            "commit",
+            "Update main.t 2",
+            "commit",
+            "Delete main.t 1",
+            "commit"
+        ]
+    );
+
+    // A commit hook can veto a commit
+    allow.store(false, std::sync::atomic::Ordering::SeqCst);
+    let mut tx = conn.begin().await?;
+    query("INSERT INTO t VALUES (4)").execute(&mut *tx).await?;
+    assert!(tx.commit().await.is_err());
+    assert_eq!(take(), vec!["Insert main.t 3", "commit", "rollback"]);
+    let count: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut *conn)
+        .await?;
+    assert_eq!(count, 1);
+    allow.store(true, std::sync::atomic::Ordering::SeqCst);
+    take();
+
+    {
+        let mut handle = conn.lock_handle().await?;
+        handle.remove_update_hook();
+        handle.remove_commit_hook();
+        handle.remove_rollback_hook();
+    }
+    query("INSERT INTO t VALUES (5)")
+        .execute(&mut *conn)
+        .await?;
+    assert!(take().is_empty());
+
+    drop(conn);
+    // Change tracking saw the committed writes, but not the vetoed one
+    assert!(pool.maybe_analyze(6).await?.is_empty());
+    assert_eq!(pool.maybe_analyze(5).await?.len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_reports_panics_in_update_and_rollback_hooks() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    query("CREATE TEMP TABLE t (a INTEGER)")
+        .execute(&mut conn)
+        .await?;
+    {
+        let mut handle = conn.lock_handle().await?;
+        handle.set_update_hook(|_, _, _, _| panic!("update canary"));
+        handle.set_rollback_hook(|| panic!("rollback canary"));
+    }
+
+    // The statement takes effect, but fails, and so does every later one until the hook is removed
+    for _ in 0..2 {
+        match query("INSERT INTO t VALUES (1)").execute(&mut conn).await {
+            Err(Error::CallbackPanicked { callback, message }) => {
+                assert_eq!(callback, "update hook");
+                assert_eq!(message, "update canary");
+            }
+            other => panic!("expected a callback panic, got {other:?}"),
+        }
+    }
+    conn.lock_handle().await?.remove_update_hook();
+    let count: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(count, 2);
+
+    // The rollback goes ahead, but fails
+    let mut tx = conn.begin().await?;
+    query("INSERT INTO t VALUES (2)").execute(&mut *tx).await?;
+    match tx.rollback().await {
+        Err(Error::CallbackPanicked { callback, .. }) => assert_eq!(callback, "rollback hook"),
+        other => panic!("expected a callback panic, got {other:?}"),
+    }
+    conn.lock_handle().await?.remove_rollback_hook();
+    let count: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(count, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_deserializes_rows_with_serde() -> anyhow::Result<()> {
+    use musq::{query_as_serde, row::from_row_serde};
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Kind {
+        Admin,
+        User,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Prefs {
+        theme: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct User {
+        id: i64,
+        name: String,
+        active: bool,
+        score: Option<f64>,
+        kind: Kind,
+        prefs: Prefs,
+        tags: Vec<String>,
+        avatar: Vec<u8>,
+        #[serde(default)]
+        missing: Option<i64>,
+    }
+
+    let mut conn = connection().await?;
+    let sql = r#"
+        SELECT
+            ? AS id, 'alice' AS name, 1 AS active, NULL AS score, 'Admin' AS kind,
+            '{"theme": "dark"}' AS prefs, '["a", "b"]' AS tags, X'0102' AS avatar
+    "#;
+    let user: User = query_as_serde(sql).bind(7).fetch_one(&mut conn).await?;
+    assert_eq!(
+        user,
+        User {
+            id: 7,
+            name: "alice".into(),
+            active: true,
+            score: None,
+            kind: Kind::Admin,
+            prefs: Prefs {
+                theme: "dark".into()
+            },
+            tags: vec!["a".into(), "b".into()],
+            avatar: vec![1, 2],
+            missing: None,
+        }
+    );
+
+    // Tuples map columns by position, and &str borrows from the row
+    let row = query("SELECT 1, 'x', 2.5").fetch_one(&mut conn).await?;
+    let (a, b, c): (i64, &str, f64) = from_row_serde(&row)?;
+    assert_eq!((a, b, c), (1, "x", 2.5));
+
+    let err = from_row_serde::<User>(&row).unwrap_err();
+    assert!(matches!(err, Error::Decode(_)), "{err}");
+    let err = query_as_serde::<User>("SELECT 'x' AS id")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("column id"), "{err}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_walks_row_columns() -> anyhow::Result<()> {
+    use musq::SqliteDataType;
+
+    let mut conn = connection().await?;
+    let row = query("SELECT 1 AS id, 'x' AS name, NULL AS note, X'01' AS data")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(row.len(), 4);
+    assert_eq!(
+        row.columns().iter().map(|c| c.name()).collect::<Vec<_>>(),
+        ["id", "name", "note", "data"]
+    );
+
+    let mut fields = Vec::new();
+    for (column, value) in row.iter() {
+        let field = match value.type_info() {
+            SqliteDataType::Null => "NULL".to_string(),
+            SqliteDataType::Blob => format!("{:?}", value.blob()),
+            _ => value.text()?.to_string(),
+        };
+        fields.push(format!("{}={field}", column.name()));
+    }
+    assert_eq!(fields, ["id=1", "name=x", "note=NULL", "data=[1]"]);
+
+    assert_eq!(row.try_get_raw(1)?.text()?, "x");
+    assert!(matches!(
+        row.try_get_raw(4),
+        Err(Error::ColumnIndexOutOfBounds { index: 4, len: 4 })
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_converts_rows_to_maps_and_json() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    let sql = "SELECT 1 AS id, 2.5 AS score, 'x' AS name, NULL AS note, X'666f6f62' AS data, \
+               '{\"a\": 1}' AS doc, 9e999 AS big";
+    let row = query(sql).fetch_one(&mut conn).await?;
+    assert_eq!(
+        row.to_json(),
+        serde_json::json!({
+            "id": 1,
+            "score": 2.5,
+            "name": "x",
+            "note": null,
+            "data": "Zm9vYg==",
+            "doc": "{\"a\": 1}",
+            "big": null,
+        })
+    );
+
+    let map = row.into_map();
+    assert_eq!(
+        map.keys().collect::<Vec<_>>(),
+        ["id", "score", "name", "note", "data", "doc", "big"]
+    );
+    assert_eq!(map["name"].text()?, "x");
+    assert!(map["note"].is_null());
+
+    let row = query("SELECT X'', X'66', X'666f', X'666f6f'")
+        .fetch_one(&mut conn)
+        .await?;
+    let blobs: Vec<_> = row.iter().map(|(_, value)| value.to_json()).collect();
+    assert_eq!(blobs, ["", "Zg==", "Zm8=", "Zm9v"]);
+
+    let row = query("SELECT 1 AS a, 2 AS a").fetch_one(&mut conn).await?;
+    assert_eq!(row.to_json(), serde_json::json!({"a": 2}));
+    assert_eq!(row.into_map()["a"].int64(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_composes_query_fragments() -> anyhow::Result<()> {
+    use musq::{fragment, Fragment, QueryBuilder};
+
+    let mut conn = connection().await?;
+    query(
+        r#"
+        CREATE TABLE products (id INTEGER PRIMARY KEY, name TEXT, price INTEGER, stock INTEGER);
+        INSERT INTO products (name, price, stock) VALUES
+            ('a', 5, 0), ('b', 15, 3), ('c', 25, 1), ('d', 35, 0);
+        "#,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    fn in_stock(min: i64) -> Fragment {
+        fragment!("stock >= {min}")
+    }
+
+    let price = 10;
+    let filter = fragment!(
+        "price > {price} AND {stock:frag} AND name <> {name}",
+        stock = in_stock(1),
+        name = "{x}"
+    );
+    assert_eq!(filter.sql(), "price > ? AND stock >= ? AND name <> ?");
+
+    let mut qb = QueryBuilder::new("SELECT id FROM products WHERE ");
+    qb.push_fragment(&filter).push(" ORDER BY id");
+    let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&mut conn).await?;
+    assert_eq!(ids, vec![2, 3]);
+
+    // Fragments can be reused and joined
+    let either = Fragment::join(" OR ", [filter, fragment!("price < {price}")]);
+    let mut qb = QueryBuilder::from(fragment!(
+        "SELECT count(*) FROM products WHERE {either:frag}"
+    ));
+    let n: i64 = qb.build_query_scalar().fetch_one(&mut conn).await?;
+    assert_eq!(n, 3);
+    assert_eq!(fragment!("'{{}}' = {price}").sql(), "'{}' = ?");
+
+    // Values bound more than once are cloned
+    let name = String::from("b");
+    let mut qb = QueryBuilder::from(fragment!(
+        "SELECT count(*) FROM products WHERE name IN ({name}, {other}, {name}, {other})",
+        other = String::from("c")
+    ));
+    let n: i64 = qb.build_query_scalar().fetch_one(&mut conn).await?;
+    assert_eq!(n, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_builds_lists_and_in_clauses() -> anyhow::Result<()> {
+    use musq::QueryBuilder;
+
+    let mut conn = connection().await?;
+    query("CREATE TABLE products (id INTEGER PRIMARY KEY, name TEXT, price INTEGER)")
+        .execute(&mut conn)
+        .await?;
+
+    let products = [("a", 5), ("b", 15), ("c", 25), ("d", 35)];
+    let mut qb = QueryBuilder::new("INSERT INTO products (name, price) VALUES ");
+    qb.push_tuples(products, |mut b, (name, price)| {
+        b.push_bind(name).push_bind(price);
+    });
+    assert_eq!(
+        qb.sql(),
+        "INSERT INTO products (name, price) VALUES (?, ?), (?, ?), (?, ?), (?, ?)"
+    );
+    qb.build().execute(&mut conn).await?;
+
+    let mut qb = QueryBuilder::new("SELECT id FROM products WHERE ");
+    qb.push_in("name", ["a", "c", "d"]).push(" AND ");
+    let mut filters = qb.separated(" AND ");
+    filters.push("price > ").push_bind_unseparated(10);
+    filters.push("price < ").push_bind_unseparated(30);
+    assert!(!filters.is_empty());
+    qb.push(" ORDER BY id");
+    assert_eq!(
+        qb.sql(),
+        r#"SELECT id FROM products WHERE "name" IN (?, ?, ?) AND price > ? AND price < ? ORDER BY id"#
+    );
+    let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&mut conn).await?;
+    assert_eq!(ids, vec![3]);
+
+    // An empty list matches nothing
+    let mut qb = QueryBuilder::new("SELECT count(*) FROM products WHERE ");
+    qb.push_in("id", Vec::<i64>::new());
+    let n: i64 = qb.build_query_scalar().fetch_one(&mut conn).await?;
+    assert_eq!(n, 0);
+
+    // Tuples compare as row values
+    let mut qb = QueryBuilder::new("SELECT id FROM products WHERE (name, price) IN (VALUES ");
+    qb.push_tuples([("b", 15), ("c", 99)], |mut b, (name, price)| {
+        b.push_bind(name).push_bind(price);
+    })
+    .push(")");
+    let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&mut conn).await?;
+    assert_eq!(ids, vec![2]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_sorts_by_allowlisted_columns() -> anyhow::Result<()> {
+    use musq::{Error, FromRow, QueryBuilder, SortDirection, SortSpec};
+
+    #[derive(FromRow)]
+    #[musq(table = "products")]
+    #[allow(dead_code)]
+    struct Product {
+        id: i64,
+        name: String,
+        price: i64,
+    }
+
+    let mut conn = connection().await?;
+    query(
+        r#"
+        CREATE TABLE products (id INTEGER PRIMARY KEY, name TEXT, price INTEGER, secret TEXT);
+        INSERT INTO products (name, price) VALUES ('a', 20), ('b', 10), ('c', 20);
+        "#,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    let sort = SortSpec::for_table::<Product>().parse("-price, NAME asc")?;
+    let mut qb = QueryBuilder::new("SELECT id FROM products");
+    qb.push_order_by(&sort);
+    assert_eq!(
+        qb.sql(),
+        r#"SELECT id FROM products ORDER BY "price" DESC, "name" ASC"#
+    );
+    let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&mut conn).await?;
+    assert_eq!(ids, vec![1, 3, 2]);
+
+    let sort = SortSpec::new(["p.id"]).by("p.id", SortDirection::Desc)?;
+    let mut qb = QueryBuilder::new("SELECT p.id FROM products p");
+    qb.push_order_by(&sort);
+    let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&mut conn).await?;
+    assert_eq!(ids, vec![3, 2, 1]);
+
+    let mut qb = QueryBuilder::new("SELECT id FROM products");
+    qb.push_order_by(&SortSpec::for_table::<Product>().parse(" , ")?);
+    assert_eq!(qb.sql(), "SELECT id FROM products");
+
+    assert!(matches!(
+        SortSpec::for_table::<Product>().parse("secret"),
+        Err(Error::ColumnNotFound(c)) if c == "secret"
+    ));
+    for bad in [
+        "price; DROP TABLE products",
+        "-price desc",
+        "price sideways",
+        "-",
+    ] {
+        assert!(
+            SortSpec::for_table::<Product>().parse(bad).is_err(),
+            "{bad}"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_builds_json_queries() -> anyhow::Result<()> {
+    use musq::{types::JsonPath, QueryBuilder};
+
+    let path = JsonPath::root().key("user").key("first name").index(1);
+    assert_eq!(path.as_str(), r#"$.user."first name"[1]"#);
+
+    let mut conn = connection().await?;
+    query(
+        r#"
+        CREATE TABLE events (id INTEGER PRIMARY KEY, payload TEXT, raw BLOB);
+        INSERT INTO events (payload, raw) VALUES
+            ('{"user": {"id": 1, "tags": ["a", "b"]}}', CAST('{"n": 1}' AS BLOB)),
+            ('{"user": {"id": 2, "tags": ["c"]}}', NULL);
+        "#,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    let mut qb = QueryBuilder::new("SELECT id FROM events WHERE ");
+    qb.push_json_extract("events.payload", &JsonPath::root().key("user").key("id"))
+        .push(" = ")
+        .push_bind(2);
+    assert_eq!(
+        qb.sql(),
+        r#"SELECT id FROM events WHERE json_extract("events"."payload", ?) = ?"#
+    );
+    let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&mut conn).await?;
+    assert_eq!(ids, vec![2]);
+    assert_eq!(qb.sql(), "");
+
+    let mut qb = QueryBuilder::new("SELECT ");
+    qb.push_json_extract(
+        "payload",
+        &JsonPath::root().key("user").key("tags").from_end(1),
+    )
+    .push(" FROM events ORDER BY id");
+    let last: Vec<String> = qb.build_query_scalar().fetch_all(&mut conn).await?;
+    assert_eq!(last, vec!["b", "c"]);
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct User {
+        id: i64,
+        tags: Vec<String>,
+    }
+    let row = query("SELECT json_extract(payload, '$.user') AS user, raw FROM events ORDER BY id")
+        .fetch_one(&mut conn)
+        .await?;
+    let user: User = row.get_json("user")?;
+    assert_eq!(
+        user,
+        User {
+            id: 1,
+            tags: vec!["a".into(), "b".into()]
+        }
+    );
+    let raw: serde_json::Value = row.get_json("raw")?;
+    assert_eq!(raw, serde_json::json!({"n": 1}));
+    assert!(matches!(
+        row.get_json::<User>("raw"),
+        Err(Error::ColumnDecode { .. })
+    ));
+
+    let row = query("SELECT raw FROM events WHERE id = 2")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(row.get_json::<Option<User>>("raw")?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_builds_full_text_queries() -> anyhow::Result<()> {
+    use musq::{
+        types::{Fts5Highlight, Fts5Rank},
+        QueryBuilder,
+    };
+
+    let mut conn = connection().await?;
+    query(
+        r#"
+        CREATE VIRTUAL TABLE docs USING fts5(title, body);
+        INSERT INTO docs (title, body) VALUES
+            ('Rust', 'rust and sqlite go well together'),
+            ('SQL', 'sqlite is a small database, and sqlite is everywhere'),
+            ('Quotes', 'say "hi" OR leave');
+        "#,
+    )
+    .execute(&mut conn)
+    .await?;
+
+    // Query syntax in user input is matched literally
+    let mut qb = QueryBuilder::new("SELECT title, rank FROM docs WHERE ");
+    qb.push_match("docs", "sqlite").push(" ORDER BY rank");
+    let ranked: Vec<(String, Fts5Rank)> = qb.build_query_as().fetch_all(&mut conn).await?;
+    assert_eq!(ranked.len(), 2);
+    assert_eq!(ranked[0].0, "SQL");
+    assert!(ranked[0].1.relevance() > ranked[1].1.relevance());
+
+    for (input, expected) in [
+        (r#""hi" OR"#, vec!["Quotes"]),
+        ("rust sqlite", vec!["Rust"]),
+        ("NEAR( body:", vec![]),
+        ("", vec![]),
+    ] {
+        let mut qb = QueryBuilder::new("SELECT title FROM docs WHERE ");
+        qb.push_match("docs", input);
+        let titles: Vec<String> = qb.build_query_scalar().fetch_all(&mut conn).await?;
+        assert_eq!(titles, expected, "{input}");
+    }
+
+    let mut qb = QueryBuilder::new("SELECT ");
+    qb.push_highlight("docs", 1)
+        .push(" FROM docs WHERE ")
+        .push_match("docs", "sqlite small");
+    let highlight: Fts5Highlight = qb.build_query_scalar().fetch_one(&mut conn).await?;
+    assert_eq!(
+        highlight.text(),
+        "sqlite is a small database, and sqlite is everywhere"
+    );
+    assert_eq!(
+        highlight.render("[", "]"),
+        "[sqlite] is a [small] database, and [sqlite] is everywhere"
+    );
+    assert_eq!(
+        highlight.segments().filter(|(_, matched)| *matched).count(),
+        3
+    );
+
+    let mut qb = QueryBuilder::new("SELECT ");
+    qb.push_snippet("docs", -1, "...", 3)
+        .push(" FROM docs WHERE ")
+        .push_match("docs", "everywhere");
+    let snippet: Fts5Highlight = qb.build_query_scalar().fetch_one(&mut conn).await?;
+    assert_eq!(
+        snippet.render("<b>", "</b>"),
+        "...sqlite is <b>everywhere</b>"
+    );
+    Ok(())
+}
+#[tokio::test]
+async fn it_binds_typed_values() -> anyhow::Result<()> {
+    use musq::{query_as_with, query_with, Arguments, SqliteDataType};
+
+    let mut conn = connection().await?;
+    let mut args = Arguments::default();
+    args.add_typed("42", SqliteDataType::Int);
+    args.add_typed(5, SqliteDataType::Text);
+    args.add_typed(2, SqliteDataType::Float);
+    args.add_typed("hi", SqliteDataType::Blob);
+    args.add_typed("nope", SqliteDataType::Int);
+    args.add_typed(None::<i64>, SqliteDataType::Text);
+    let types: (String, String, String, String, String, String) = query_as_with(
+        "SELECT typeof(?), typeof(?), typeof(?), typeof(?), typeof(?), typeof(?)",
+        args,
+    )
+    .fetch_one(&mut conn)
+    .await?;
+    assert_eq!(
+        types,
+        (
+            "integer".into(),
+            "text".into(),
+            "real".into(),
+            "blob".into(),
+            "text".into(),
+            "null".into()
+        )
+    );
+
+    query("CREATE TABLE t (n INTEGER, s TEXT CHECK (s IS NULL OR typeof(s) = 'text')) STRICT")
+        .execute(&mut conn)
+        .await?;
+    let mut args = Arguments::default();
+    args.add_typed("7", SqliteDataType::Int);
+    args.add_typed(1.5, SqliteDataType::Text);
+    query_with("INSERT INTO t VALUES (?, ?)", args)
+        .execute(&mut conn)
+        .await?;
+    query("INSERT INTO t VALUES (?, ?)")
+        .bind(8)
+        .bind_null_as(SqliteDataType::Text)
+        .execute(&mut conn)
+        .await?;
+    let rows: Vec<(i64, Option<String>)> = query_as("SELECT n, s FROM t ORDER BY n")
+        .fetch_all(&mut conn)
+        .await?;
+    assert_eq!(rows, vec![(7, Some("1.5".into())), (8, None)]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_warms_up_pools() -> anyhow::Result<()> {
+    let pool = Musq::new().max_connections(4).open_in_memory().await?;
+    // Opening the pool opens its first connection
+    assert_eq!(pool.size(), 1);
+    assert_eq!(pool.warm_up(3).await?, 2);
+    assert_eq!((pool.size(), pool.num_idle()), (3, 3));
+
+    // Existing connections count towards the target, and the target is capped at the pool's size
+    let conn = pool.acquire().await?;
+    assert_eq!(pool.warm_up(10).await?, 1);
+    assert_eq!((pool.size(), pool.num_idle()), (4, 3));
+    drop(conn);
+    assert_eq!(pool.warm_up(2).await?, 0);
+
+    pool.close().await;
+    assert!(matches!(pool.warm_up(1).await, Err(Error::PoolClosed)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_warms_up_pools_in_parallel() -> anyhow::Result<()> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let fail = Arc::new(AtomicBool::new(false));
+    let pool = Musq::new()
+        .max_connections(5)
+        .after_connect({
+            let fail = fail.clone();
+            move |_| {
+                let fail = fail.load(Ordering::SeqCst);
+                Box::pin(async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    if fail {
+                        return Err(Error::Protocol("refused".into()));
+                    }
+                    Ok(())
+                })
+            }
+        })
+        .open_in_memory()
+        .await?;
+    let started = std::time::Instant::now();
+    assert_eq!(pool.warm_up(4).await?, 3);
+    assert!(started.elapsed() < Duration::from_millis(250));
+
+    // Every failure is reported
+    fail.store(true, Ordering::SeqCst);
+    drop(pool.acquire().await?.detach());
+    drop(pool.acquire().await?.detach());
+    assert_eq!(pool.size(), 2);
+    match pool.warm_up(5).await {
+        Err(Error::WarmUpFailed { opened, errors }) => {
+            assert_eq!((opened, errors.len()), (0, 3));
+            assert!(matches!(errors[0], Error::Protocol(_)));
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+    assert_eq!(pool.size(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_reaps_expired_connections() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("musq-reap")?;
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .max_connections(3)
+        .max_lifetime(Duration::from_millis(100))
+        .open(dir.path().join("lifetime.db"))
+        .await?;
+    let conn = pool.acquire().await?;
+    let id = conn.id();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    // Expired connections are closed when returned
+    drop(conn);
+    while pool.size() > 0 {
+        tokio::task::yield_now().await;
+    }
+    let conn = pool.acquire().await?;
+    assert_ne!(conn.id(), id);
+    drop(conn);
+    // and when idle
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(pool.size(), 0);
+
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .max_connections(3)
+        .min_connections(1)
+        .idle_timeout(Duration::from_millis(50))
+        .open(dir.path().join("idle.db"))
+        .await?;
+    assert_eq!(pool.warm_up(3).await?, 2);
+    assert_eq!(pool.size(), 3);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    // Idle connections are closed down to min_connections
+    assert_eq!((pool.size(), pool.num_idle()), (1, 1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_manages_savepoints() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    query("CREATE TABLE t (id INTEGER PRIMARY KEY)")
+        .execute(&mut conn)
+        .await?;
+    async fn ids(conn: &mut Connection) -> musq::Result<Vec<i64>> {
+        query_scalar("SELECT id FROM t ORDER BY id")
+            .fetch_all(conn)
+            .await
+    }
+
+    let mut tx = conn.begin().await?;
+    query("INSERT INTO t VALUES (1)").execute(&mut *tx).await?;
+
+    // A rolled back savepoint undoes only its own changes
+    let mut sp = tx.savepoint("import batch").await?;
+    query("INSERT INTO t VALUES (2)").execute(&mut *sp).await?;
+    sp.rollback().await?;
+
+    let mut sp = tx.savepoint("outer").await?;
+    query("INSERT INTO t VALUES (3)").execute(&mut *sp).await?;
+    let mut inner = sp.savepoint("inner").await?;
+    query("INSERT INTO t VALUES (4)")
+        .execute(&mut *inner)
+        .await?;
+    drop(inner);
+    sp.release().await?;
+
+    {
+        let sp = tx.savepoint("dropped").await?;
+        drop(sp);
+    }
+    assert_eq!(ids(&mut tx).await?, vec![1, 3]);
+    tx.commit().await?;
+    assert_eq!(ids(&mut conn).await?, vec![1, 3]);
+
+    // Savepoints inside an aborted transaction go with it
+    let mut tx = conn.begin().await?;
+    let mut sp = tx.savepoint("sp").await?;
+    query("INSERT INTO t VALUES (5)").execute(&mut *sp).await?;
+    sp.release().await?;
+    tx.rollback().await?;
+    assert_eq!(ids(&mut conn).await?, vec![1, 3]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_opens_read_scopes_lazily() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    query("CREATE TABLE t (id INTEGER PRIMARY KEY); INSERT INTO t VALUES (1)")
+        .execute(&mut conn)
+        .await?;
+    async fn ids(conn: &mut Connection) -> musq::Result<Vec<i64>> {
+        query_scalar("SELECT id FROM t ORDER BY id")
+            .fetch_all(conn)
+            .await
+    }
+
+    let mut tx = conn.begin().await?;
+
+    // Reads open nothing
+    let mut scope = tx.read_scope();
+    let n: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut scope)
+        .await?;
+    assert_eq!(n, 1);
+    assert!(!scope.is_open());
+    scope.commit().await?;
+
+    // The first write opens a savepoint, which can be rolled back
+    let mut scope = tx.read_scope();
+    query("SELECT * FROM t").fetch_all(&mut scope).await?;
+    query("INSERT INTO t VALUES (2)")
+        .execute(&mut scope)
+        .await?;
+    assert!(scope.is_open());
+    scope.rollback().await?;
+
+    let mut scope = tx.read_scope();
+    query("INSERT INTO t VALUES (3)")
+        .execute(&mut scope)
+        .await?;
+    scope.commit().await?;
+    assert_eq!(ids(&mut tx).await?, vec![1, 3]);
+    tx.commit().await?;
+
+    // Outside a transaction, a write opens one, which is rolled back if the scope is dropped
+    let mut scope = conn.read_scope();
+    query("INSERT INTO t VALUES (4)")
+        .execute(&mut scope)
+        .await?;
+    drop(scope);
+    assert_eq!(ids(&mut conn).await?, vec![1, 3]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_begins_immediate_transactions() -> anyhow::Result<()> {
+    use musq::TransactionBehavior;
+
+    let dir = tempdir::TempDir::new("musq")?;
+    let pool = Musq::new()
+        .create_if_missing(true)
+        .busy_timeout(Duration::from_millis(10))
+        .open(dir.path().join("db.sqlite"))
+        .await?;
+    query("CREATE TABLE t (v INTEGER)").execute(&pool).await?;
+
+    // An immediate transaction holds the write lock before it writes anything
+    let mut tx = pool.begin_immediate().await?;
+    let mut other = pool.acquire().await?;
+    let err = other
+        .begin_with(TransactionBehavior::Immediate)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("locked"), "{err}");
+    // A deferred transaction can still read
+    let mut read = other.begin().await?;
+    let n: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut *read)
+        .await?;
+    assert_eq!(n, 0);
+    read.rollback().await?;
+
+    query("INSERT INTO t VALUES (1)").execute(&mut *tx).await?;
+    // Nested transactions are savepoints, whatever their behavior
+    let mut nested = tx.begin_with(TransactionBehavior::Exclusive).await?;
+    query("INSERT INTO t VALUES (2)")
+        .execute(&mut *nested)
+        .await?;
+    nested.rollback().await?;
+    tx.commit().await?;
+
+    let mut tx = other.begin_with(TransactionBehavior::Exclusive).await?;
+    query("INSERT INTO t VALUES (3)").execute(&mut *tx).await?;
+    tx.commit().await?;
+    let v: Vec<i64> = query_scalar("SELECT v FROM t ORDER BY v")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(v, vec![1, 3]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_maintains_min_connections() -> anyhow::Result<()> {
+    async fn wait_for_size(pool: &musq::Pool, size: u32) -> anyhow::Result<()> {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while pool.size() != size {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await?;
+        Ok(())
+    }
+
+    let pool = Musq::new()
+        .min_connections(2)
+        .max_connections(3)
+        .acquire_timeout(Duration::from_millis(100))
+        .open_in_memory()
+        .await?;
+    assert_eq!((pool.size(), pool.num_idle()), (2, 2));
+
+    // Closed and detached connections are replaced
+    pool.acquire().await?.close().await?;
+    wait_for_size(&pool, 2).await?;
+    let detached = pool.acquire().await?.detach();
+    wait_for_size(&pool, 2).await?;
+    drop(detached);
+
+    // Replenishing never takes the pool past its maximum, or gets in the way of acquire timeouts
+    let conns = vec![
+        pool.acquire().await?,
+        pool.acquire().await?,
+        pool.acquire().await?,
+    ];
+    assert!(matches!(
+        pool.acquire().await,
+        Err(Error::PoolAcquireTimedOut { .. })
+    ));
+    assert_eq!(pool.size(), 3);
+    drop(conns);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!((pool.size(), pool.num_idle()), (3, 3));
+
+    // Nothing is reopened after the pool is closed
+    pool.close().await;
+    assert_eq!(pool.size(), 0);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(pool.size(), 0);
+
+    // The minimum is capped at the maximum
+    let pool = Musq::new()
+        .min_connections(5)
+        .max_connections(2)
+        .open_in_memory()
+        .await?;
+    assert_eq!(pool.size(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_retries_busy_statements() -> anyhow::Result<()> {
+    use musq::RetryPolicy;
+
+    let dir = tempdir::TempDir::new("musq")?;
+    let path = dir.path().join("db.sqlite");
+    let writer = Musq::new().create_if_missing(true).open(&path).await?;
+    query("CREATE TABLE t (v INTEGER)").execute(&writer).await?;
+    let impatient = Musq::new().busy_timeout(Duration::ZERO).open(&path).await?;
+    let patient = Musq::new()
+        .busy_timeout(Duration::ZERO)
+        .retry_policy(RetryPolicy {
+            max_attempts: 100,
+            base_delay: Duration::from_millis(5),
+            max_delay: Duration::from_millis(20),
+            jitter: 0.5,
+        })
+        .open(&path)
+        .await?;
+
+    let mut tx = writer.begin_immediate().await?;
+    let err = query("INSERT INTO t VALUES (1)")
+        .execute(&impatient)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("locked"), "{err}");
+
+    let release = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        query("INSERT INTO t VALUES (2)").execute(&mut *tx).await?;
+        tx.commit().await
+    });
+    query("INSERT INTO t VALUES (3)").execute(&patient).await?;
+    release.await??;
+
+    let v: Vec<i64> = query_scalar("SELECT v FROM t ORDER BY v")
+        .fetch_all(&writer)
+        .await?;
+    assert_eq!(v, vec![2, 3]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_reports_acquire_timeouts() -> anyhow::Result<()> {
+    let pool = Musq::new()
+        .max_connections(1)
+        .acquire_timeout(Duration::from_millis(50))
+        .open_in_memory()
+        .await?;
+    let conn = pool.acquire().await?;
+    match pool.acquire().await {
+        Err(Error::PoolAcquireTimedOut { waited, stats }) => {
+            assert!(waited >= Duration::from_millis(50));
+            assert_eq!(
+                stats,
+                musq::PoolStats {
+                    size: 1,
+                    idle: 0,
+                    max_connections: 1
+                }
+            );
+        }
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    assert_eq!(pool.stats().idle, 0);
+    drop(conn);
+
+    // Waiters on a closed pool see that it closed, not a timeout
+    let held = pool.acquire().await?;
+    let waiter = tokio::spawn({
+        let pool = pool.clone();
+        async move { pool.acquire().await.map(|_| ()) }
+    });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let close = tokio::spawn({
+        let pool = pool.clone();
+        async move { pool.close().await }
+    });
+    assert!(matches!(waiter.await?, Err(Error::PoolClosed)));
+    drop(held);
+    close.await?;
+    Ok(())
+}
+
+fn numbers(pool: &musq::Pool, n: i64) -> futures::stream::BoxStream<'static, musq::Result<i64>> {
+    query_scalar("WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < ?) SELECT x FROM c")
+        .bind(n)
+        .fetch_owned(pool)
+}
+
+#[tokio::test]
+async fn it_fetches_owned_streams() -> anyhow::Result<()> {
+    let pool = Musq::new().max_connections(1).open_in_memory().await?;
+    let stream = numbers(&pool, 5);
+    // The stream holds its connection until it is finished
+    let spawned = tokio::spawn(stream.try_collect::<Vec<_>>());
+    assert_eq!(spawned.await??, vec![1, 2, 3, 4, 5]);
+
+    let mut rows = query_as::<(i64,)>("SELECT 1").fetch_owned(&pool);
+    assert_eq!(rows.try_next().await?, Some((1,)));
+    assert!(pool.try_acquire().is_none());
+    drop(rows);
+    while pool.num_idle() == 0 {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(
+        query("SELECT 1")
+            .fetch_owned(&pool)
+            .try_collect::<Vec<_>>()
+            .await?
+            .len(),
+        1
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_runs_pool_connection_hooks() -> anyhow::Result<()> {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    let checks = Arc::new(AtomicUsize::new(0));
+    let healthy = Arc::new(AtomicBool::new(true));
+    let keep = Arc::new(AtomicBool::new(true));
+    let pool = Musq::new()
+        .max_connections(1)
+        .after_connect(|conn| {
+            Box::pin(async move {
+                conn.execute(query("PRAGMA temp_store = MEMORY")).await?;
+                Ok(())
+            })
+        })
+        .before_acquire({
+            let (checks, healthy) = (checks.clone(), healthy.clone());
+            move |_| {
+                checks.fetch_add(1, Ordering::SeqCst);
+                let healthy = healthy.load(Ordering::SeqCst);
+                Box::pin(async move { Ok(healthy) })
+            }
+        })
+        .after_release({
+            let keep = keep.clone();
+            move |_| {
+                let keep = keep.load(Ordering::SeqCst);
+                Box::pin(async move { Ok(keep) })
+            }
+        })
+        .open_in_memory()
+        .await?;
+
+    let mut conn = pool.acquire().await?;
+    let temp_store: i64 = query_scalar("PRAGMA temp_store")
+        .fetch_one(&mut *conn)
+        .await?;
+    assert_eq!(temp_store, 2);
+    let id = conn.id();
+    conn.return_to_pool().await;
+    drop(conn);
+
+    // Idle connections are checked before they are handed out
+    let before = checks.load(Ordering::SeqCst);
+    let conn = pool.acquire().await?;
+    assert_eq!((conn.id(), checks.load(Ordering::SeqCst)), (id, before + 1));
+    drop(conn);
+    while pool.num_idle() == 0 {
+        tokio::task::yield_now().await;
+    }
+    healthy.store(false, Ordering::SeqCst);
+    let conn = pool.acquire().await?;
+    assert_ne!(conn.id(), id);
+    let id = conn.id();
+    healthy.store(true, Ordering::SeqCst);
+
+    // Connections rejected on release are closed instead of returned
+    keep.store(false, Ordering::SeqCst);
+    drop(conn);
+    while pool.size() > 0 {
+        tokio::task::yield_now().await;
+    }
+    assert_ne!(pool.acquire().await?.id(), id);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_reports_pool_metrics() -> anyhow::Result<()> {
+    let pool = Musq::new()
+        .max_connections(2)
+        .acquire_timeout(Duration::from_millis(50))
+        .open_in_memory()
+        .await?;
+    let a = pool.acquire().await?;
+    let b = pool.acquire().await?;
+    assert!(pool.acquire().await.is_err());
+    let metrics = pool.metrics();
+    assert_eq!(
+        (
+            metrics.acquires,
+            metrics.timeouts,
+            metrics.opened,
+            metrics.closed
+        ),
+        (2, 1, 2, 0)
+    );
+    assert_eq!((metrics.idle, metrics.active), (0, 2));
+    assert_eq!(metrics.acquire_wait.count(), 2);
+    assert_eq!(
+        metrics.acquire_wait.cumulative().last().map(|(_, n)| n),
+        Some(2)
+    );
+
+    drop(a);
+    b.close().await?;
+    while pool.num_idle() == 0 {
+        tokio::task::yield_now().await;
+    }
+    let c = pool.try_acquire().expect("an idle connection");
+    let metrics = pool.metrics();
+    assert_eq!(
+        (metrics.acquires, metrics.opened, metrics.closed),
+        (3, 2, 1)
+    );
+    assert_eq!((metrics.idle, metrics.active), (0, 1));
+    drop(c);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_watches_for_slow_queries() -> anyhow::Result<()> {
+    use musq::{SlowQuery, WatchdogAction};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    let endless =
+        "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c";
+    let pool = Musq::new()
+        .watchdog(Duration::from_millis(50), WatchdogAction::Interrupt)
+        .open_in_memory()
+        .await?;
+    let err = query(endless).execute(&pool).await.unwrap_err();
+    assert!(matches!(err, Error::Interrupted), "{err}");
+    let n: i64 = query_scalar("SELECT 1").fetch_one(&pool).await?;
+    assert_eq!(n, 1);
+
+    let slow = Arc::new(Mutex::new(Vec::<SlowQuery>::new()));
+    let pool = Musq::new()
+        .watchdog(
+            Duration::from_millis(1),
+            WatchdogAction::Callback({
+                let slow = slow.clone();
+                Arc::new(move |q: &SlowQuery| slow.lock().unwrap().push(q.clone()))
+            }),
+        )
+        .open_in_memory()
+        .await?;
+    let mut conn = pool.acquire().await?;
+    // A user progress handler keeps working alongside the watchdog
+    let ticks = Arc::new(AtomicUsize::new(0));
+    conn.lock_handle().await?.set_progress_handler(100_000, {
+        let ticks = ticks.clone();
+        move || {
+            ticks.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+    });
+    let sql = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 2000000) SELECT count(*) FROM c";
+    let n: i64 = query_scalar(sql).fetch_one(&mut *conn).await?;
+    assert_eq!(n, 2_000_000);
+    assert!(ticks.load(Ordering::Relaxed) > 0);
+
+    // The watchdog fires once per statement
+    let slow = slow.lock().unwrap().clone();
+    assert_eq!(slow.len(), 1);
+    assert_eq!(slow[0].sql, sql);
+    assert_eq!(slow[0].connection, conn.id());
+    assert!(slow[0].elapsed >= Duration::from_millis(1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_times_out_queries() -> anyhow::Result<()> {
+    let mut conn = connection().await?;
+    let endless =
+        "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c";
+    let err = query(endless)
+        .timeout(Duration::from_millis(50))
+        .execute(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, Error::QueryTimedOut { timeout } if timeout == Duration::from_millis(50)),
+        "{err}"
+    );
+
+    // The timeout applies only to the query it was set on
+    let n: i64 = query_scalar(
+        "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 10) SELECT count(*) FROM c",
+    )
+    .timeout(Duration::from_secs(10))
+    .fetch_one(&mut conn)
+    .await?;
+    assert_eq!(n, 10);
+    let (n,): (i64,) = query_as("SELECT 1").fetch_one(&mut conn).await?;
+    assert_eq!(n, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_cancels_queries() -> anyhow::Result<()> {
+    use tokio_util::sync::CancellationToken;
+
+    let mut conn = connection().await?;
+    let endless =
+        "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c";
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.cancel();
+    });
+    let err = query(endless)
+        .with_cancellation(token.clone())
+        .execute(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Cancelled), "{err}");
+
+    // A cancelled token stops queries before they start, and between rows
+    query("CREATE TABLE t (a INTEGER)")
+        .execute(&mut conn)
+        .await?;
+    let err = query("INSERT INTO t VALUES (1)")
+        .with_cancellation(token)
+        .execute(&mut conn)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Cancelled), "{err}");
+    let n: i64 = query_scalar("SELECT count(*) FROM t")
+        .fetch_one(&mut conn)
+        .await?;
+    assert_eq!(n, 0);
+
+    let token = CancellationToken::new();
+    let mut rows = query_scalar::<i64>(
+        "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 100000) SELECT x FROM c",
+    )
+    .with_cancellation(token.clone())
+    .fetch(&mut conn);
+    assert_eq!(rows.try_next().await?, Some(1));
+    token.cancel();
+    let mut cancelled = false;
+    while let Some(row) = futures::StreamExt::next(&mut rows).await {
+        if let Err(err) = row {
+            assert!(matches!(err, Error::Cancelled), "{err}");
+            cancelled = true;
+            break;
+        }
+    }
+    assert!(cancelled);
+    drop(rows);
+
+    // The token only applies to the query it was set on
+    let (n,): (i64,) = query_as("SELECT 1").fetch_one(&mut conn).await?;
+    assert_eq!(n, 1);
+    Ok(())
+}
+
+async fn count_rows<'c>(e: impl Executor<'c>) -> musq::Result<i64> {
+    query_scalar("SELECT count(*) FROM t").fetch_one(e).await
+}
+
+async fn insert_row<'c>(e: impl Executor<'c>, x: i64) -> musq::Result<()> {
+    query("INSERT INTO t (x) VALUES (?)")
+        .bind(x)
+        .execute(e)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_accepts_any_executor_in_generic_functions() -> anyhow::Result<()> {
+    let pool = Musq::new().max_connections(1).open_in_memory().await?;
+    query("CREATE TABLE t (x INTEGER)").execute(&pool).await?;
+
+    insert_row(&pool, 1).await?;
+    assert_eq!(count_rows(&pool).await?, 1);
+
+    let mut conn = pool.acquire().await?;
+    insert_row(&mut conn, 2).await?;
+    assert_eq!(count_rows(&mut conn).await?, 2);
+    assert_eq!(count_rows(&mut *conn).await?, 2);
+
+    let mut tx = conn.begin().await?;
+    insert_row(&mut tx, 3).await?;
+    let mut sp = tx.savepoint("sp").await?;
+    insert_row(&mut sp, 4).await?;
+    assert_eq!(count_rows(&mut sp).await?, 4);
+    sp.rollback().await?;
+    assert_eq!(count_rows(&mut tx).await?, 3);
+    tx.commit().await?;
+    assert_eq!(count_rows(&mut conn).await?, 3);
+    Ok(())
+}
@@ -76,6 +76,198 @@ mod time_tests {
     ));
 }
 
+mod int_tests {
+    use super::*;
+    use musq::types::U64BitCast;
+    use std::num::{NonZeroI32, NonZeroI64, NonZeroU8};
+
+    test_type!(nonzero_i32<NonZeroI32>("94101" == NonZeroI32::new(94101).unwrap()));
+
+    test_type!(nonzero_i64<NonZeroI64>("-9358295312" == NonZeroI64::new(-9358295312).unwrap()));
+
+    test_type!(nonzero_u8<NonZeroU8>("255" == NonZeroU8::new(255).unwrap()));
+
+    test_type!(u64_bit_cast<U64BitCast>(
+        "0" == U64BitCast(0),
+        "9223372036854775807" == U64BitCast(i64::MAX as u64),
+        "-1" == U64BitCast(u64::MAX),
+        "-9223372036854775808" == U64BitCast(1 << 63),
+    ));
+
+    #[tokio::test]
+    async fn it_rejects_zero_for_nonzero_integers() -> anyhow::Result<()> {
+        let mut conn = musq_test::connection().await?;
+        let row = musq::query("SELECT 0, 256").fetch_one(&mut conn).await?;
+        assert!(matches!(
+            row.get_value_idx::<NonZeroI32>(0),
+            Err(musq::Error::ColumnDecode {
+                source: musq::DecodeError::Conversion(_),
+                ..
+            })
+        ));
+        assert!(row.get_value_idx::<NonZeroU8>(1).is_err());
+        assert_eq!(
+            row.get_value_idx::<Option<NonZeroI32>>(1)?,
+            NonZeroI32::new(256)
+        );
+        Ok(())
+    }
+}
+
+mod std_time_tests {
+    use super::*;
+    use musq::types::Rfc3339Time;
+    use musq_test::test_unprepared_type;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    test_type!(system_time<SystemTime>(
+        "0" == UNIX_EPOCH,
+        "1700000000" == UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        "-86400" == UNIX_EPOCH - Duration::from_secs(86_400),
+    ));
+
+    test_unprepared_type!(system_time_from_text<SystemTime>(
+        "'2023-11-14 22:13:20'" == UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        "'2023-11-14T23:13:20+01:00'" == UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        "1.5" == UNIX_EPOCH + Duration::from_millis(1500),
+    ));
+
+    test_type!(rfc3339_time<Rfc3339Time>(
+        "'2023-11-14T22:13:20Z'" == Rfc3339Time(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+        "'1970-01-01T00:00:01.5Z'" == Rfc3339Time(UNIX_EPOCH + Duration::from_millis(1500)),
+    ));
+
+    test_type!(duration<Duration>(
+        "0" == Duration::ZERO,
+        "1500" == Duration::from_millis(1500),
+    ));
+
+    #[tokio::test]
+    async fn it_rounds_system_times_down() -> anyhow::Result<()> {
+        let mut conn = musq_test::connection().await?;
+        let (before, after): (i64, i64) = musq::query_as("SELECT ?, ?")
+            .bind(UNIX_EPOCH - Duration::from_millis(1500))
+            .bind(UNIX_EPOCH + Duration::from_millis(1500))
+            .fetch_one(&mut conn)
+            .await?;
+        assert_eq!((before, after), (-2, 1));
+
+        let row = musq::query("SELECT -1").fetch_one(&mut conn).await?;
+        assert!(row.get_value_idx::<Duration>(0).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_encodes_out_of_range_rfc3339_times_as_seconds() -> anyhow::Result<()> {
+        let mut conn = musq_test::connection().await?;
+        for secs in [253_402_300_800, 400_000_000_000] {
+            let time = UNIX_EPOCH + Duration::from_secs(secs);
+            let (kind, value): (String, Rfc3339Time) = musq::query_as("SELECT typeof(?1), ?1")
+                .bind(Rfc3339Time(time))
+                .fetch_one(&mut conn)
+                .await?;
+            assert_eq!(kind, "integer");
+            assert_eq!(value, Rfc3339Time(time));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_tests {
+    use super::*;
+    use musq::types::chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+    use musq_test::test_unprepared_type;
+
+    fn dt(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32, ms: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .and_then(|date| date.and_hms_milli_opt(h, mi, s, ms))
+            .unwrap()
+    }
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32, ms: u32) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(dt(y, mo, d, h, mi, s, ms), Utc)
+    }
+
+    test_type!(chrono_date_time_utc<DateTime<Utc>>(
+        "SELECT datetime({0}) is datetime(?), {0}, ?",
+        "'2015-11-19 01:01:39+01:00'" == utc(2015, 11, 19, 0, 1, 39, 0),
+        "'2014-10-18 00:00:38.697+00:00'" == utc(2014, 10, 18, 0, 0, 38, 697),
+        "'2013-09-17 23:59-01:00'" == utc(2013, 9, 18, 0, 59, 0, 0),
+        "'2016-03-07T22:36:55.135+03:30'" == utc(2016, 3, 7, 19, 6, 55, 135),
+        "'2017-04-11T14:35Z'" == utc(2017, 4, 11, 14, 35, 0, 0),
+        "'2018-05-12 15:36:12'" == utc(2018, 5, 12, 15, 36, 12, 0),
+    ));
+
+    test_type!(chrono_naive_date_time<NaiveDateTime>(
+        "SELECT datetime({0}) is datetime(?), {0}, ?",
+        "'2019-01-02 05:10:20'" == dt(2019, 1, 2, 5, 10, 20, 0),
+        "'2018-12-01 04:09:19.543'" == dt(2018, 12, 1, 4, 9, 19, 543),
+        "'2017-11-30 03:08'" == dt(2017, 11, 30, 3, 8, 0, 0),
+        "'2016-10-29T02:07:17'" == dt(2016, 10, 29, 2, 7, 17, 0),
+        "'2014-08-27T00:05'" == dt(2014, 8, 27, 0, 5, 0, 0),
+        "'2013-07-26 23:04:14Z'" == dt(2013, 7, 26, 23, 4, 14, 0),
+        "'2009-03-22T19:00:10.21Z'" == dt(2009, 3, 22, 19, 0, 10, 210),
+        "'2008-02-21'" == dt(2008, 2, 21, 0, 0, 0, 0),
+    ));
+
+    // Integers are Unix timestamps, and reals Julian day numbers
+    test_unprepared_type!(chrono_numeric_date_time<NaiveDateTime>(
+        "1500000000" == dt(2017, 7, 14, 2, 40, 0, 0),
+        "2440588.25" == dt(1970, 1, 1, 18, 0, 0, 0),
+        "julianday('2012-06-25 22:03:13.321')" == dt(2012, 6, 25, 22, 3, 13, 321),
+    ));
+
+    test_type!(chrono_date<NaiveDate>(
+        "SELECT date({0}) is date(?), {0}, ?",
+        "'2002-06-04'" == NaiveDate::from_ymd_opt(2002, 6, 4).unwrap(),
+    ));
+
+    test_type!(chrono_time<NaiveTime>(
+        "SELECT time({0}) is time(?), {0}, ?",
+        "'21:46:32'" == NaiveTime::from_hms_opt(21, 46, 32).unwrap(),
+        "'20:45:31.133'" == NaiveTime::from_hms_milli_opt(20, 45, 31, 133).unwrap(),
+        "'19:44'" == NaiveTime::from_hms_opt(19, 44, 0).unwrap(),
+    ));
+}
+
+#[cfg(feature = "rust_decimal")]
+mod decimal_tests {
+    use super::*;
+    use musq::types::rust_decimal::Decimal;
+    use musq_test::test_unprepared_type;
+
+    test_type!(decimal<Decimal>(
+        "'1.50'" == Decimal::new(150, 2),
+        "'-12345678901234567890.123456789'" == "-12345678901234567890.123456789".parse::<Decimal>().unwrap(),
+        "'0'" == Decimal::ZERO,
+    ));
+
+    // Numbers and scientific notation written by other code decode too
+    test_unprepared_type!(decimal_numeric<Decimal>(
+        "42" == Decimal::from(42),
+        "0.25" == Decimal::new(25, 2),
+        "'1.5e3'" == Decimal::from(1500),
+    ));
+
+    #[tokio::test]
+    async fn it_rejects_invalid_decimals() -> anyhow::Result<()> {
+        let mut conn = musq_test::connection().await?;
+        let row = musq::query("SELECT 'abc', X'01'")
+            .fetch_one(&mut conn)
+            .await?;
+        assert!(matches!(
+            row.get_value_idx::<Decimal>(0),
+            Err(musq::Error::ColumnDecode {
+                source: musq::DecodeError::Conversion(_),
+                ..
+            })
+        ));
+        assert!(row.get_value_idx::<Decimal>(1).is_err());
+        Ok(())
+    }
+}
+
 mod bstr {
     use super::*;
     use musq::types::bstr::BString;
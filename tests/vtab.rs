@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use musq::{
+    encode::Encode,
+    query_as, query_scalar,
+    vtab::{ConstraintOp, IndexInfo, VirtualCursor, VirtualTable},
+    ArgumentValue, Error, Musq, Value,
+};
+
+/// `series(stop)` yields the integers from 1 to `stop`, or to 5 when scanned without an argument.
+struct Series;
+
+#[derive(Default)]
+struct SeriesCursor {
+    value: i64,
+    stop: i64,
+}
+
+impl VirtualTable for Series {
+    type Cursor = SeriesCursor;
+
+    fn schema(&self) -> String {
+        "CREATE TABLE x(value, stop HIDDEN)".into()
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> Result<(), String> {
+        let constraints = info.constraints();
+        match constraints
+            .iter()
+            .position(|c| c.column == 1 && c.op == ConstraintOp::Eq)
+        {
+            Some(i) if constraints[i].usable => {
+                info.use_constraint(i, 0, true);
+                info.set_index_num(1);
+            }
+            Some(_) => info.reject(),
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn open(&self) -> Result<SeriesCursor, String> {
+        Ok(SeriesCursor::default())
+    }
+}
+
+impl VirtualCursor for SeriesCursor {
+    fn filter(&mut self, index_num: i32, args: &[Value]) -> Result<(), String> {
+        self.stop = if index_num == 1 { args[0].int64() } else { 5 };
+        if self.stop > 1000 {
+            return Err("series too long".into());
+        }
+        self.value = 1;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<(), String> {
+        self.value += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.value > self.stop
+    }
+
+    fn column(&self, index: usize) -> Result<ArgumentValue, String> {
+        match index {
+            0 => Ok(self.value.encode()),
+            _ => Ok(self.stop.encode()),
+        }
+    }
+
+    fn rowid(&self) -> Result<i64, String> {
+        Ok(self.value)
+    }
+}
+
+/// A table over a shared list of names, which panics on reading the name "panic".
+struct Names(Arc<Vec<&'static str>>);
+
+struct NamesCursor {
+    names: Arc<Vec<&'static str>>,
+    pos: usize,
+}
+
+impl VirtualTable for Names {
+    type Cursor = NamesCursor;
+
+    fn schema(&self) -> String {
+        "CREATE TABLE x(name TEXT)".into()
+    }
+
+    fn open(&self) -> Result<NamesCursor, String> {
+        Ok(NamesCursor {
+            names: self.0.clone(),
+            pos: 0,
+        })
+    }
+}
+
+impl VirtualCursor for NamesCursor {
+    fn filter(&mut self, _index_num: i32, _args: &[Value]) -> Result<(), String> {
+        self.pos = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<(), String> {
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.pos >= self.names.len()
+    }
+
+    fn column(&self, _index: usize) -> Result<ArgumentValue, String> {
+        let name = self.names[self.pos];
+        if name == "panic" {
+            panic!("bad name");
+        }
+        Ok(name.encode())
+    }
+}
+
+#[tokio::test]
+async fn it_queries_virtual_tables() -> anyhow::Result<()> {
+    let pool = Musq::new()
+        .register_vtab("series", Series)
+        .register_vtab("names", Names(Arc::new(vec!["b", "a", "c"])))
+        .max_connections(2)
+        .open_in_memory()
+        .await?;
+
+    let values: Vec<i64> = query_scalar("SELECT value FROM series(3)")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(values, [1, 2, 3]);
+    let values: Vec<i64> = query_scalar("SELECT value FROM series WHERE stop = 2")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(values, [1, 2]);
+    let values: Vec<(i64, i64)> = query_as("SELECT rowid, value FROM series WHERE value % 2 = 0")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(values, [(2, 2), (4, 4)]);
+
+    // Virtual tables join with each other, on every connection
+    let mut conns = vec![pool.acquire().await?, pool.acquire().await?];
+    for conn in &mut conns {
+        let rows: Vec<(String, i64)> =
+            query_as("SELECT name, value FROM names JOIN series(2) ORDER BY name, value")
+                .fetch_all(&mut **conn)
+                .await?;
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], ("a".to_string(), 1));
+    }
+    drop(conns);
+
+    let err = query_scalar::<i64>("SELECT value FROM series(2000)")
+        .fetch_all(&pool)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("series too long"), "{err}");
+    assert!(query_scalar::<i64>("SELECT value FROM nope")
+        .fetch_all(&pool)
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_poisons_panicking_virtual_tables() -> anyhow::Result<()> {
+    let pool = Musq::new()
+        .register_vtab("names", Names(Arc::new(vec!["a", "panic"])))
+        .max_connections(1)
+        .open_in_memory()
+        .await?;
+
+    let err = query_scalar::<String>("SELECT name FROM names")
+        .fetch_all(&pool)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::CallbackPanicked { .. }), "{err}");
+    let err = query_scalar::<i64>("SELECT count(*) FROM names")
+        .fetch_one(&pool)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::CallbackPanicked { .. }), "{err}");
+    Ok(())
+}